@@ -5,9 +5,11 @@ use criterion::{
 use linfa::benchmarks::config;
 use linfa::prelude::*;
 use linfa::DatasetBase;
-use linfa_clustering::{IncrKMeansError, KMeans, KMeansInit};
+use linfa_clustering::{
+    Accumulation, IncrKMeansError, KMeans, KMeansAlgorithm, KMeansInit, Parallelism,
+};
 use linfa_datasets::generate;
-use ndarray::Array2;
+use ndarray::{Array1, Array2, Axis, Zip};
 use ndarray_rand::RandomExt;
 use ndarray_rand::{rand::SeedableRng, rand_distr::Uniform};
 use rand_xoshiro::Xoshiro256Plus;
@@ -68,6 +70,8 @@ fn k_means_bench(c: &mut Criterion) {
     benchmark.finish();
 }
 
+// The models returned through `Err` by `try_fold` are large, but only moved once per batch
+#[allow(clippy::result_large_err)]
 fn k_means_incr_bench(c: &mut Criterion) {
     let mut rng = Xoshiro256Plus::seed_from_u64(40);
     let cluster_sizes = [(100, 4), (400, 10), (3000, 10)];
@@ -161,18 +165,324 @@ fn k_means_init_bench(c: &mut Criterion) {
     }
 }
 
+fn k_means_predict_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let n_clusters_list = [10, 50, 100, 500, 1000];
+    let n_observations = 10000;
+    let n_features = 3;
+
+    let mut benchmark = c.benchmark_group("k_means_predict");
+    config::set_default_benchmark_configs(&mut benchmark);
+    benchmark.plot_config(PlotConfiguration::default().summary_scale(AxisScale::Logarithmic));
+
+    for &n_clusters in &n_clusters_list {
+        let rng = &mut rng;
+        let centroids =
+            Array2::random_using((n_clusters, n_features), Uniform::new(-30., 30.), rng);
+        let dataset = DatasetBase::from(generate::blobs(
+            n_observations / n_clusters,
+            &centroids,
+            rng,
+        ));
+        let model = KMeans::params_with_rng(n_clusters, rng.clone())
+            .init_method(KMeansInit::Precomputed(centroids))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&dataset)
+            .unwrap();
+
+        benchmark.bench_function(BenchmarkId::new("linear", n_clusters), |bencher| {
+            bencher.iter(|| {
+                let _: Array1<usize> = model.predict(black_box(dataset.records()));
+            });
+        });
+        benchmark.bench_function(BenchmarkId::new("kdtree", n_clusters), |bencher| {
+            bencher.iter(|| model.predict_exact_tree(black_box(dataset.records())));
+        });
+        benchmark.bench_function(BenchmarkId::new("kdtree_approx", n_clusters), |bencher| {
+            bencher.iter(|| model.predict_approx(black_box(dataset.records()), 1));
+        });
+    }
+
+    benchmark.finish();
+}
+
+fn k_means_predict_low_dim_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let n_features_list = [2, 3, 4];
+    let n_clusters = 10;
+    let n_observations = 10000;
+
+    let mut benchmark = c.benchmark_group("k_means_predict_low_dim");
+    config::set_default_benchmark_configs(&mut benchmark);
+
+    for &n_features in &n_features_list {
+        let rng = &mut rng;
+        let centroids =
+            Array2::random_using((n_clusters, n_features), Uniform::new(-30., 30.), rng);
+        let dataset = DatasetBase::from(generate::blobs(
+            n_observations / n_clusters,
+            &centroids,
+            rng,
+        ));
+        let model = KMeans::params_with_rng(n_clusters, rng.clone())
+            .init_method(KMeansInit::Precomputed(centroids))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&dataset)
+            .unwrap();
+
+        benchmark.bench_function(BenchmarkId::new("predict", n_features), |bencher| {
+            bencher.iter(|| {
+                let _: Array1<usize> = model.predict(black_box(dataset.records()));
+            });
+        });
+    }
+
+    benchmark.finish();
+}
+
+fn k_means_predict_high_dim_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let sizes = [
+        (8, 10),
+        (8, 100),
+        (32, 10),
+        (32, 100),
+        (128, 10),
+        (128, 100),
+    ];
+    let n_observations = 10000;
+
+    let mut benchmark = c.benchmark_group("k_means_predict_high_dim");
+    config::set_default_benchmark_configs(&mut benchmark);
+
+    for &(n_features, n_clusters) in &sizes {
+        let rng = &mut rng;
+        let centroids =
+            Array2::random_using((n_clusters, n_features), Uniform::new(-30., 30.), rng);
+        let dataset = DatasetBase::from(generate::blobs(
+            n_observations / n_clusters,
+            &centroids,
+            rng,
+        ));
+        let model = KMeans::params_with_rng(n_clusters, rng.clone())
+            .init_method(KMeansInit::Precomputed(centroids))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&dataset)
+            .unwrap();
+
+        benchmark.bench_function(
+            BenchmarkId::new("predict", format!("{}x{}", n_features, n_clusters)),
+            |bencher| {
+                bencher.iter(|| {
+                    let _: Array1<usize> = model.predict(black_box(dataset.records()));
+                });
+            },
+        );
+    }
+
+    benchmark.finish();
+}
+
+fn k_means_high_dim_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let (cluster_size, n_clusters, n_features) = (300, 10, 100);
+
+    let mut benchmark = c.benchmark_group("k_means_high_dim");
+    config::set_default_benchmark_configs(&mut benchmark);
+
+    let centroids = Array2::random_using((n_clusters, n_features), Uniform::new(-3., 3.), &mut rng);
+    let dataset = DatasetBase::from(generate::blobs(cluster_size, &centroids, &mut rng));
+    benchmark.bench_function(
+        BenchmarkId::new("fit", format!("{}x{}", n_clusters, n_features)),
+        |bencher| {
+            bencher.iter(|| {
+                KMeans::params_with_rng(black_box(n_clusters), black_box(rng.clone()))
+                    .n_runs(1)
+                    .fit(&dataset)
+                    .unwrap()
+            });
+        },
+    );
+
+    benchmark.finish();
+}
+
+fn k_means_assignment_chunk_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let (cluster_size, n_clusters, n_features) = (100, 100, 100);
+
+    let mut benchmark = c.benchmark_group("k_means_assignment_chunk");
+    config::set_default_benchmark_configs(&mut benchmark);
+
+    let centroids = Array2::random_using((n_clusters, n_features), Uniform::new(-3., 3.), &mut rng);
+    let dataset = DatasetBase::from(generate::blobs(cluster_size, &centroids, &mut rng));
+    for chunk_size in [None, Some(64), Some(512), Some(4096)] {
+        benchmark.bench_function(
+            BenchmarkId::new(
+                "fit",
+                chunk_size.map_or("rayon".to_string(), |size| size.to_string()),
+            ),
+            |bencher| {
+                bencher.iter(|| {
+                    KMeans::params_with_rng(n_clusters, rng.clone())
+                        .init_method(KMeansInit::Random)
+                        .n_runs(1)
+                        .max_n_iterations(10)
+                        .assignment_chunk_size(black_box(chunk_size))
+                        .fit(&dataset)
+                        .unwrap()
+                });
+            },
+        );
+    }
+
+    benchmark.finish();
+}
+
+fn k_means_elkan_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let (n_observations, n_features) = (10_000, 100);
+
+    let mut benchmark = c.benchmark_group("k_means_elkan");
+    config::set_default_benchmark_configs(&mut benchmark);
+
+    // The same number of observations split into more and more clusters
+    for n_clusters in [10, 100, 500] {
+        let centroids =
+            Array2::random_using((n_clusters, n_features), Uniform::new(-3., 3.), &mut rng);
+        let observations = generate::blobs(n_observations / n_clusters, &centroids, &mut rng);
+        let dataset = DatasetBase::from(observations);
+        for (name, algorithm) in [
+            ("lloyd", KMeansAlgorithm::Lloyd),
+            ("elkan", KMeansAlgorithm::Elkan),
+            ("full", KMeansAlgorithm::Full),
+        ] {
+            benchmark.bench_function(BenchmarkId::new(name, n_clusters), |bencher| {
+                bencher.iter(|| {
+                    KMeans::params_with_rng(n_clusters, rng.clone())
+                        .init_method(KMeansInit::Random)
+                        .n_runs(1)
+                        .max_n_iterations(10)
+                        .algorithm(black_box(algorithm.clone()))
+                        .fit(&dataset)
+                        .unwrap()
+                });
+            });
+        }
+    }
+
+    benchmark.finish();
+}
+
+fn k_means_accumulation_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let (n_observations, n_features) = (1_000_000, 4);
+
+    let mut benchmark = c.benchmark_group("k_means_accumulation");
+    config::set_default_benchmark_configs(&mut benchmark);
+
+    // A single large `f32` cluster, far from the origin so that rounding errors add up
+    let observations: Array2<f32> = Array2::random_using(
+        (n_observations, n_features),
+        Uniform::new(999., 1001.),
+        &mut rng,
+    );
+    let init = Array2::from_elem((1, n_features), 1000f32);
+    // The centroid of a single m_k-means step is the mean of the observations and of `init`
+    let exact = observations
+        .mapv(f64::from)
+        .sum_axis(Axis(0))
+        .mapv(|sum| (sum + 1000.) / (n_observations + 1) as f64);
+    let dataset = DatasetBase::from(observations);
+
+    for accumulation in [
+        Accumulation::Naive,
+        Accumulation::Kahan,
+        Accumulation::Pairwise,
+    ] {
+        let fit = || {
+            KMeans::params_with_rng(1, rng.clone())
+                .init_method(KMeansInit::Precomputed(init.clone()))
+                .n_runs(1)
+                .max_n_iterations(1)
+                .accumulation(black_box(accumulation))
+                .fit(&dataset)
+                .unwrap()
+        };
+        let error = Zip::from(fit().centroids().row(0))
+            .and(&exact)
+            .fold(0f64, |acc, &c, &e| acc.max((f64::from(c) - e).abs()));
+        println!("{:?}: largest centroid error = {:e}", accumulation, error);
+
+        benchmark.bench_function(
+            BenchmarkId::new("fit", format!("{:?}", accumulation)),
+            |bencher| bencher.iter(fit),
+        );
+    }
+
+    benchmark.finish();
+}
+
+fn k_means_parallelism_bench(c: &mut Criterion) {
+    let mut rng = Xoshiro256Plus::seed_from_u64(40);
+    let (n_clusters, n_features, n_runs) = (8, 4, 8);
+
+    let mut benchmark = c.benchmark_group("k_means_parallelism");
+    config::set_default_benchmark_configs(&mut benchmark);
+    benchmark.plot_config(PlotConfiguration::default().summary_scale(AxisScale::Logarithmic));
+
+    // Many restarts on a small dataset, and on a large one
+    for n_observations in [1_000, 100_000] {
+        let rng = &mut rng;
+        let observations =
+            Array2::random_using((n_observations, n_features), Uniform::new(-30., 30.), rng);
+        let dataset = DatasetBase::from(observations);
+
+        for parallelism in [Parallelism::Restarts, Parallelism::Observations] {
+            benchmark.bench_function(
+                BenchmarkId::new(format!("{:?}", parallelism), n_observations),
+                |bencher| {
+                    bencher.iter(|| {
+                        KMeans::params_with_rng(n_clusters, rng.clone())
+                            .n_runs(n_runs)
+                            .max_n_iterations(black_box(50))
+                            .parallelism(black_box(parallelism))
+                            .fit(&dataset)
+                            .unwrap()
+                    });
+                },
+            );
+        }
+    }
+
+    benchmark.finish();
+}
+
 #[cfg(not(target_os = "windows"))]
 criterion_group! {
     name = benches;
     config = config::get_default_profiling_configs();
-    targets = k_means_bench, k_means_init_bench, k_means_incr_bench
+    targets = k_means_bench, k_means_init_bench, k_means_incr_bench, k_means_predict_bench,
+        k_means_predict_low_dim_bench, k_means_predict_high_dim_bench, k_means_high_dim_bench,
+        k_means_accumulation_bench, k_means_parallelism_bench, k_means_assignment_chunk_bench, k_means_elkan_bench
 }
 #[cfg(target_os = "windows")]
 criterion_group!(
     benches,
     k_means_bench,
     k_means_init_bench,
-    k_means_incr_bench
+    k_means_incr_bench,
+    k_means_predict_bench,
+    k_means_predict_low_dim_bench,
+    k_means_predict_high_dim_bench,
+    k_means_high_dim_bench,
+    k_means_accumulation_bench,
+    k_means_parallelism_bench,
+    k_means_assignment_chunk_bench,
+    k_means_elkan_bench
 );
 
 criterion_main!(benches);
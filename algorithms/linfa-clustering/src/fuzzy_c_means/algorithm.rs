@@ -0,0 +1,357 @@
+use crate::fuzzy_c_means::errors::FuzzyCMeansError;
+use crate::fuzzy_c_means::hyperparams::{FuzzyCMeansParams, FuzzyCMeansValidParams};
+use linfa::prelude::*;
+use linfa::{DatasetBase, Float};
+use linfa_nn::distance::{Distance, L2Dist};
+use ndarray::{Array1, Array2, ArrayBase, Data, Ix2, Zip};
+use ndarray_rand::rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// Fuzzy c-means clusters observations around `n_clusters` centroids, like
+/// [K-means](crate::KMeans), but each observation belongs to every cluster with a membership
+/// between 0 and 1, its memberships summing to 1. An observation between two clusters can thus
+/// belong 70/30 to them instead of being assigned to the closest one.
+///
+/// ## The algorithm
+///
+/// Fuzzy c-means, by Dunn and Bezdek, minimizes `sum_ij u_ij^m ||x_i - c_j||²` over the
+/// centroids `c_j` and the memberships `u_ij` of observation `i` in cluster `j`, where the
+/// fuzziness `m > 1` controls how soft the clusters are. Starting from random memberships, it
+/// alternates between:
+///
+/// 1. Centroid update: each centroid is the mean of the observations weighted by `u_ij^m`.
+/// 2. Membership update: `u_ij` is proportional to `||x_i - c_j||^(-2 / (m - 1))`, such that
+///    closer clusters get larger memberships. An observation lying on centroids shares its
+///    membership equally between them.
+///
+/// until no membership changes by more than `tolerance`, or `max_n_iterations` iterations were
+/// made. Like K-means, the algorithm converges to a local minimum of the objective, which depends
+/// on the random initial memberships.
+///
+/// ## Tutorial
+///
+/// ```rust
+/// use linfa::DatasetBase;
+/// use linfa::traits::{Fit, Predict};
+/// use linfa_clustering::FuzzyCMeans;
+/// use linfa_datasets::generate;
+/// use ndarray::array;
+/// use ndarray_rand::rand::SeedableRng;
+/// use rand_xoshiro::Xoshiro256Plus;
+///
+/// let mut rng = Xoshiro256Plus::seed_from_u64(42);
+/// let expected_centroids = array![[0., 0.], [10., 0.]];
+/// let observations = generate::blobs(100, &expected_centroids, &mut rng);
+///
+/// let model = FuzzyCMeans::params_with_rng(2, rng)
+///     .fit(&DatasetBase::from(observations))
+///     .expect("FuzzyCMeans fitted");
+/// // An observation between the clusters belongs to both
+/// let memberships = model.predict_proba(&array![[5., 0.], [3., 0.]]);
+/// assert!((memberships[[0, 0]] - 0.5).abs() < 0.1);
+/// assert!(memberships[[1, 0]].max(memberships[[1, 1]]) < 0.9);
+/// // `predict` returns the cluster with the largest membership
+/// let labels = model.predict(&array![[1., 0.], [9., 0.]]);
+/// assert_ne!(labels[0], labels[1]);
+/// ```
+pub struct FuzzyCMeans<F> {
+    /// Centroids of the clusters, with shape `(n_clusters, n_features)`
+    centroids: Array2<F>,
+    /// Memberships of the training observations, with shape `(n_observations, n_clusters)`
+    memberships: Array2<F>,
+    /// Exponent `m` of the memberships in the objective
+    fuzziness: F,
+    /// Value of the objective for the training observations
+    objective: F,
+    /// Number of iterations of the fit
+    n_iterations: u64,
+}
+
+impl<F: Float> FuzzyCMeans<F> {
+    /// Configures the hyperparameters with the number of clusters, and the random number
+    /// generator of the initial memberships seeded with 42
+    pub fn params(n_clusters: usize) -> FuzzyCMeansParams<F, Xoshiro256Plus> {
+        FuzzyCMeansParams::new(n_clusters, Xoshiro256Plus::seed_from_u64(42))
+    }
+
+    /// Configures the hyperparameters with the number of clusters and the random number generator
+    /// of the initial memberships
+    pub fn params_with_rng<R: Rng>(n_clusters: usize, rng: R) -> FuzzyCMeansParams<F, R> {
+        FuzzyCMeansParams::new(n_clusters, rng)
+    }
+
+    /// Return the centroids of the clusters, with shape `(n_clusters, n_features)`
+    pub fn centroids(&self) -> &Array2<F> {
+        &self.centroids
+    }
+
+    /// Return the memberships of the training observations, with shape
+    /// `(n_observations, n_clusters)`
+    pub fn memberships(&self) -> &Array2<F> {
+        &self.memberships
+    }
+
+    /// Return the exponent `m` of the memberships the model was fitted with
+    pub fn fuzziness(&self) -> F {
+        self.fuzziness
+    }
+
+    /// Return the value of the objective, `sum_ij u_ij^m ||x_i - c_j||²`, for the training
+    /// observations
+    pub fn objective(&self) -> F {
+        self.objective
+    }
+
+    /// Return the number of iterations of the fit
+    pub fn n_iterations(&self) -> u64 {
+        self.n_iterations
+    }
+
+    /// Return the memberships of `observations` in the clusters, with shape
+    /// `(n_observations, n_clusters)`. The memberships of each observation sum to 1, and are
+    /// given by the membership update of the fit.
+    pub fn predict_proba(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> Array2<F> {
+        let sq_dists = L2Dist.rdistance_matrix(observations.view(), self.centroids.view());
+        memberships(sq_dists, self.fuzziness)
+    }
+}
+
+impl<F: Float, R: Rng + Clone, D: Data<Elem = F>, T> Fit<ArrayBase<D, Ix2>, T, FuzzyCMeansError>
+    for FuzzyCMeansValidParams<F, R>
+{
+    type Object = FuzzyCMeans<F>;
+
+    /// Fit fuzzy c-means on the observations of `dataset`, see [`FuzzyCMeans`].
+    ///
+    /// Returns an error if there are fewer observations than clusters.
+    fn fit(
+        &self,
+        dataset: &DatasetBase<ArrayBase<D, Ix2>, T>,
+    ) -> Result<Self::Object, FuzzyCMeansError> {
+        let observations = dataset.records().view();
+        let n_observations = observations.nrows();
+        let n_clusters = self.n_clusters();
+        if n_observations == 0 {
+            return Err(FuzzyCMeansError::EmptyObservations);
+        } else if n_observations < n_clusters {
+            return Err(FuzzyCMeansError::NotEnoughObservations {
+                n_observations,
+                n_clusters,
+            });
+        }
+
+        let mut rng = self.rng().clone();
+        let mut memberships = Array2::from_shape_simple_fn((n_observations, n_clusters), || {
+            F::cast(rng.gen::<f64>())
+        });
+        for mut row in memberships.rows_mut() {
+            let total = row.sum();
+            row.mapv_inplace(|u| u / total);
+        }
+
+        let mut centroids = Array2::zeros((n_clusters, observations.ncols()));
+        let mut sq_dists;
+        let mut n_iterations = 0;
+        loop {
+            n_iterations += 1;
+            let weights = memberships.mapv(|u| u.powf(self.fuzziness()));
+            let weighted_sums = weights.t().dot(&observations);
+            Zip::from(centroids.rows_mut())
+                .and(weighted_sums.rows())
+                .and(weights.columns())
+                .for_each(|mut centroid, sum, weights| {
+                    let total = weights.sum();
+                    // A cluster without any membership keeps its previous centroid
+                    if total > F::zero() {
+                        centroid.assign(&sum.mapv(|x| x / total));
+                    }
+                });
+
+            sq_dists = L2Dist.rdistance_matrix(observations, centroids.view());
+            let updated = self::memberships(sq_dists.clone(), self.fuzziness());
+            let change = Zip::from(&updated)
+                .and(&memberships)
+                .fold(F::zero(), |max, &new, &old| max.max((new - old).abs()));
+            memberships = updated;
+            if change <= self.tolerance() || n_iterations >= self.max_n_iterations() {
+                break;
+            }
+        }
+
+        let objective = Zip::from(&memberships)
+            .and(&sq_dists)
+            .fold(F::zero(), |acc, &u, &d| acc + u.powf(self.fuzziness()) * d);
+        Ok(FuzzyCMeans {
+            centroids,
+            memberships,
+            fuzziness: self.fuzziness(),
+            objective,
+            n_iterations,
+        })
+    }
+}
+
+impl<F: Float, D: Data<Elem = F>> PredictInplace<ArrayBase<D, Ix2>, Array1<usize>>
+    for FuzzyCMeans<F>
+{
+    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`,
+    /// `predict` returns, for each observation, the index of the cluster it has the largest
+    /// membership in, which is its closest centroid.
+    fn predict_inplace(&self, observations: &ArrayBase<D, Ix2>, labels: &mut Array1<usize>) {
+        assert_eq!(
+            observations.nrows(),
+            labels.len(),
+            "The number of data points must match the number of labels."
+        );
+
+        let sq_dists = L2Dist.rdistance_matrix(observations.view(), self.centroids.view());
+        Zip::from(labels)
+            .and(sq_dists.rows())
+            .for_each(|label, sq_dists| {
+                *label = sq_dists
+                    .iter()
+                    .enumerate()
+                    .fold((0, F::infinity()), |closest, (j, &d)| {
+                        if d < closest.1 {
+                            (j, d)
+                        } else {
+                            closest
+                        }
+                    })
+                    .0;
+            });
+    }
+
+    fn default_target(&self, x: &ArrayBase<D, Ix2>) -> Array1<usize> {
+        Array1::zeros(x.nrows())
+    }
+}
+
+// Memberships of observations given their squared distances to the centroids. The distances are
+// divided by the smallest one first, so that the largest membership is computed from 1 and the
+// powers can't overflow.
+fn memberships<F: Float>(mut sq_dists: Array2<F>, fuzziness: F) -> Array2<F> {
+    let exponent = -F::one() / (fuzziness - F::one());
+    for mut row in sq_dists.rows_mut() {
+        let min = row.fold(F::infinity(), |min, &d| min.min(d));
+        if min > F::zero() {
+            row.mapv_inplace(|d| (d / min).powf(exponent));
+        } else {
+            row.mapv_inplace(|d| if d > F::zero() { F::zero() } else { F::one() });
+        }
+        let total = row.sum();
+        row.mapv_inplace(|u| u / total);
+    }
+    sq_dists
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use linfa_datasets::generate;
+    use ndarray::{array, Axis};
+
+    #[test]
+    fn autotraits() {
+        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
+        has_autotraits::<FuzzyCMeans<f64>>();
+        has_autotraits::<FuzzyCMeansParams<f64, Xoshiro256Plus>>();
+        has_autotraits::<FuzzyCMeansValidParams<f64, Xoshiro256Plus>>();
+        has_autotraits::<FuzzyCMeansError>();
+    }
+
+    #[test]
+    fn membership_update() {
+        let model = FuzzyCMeans {
+            centroids: array![[0., 0.], [3., 0.]],
+            memberships: Array2::zeros((0, 2)),
+            fuzziness: 2.,
+            objective: 0.,
+            n_iterations: 0,
+        };
+        // Squared distances of 1 and 4, inversely proportional memberships for m = 2
+        let memberships = model.predict_proba(&array![[1., 0.], [0., 0.], [1.5, 5.]]);
+        assert_abs_diff_eq!(
+            memberships,
+            array![[0.8, 0.2], [1., 0.], [0.5, 0.5]],
+            epsilon = 1e-12
+        );
+        assert_eq!(model.predict(&array![[1., 0.], [2., 1.]]), array![0, 1]);
+
+        // Observations on several centroids share their membership
+        let model = FuzzyCMeans {
+            centroids: array![[0., 0.], [0., 0.], [3., 0.]],
+            ..model
+        };
+        assert_abs_diff_eq!(
+            model.predict_proba(&array![[0., 0.]]),
+            array![[0.5, 0.5, 0.]]
+        );
+    }
+
+    #[test]
+    fn fits_blobs() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = generate::blobs(100, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+
+        let model = FuzzyCMeans::params_with_rng(3, rng).fit(&dataset).unwrap();
+        assert!(model.n_iterations() < 300);
+        assert_abs_diff_eq!(model.memberships().sum_axis(Axis(1)), Array1::ones(300));
+        assert_abs_diff_eq!(
+            model.memberships(),
+            &model.predict_proba(&observations),
+            epsilon = 1e-3
+        );
+        for expected in expected_centroids.rows() {
+            let closest = model
+                .centroids()
+                .rows()
+                .into_iter()
+                .map(|c| L2Dist.distance(c, expected))
+                .fold(f64::INFINITY, f64::min);
+            assert!(closest < 0.5);
+        }
+        // `blobs` generates the observations of each blob contiguously
+        let labels = model.predict(&observations);
+        for blob in labels.exact_chunks(100) {
+            assert!(blob.iter().all(|&l| l == blob[0]));
+        }
+
+        // A larger fuzziness gives softer memberships
+        let softer = FuzzyCMeans::params(3).fuzziness(4.).fit(&dataset).unwrap();
+        let mean_max = |model: &FuzzyCMeans<f64>| {
+            model
+                .memberships()
+                .map_axis(Axis(1), |row| row.fold(0., |max: f64, &u| max.max(u)))
+                .mean()
+                .unwrap()
+        };
+        assert!(mean_max(&softer) < mean_max(&model));
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let params = FuzzyCMeans::<f64>::params(2);
+        let res = params.fit(&DatasetBase::from(Array2::<f64>::zeros((0, 2))));
+        assert!(matches!(res, Err(FuzzyCMeansError::EmptyObservations)));
+        let res = params.fit(&DatasetBase::from(array![[1., 1.]]));
+        assert!(matches!(
+            res,
+            Err(FuzzyCMeansError::NotEnoughObservations {
+                n_observations: 1,
+                n_clusters: 2
+            })
+        ));
+    }
+}
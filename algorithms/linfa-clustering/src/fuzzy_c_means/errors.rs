@@ -0,0 +1,33 @@
+use thiserror::Error;
+
+/// An error when fitting with an invalid hyperparameter
+#[derive(Error, Debug)]
+pub enum FuzzyCMeansParamsError {
+    #[error("n_clusters cannot be 0")]
+    NClusters,
+    #[error("fuzziness must be greater than 1 and finite")]
+    Fuzziness,
+    #[error("tolerance must be greater than 0")]
+    Tolerance,
+    #[error("max_n_iterations cannot be 0")]
+    MaxIterations,
+}
+
+/// An error when fitting a fuzzy c-means model
+#[derive(Error, Debug)]
+pub enum FuzzyCMeansError {
+    /// When any of the hyperparameters are set the wrong value
+    #[error("Invalid hyperparameter: {0}")]
+    InvalidParams(#[from] FuzzyCMeansParamsError),
+    /// When fitting on a dataset without any observation
+    #[error("Fitting failed: No observations")]
+    EmptyObservations,
+    /// When fitting on fewer observations than clusters
+    #[error("Fitting failed: {n_observations} observations for {n_clusters} clusters")]
+    NotEnoughObservations {
+        n_observations: usize,
+        n_clusters: usize,
+    },
+    #[error(transparent)]
+    LinfaError(#[from] linfa::error::Error),
+}
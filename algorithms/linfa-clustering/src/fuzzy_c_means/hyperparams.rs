@@ -0,0 +1,156 @@
+use crate::fuzzy_c_means::errors::FuzzyCMeansParamsError;
+use linfa::{Float, ParamGuard};
+use ndarray_rand::rand::Rng;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// The set of hyperparameters that can be specified for the execution of
+/// the [fuzzy c-means algorithm](crate::FuzzyCMeans).
+pub struct FuzzyCMeansValidParams<F: Float, R: Rng> {
+    /// The number of clusters we will be looking for in the training dataset.
+    n_clusters: usize,
+    /// Exponent `m` of the memberships in the objective, controlling how soft the clusters are
+    fuzziness: F,
+    /// The training is considered complete if no membership changes by more than `tolerance`
+    /// during an iteration.
+    tolerance: F,
+    /// Maximum number of iterations
+    max_n_iterations: u64,
+    /// Random number generator of the initial memberships
+    rng: R,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// An helper struct used to construct a set of [valid hyperparameters](FuzzyCMeansParams) for
+/// the [fuzzy c-means algorithm](crate::FuzzyCMeans) (using the builder pattern).
+pub struct FuzzyCMeansParams<F: Float, R: Rng>(FuzzyCMeansValidParams<F, R>);
+
+impl<F: Float, R: Rng> FuzzyCMeansParams<F, R> {
+    /// `new` lets us configure our training algorithm parameters:
+    /// * we will be looking for `n_clusters` in the training dataset;
+    /// * the initial memberships are drawn at random with `rng`.
+    ///
+    /// Defaults are provided if optional parameters are not specified:
+    /// * `fuzziness = 2.0`
+    /// * `tolerance = 1e-4`
+    /// * `max_n_iterations = 300`
+    pub fn new(n_clusters: usize, rng: R) -> Self {
+        Self(FuzzyCMeansValidParams {
+            n_clusters,
+            fuzziness: F::cast(2.),
+            tolerance: F::cast(1e-4),
+            max_n_iterations: 300,
+            rng,
+        })
+    }
+
+    /// Change the value of `fuzziness`, the exponent `m > 1` of the memberships. The memberships
+    /// tend to hard K-means assignments as `m` tends to 1, and to the same membership in every
+    /// cluster as `m` grows.
+    pub fn fuzziness(mut self, fuzziness: F) -> Self {
+        self.0.fuzziness = fuzziness;
+        self
+    }
+
+    /// Change the value of `tolerance`
+    pub fn tolerance(mut self, tolerance: F) -> Self {
+        self.0.tolerance = tolerance;
+        self
+    }
+
+    /// Change the value of `max_n_iterations`
+    pub fn max_n_iterations(mut self, max_n_iterations: u64) -> Self {
+        self.0.max_n_iterations = max_n_iterations;
+        self
+    }
+}
+
+impl<F: Float, R: Rng> ParamGuard for FuzzyCMeansParams<F, R> {
+    type Checked = FuzzyCMeansValidParams<F, R>;
+    type Error = FuzzyCMeansParamsError;
+
+    fn check_ref(&self) -> Result<&Self::Checked, Self::Error> {
+        if self.0.n_clusters == 0 {
+            Err(FuzzyCMeansParamsError::NClusters)
+        } else if !(self.0.fuzziness.is_finite() && self.0.fuzziness > F::one()) {
+            Err(FuzzyCMeansParamsError::Fuzziness)
+        } else if self.0.tolerance <= F::zero() {
+            Err(FuzzyCMeansParamsError::Tolerance)
+        } else if self.0.max_n_iterations == 0 {
+            Err(FuzzyCMeansParamsError::MaxIterations)
+        } else {
+            Ok(&self.0)
+        }
+    }
+
+    fn check(self) -> Result<Self::Checked, Self::Error> {
+        self.check_ref()?;
+        Ok(self.0)
+    }
+}
+
+impl<F: Float, R: Rng> FuzzyCMeansValidParams<F, R> {
+    /// The number of clusters we will be looking for in the training dataset.
+    pub fn n_clusters(&self) -> usize {
+        self.n_clusters
+    }
+
+    /// Exponent `m` of the memberships in the objective
+    pub fn fuzziness(&self) -> F {
+        self.fuzziness
+    }
+
+    /// The training is considered complete if no membership changes by more than `tolerance`
+    /// during an iteration.
+    pub fn tolerance(&self) -> F {
+        self.tolerance
+    }
+
+    /// Maximum number of iterations
+    pub fn max_n_iterations(&self) -> u64 {
+        self.max_n_iterations
+    }
+
+    /// Random number generator of the initial memberships
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FuzzyCMeans, FuzzyCMeansParamsError};
+    use linfa::ParamGuard;
+
+    #[test]
+    fn n_clusters_cannot_be_zero() {
+        let res = FuzzyCMeans::<f64>::params(0).check();
+        assert!(matches!(res, Err(FuzzyCMeansParamsError::NClusters)));
+    }
+
+    #[test]
+    fn fuzziness_must_be_greater_than_one() {
+        for fuzziness in [1., 0.5, f64::INFINITY, f64::NAN] {
+            let res = FuzzyCMeans::params(2).fuzziness(fuzziness).check();
+            assert!(matches!(res, Err(FuzzyCMeansParamsError::Fuzziness)));
+        }
+    }
+
+    #[test]
+    fn tolerance_must_be_positive() {
+        let res = FuzzyCMeans::params(2).tolerance(0.).check();
+        assert!(matches!(res, Err(FuzzyCMeansParamsError::Tolerance)));
+    }
+
+    #[test]
+    fn max_n_iterations_cannot_be_zero() {
+        let res = FuzzyCMeans::<f64>::params(2).max_n_iterations(0).check();
+        assert!(matches!(res, Err(FuzzyCMeansParamsError::MaxIterations)));
+    }
+}
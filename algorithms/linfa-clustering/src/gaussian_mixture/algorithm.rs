@@ -167,7 +167,7 @@ impl<F: Float> GaussianMixtureModel<F> {
         )?;
         weights /= F::cast(n_samples);
 
-        // GmmCovarType = full
+        // Diagonal covariances are stored as full matrices, whose Cholesky factors stay diagonal
         let precisions_chol = Self::compute_precisions_cholesky_full(&covariances)?;
         let precisions = Self::compute_precisions_full(&precisions_chol);
 
@@ -211,10 +211,50 @@ impl<F: Float> GaussianMixtureModel<F> {
         self.means()
     }
 
+    /// Returns the probability of each component given each observation, with shape
+    /// `(n_observations, n_clusters)`. `predict` assigns each observation to the component with
+    /// the highest probability.
+    pub fn predict_proba<D: Data<Elem = F>>(&self, observations: &ArrayBase<D, Ix2>) -> Array2<F> {
+        let (_, log_resp) = self.estimate_log_prob_resp(observations);
+        log_resp.mapv(F::exp)
+    }
+
+    /// Returns the log-likelihood of `observations` under the mixture, summed over the
+    /// observations.
+    pub fn log_likelihood<D: Data<Elem = F>>(&self, observations: &ArrayBase<D, Ix2>) -> F {
+        let (log_prob_norm, _) = self.estimate_log_prob_resp(observations);
+        log_prob_norm.sum()
+    }
+
+    /// Returns the [Bayesian information criterion](https://en.wikipedia.org/wiki/Bayesian_information_criterion)
+    /// of the model on `observations`. Lower is better, for example to select `n_clusters` or the
+    /// covariance type on the same data.
+    pub fn bic<D: Data<Elem = F>>(&self, observations: &ArrayBase<D, Ix2>) -> F {
+        self.n_parameters() * F::cast(observations.nrows()).ln()
+            - F::cast(2.) * self.log_likelihood(observations)
+    }
+
+    /// Returns the [Akaike information criterion](https://en.wikipedia.org/wiki/Akaike_information_criterion)
+    /// of the model on `observations`. Lower is better.
+    pub fn aic<D: Data<Elem = F>>(&self, observations: &ArrayBase<D, Ix2>) -> F {
+        F::cast(2.) * self.n_parameters() - F::cast(2.) * self.log_likelihood(observations)
+    }
+
+    // Number of free parameters of the mixture: the means, the covariances and all the weights
+    // but one, which is set by the others
+    fn n_parameters(&self) -> F {
+        let (n_clusters, n_features) = self.means.dim();
+        let covariance_params = match self.covar_type {
+            GmmCovarType::Full => n_clusters * n_features * (n_features + 1) / 2,
+            GmmCovarType::Diag => n_clusters * n_features,
+        };
+        F::cast(covariance_params + n_clusters * n_features + n_clusters - 1)
+    }
+
     fn estimate_gaussian_parameters<D: Data<Elem = F>>(
         observations: &ArrayBase<D, Ix2>,
         resp: &Array2<F>,
-        _covar_type: &GmmCovarType,
+        covar_type: &GmmCovarType,
         reg_covar: F,
     ) -> Result<(Array1<F>, Array2<F>, Array3<F>)> {
         let nk = resp.sum_axis(Axis(0));
@@ -227,12 +267,39 @@ impl<F: Float> GaussianMixtureModel<F> {
 
         let nk2 = nk.to_owned().insert_axis(Axis(1));
         let means = resp.t().dot(observations) / nk2;
-        // GmmCovarType = Full
-        let covariances =
-            Self::estimate_gaussian_covariances_full(observations, resp, &nk, &means, reg_covar);
+        let covariances = match covar_type {
+            GmmCovarType::Full => {
+                Self::estimate_gaussian_covariances_full(observations, resp, &nk, &means, reg_covar)
+            }
+            GmmCovarType::Diag => {
+                Self::estimate_gaussian_covariances_diag(observations, resp, &nk, &means, reg_covar)
+            }
+        };
         Ok((nk, means, covariances))
     }
 
+    // Same as `estimate_gaussian_covariances_full` with the off-diagonal terms left to zero
+    fn estimate_gaussian_covariances_diag<D: Data<Elem = F>>(
+        observations: &ArrayBase<D, Ix2>,
+        resp: &Array2<F>,
+        nk: &Array1<F>,
+        means: &Array2<F>,
+        reg_covar: F,
+    ) -> Array3<F> {
+        let n_clusters = means.nrows();
+        let n_features = means.ncols();
+        let mut covariances = Array::zeros((n_clusters, n_features, n_features));
+        for k in 0..n_clusters {
+            let diff = observations - &means.row(k);
+            let variances = (&diff * &diff).t().dot(&resp.index_axis(Axis(1), k)) / nk[k];
+            covariances
+                .slice_mut(s![k, .., ..])
+                .diag_mut()
+                .assign(&variances.mapv(|x| x + reg_covar));
+        }
+        covariances
+    }
+
     fn estimate_gaussian_covariances_full<D: Data<Elem = F>>(
         observations: &ArrayBase<D, Ix2>,
         resp: &Array2<F>,
@@ -311,7 +378,6 @@ impl<F: Float> GaussianMixtureModel<F> {
         )?;
         self.means = means;
         self.weights = weights / F::cast(n_samples);
-        // GmmCovarType = Full()
         self.precisions_chol = Self::compute_precisions_cholesky_full(&covariances)?;
         Ok(())
     }
@@ -364,7 +430,6 @@ impl<F: Float> GaussianMixtureModel<F> {
         let n_features = observations.ncols();
         let means = self.means();
         let n_clusters = means.nrows();
-        // GmmCovarType = full
         // det(precision_chol) is half of det(precision)
         let log_det = Self::compute_log_det_cholesky_full(&self.precisions_chol, n_features);
         let mut log_prob: Array2<F> = Array::zeros((n_samples, n_clusters));
@@ -654,6 +719,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_diag_covariances_and_model_selection() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let means = array![[0., 0.], [5., 5.]];
+        // Strongly correlated features, which a diagonal covariance can't capture
+        let covariance = array![[1., 0.9], [0.9, 1.]];
+        let n = 300;
+        let mut observations = Array2::zeros((2 * n, 2));
+        for (i, mut row) in observations.rows_mut().into_iter().enumerate() {
+            let mvn = MultivariateNormal::new(&means.row(i / n), &covariance.view()).unwrap();
+            row.assign(&mvn.sample(&mut rng));
+        }
+        let fit = |covar_type| {
+            GaussianMixtureModel::params(2)
+                .covariance_type(covar_type)
+                .with_rng(rng.clone())
+                .fit(&DatasetBase::from(observations.clone()))
+                .expect("GMM fitting")
+        };
+        let full = fit(GmmCovarType::Full);
+        let diag = fit(GmmCovarType::Diag);
+
+        for covariance in diag.covariances().outer_iter() {
+            assert_eq!(covariance[[0, 1]], 0.);
+            assert_eq!(covariance[[1, 0]], 0.);
+        }
+        assert!(full.log_likelihood(&observations) > diag.log_likelihood(&observations));
+        assert!(full.bic(&observations) < diag.bic(&observations));
+        assert!(full.aic(&observations) < diag.aic(&observations));
+        // 2 * 3 covariance, 2 * 2 mean and 1 weight parameters
+        assert_abs_diff_eq!(
+            full.bic(&observations) - full.aic(&observations),
+            11. * ((2 * n) as f64).ln() - 22.,
+            epsilon = 1e-8
+        );
+
+        let proba = full.predict_proba(&observations);
+        assert_eq!(proba.dim(), (2 * n, 2));
+        proba
+            .sum_axis(Axis(1))
+            .for_each(|&total| assert_abs_diff_eq!(total, 1., epsilon = 1e-8));
+        let labels = full.predict(&observations);
+        Zip::from(proba.rows())
+            .and(&labels)
+            .for_each(|row, &label| assert_eq!(row.argmax().unwrap(), label));
+    }
+
     #[test]
     fn test_invalid_n_runs() {
         assert!(
@@ -16,6 +16,9 @@ use linfa::{Float, ParamGuard};
 pub enum GmmCovarType {
     /// each component has its own general covariance matrix
     Full,
+    /// each component has its own diagonal covariance matrix, i.e. features are independent
+    /// within each component
+    Diag,
 }
 
 #[cfg_attr(
@@ -1,14 +1,36 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::hash::Hash;
+use std::io::{self, Write};
+use std::ops::ControlFlow;
 
+use crate::k_means::centroid_tree::{CentroidTree, CentroidTreeCache};
 use crate::k_means::{KMeansParams, KMeansValidParams};
-use crate::IncrKMeansError;
-use crate::{k_means::errors::KMeansError, KMeansInit};
+use crate::{
+    fit_stats, AbortableKMeansError, ClusterSummary, DataStats, IncrKMeansError, KMeansParamsError,
+};
+use crate::{
+    k_means::errors::KMeansError, Accumulation, CentroidUpdate, ClusterPriors, EmptyClusters,
+    KMeansAlgorithm, KMeansInit,
+};
 use linfa::{prelude::*, DatasetBase, Float};
 use linfa_nn::distance::{Distance, L2Dist};
-use ndarray::{Array1, Array2, ArrayBase, Axis, Data, DataMut, Ix1, Ix2, Zip};
+use ndarray::parallel::prelude::*;
+use ndarray::{
+    Array1, Array2, ArrayBase, ArrayView1, ArrayView2, Axis, CowArray, Data, DataMut, Dimension,
+    Ix1, Ix2, Zip,
+};
 use ndarray_rand::rand::{Rng, SeedableRng};
+use ndarray_rand::rand_distr::StandardNormal;
+use ndarray_rand::RandomExt;
 use rand_xoshiro::Xoshiro256Plus;
+#[cfg(feature = "sprs")]
+use {
+    ndarray::s,
+    ndarray_rand::rand::distributions::{Distribution, WeightedIndex},
+    sprs::{CsMatView, CsVecView},
+};
 
 #[cfg(feature = "serde")]
 use serde_crate::{Deserialize, Serialize};
@@ -52,7 +74,8 @@ use serde_crate::{Deserialize, Serialize};
 /// The initialisation step is a one-off, done at the very beginning.
 /// Assignment and update are repeated in a loop until convergence is reached (either the
 /// euclidean distance between the old and the new clusters is below `tolerance` or
-/// we exceed the `max_n_iterations`).
+/// we exceed the `max_n_iterations`). The tolerance can also tighten over the iterations, see
+/// [`Convergence`](crate::Convergence).
 ///
 /// ## Incremental Algorithm
 ///
@@ -64,8 +87,32 @@ use serde_crate::{Deserialize, Serialize};
 /// Due to using smaller batches, Mini-Batch K-means takes significantly less time to execute than
 /// the standard K-means algorithm, although it may yield slightly worse centroids.
 ///
+/// The batches are chosen by the caller: [`MiniBatchSampling`](crate::MiniBatchSampling) can draw
+/// them either with replacement or as one shuffled pass over the dataset per epoch.
+///
 /// More details on Mini-Batch K-means can be found [here](https://www.eecs.tufts.edu/~dsculley/papers/fastkmeans.pdf).
 ///
+/// ## Distance metrics
+///
+/// The assignment step uses the distance metric of the hyperparameters, see
+/// [`KMeans::params_with`], while the update step computes each centroid from the members of its
+/// cluster according to [`CentroidUpdate`](crate::CentroidUpdate). Any metric implementing
+/// [`Distance`] works, for example:
+/// - [`L2Dist`] (the default) with the mean update, which minimizes the squared distances;
+/// - [`L1Dist`](linfa_nn::distance::L1Dist) with [`CentroidUpdate::Median`](crate::CentroidUpdate::Median),
+///   which minimizes the manhattan distances (K-medians);
+/// - [`CosineDist`](linfa_nn::distance::CosineDist), which clusters text and histogram data by
+///   direction. With observations normalized to unit length, the mean update points the
+///   centroids in the mean direction of their members (spherical K-means);
+/// - [`JensenShannonDist`](linfa_nn::distance::JensenShannonDist), which clusters probability
+///   distributions such as normalized histograms or topic vectors, since the mean of
+///   distributions is a distribution.
+///
+/// Fitting fails with [`KMeansError::InvalidObservation`] on observations outside the domain of
+/// the metric, such as observations that aren't distributions for the Jensen-Shannon distance.
+/// Precomputed initial centroids don't depend on the metric, so they can be combined with any of
+/// them.
+///
 /// ## Parallelisation
 ///
 /// The work performed by the assignment step does not require any coordination:
@@ -81,6 +128,9 @@ use serde_crate::{Deserialize, Serialize};
 /// (most likely due to our strategy used to split work between threads), hence
 /// the update step is currently executed on a single thread.
 ///
+/// A fitted model is `Send + Sync` and all its prediction methods take `&self`, so a single model
+/// can be shared between threads, for example behind an `Arc`, and queried concurrently.
+///
 /// ## Tutorial
 ///
 /// Let's do a walkthrough of a training-predict-save example.
@@ -180,19 +230,119 @@ pub struct KMeans<F: Float, D: Distance<F>> {
     cluster_count: Array1<F>,
     inertia: F,
     dist_fn: D,
+    // Feature weights and exponent `beta` learned by `KMeansAlgorithm::WeightedFeatures`
+    feature_weights: Option<(Array1<F>, F)>,
+    // Mean and standard deviation of the distances of the training points of each cluster to its
+    // centroid, or `None` if unknown. Missing from models serialized before it was added.
+    #[cfg_attr(feature = "serde", serde(default))]
+    member_dist_stats: Vec<Option<(F, F)>>,
+    // Statistics of the members of each cluster among the training points, or `None` if unknown.
+    // Missing from models serialized before it was added.
+    #[cfg_attr(feature = "serde", serde(default))]
+    training_summary: Option<ClusterSummary<F>>,
+    // Number of iterations of the K-means loop of the fit of the model, 0 for models serialized
+    // before it was added
+    #[cfg_attr(feature = "serde", serde(default))]
+    n_iterations: u64,
+    // Centroids and labels of the observations at each recorded iteration of the fit, see
+    // `record_trajectory`
+    #[cfg_attr(feature = "serde", serde(default))]
+    trajectory: Vec<(Array2<F>, Array1<usize>)>,
+    // Global statistics of the training observations, if the fit needed them
+    #[cfg_attr(feature = "serde", serde(default))]
+    data_stats: Option<DataStats<F>>,
+    // Why the K-means loop of the best run of the fit stopped, if the model comes from one
+    #[cfg_attr(feature = "serde", serde(default))]
+    stop_reason: Option<StopReason>,
+    // Seed of the random number generator of the hyperparameters of the fit, if they were given
+    // one with `random_seed`
+    #[cfg_attr(feature = "serde", serde(default))]
+    random_seed: Option<u64>,
+    // KD tree over the centroids, built by the first call to `predict_exact_tree` or
+    // `predict_approx` and cleared by the methods changing the centroids
+    #[cfg_attr(feature = "serde", serde(skip))]
+    centroid_tree: CentroidTreeCache<F>,
 }
 
 impl<F: Float> KMeans<F, L2Dist> {
     pub fn params(nclusters: usize) -> KMeansParams<F, Xoshiro256Plus, L2Dist> {
-        KMeansParams::new(nclusters, Xoshiro256Plus::seed_from_u64(42), L2Dist)
+        KMeansParams::new(nclusters, Xoshiro256Plus::seed_from_u64(42), L2Dist).random_seed(42)
     }
 
     pub fn params_with_rng<R: Rng>(nclusters: usize, rng: R) -> KMeansParams<F, R, L2Dist> {
         KMeansParams::new(nclusters, rng, L2Dist)
     }
+
+    /// Return the matrix of **squared** euclidean distances between each observation and each
+    /// centroid, with shape `(n_observations, n_clusters)`. This is the cost matrix of assigning
+    /// observations to clusters, as taken by optimal transport solvers.
+    ///
+    /// Unlike `transform`, which returns the distance to the closest centroid only, no square
+    /// root is taken. The distances are computed in blocks with
+    /// [`Distance::rdistance_matrix`], which boils down to a matrix product and can differ from
+    /// the pairwise distances by rounding errors. With feature weights, the weighted distance of
+    /// `predict` is used.
+    pub fn cost_matrix(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> Array2<F> {
+        let centroids = self.scale_features(&self.centroids);
+        let observations = self.scale_features(observations);
+        let mut costs = Array2::zeros((observations.nrows(), centroids.nrows()));
+        observations
+            .axis_chunks_iter(Axis(0), BATCH_SIZE)
+            .into_par_iter()
+            .zip(costs.axis_chunks_iter_mut(Axis(0), BATCH_SIZE))
+            .for_each(|(observations, mut costs)| {
+                costs.assign(&L2Dist.rdistance_matrix(observations, centroids.view()))
+            });
+        costs
+    }
+
+    /// Return inverse distance weighted soft assignments of `observations` to the clusters, with
+    /// shape `(n_observations, n_clusters)`: the membership of observation `i` in cluster `j` is
+    /// proportional to `1 / (d_ij² + eps)`, where `d_ij` is their euclidean distance, and the
+    /// memberships of each observation sum to 1.
+    ///
+    /// Unlike a softmax over the distances, there is no temperature to tune. `eps` keeps the
+    /// memberships finite for an observation lying on a centroid, which gets a membership close
+    /// to 1 in its cluster, and it should be small compared to the squared distances between
+    /// centroids. The squared distances are those of [`cost_matrix`](KMeans::cost_matrix).
+    pub fn predict_idw(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        eps: F,
+    ) -> Array2<F> {
+        // The matrix product of `cost_matrix` can round small squared distances below 0
+        let mut memberships = self
+            .cost_matrix(observations)
+            .mapv_into(|cost| F::one() / (cost.max(F::zero()) + eps));
+        memberships.rows_mut().into_iter().for_each(|mut row| {
+            let total = row.sum();
+            row /= total;
+        });
+        memberships
+    }
 }
 
 impl<F: Float, D: Distance<F>> KMeans<F, D> {
+    // Model with the given centroids and nothing known about their clusters, for the callers to
+    // fill in what they know
+    fn from_centroids(centroids: Array2<F>, dist_fn: D) -> Self {
+        KMeans {
+            cluster_count: Array1::zeros(centroids.nrows()),
+            centroids,
+            inertia: F::zero(),
+            dist_fn,
+            feature_weights: None,
+            member_dist_stats: Vec::new(),
+            training_summary: None,
+            n_iterations: 0,
+            trajectory: Vec::new(),
+            data_stats: None,
+            stop_reason: None,
+            random_seed: None,
+            centroid_tree: CentroidTreeCache::default(),
+        }
+    }
+
     pub fn params_with<R: Rng>(nclusters: usize, rng: R, dist_fn: D) -> KMeansParams<F, R, D> {
         KMeansParams::new(nclusters, rng, dist_fn)
     }
@@ -210,588 +360,6162 @@ impl<F: Float, D: Distance<F>> KMeans<F, D> {
 
     /// Return the sum of distances between each training point and its closest centroid, averaged
     /// across all training points.  When training incrementally, this value is computed on the
-    /// most recent batch.
+    /// most recent batch. With a [`DensityWeighting`](crate::DensityWeighting), the distances are
+    /// weighted by the sample weights, and with
+    /// [`fit_weighted`](crate::KMeansValidParams::fit_weighted) they are averaged with the weights
+    /// of the observations. [`stop_reason`](KMeans::stop_reason) tells whether the loop converged
+    /// or ran out of iterations, and [`score`](KMeans::score) evaluates held-out data.
     pub fn inertia(&self) -> F {
         self.inertia
     }
-}
 
-impl<F: Float, R: Rng + Clone, DA: Data<Elem = F>, T, D: Distance<F>>
-    Fit<ArrayBase<DA, Ix2>, T, KMeansError> for KMeansValidParams<F, R, D>
-{
-    type Object = KMeans<F, D>;
+    /// Return the number of iterations of the K-means loop that produced the centroids: the
+    /// iterations of the best run of `fit`, or the number of batches seen by `fit_with`. An
+    /// iteration is an assignment step followed by an update step. Methods editing the centroids
+    /// of a fitted model, such as [`refit_clusters`](KMeans::refit_clusters), leave it unchanged.
+    pub fn n_iterations(&self) -> u64 {
+        self.n_iterations
+    }
 
-    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`,
-    /// `fit` identifies `n_clusters` centroids based on the training data distribution.
+    /// Return the frames recorded by `fit` with
+    /// [`record_trajectory`](crate::KMeansParams::record_trajectory), empty otherwise. Frame `i`
+    /// holds the centroids at the start of iteration `i` of the best run and the labels the
+    /// assignment step of that iteration gave to the training observations, so consecutive
+    /// frames show both the moves of the centroids and the reassignments of the observations.
+    /// The centroids after the last update step are [`centroids`](KMeans::centroids).
+    pub fn trajectory(&self) -> &[(Array2<F>, Array1<usize>)] {
+        &self.trajectory
+    }
+
+    /// Return the global statistics of the training observations, which `fit` computes once when
+    /// a hyperparameter needs them, currently a non-zero `shrinkage`, and `None` otherwise. They
+    /// are the same as those of [`fit_stats`](crate::fit_stats) on the training observations.
+    pub fn data_stats(&self) -> Option<&DataStats<F>> {
+        self.data_stats.as_ref()
+    }
+
+    /// Return why the K-means loop of the best run of `fit` stopped, see [`StopReason`], or
+    /// `None` if the model doesn't come from that loop, such as a model of
+    /// [`fit_with`](linfa::traits::FitWith::fit_with) or of the mini-batch fit of
+    /// `memory_budget_bytes`. Methods editing the centroids of a fitted model leave it unchanged.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// Return the statistics of the members of each cluster among the training points, as
+    /// assigned by the last assignment step of the fit, see [`ClusterSummary`]. Methods which
+    /// reassign observations, such as [`refit_clusters`](KMeans::refit_clusters), refresh it from
+    /// their observations. Returns `None` if the model comes from
+    /// [`fit_with`](linfa::traits::FitWith::fit_with), after `remove_cluster` or
+    /// `reseed_centroid`, and for models serialized before it was added.
+    pub fn training_summary(&self) -> Option<&ClusterSummary<F>> {
+        self.training_summary.as_ref()
+    }
+
+    /// Return the seed of the random number generator of the hyperparameters that fitted the
+    /// model, set with [`random_seed`](crate::KMeansParams::random_seed) or 42 for
+    /// [`params`](KMeans::params), so that the model can be fitted again from its recorded
+    /// configuration. Returns `None` if the hyperparameters were given a generator, such as with
+    /// `params_with_rng`, or for models serialized before it was added.
+    pub fn random_seed(&self) -> Option<u64> {
+        self.random_seed
+    }
+
+    /// Return whether the best run of `fit` converged, that is whether its centroids ended up
+    /// moving by less than the tolerance or its assignments stopped changing
+    pub fn converged(&self) -> bool {
+        matches!(
+            self.stop_reason,
+            Some(StopReason::CentroidShift | StopReason::StableAssignments)
+        )
+    }
+
+    /// Return the opposite of the inertia of the model on `observations`, i.e. minus the mean of
+    /// the distances of the observations to their closest centroid, as given by `transform`.
+    /// Higher is better, which makes it suited to evaluate a fitted model on held-out data, or to
+    /// compare models whatever the size of the datasets. Returns 0 if there are no observations.
+    pub fn score(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> F {
+        if observations.nrows() == 0 {
+            return F::zero();
+        }
+        let dists: Array1<F> = self.transform(observations);
+        -dists.sum() / F::cast(observations.nrows())
+    }
+
+    /// Return the feature weights learned by
+    /// [`KMeansAlgorithm::WeightedFeatures`](crate::KMeansAlgorithm::WeightedFeatures), or `None`
+    /// if the model was trained with another algorithm.
     ///
-    /// An instance of `KMeans` is returned.
+    /// When feature weights are present, `predict`, `transform`, `predict_confidence` and
+    /// `predict_exact_tree` use the same weighted distance as the training loop. Other methods
+    /// use the unweighted distance.
+    pub fn feature_weights(&self) -> Option<&Array1<F>> {
+        self.feature_weights.as_ref().map(|(weights, _)| weights)
+    }
+
+    /// Return the distance metric of the model
+    pub fn dist_fn(&self) -> &D {
+        &self.dist_fn
+    }
+
+    /// Return the number of features of the observations the model was trained on, which
+    /// observations must have to be scored.
+    pub fn expects_features(&self) -> usize {
+        self.centroids.ncols()
+    }
+
+    /// Check that `observations` have the number of features the model expects, before scoring
+    /// them. `predict` and the other scoring methods assume it and panic otherwise, so this is a
+    /// cheap guard for models loaded from disk or for batches of untrusted data.
     ///
-    fn fit(
+    /// Returns [`KMeansError::FeatureMismatch`] if the number of columns doesn't match
+    /// [`expects_features`](Self::expects_features), and
+    /// [`KMeansError::NonFiniteObservation`] if an observation has a NaN or infinite value, which
+    /// would get an arbitrary label.
+    pub fn validate_input(
         &self,
-        dataset: &DatasetBase<ArrayBase<DA, Ix2>, T>,
-    ) -> Result<Self::Object, KMeansError> {
-        let mut rng = self.rng().clone();
-        let observations = dataset.records().view();
-        let n_samples = dataset.nsamples();
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Result<(), KMeansError> {
+        let (expected, actual) = (self.expects_features(), observations.ncols());
+        if expected != actual {
+            Err(KMeansError::FeatureMismatch { expected, actual })
+        } else if let Some(index) = non_finite_row(observations) {
+            Err(KMeansError::NonFiniteObservation { index })
+        } else {
+            Ok(())
+        }
+    }
 
-        let mut min_inertia = F::infinity();
-        let mut best_centroids = None;
-        let mut memberships = Array1::zeros(n_samples);
-        let mut dists = Array1::zeros(n_samples);
+    /// Factor applied to each feature by `scale_features`, if the model has feature weights
+    pub(crate) fn feature_scaling(&self) -> Option<Array1<F>> {
+        self.feature_weights
+            .as_ref()
+            .map(|(weights, beta)| feature_scale(weights, *beta))
+    }
 
-        let n_runs = self.n_runs();
+    /// Scale the features of `points` by the learned feature weights, if any, so that plain
+    /// distances between scaled points are the weighted distances used during training.
+    fn scale_features<'a, S: Data<Elem = F>, I: Dimension>(
+        &self,
+        points: &'a ArrayBase<S, I>,
+    ) -> CowArray<'a, F, I> {
+        match self.feature_scaling() {
+            None => points.view().into(),
+            Some(scale) => {
+                let mut points = points.to_owned();
+                let last_axis = Axis(points.ndim() - 1);
+                points
+                    .lanes_mut(last_axis)
+                    .into_iter()
+                    .for_each(|mut lane| lane *= &scale);
+                points.into()
+            }
+        }
+    }
 
-        for _ in 0..n_runs {
-            let mut centroids =
-                self.init_method()
-                    .run(self.dist_fn(), self.n_clusters(), observations, &mut rng);
-            let mut n_iter = 0;
-            let inertia = loop {
-                update_memberships_and_dists(
-                    self.dist_fn(),
-                    &centroids,
-                    &observations,
-                    &mut memberships,
-                    &mut dists,
-                );
-                let new_centroids = compute_centroids(&centroids, &observations, &memberships);
-                let distance = self
-                    .dist_fn()
-                    .distance(centroids.view(), new_centroids.view());
-                centroids = new_centroids;
-                n_iter += 1;
-                if distance < self.tolerance() || n_iter == self.max_n_iterations() {
-                    break dists.sum();
+    /// Mean and standard deviation of the distances of the training points of `cluster` to its
+    /// centroid, if known
+    fn dist_stats(&self, cluster: usize) -> Option<(F, F)> {
+        self.member_dist_stats.get(cluster).copied().flatten()
+    }
+
+    /// Remove the cluster at `index` from the model. The following clusters are renumbered so that
+    /// cluster indices stay contiguous, and `labels` is remapped accordingly.
+    ///
+    /// Only the labels of the observations are known here, so the members of the removed cluster
+    /// are all reassigned to the surviving centroid closest to the removed centroid, which also
+    /// inherits its training point count. `inertia` is left untouched.
+    ///
+    /// Returns an error if `index` is not a valid cluster index, or if the model has a single
+    /// cluster.
+    pub fn remove_cluster(
+        &mut self,
+        index: usize,
+        labels: &mut ArrayBase<impl DataMut<Elem = usize>, Ix1>,
+    ) -> Result<(), KMeansError> {
+        self.centroid_tree.clear();
+        let n_clusters = self.centroids.nrows();
+        if index >= n_clusters {
+            return Err(KMeansError::InvalidClusterIndex { index, n_clusters });
+        } else if n_clusters == 1 {
+            return Err(KMeansError::NoClusterLeft);
+        }
+
+        let kept: Vec<usize> = (0..n_clusters).filter(|&c| c != index).collect();
+        let centroids = self.centroids.select(Axis(0), &kept);
+        let (target, _) = closest_centroid(&self.dist_fn, &centroids, &self.centroids.row(index));
+        let mut cluster_count = self.cluster_count.select(Axis(0), &kept);
+        cluster_count[target] += self.cluster_count[index];
+
+        labels.mapv_inplace(|label| match label.cmp(&index) {
+            Ordering::Less => label,
+            Ordering::Equal => target,
+            Ordering::Greater => label - 1,
+        });
+        // The target cluster gained members whose distances are unknown
+        self.member_dist_stats = kept
+            .iter()
+            .enumerate()
+            .map(|(new, &old)| {
+                if new == target {
+                    None
+                } else {
+                    self.dist_stats(old)
                 }
-            };
+            })
+            .collect();
+        self.training_summary = None;
+        self.centroids = centroids;
+        self.cluster_count = cluster_count;
+        Ok(())
+    }
 
-            // We keep the centroids which minimize the inertia (defined as the sum of
-            // the squared distances of the closest centroid for all observations)
-            // over the n runs of the KMeans algorithm.
-            if inertia < min_inertia {
-                min_inertia = inertia;
-                best_centroids = Some(centroids.clone());
-            }
+    /// Reorder the clusters of the model by lexicographic order of their centroid coordinates,
+    /// and remap `labels` accordingly. Two fits which found the same clusters in a different order
+    /// become comparable: their centroids and labels are equal after canonicalization, up to
+    /// floating point differences between the centroids. Centroids that differ only by such
+    /// differences in their leading coordinates may still be ordered differently.
+    ///
+    /// Cluster counts and distance statistics follow their cluster.
+    pub fn canonicalize(&mut self, labels: &mut ArrayBase<impl DataMut<Elem = usize>, Ix1>) {
+        self.centroid_tree.clear();
+        let mut order: Vec<usize> = (0..self.centroids.nrows()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (self.centroids.row(a), self.centroids.row(b));
+            a.iter()
+                .zip(b.iter())
+                .map(|(a, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .find(|&ordering| ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let mut new_labels = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_labels[old] = new;
+        }
+        labels.mapv_inplace(|label| new_labels[label]);
+        self.centroids = self.centroids.select(Axis(0), &order);
+        self.cluster_count = self.cluster_count.select(Axis(0), &order);
+        self.member_dist_stats = order.iter().map(|&c| self.dist_stats(c)).collect();
+        if let Some(summary) = &mut self.training_summary {
+            summary.reorder(&order);
         }
+    }
 
-        match best_centroids {
-            Some(centroids) => {
-                let mut cluster_count = Array1::zeros(self.n_clusters());
-                memberships
-                    .iter()
-                    .for_each(|&c| cluster_count[c] += F::one());
-                Ok(KMeans {
-                    centroids,
-                    cluster_count,
-                    inertia: min_inertia / F::cast(dataset.nsamples()),
-                    dist_fn: self.dist_fn().clone(),
-                })
-            }
-            _ => Err(KMeansError::InertiaError),
+    /// Move the centroid at `index` to `new_position`, to steer the clustering by hand. Training
+    /// can then go on from the edited model, for instance with
+    /// [`fit_with`](linfa::traits::FitWith::fit_with) or `perturb_and_refit`.
+    ///
+    /// The distance statistics of the moved cluster are discarded, so that
+    /// `predict_with_zscore_rejection` never rejects observations closest to it until they are
+    /// refreshed. Cluster counts and `inertia` are left untouched.
+    ///
+    /// Returns an error if `index` is not a valid cluster index, or if `new_position` doesn't have
+    /// the number of features of the model.
+    pub fn reseed_centroid(
+        &mut self,
+        index: usize,
+        new_position: Array1<F>,
+    ) -> Result<(), KMeansError> {
+        self.centroid_tree.clear();
+        let n_clusters = self.centroids.nrows();
+        if index >= n_clusters {
+            return Err(KMeansError::InvalidClusterIndex { index, n_clusters });
+        }
+        let (expected, actual) = (self.expects_features(), new_position.len());
+        if expected != actual {
+            return Err(KMeansError::FeatureMismatch { expected, actual });
+        }
+
+        self.centroids.row_mut(index).assign(&new_position);
+        if let Some(stats) = self.member_dist_stats.get_mut(index) {
+            *stats = None;
         }
+        self.training_summary = None;
+        Ok(())
     }
-}
 
-impl<'a, F: Float + Debug, R: Rng + Clone, DA: Data<Elem = F>, T, D: 'a + Distance<F> + Debug>
-    FitWith<'a, ArrayBase<DA, Ix2>, T, IncrKMeansError<KMeans<F, D>>>
-    for KMeansValidParams<F, R, D>
-{
-    type ObjectIn = Option<KMeans<F, D>>;
-    type ObjectOut = KMeans<F, D>;
+    /// Returns the edges of a minimum spanning tree of the centroids, as `(a, b, distance)`
+    /// triples where `distance` is the distance between centroids `a` and `b` according to the
+    /// distance metric of the model (euclidean by default). There are `n_clusters - 1` edges,
+    /// which connect every centroid.
+    ///
+    /// Clusters joined by an edge are adjacent in the topology of the clustering, and cutting the
+    /// longest edges groups the clusters into super-clusters. The tree is grown by Prim's
+    /// algorithm from the first centroid, in `O(n_clusters²)` distance computations, and the
+    /// edges are listed in the order they were added, `a` being the centroid already in the tree.
+    pub fn centroid_mst(&self) -> Vec<(usize, usize, F)> {
+        let n_clusters = self.centroids.nrows();
+        let dist = |a: usize, b: usize| {
+            self.dist_fn
+                .distance(self.centroids.row(a), self.centroids.row(b))
+        };
+        // Closest centroid of the tree to each centroid outside of it, with its distance
+        let mut closest: Vec<Option<(usize, F)>> = (0..n_clusters)
+            .map(|c| (c > 0).then(|| (0, dist(0, c))))
+            .collect();
+        let mut edges = Vec::with_capacity(n_clusters.saturating_sub(1));
+        while let Some((next, (from, distance))) = closest
+            .iter()
+            .enumerate()
+            .filter_map(|(c, closest)| closest.map(|closest| (c, closest)))
+            .min_by(|a, b| (a.1).1.partial_cmp(&(b.1).1).unwrap_or(Ordering::Equal))
+        {
+            edges.push((from, next, distance));
+            closest[next] = None;
+            for (c, closest) in closest.iter_mut().enumerate() {
+                if let Some((_, best)) = closest {
+                    let d = dist(next, c);
+                    if d < *best {
+                        *closest = Some((next, d));
+                    }
+                }
+            }
+        }
+        edges
+    }
 
-    /// Performs a single batch update of the Mini-Batch K-means algorithm.
+    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`, return the
+    /// index of the closest centroid for each observation, like `predict`, along with a confidence
+    /// for each assignment.
     ///
-    /// Given an input matrix `observations`, with shape `(n_batch, n_features)` and a previous
-    /// `KMeans` model, the model's centroids are updated with the input matrix. If `model` is
-    /// `None`, then it's initialized using the specified initialization algorithm. The return
-    /// value consists of the updated model and a `bool` value that indicates whether the algorithm
-    /// has converged.
-    fn fit_with(
+    /// The confidence is computed from the distances `d1` and `d2` of the observation to its
+    /// closest and second closest centroids as `1 - d1 / (d1 + d2)`. It lies between `0.5`, for an
+    /// observation equally distant from both centroids, and `1`, for an observation lying on its
+    /// centroid. With a single cluster the confidence is always `1`.
+    pub fn predict_confidence(
         &self,
-        model: Self::ObjectIn,
-        dataset: &'a DatasetBase<ArrayBase<DA, Ix2>, T>,
-    ) -> Result<Self::ObjectOut, IncrKMeansError<Self::ObjectOut>> {
-        let observations = dataset.records().view();
-        let n_samples = dataset.nsamples();
-
-        let mut model = match model {
-            Some(model) => model,
-            None => {
-                let centroids = if let KMeansInit::Precomputed(centroids) = self.init_method() {
-                    // If using precomputed centroids, don't run the init algorithm multiple times
-                    centroids.clone()
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> (Array1<usize>, Array1<F>) {
+        let centroids = self.scale_features(&self.centroids);
+        let observations = self.scale_features(observations);
+        let mut memberships = Array1::zeros(observations.nrows());
+        let mut confidences = Array1::zeros(observations.nrows());
+        Zip::from(observations.rows())
+            .and(&mut memberships)
+            .and(&mut confidences)
+            .par_for_each(|observation, membership, confidence| {
+                let ((closest, dist), (_, second_dist)) =
+                    closest_two_centroids(&self.dist_fn, &centroids, &observation);
+                let dist = self.dist_fn.rdist_to_dist(dist);
+                let second_dist = self.dist_fn.rdist_to_dist(second_dist);
+                *membership = closest;
+                *confidence = if second_dist.is_infinite() {
+                    F::one()
+                } else if dist + second_dist == F::zero() {
+                    // The observation lies on two identical centroids
+                    F::cast(0.5)
                 } else {
-                    let mut rng = self.rng().clone();
-                    let mut dists = Array1::zeros(n_samples);
-                    // Initial centroids derived from the first batch by running the init algorithm
-                    // n_runs times and taking the centroids with the lowest inertia
-                    (0..self.n_runs())
-                        .map(|_| {
-                            let centroids = self.init_method().run(
-                                self.dist_fn(),
-                                self.n_clusters(),
-                                observations,
-                                &mut rng,
-                            );
-                            update_min_dists(self.dist_fn(), &centroids, &observations, &mut dists);
-                            (centroids, dists.sum())
-                        })
-                        .min_by(|(_, d1), (_, d2)| {
-                            if d1 < d2 {
-                                Ordering::Less
-                            } else {
-                                Ordering::Greater
-                            }
-                        })
-                        .unwrap()
-                        .0
+                    F::one() - dist / (dist + second_dist)
                 };
-                KMeans {
-                    centroids,
-                    cluster_count: Array1::zeros(self.n_clusters()),
-                    inertia: F::zero(),
-                    dist_fn: self.dist_fn().clone(),
-                }
-            }
-        };
+            });
+        (memberships, confidences)
+    }
 
-        let mut memberships = Array1::zeros(n_samples);
-        let mut dists = Array1::zeros(n_samples);
-        update_memberships_and_dists(
-            self.dist_fn(),
-            &model.centroids,
-            &observations,
+    /// Assigns each observation to its closest centroid, like `predict`, and also returns the
+    /// number of observations assigned to each cluster, as an array of length `n_clusters`. This
+    /// is useful to monitor drifts in the distribution of the assignments when serving a model.
+    pub fn predict_with_counts(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> (Array1<usize>, Array1<usize>) {
+        let mut memberships = Array1::zeros(observations.nrows());
+        let mut dists = Array1::zeros(observations.nrows());
+        batch_update_memberships_and_dists(
+            &self.dist_fn,
+            &self.scale_features(&self.centroids),
+            &self.scale_features(observations),
             &mut memberships,
             &mut dists,
         );
-        let new_centroids = compute_centroids_incremental(
-            &observations,
-            &memberships,
-            &model.centroids,
-            &mut model.cluster_count,
+        let mut counts = Array1::zeros(self.centroids.nrows());
+        memberships.iter().for_each(|&c| counts[c] += 1);
+        (memberships, counts)
+    }
+
+    /// Assigns each observation to its closest centroid, like `predict`, and also returns the
+    /// distance of each observation to that centroid, in the metric of the model, while
+    /// `transform` returns its reduced distance, the squared distance for `L2Dist`.
+    pub fn predict_with_distances(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> (Array1<usize>, Array1<F>) {
+        let mut memberships = Array1::zeros(observations.nrows());
+        let mut dists = Array1::zeros(observations.nrows());
+        batch_update_memberships_and_dists(
+            &self.dist_fn,
+            &self.scale_features(&self.centroids),
+            &self.scale_features(observations),
+            &mut memberships,
+            &mut dists,
         );
-        model.inertia = dists.sum() / F::cast(n_samples);
-        let dist = self
-            .dist_fn()
-            .distance(model.centroids.view(), new_centroids.view());
-        model.centroids = new_centroids;
+        (
+            memberships,
+            dists.mapv_into(|d| self.dist_fn.rdist_to_dist(d)),
+        )
+    }
 
-        if dist < self.tolerance() {
-            Ok(model)
-        } else {
-            Err(IncrKMeansError::NotConverged(model))
+    /// Return the matrix of distances between each observation and each centroid, in the metric
+    /// of the model, with shape `(n_observations, n_clusters)`, for use as cluster-distance
+    /// features by a downstream model. With feature weights, the weighted distance of `predict`
+    /// is used.
+    ///
+    /// Unlike [`cost_matrix`](KMeans::cost_matrix), which returns squared euclidean distances,
+    /// this works with any metric, and computes each distance separately.
+    pub fn centroid_distances(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Array2<F> {
+        let centroids = self.scale_features(&self.centroids);
+        let observations = self.scale_features(observations);
+        let mut distances = Array2::zeros((observations.nrows(), centroids.nrows()));
+        Zip::from(distances.rows_mut())
+            .and(observations.rows())
+            .par_for_each(|mut distances, observation| {
+                Zip::from(&mut distances)
+                    .and(centroids.rows())
+                    .for_each(|d, centroid| *d = self.dist_fn.distance(observation, centroid));
+            });
+        distances
+    }
+
+    /// Assigns a new batch of `observations` to the clusters, like `predict`, and compares the
+    /// assignments to the training ones to monitor drifts of the data, see [`DriftReport`].
+    pub fn drift_report(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> DriftReport<F> {
+        let (memberships, batch_counts) = self.predict_with_counts(observations);
+        let n_batch = F::cast(observations.nrows());
+        let n_training = self.cluster_count.sum();
+        let ratios = Zip::from(&batch_counts)
+            .and(&self.cluster_count)
+            .map_collect(|&batch_count, &training_count| {
+                let batch_share = if batch_count == 0 {
+                    F::zero()
+                } else {
+                    F::cast(batch_count) / n_batch
+                };
+                match (training_count > F::zero(), batch_count > 0) {
+                    (true, _) => batch_share / (training_count / n_training),
+                    (false, true) => F::infinity(),
+                    (false, false) => F::one(),
+                }
+            });
+
+        let mut mean_shifts = Array2::zeros(self.centroids.dim());
+        Zip::from(observations.rows())
+            .and(&memberships)
+            .for_each(|observation, &c| {
+                mean_shifts.row_mut(c).scaled_add(F::one(), &observation);
+            });
+        Zip::from(mean_shifts.rows_mut())
+            .and(self.centroids.rows())
+            .and(&batch_counts)
+            .for_each(|mut shift, centroid, &count| {
+                if count > 0 {
+                    shift /= F::cast(count);
+                    shift -= &centroid;
+                }
+            });
+
+        DriftReport {
+            batch_counts,
+            training_counts: self.cluster_count.clone(),
+            ratios,
+            mean_shifts,
         }
     }
-}
 
-impl<F: Float, DA: Data<Elem = F>, D: Distance<F>> Transformer<&ArrayBase<DA, Ix2>, Array1<F>>
-    for KMeans<F, D>
-{
-    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`,
-    /// `transform` returns, for each observation, its squared distance to its centroid.
-    fn transform(&self, observations: &ArrayBase<DA, Ix2>) -> Array1<F> {
+    /// Assigns each observation to its closest centroid, like `predict`, unless it lies more than
+    /// `z` standard deviations farther from the centroid than the training points of its
+    /// cluster, in which case it is rejected as a novelty and `None` is returned instead.
+    ///
+    /// An observation at distance `d` from its closest centroid is rejected if
+    /// `d > mean + z * std`, where `mean` and `std` are the mean and standard deviation of the
+    /// distances of the training points of that cluster to its centroid. Unlike a single
+    /// threshold on `d`, this adapts to the spread of each cluster. The statistics are computed
+    /// by `fit`, on the last batch by `fit_with`, and refreshed by `split_until_radius` and
+    /// `perturb_and_refit`. Observations closest to a cluster without statistics, such as a
+    /// cluster that was empty during training or edited since, are never rejected.
+    pub fn predict_with_zscore_rejection(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        z: F,
+    ) -> Array1<Option<usize>> {
+        let mut memberships = Array1::zeros(observations.nrows());
         let mut dists = Array1::zeros(observations.nrows());
-        update_min_dists(
+        batch_update_memberships_and_dists(
             &self.dist_fn,
-            &self.centroids,
-            &observations.view(),
+            &self.scale_features(&self.centroids),
+            &self.scale_features(observations),
+            &mut memberships,
             &mut dists,
         );
-        dists
+        Zip::from(&memberships)
+            .and(&dists)
+            .map_collect(|&c, &rdist| match self.dist_stats(c) {
+                Some((mean, std)) if self.dist_fn.rdist_to_dist(rdist) > mean + z * std => None,
+                _ => Some(c),
+            })
     }
-}
 
-impl<F: Float, DA: Data<Elem = F>, D: Distance<F>> PredictInplace<ArrayBase<DA, Ix2>, Array1<usize>>
-    for KMeans<F, D>
-{
-    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`,
-    /// `predict` returns, for each observation, the index of the closest cluster/centroid.
+    /// Vector quantization of `observations` with the centroids as codebook: returns a matrix with
+    /// the same shape as `observations` where each observation is replaced by the coordinates of
+    /// its closest centroid, as given by `predict`.
+    pub fn quantize(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> Array2<F> {
+        let memberships: Array1<usize> = self.predict(observations);
+        self.centroids
+            .select(Axis(0), memberships.as_slice().unwrap())
+    }
+
+    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`, returns
+    /// for each observation the coordinates of its closest centroid, with the same shape, rather
+    /// than its index as `predict` does. Row `i` is `centroids().row(predict(observations)[i])`,
+    /// without the gather step.
     ///
-    /// You can retrieve the centroid associated to an index using the
-    /// [`centroids` method](#method.centroids).
-    fn predict_inplace(&self, observations: &ArrayBase<DA, Ix2>, memberships: &mut Array1<usize>) {
+    /// Same as [`quantize`](KMeans::quantize). Not to be confused with
+    /// [`transform`](linfa::traits::Transformer::transform), which returns the distance of each
+    /// observation to its closest centroid.
+    pub fn predict_centroids(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Array2<F> {
+        self.quantize(observations)
+    }
+
+    /// Return the index of the closest centroid of a single `observation`, with shape
+    /// `(n_features,)`, like `predict` on a matrix with a single row, without wrapping it in one.
+    ///
+    /// Panics if `observation` doesn't have [`expects_features`](KMeans::expects_features)
+    /// features.
+    pub fn predict_one(&self, observation: &ArrayBase<impl Data<Elem = F>, Ix1>) -> usize {
         assert_eq!(
-            observations.nrows(),
-            memberships.len(),
-            "The number of data points must match the number of memberships."
+            observation.len(),
+            self.expects_features(),
+            "The observation must have as many features as the centroids."
         );
-
-        update_cluster_memberships(
+        closest_centroid(
             &self.dist_fn,
-            &self.centroids,
-            &observations.view(),
-            memberships,
-        );
+            &self.scale_features(&self.centroids),
+            &self.scale_features(observation),
+        )
+        .0
     }
 
-    fn default_target(&self, x: &ArrayBase<DA, Ix2>) -> Array1<usize> {
-        Array1::zeros(x.nrows())
+    /// Mean squared reconstruction error of [`quantize`](KMeans::quantize): the squared euclidean
+    /// distance between each observation and its closest centroid, averaged over the
+    /// observations. Returns 0 if there are no observations.
+    pub fn quantization_error(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> F {
+        if observations.nrows() == 0 {
+            return F::zero();
+        }
+        let memberships: Array1<usize> = self.predict(observations);
+        let total = Zip::from(observations.rows()).and(&memberships).fold(
+            F::zero(),
+            |acc, observation, &membership| {
+                acc + L2Dist.rdistance(observation, self.centroids.row(membership))
+            },
+        );
+        total / F::cast(observations.nrows())
     }
-}
 
-impl<F: Float, DA: Data<Elem = F>, D: Distance<F>> PredictInplace<ArrayBase<DA, Ix1>, usize>
-    for KMeans<F, D>
-{
-    /// Given one input observation, return the index of its closest cluster
+    /// Lazily assigns each observation to its closest centroid, like `predict`, and yields one
+    /// [`AssignmentRecord`] per observation, in order, with the distance to the centroid. This
+    /// suits row-oriented sinks such as database inserts, without allocating arrays of labels and
+    /// distances to zip afterwards.
     ///
-    /// You can retrieve the centroid associated to an index using the
-    /// [`centroids` method](#method.centroids).
-    fn predict_inplace(&self, observation: &ArrayBase<DA, Ix1>, membership: &mut usize) {
-        *membership = closest_centroid(&self.dist_fn, &self.centroids, observation).0;
+    /// Each observation is only processed when its record is requested, on the current thread.
+    pub fn assignments<'a>(
+        &'a self,
+        observations: &'a ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> impl Iterator<Item = AssignmentRecord<F>> + 'a {
+        let centroids = self.scale_features(&self.centroids);
+        observations
+            .rows()
+            .into_iter()
+            .enumerate()
+            .map(move |(index, observation)| {
+                let observation = self.scale_features(&observation);
+                let (cluster, dist) = closest_centroid(&self.dist_fn, &centroids, &observation);
+                AssignmentRecord {
+                    index,
+                    cluster,
+                    distance: self.dist_fn.rdist_to_dist(dist),
+                }
+            })
     }
 
-    fn default_target(&self, _x: &ArrayBase<DA, Ix1>) -> usize {
-        0
-    }
-}
+    /// Assigns each observation to its closest centroid, like `predict`, and writes the labels to
+    /// `writer` in the given `format` instead of returning them.
+    ///
+    /// Observations are processed in blocks of a few thousands, so memory usage stays bounded
+    /// whatever the number of observations. Large inputs can thus be scored by calling this on
+    /// consecutive chunks of data with the same writer, each call appending the labels of its
+    /// chunk. Writes go straight to `writer`, so wrapping it in a
+    /// [`BufWriter`](std::io::BufWriter) is recommended for files and sockets.
+    pub fn predict_to_writer<W: Write>(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        format: LabelFormat,
+        mut writer: W,
+    ) -> io::Result<()> {
+        // Large enough for the blocks of `batch_update_memberships_and_dists` to run in parallel
+        const CHUNK_SIZE: usize = 64 * BATCH_SIZE;
 
-/// K-means is an iterative algorithm.
-/// We will perform the assignment and update steps until we are satisfied
-/// (according to our convergence criteria).
-///
-/// `compute_centroids` returns a 2-dimensional array,
-/// where the i-th row corresponds to the i-th cluster.
-fn compute_centroids<F: Float>(
-    old_centroids: &Array2<F>,
-    // (n_observations, n_features)
-    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
-    // (n_observations,)
-    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
-) -> Array2<F> {
-    let n_clusters = old_centroids.nrows();
-    let mut counts: Array1<usize> = Array1::ones(n_clusters);
-    let mut centroids = Array2::zeros((n_clusters, observations.ncols()));
+        if format == LabelFormat::Binary {
+            writer.write_all(&(observations.nrows() as u64).to_le_bytes())?;
+        }
+        let centroids = self.scale_features(&self.centroids);
+        for chunk in observations.axis_chunks_iter(Axis(0), CHUNK_SIZE) {
+            let mut memberships = Array1::zeros(chunk.nrows());
+            let mut dists = Array1::zeros(chunk.nrows());
+            batch_update_memberships_and_dists(
+                &self.dist_fn,
+                &centroids,
+                &self.scale_features(&chunk),
+                &mut memberships,
+                &mut dists,
+            );
+            for &membership in &memberships {
+                match format {
+                    LabelFormat::Text => writeln!(writer, "{}", membership)?,
+                    LabelFormat::Binary => writer.write_all(&(membership as u64).to_le_bytes())?,
+                }
+            }
+        }
+        Ok(())
+    }
 
-    Zip::from(observations.rows())
-        .and(cluster_memberships)
-        .for_each(|observation, &cluster_membership| {
-            let mut centroid = centroids.row_mut(cluster_membership);
-            centroid += &observation;
-            counts[cluster_membership] += 1;
-        });
-    // m_k-means: Treat the old centroid like another point in the cluster
-    centroids += old_centroids;
+    /// Assigns each observation of each of the `batches` to its closest centroid, like `predict`,
+    /// returning an iterator of the labels of each batch.
+    ///
+    /// Batches are labelled lazily as the iterator is advanced, so that datasets which don't fit
+    /// in memory can be labelled block by block, for example when read from files or a
+    /// database. Like `predict`, each batch is labelled in parallel.
+    pub fn predict_batches<'a, I, S>(
+        &'a self,
+        batches: I,
+    ) -> impl Iterator<Item = Array1<usize>> + 'a
+    where
+        I: IntoIterator<Item = ArrayBase<S, Ix2>>,
+        I::IntoIter: 'a,
+        S: Data<Elem = F>,
+    {
+        batches.into_iter().map(move |batch| self.predict(&batch))
+    }
 
-    Zip::from(centroids.rows_mut())
-        .and(&counts)
-        .for_each(|mut centroid, &cnt| centroid /= F::cast(cnt));
-    centroids
+    /// Assigns each observation to its closest centroid, like `predict`, returning an iterator of
+    /// the labels of consecutive blocks of `chunk_size` observations, the last block holding the
+    /// remaining ones.
+    ///
+    /// Only the labels and intermediate distances of a single block are held in memory at a
+    /// time, which suits observations backed by a memory-mapped file, see
+    /// [`predict_batches`](KMeans::predict_batches).
+    ///
+    /// Panics if `chunk_size` is 0.
+    pub fn predict_chunked<'a>(
+        &'a self,
+        observations: &'a ArrayBase<impl Data<Elem = F>, Ix2>,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Array1<usize>> + 'a {
+        assert!(chunk_size > 0, "The chunk size cannot be 0.");
+        self.predict_batches(observations.axis_chunks_iter(Axis(0), chunk_size))
+    }
+
+    /// Return a `(n_clusters, n_clusters)` matrix measuring how much clusters overlap on
+    /// `observations`. Entry `(i, j)` is the fraction of the observations closest to centroid `i`
+    /// whose second closest centroid is `j`.
+    ///
+    /// Each row sums to 1, except for clusters without any observation and for models with a
+    /// single cluster, whose rows are all 0. Observations near the boundary between two clusters
+    /// contribute to the off-diagonal entries regardless of how close to the boundary they are, so
+    /// large off-diagonal values mostly flag pairs of clusters that are adjacent to each other
+    /// without a clear gap in between.
+    pub fn cluster_overlap_matrix(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Array2<F> {
+        let centroids = self.scale_features(&self.centroids);
+        let observations = self.scale_features(observations);
+        let n_clusters = centroids.nrows();
+        let mut overlap = Array2::zeros((n_clusters, n_clusters));
+        let mut counts = Array1::<F>::zeros(n_clusters);
+        for observation in observations.rows() {
+            let ((closest, _), (second, second_dist)) =
+                closest_two_centroids(&self.dist_fn, &centroids, &observation);
+            counts[closest] += F::one();
+            if second_dist.is_finite() {
+                overlap[(closest, second)] += F::one();
+            }
+        }
+        Zip::from(overlap.rows_mut())
+            .and(&counts)
+            .for_each(|mut row, &count| {
+                if count > F::zero() {
+                    row /= count;
+                }
+            });
+        overlap
+    }
+
+    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`, return the
+    /// index of the closest centroid for each observation, like `predict`, but by querying a KD
+    /// tree over the centroids instead of scanning all of them.
+    ///
+    /// The result is exact, not approximate, with ties going to the lowest index as in `predict`.
+    /// The tree is built by the first call to `predict_exact_tree` or
+    /// [`predict_approx`](KMeans::predict_approx) and cached on the model, then rebuilt after
+    /// the centroids change, for instance by [`update`](KMeans::update) or
+    /// [`remove_cluster`](KMeans::remove_cluster). The tree isn't serialized with the model.
+    ///
+    /// Looking up a centroid in the tree has a larger constant cost than computing a distance, so
+    /// this only pays off for a large number of clusters: on 3-dimensional data (see the
+    /// `k_means_predict` benchmark) the tree is slower than `predict` for 10 clusters, about twice
+    /// as fast with 50 clusters, and roughly 12 times faster with 1000 clusters. The benefit
+    /// shrinks as the number of features grows.
+    ///
+    /// Pruning the tree assumes that the distance between two points is at least their distance
+    /// along any single feature, as for the distances of the Minkowski family, see
+    /// [`Distance::is_minkowski`]. With other distances, such as the cosine distance, this falls
+    /// back to the scan of `predict`.
+    ///
+    /// Panics if the number of features of `observations` doesn't match the centroids.
+    pub fn predict_exact_tree(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Array1<usize> {
+        self.predict_with_tree(observations, usize::MAX)
+    }
+
+    /// Same as [`predict_exact_tree`](KMeans::predict_exact_tree), but only looks for the closest
+    /// centroid of each observation in the first `max_leaves` leaves of the tree that could hold
+    /// it, visiting the leaf of the observation first. Each leaf holds up to 8 centroids.
+    ///
+    /// The centroid found may not be the closest one, but the search stops after a bounded amount
+    /// of work, which is faster than the exact search when a lot of leaves lie close to the
+    /// observations, as with many clusters or features. `max_leaves` of 1 is the fastest and
+    /// least accurate setting, 0 being treated as 1, while `usize::MAX` gives the exact result.
+    /// On the `k_means_predict` benchmark, a single leaf is about 1.7 times faster than the exact
+    /// search with 1000 clusters.
+    ///
+    /// Panics if the number of features of `observations` doesn't match the centroids.
+    pub fn predict_approx(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        max_leaves: usize,
+    ) -> Array1<usize> {
+        self.predict_with_tree(observations, max_leaves)
+    }
+
+    fn predict_with_tree(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        max_leaves: usize,
+    ) -> Array1<usize> {
+        assert_eq!(
+            observations.ncols(),
+            self.centroids.ncols(),
+            "The number of features must match the number of features of the centroids."
+        );
+        if !self.dist_fn.is_minkowski() {
+            return self.predict(observations);
+        }
+        let tree = self
+            .centroid_tree
+            .get_or_build(|| CentroidTree::new(self.scale_features(&self.centroids).into_owned()));
+        let observations = self.scale_features(observations);
+
+        let mut memberships = Array1::zeros(observations.nrows());
+        Zip::from(observations.rows())
+            .and(&mut memberships)
+            .par_for_each(|observation, membership| {
+                *membership = tree
+                    .closest_centroid(&self.dist_fn, observation, max_leaves)
+                    .0
+            });
+        memberships
+    }
+
+    /// Split clusters whose radius on `observations` is larger than `max_radius`, until all
+    /// clusters are within that radius or the model has `max_clusters` clusters.
+    ///
+    /// The radius of a cluster is the largest distance between its centroid and the observations
+    /// closest to it. At each step the cluster with the largest radius is split in two by running
+    /// 2-means on its members, seeded from `rng`, and all observations are then assigned to their
+    /// closest centroid again. A cluster whose members are all the same point is never split: its
+    /// centroid moves onto that point instead. The cluster counts and inertia of the model are
+    /// refreshed from `observations`.
+    ///
+    /// Returns the index of the closest centroid of each observation in the final model, or an
+    /// error if `observations` fail [`validate_input`](KMeans::validate_input), if the radius of a
+    /// cluster is NaN because of non-finite centroids, or if the 2-means fit of a cluster fails.
+    pub fn split_until_radius(
+        &mut self,
+        observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+        max_radius: F,
+        max_clusters: usize,
+        rng: &mut impl Rng,
+    ) -> Result<Array1<usize>, KMeansError> {
+        self.validate_input(observations)?;
+        self.centroid_tree.clear();
+        let n_samples = observations.nrows();
+        let mut memberships = Array1::zeros(n_samples);
+        let mut dists = Array1::zeros(n_samples);
+        let mut inertia;
+        loop {
+            inertia = update_memberships_and_dists(
+                &self.dist_fn,
+                &self.centroids,
+                observations,
+                &mut memberships,
+                &mut dists,
+            );
+            let n_clusters = self.centroids.nrows();
+            if n_clusters >= max_clusters {
+                break;
+            }
+            if let Some(i) = dists.iter().position(|dist| dist.is_nan()) {
+                return Err(KMeansError::NonFiniteRadius {
+                    index: memberships[i],
+                });
+            }
+            let mut radii = Array1::zeros(n_clusters);
+            Zip::from(&memberships).and(&dists).for_each(|&c, &d| {
+                radii[c] = F::max(radii[c], self.dist_fn.rdist_to_dist(d));
+            });
+            // Clusters covered exactly by their centroid can't be split any further
+            let widest = radii
+                .iter()
+                .enumerate()
+                .filter(|&(_, &radius)| radius > max_radius && radius > F::zero())
+                .fold(
+                    None,
+                    |widest: Option<(usize, F)>, (c, &radius)| match widest {
+                        Some((_, max)) if max >= radius => widest,
+                        _ => Some((c, radius)),
+                    },
+                );
+            let widest = match widest {
+                Some((widest, _)) => widest,
+                None => break,
+            };
+
+            let members: Vec<usize> = (0..n_samples)
+                .filter(|&i| memberships[i] == widest)
+                .collect();
+            let first = observations.row(members[0]);
+            if members[1..].iter().all(|&i| observations.row(i) == first) {
+                // Copies of a single observation can't be split, but they can be covered exactly
+                self.centroids.row_mut(widest).assign(&first);
+                continue;
+            }
+            let members = DatasetBase::from(observations.select(Axis(0), &members));
+            let halves = KMeans::params_with(
+                2,
+                Xoshiro256Plus::seed_from_u64(rng.gen()),
+                self.dist_fn.clone(),
+            )
+            .n_runs(1)
+            .fit(&members)?;
+            self.centroids
+                .row_mut(widest)
+                .assign(&halves.centroids.row(0));
+            self.centroids
+                .push_row(halves.centroids.row(1))
+                .expect("centroids have the same number of features");
+        }
+
+        let mut cluster_count = Array1::zeros(self.centroids.nrows());
+        memberships
+            .iter()
+            .for_each(|&c| cluster_count[c] += F::one());
+        self.cluster_count = cluster_count;
+        self.member_dist_stats =
+            member_dist_stats(&self.dist_fn, self.centroids.nrows(), &memberships, &dists);
+        self.training_summary = Some(ClusterSummary::from_assignments(
+            &self.dist_fn,
+            self.centroids.nrows(),
+            &memberships,
+            &dists,
+        ));
+        if n_samples > 0 {
+            self.inertia = inertia / F::cast(n_samples);
+        }
+        Ok(memberships)
+    }
+
+    /// Try to escape a local minimum by perturbing the centroids and running the m_k-means loop
+    /// again on `observations`, starting from the perturbed centroids.
+    ///
+    /// Each centroid is moved by gaussian noise with a standard deviation of `noise_scale` times
+    /// the standard deviation of each feature in `observations`. The loop then runs until the
+    /// centroids move by less than `tolerance` or `max_n_iterations` is reached, as in `fit`.
+    /// The new centroids are kept only if they lower the inertia on `observations`, in which case
+    /// the cluster counts and inertia of the model are refreshed from `observations` and `true`
+    /// is returned. Otherwise the model is left untouched and `false` is returned.
+    ///
+    /// Unlike `n_runs`, which restarts from scratch, this is a cheap local search step that can be
+    /// repeated on an already fitted model.
+    pub fn perturb_and_refit(
+        &mut self,
+        observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+        noise_scale: F,
+        tolerance: F,
+        max_n_iterations: u64,
+        rng: &mut impl Rng,
+    ) -> bool {
+        self.centroid_tree.clear();
+        let n_samples = observations.nrows();
+        if n_samples == 0 {
+            return false;
+        }
+        let mut memberships = Array1::zeros(n_samples);
+        let mut dists = Array1::zeros(n_samples);
+        update_min_dists(&self.dist_fn, &self.centroids, observations, &mut dists);
+        let current_inertia = dists.sum();
+
+        let std = observations.std_axis(Axis(0), F::zero()) * noise_scale;
+        let noise = Array2::<f64>::random_using(self.centroids.dim(), StandardNormal, rng);
+        let centroids = &self.centroids + &(noise.mapv(F::cast) * &std);
+        let (centroids, ..) = run_lloyd(
+            &self.dist_fn,
+            centroids,
+            observations,
+            &UpdateStep::default(),
+            |_| tolerance,
+            |_, _, _, _, _| ControlFlow::Continue(()),
+            max_n_iterations,
+            None,
+            false,
+            false,
+            &mut memberships,
+            &mut dists,
+        );
+
+        // The inertia returned by the loop predates the last update step, so compute it again on
+        // the final centroids
+        let inertia = update_memberships_and_dists(
+            &self.dist_fn,
+            &centroids,
+            observations,
+            &mut memberships,
+            &mut dists,
+        );
+        if inertia >= current_inertia {
+            return false;
+        }
+        let mut cluster_count = Array1::zeros(centroids.nrows());
+        memberships
+            .iter()
+            .for_each(|&c| cluster_count[c] += F::one());
+        self.member_dist_stats =
+            member_dist_stats(&self.dist_fn, centroids.nrows(), &memberships, &dists);
+        self.training_summary = Some(ClusterSummary::from_assignments(
+            &self.dist_fn,
+            centroids.nrows(),
+            &memberships,
+            &dists,
+        ));
+        self.centroids = centroids;
+        self.cluster_count = cluster_count;
+        self.inertia = inertia / F::cast(n_samples);
+        true
+    }
+
+    /// Refresh the model online with a new batch of `observations`, as `fit_with` does for the
+    /// batches after the first one: the batch is assigned to the centroids, and each centroid
+    /// moves toward the mean of its new members with a learning rate of one over the number of
+    /// observations its cluster has seen so far, kept in [`cluster_count`](KMeans::cluster_count).
+    /// The inertia and distance statistics of the model become those of the batch. Returns the
+    /// distance by which the batch moved the centroids, which is infinite for an empty batch,
+    /// which leaves the model unchanged.
+    ///
+    /// Like `fit_with`, this compares the observations to the centroids with the unweighted
+    /// distance and leaves the feature weights of the model unchanged. Returns an error if
+    /// `observations` don't have the number of features of the model.
+    pub fn update(
+        &mut self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Result<F, KMeansError> {
+        self.centroid_tree.clear();
+        self.validate_input(observations)?;
+        if observations.nrows() == 0 {
+            return Ok(F::infinity());
+        }
+        Ok(self.incremental_step(observations))
+    }
+
+    // Single batch update of `update` and `fit_with` on a non-empty batch, returning the distance
+    // moved by the centroids
+    fn incremental_step(&mut self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> F {
+        self.centroid_tree.clear();
+        let n_samples = observations.nrows();
+        let mut memberships = Array1::zeros(n_samples);
+        let mut dists = Array1::zeros(n_samples);
+        let inertia = update_memberships_and_dists(
+            &self.dist_fn,
+            &self.centroids,
+            &observations.view(),
+            &mut memberships,
+            &mut dists,
+        );
+        let new_centroids = compute_centroids_incremental(
+            observations,
+            &memberships,
+            &self.centroids,
+            &mut self.cluster_count,
+        );
+        self.inertia = inertia / F::cast(n_samples);
+        self.n_iterations += 1;
+        self.member_dist_stats =
+            member_dist_stats(&self.dist_fn, self.centroids.nrows(), &memberships, &dists);
+        self.training_summary = Some(ClusterSummary::from_assignments(
+            &self.dist_fn,
+            self.centroids.nrows(),
+            &memberships,
+            &dists,
+        ));
+        let dist = self
+            .dist_fn
+            .distance(self.centroids.view(), new_centroids.view());
+        self.centroids = new_centroids;
+        dist
+    }
+
+    /// Run the m_k-means loop on `observations` moving only the centroids of the clusters in
+    /// `cluster_indices`, while all others stay fixed. This is a local update for data drifts
+    /// that only affect some regions, whereas `fit_with` or a new `fit` move every centroid.
+    ///
+    /// Observations are first assigned to their closest centroid. Then, at each iteration, an
+    /// observation can only move from its current cluster to one of the listed clusters, and
+    /// only the listed centroids are updated. The loop runs until these centroids move by less
+    /// than `tolerance` or `max_n_iterations` is reached. A listed cluster without any
+    /// observation at the start is first moved to an observation drawn from `rng`, so that it
+    /// can pick up members. The cluster counts, inertia and distance statistics of the model are
+    /// refreshed from `observations`, and the final index of the cluster of each observation is
+    /// returned.
+    ///
+    /// Returns an error if an index is not a valid cluster index, or if `observations` don't have
+    /// the number of features of the model.
+    pub fn refit_clusters(
+        &mut self,
+        observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+        cluster_indices: &[usize],
+        tolerance: F,
+        max_n_iterations: u64,
+        rng: &mut impl Rng,
+    ) -> Result<Array1<usize>, KMeansError> {
+        self.centroid_tree.clear();
+        let n_clusters = self.centroids.nrows();
+        if let Some(&index) = cluster_indices.iter().find(|&&c| c >= n_clusters) {
+            return Err(KMeansError::InvalidClusterIndex { index, n_clusters });
+        }
+        self.validate_input(observations)?;
+
+        let n_samples = observations.nrows();
+        let mut memberships = Array1::zeros(n_samples);
+        let mut dists = Array1::zeros(n_samples);
+        update_memberships_and_dists(
+            &self.dist_fn,
+            &self.centroids,
+            observations,
+            &mut memberships,
+            &mut dists,
+        );
+        if n_samples > 0 {
+            for &c in cluster_indices {
+                if !memberships.iter().any(|&m| m == c) {
+                    let seed = observations.row(rng.gen_range(0..n_samples));
+                    self.centroids.row_mut(c).assign(&seed);
+                }
+            }
+        }
+
+        for _ in 0..max_n_iterations {
+            let centroids = &self.centroids;
+            Zip::from(observations.rows())
+                .and(&mut memberships)
+                .and(&mut dists)
+                .par_for_each(|observation, membership, dist| {
+                    let current = centroids.row(*membership);
+                    let mut closest = (
+                        *membership,
+                        self.dist_fn.rdistance(current, observation.view()),
+                    );
+                    for &c in cluster_indices {
+                        let d = self.dist_fn.rdistance(centroids.row(c), observation.view());
+                        if d < closest.1 {
+                            closest = (c, d);
+                        }
+                    }
+                    (*membership, *dist) = closest;
+                });
+            let new_centroids = compute_centroids(&self.centroids, observations, &memberships);
+            let old = self.centroids.select(Axis(0), cluster_indices);
+            let new = new_centroids.select(Axis(0), cluster_indices);
+            for &c in cluster_indices {
+                self.centroids.row_mut(c).assign(&new_centroids.row(c));
+            }
+            if self.dist_fn.distance(old.view(), new.view()) < tolerance {
+                break;
+            }
+        }
+
+        let mut cluster_count = Array1::zeros(n_clusters);
+        memberships
+            .iter()
+            .for_each(|&c| cluster_count[c] += F::one());
+        self.cluster_count = cluster_count;
+        self.member_dist_stats = member_dist_stats(&self.dist_fn, n_clusters, &memberships, &dists);
+        self.training_summary = Some(ClusterSummary::from_assignments(
+            &self.dist_fn,
+            n_clusters,
+            &memberships,
+            &dists,
+        ));
+        if n_samples > 0 {
+            self.inertia = dists.sum() / F::cast(n_samples);
+        }
+        Ok(memberships)
+    }
 }
 
-/// Returns new centroids which has the moving average of all observations in each cluster added to
-/// the old centroids.
-/// Updates `counts` with the number of observations in each cluster.
-fn compute_centroids_incremental<F: Float>(
-    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
-    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
-    old_centroids: &ArrayBase<impl Data<Elem = F>, Ix2>,
-    counts: &mut ArrayBase<impl DataMut<Elem = F>, Ix1>,
-) -> Array2<F> {
-    let mut centroids = old_centroids.to_owned();
-    // We can parallelize this
-    Zip::from(observations.rows())
-        .and(cluster_memberships)
-        .for_each(|obs, &c| {
-            // Computes centroids[c] += (observation - centroids[c]) / counts[c]
-            // If cluster is empty for this batch, then this wouldn't even be called, so no
-            // chance of getting NaN.
-            counts[c] += F::one();
-            let shift = (&obs - &centroids.row(c)) / counts[c];
-            let mut centroid = centroids.row_mut(c);
-            centroid += &shift;
-        });
-    centroids
+/// Assignment of an observation, as yielded by [`KMeans::assignments`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AssignmentRecord<F> {
+    /// Index of the observation
+    pub index: usize,
+    /// Index of the closest centroid
+    pub cluster: usize,
+    /// Distance between the observation and the centroid
+    pub distance: F,
 }
 
-// Update `cluster_memberships` with the index of the cluster each observation belongs to.
-pub(crate) fn update_cluster_memberships<F: Float, D: Distance<F>>(
-    dist_fn: &D,
-    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
-    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
-    cluster_memberships: &mut ArrayBase<impl DataMut<Elem = usize>, Ix1>,
-) {
-    Zip::from(observations.axis_iter(Axis(0)))
-        .and(cluster_memberships)
-        .par_for_each(|observation, cluster_membership| {
-            *cluster_membership = closest_centroid(dist_fn, centroids, &observation).0
-        });
+/// Progress of a fit, as given to the callback of [`KMeansValidParams::fit_with_callback`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FitProgress<F> {
+    /// Index of the current run, from 0 to `n_runs - 1`
+    pub run: usize,
+    /// Index of the iteration that just completed within the run, from 0
+    pub iteration: u64,
+    /// Distance by which the iteration moved the centroids, as compared to the tolerance
+    pub shift: F,
+    /// Inertia of the centroids at the start of the iteration, as evaluated by its assignment
+    /// step and normalized like [`KMeans::inertia`]. For Mini-Batch K-means, this is the inertia
+    /// of the model after the batch, on that batch.
+    pub inertia: F,
 }
 
-// Updates `dists` with the distance of each observation from its closest centroid.
-pub(crate) fn update_min_dists<F: Float, D: Distance<F>>(
-    dist_fn: &D,
-    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
-    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
-    dists: &mut ArrayBase<impl DataMut<Elem = F>, Ix1>,
-) {
-    Zip::from(observations.axis_iter(Axis(0)))
-        .and(dists)
-        .par_for_each(|observation, dist| {
-            *dist = closest_centroid(dist_fn, centroids, &observation).1
-        });
+/// Why the K-means loop of a run of `fit` stopped, as given by [`KMeans::stop_reason`]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StopReason {
+    /// The centroids moved by less than the tolerance
+    CentroidShift,
+    /// No observation changed cluster between two consecutive assignment steps, see
+    /// [`KMeansParams::stop_on_stable_assignments`](crate::KMeansParams::stop_on_stable_assignments)
+    StableAssignments,
+    /// The run reached `max_n_iterations` first
+    MaxIterations,
+    /// The inertia rose for `divergence_patience` consecutive iterations, and the run fell back
+    /// to the centroids with the lowest inertia it had seen, see
+    /// [`KMeansParams::divergence_patience`](crate::KMeansParams::divergence_patience)
+    Diverged,
+    /// The callback of [`KMeansValidParams::fit_with_callback`] aborted the run
+    Aborted,
 }
 
-// Efficient combination of `update_cluster_memberships` and `update_min_dists`.
-pub(crate) fn update_memberships_and_dists<F: Float, D: Distance<F>>(
-    dist_fn: &D,
-    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
-    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
-    cluster_memberships: &mut ArrayBase<impl DataMut<Elem = usize>, Ix1>,
-    dists: &mut ArrayBase<impl DataMut<Elem = F>, Ix1>,
-) {
-    Zip::from(observations.axis_iter(Axis(0)))
-        .and(cluster_memberships)
-        .and(dists)
-        .par_for_each(|observation, cluster_membership, dist| {
-            let (m, d) = closest_centroid(dist_fn, centroids, &observation);
-            *cluster_membership = m;
-            *dist = d;
-        });
+/// Comparison of the assignments of a new batch of observations to those of the training data, as
+/// returned by [`KMeans::drift_report`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DriftReport<F> {
+    /// Number of observations of the batch assigned to each cluster
+    pub batch_counts: Array1<usize>,
+    /// Number of training points of each cluster, see [`KMeans::cluster_count`]
+    pub training_counts: Array1<F>,
+    /// Share of the batch assigned to each cluster divided by its share of the training points,
+    /// so that a cluster above 1 is growing. Clusters without training points get a ratio of
+    /// infinity if the batch has observations in them, and 1 otherwise.
+    pub ratios: Array1<F>,
+    /// Difference between the mean of the observations of the batch assigned to each cluster and
+    /// its centroid, with shape `(n_clusters, n_features)`. Clusters without observations in the
+    /// batch have no shift.
+    pub mean_shifts: Array2<F>,
 }
 
-/// Given a matrix of centroids with shape (n_centroids, n_features) and an observation,
-/// return the index of the closest centroid (the index of the corresponding row in `centroids`).
-pub(crate) fn closest_centroid<F: Float, D: Distance<F>>(
-    dist_fn: &D,
-    // (n_centroids, n_features)
-    centroids: &ArrayBase<impl Data<Elem = F>, Ix2>,
-    // (n_features)
-    observation: &ArrayBase<impl Data<Elem = F>, Ix1>,
-) -> (usize, F) {
-    let iterator = centroids.rows().into_iter();
+impl<F: Float> DriftReport<F> {
+    /// Returns the indices of the clusters whose ratio is above `min_ratio`
+    pub fn growing_clusters(&self, min_ratio: F) -> Vec<usize> {
+        self.ratios
+            .iter()
+            .enumerate()
+            .filter(|(_, &ratio)| ratio > min_ratio)
+            .map(|(c, _)| c)
+            .collect()
+    }
+}
+
+/// Output format of [`KMeans::predict_to_writer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelFormat {
+    /// One label per line, in decimal
+    Text,
+    /// The number of labels followed by the labels, each as a little-endian `u64`
+    Binary,
+}
+
+impl<F: Float, R: Rng + Clone, DA: Data<Elem = F>, T, D: Distance<F>>
+    Fit<ArrayBase<DA, Ix2>, T, KMeansError> for KMeansValidParams<F, R, D>
+{
+    type Object = KMeans<F, D>;
+
+    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`,
+    /// `fit` identifies `n_clusters` centroids based on the training data distribution.
+    ///
+    /// `observations` can be a view into a larger array, such as a window
+    /// `data.slice(s![start..end, ..])`: the records are never copied, only the initial
+    /// centroids are.
+    ///
+    /// An instance of `KMeans` is returned.
+    ///
+    fn fit(
+        &self,
+        dataset: &DatasetBase<ArrayBase<DA, Ix2>, T>,
+    ) -> Result<Self::Object, KMeansError> {
+        self.fit_abortable(dataset, None, true, |_| ControlFlow::Continue(()))
+            .map(|(model, _)| model)
+    }
+}
+
+impl<'a, F: Float + Debug, R: Rng + Clone, DA: Data<Elem = F>, T, D: 'a + Distance<F> + Debug>
+    FitWith<'a, ArrayBase<DA, Ix2>, T, IncrKMeansError<KMeans<F, D>>>
+    for KMeansValidParams<F, R, D>
+{
+    type ObjectIn = Option<KMeans<F, D>>;
+    type ObjectOut = KMeans<F, D>;
+
+    /// Performs a single batch update of the Mini-Batch K-means algorithm.
+    ///
+    /// Given an input matrix `observations`, with shape `(n_batch, n_features)` and a previous
+    /// `KMeans` model, the model's centroids are updated with the input matrix. If `model` is
+    /// `None`, then it's initialized using the specified initialization algorithm. The return
+    /// value consists of the updated model and a `bool` value that indicates whether the algorithm
+    /// has converged.
+    fn fit_with(
+        &self,
+        model: Self::ObjectIn,
+        dataset: &'a DatasetBase<ArrayBase<DA, Ix2>, T>,
+    ) -> Result<Self::ObjectOut, IncrKMeansError<Self::ObjectOut>> {
+        match self.mini_batch_step(model, dataset.records().view()) {
+            Ok((model, shift)) if shift < self.tolerance() => Ok(model),
+            Ok((model, _)) => Err(IncrKMeansError::NotConverged(model)),
+            Err(KMeansError::EmptyObservations) => Err(IncrKMeansError::EmptyObservations),
+            Err(KMeansError::DegenerateInit) => Err(IncrKMeansError::DegenerateInit),
+            Err(KMeansError::InitFeatureMismatch { expected, actual }) => {
+                Err(IncrKMeansError::InitFeatureMismatch { expected, actual })
+            }
+            Err(err) => unreachable!("unexpected error of a mini-batch step: {}", err),
+        }
+    }
+}
+
+impl<F: Float, R: Rng + Clone, D: Distance<F> + Debug> KMeansValidParams<F, R, D> {
+    /// Same as [`fit`](linfa::traits::Fit::fit), calling `callback` after each iteration of the
+    /// K-means loop with the progress of the fit, see [`FitProgress`]. Returning
+    /// [`ControlFlow::Break`] from the callback aborts the fit, for example to stop a long fit on
+    /// a shutdown signal without losing its work.
+    ///
+    /// An aborted fit returns [`AbortableKMeansError::Aborted`] with the best model so far: the
+    /// best of the completed runs and of the centroids of the interrupted run, evaluated on the
+    /// last assignment step like the model of `fit`. The `max_cluster_fraction` check is skipped
+    /// for this partial model. With a memory budget that switches `fit` to Mini-Batch K-means,
+    /// the callback is called after each batch instead, and the partial model is the current one.
+    pub fn fit_with_callback<T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+        callback: impl FnMut(&FitProgress<F>) -> ControlFlow<()>,
+    ) -> Result<KMeans<F, D>, AbortableKMeansError<KMeans<F, D>>> {
+        match self.fit_abortable(dataset, None, false, callback) {
+            Ok((model, false)) => Ok(model),
+            Ok((model, true)) => Err(AbortableKMeansError::Aborted {
+                partial: Box::new(model),
+            }),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl<F: Float, R: Rng + Clone> KMeansValidParams<F, R, L2Dist> {
+    /// Fit a model on `dataset` and return it along with the **squared** euclidean distances
+    /// between each of its observations and each centroid, with shape
+    /// `(n_observations, n_clusters)`, for use as features by a downstream model.
+    ///
+    /// Same as calling [`fit`](linfa::traits::Fit::fit) and then
+    /// [`cost_matrix`](KMeans::cost_matrix) on the training observations.
+    pub fn fit_transform<T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+    ) -> Result<(KMeans<F, L2Dist>, Array2<F>), KMeansError> {
+        let model = self.fit(dataset)?;
+        let costs = model.cost_matrix(dataset.records());
+        Ok((model, costs))
+    }
+}
+
+impl<F: Float, R: Rng + Clone, D: Distance<F>> KMeansValidParams<F, R, D> {
+    /// Fit a model on `dataset` and return it along with the labels of its observations, the
+    /// index of the closest centroid of each observation.
+    ///
+    /// Same as calling [`fit`](linfa::traits::Fit::fit) and then
+    /// [`predict`](linfa::traits::Predict::predict) on the training observations, so that the
+    /// labels are exactly those of `predict`. The memberships of the last assignment step of the
+    /// K-means loop can't stand in for them, since the loop moves the centroids once more after
+    /// that step.
+    pub fn fit_predict<T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+    ) -> Result<(KMeans<F, D>, Array1<usize>), KMeansError> {
+        let model = self.fit(dataset)?;
+        let labels = model.predict(dataset.records());
+        Ok((model, labels))
+    }
+
+    /// Same as [`fit`](linfa::traits::Fit::fit), with a non-negative weight for each observation
+    /// of `dataset`, for example the number of occurrences of each row of aggregated data.
+    ///
+    /// An observation of weight `w` counts as `w` occurrences of the observation: the centroids
+    /// are the weighted means of their members, and the inertia and the cluster counts are
+    /// weighted, so that integer weights fit the same model as the dataset with each observation
+    /// repeated as many times, given the same initial centroids. The initialization itself
+    /// ignores the weights. `memory_budget_bytes` is ignored, since Mini-Batch K-means can't
+    /// weight its observations, and the weights multiply those of the density weighting.
+    ///
+    /// Returns an error if `weights` doesn't have one weight per observation, if a weight is
+    /// negative or not finite, if all the weights are zero, or if the centroids are updated by a
+    /// trimmed mean or a median, which can't be weighted.
+    pub fn fit_weighted<T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+        weights: &ArrayBase<impl Data<Elem = F>, Ix1>,
+    ) -> Result<KMeans<F, D>, KMeansError> {
+        if weights.len() != dataset.nsamples() {
+            return Err(KMeansError::SampleWeightsLength {
+                expected: dataset.nsamples(),
+                actual: weights.len(),
+            });
+        }
+        if weights.iter().any(|&w| !w.is_finite() || w < F::zero())
+            || weights.iter().all(|&w| w == F::zero())
+        {
+            return Err(KMeansError::InvalidSampleWeights);
+        }
+        match self.centroid_update() {
+            CentroidUpdate::Mean => {}
+            CentroidUpdate::TrimmedMean { .. } => {
+                return Err(KMeansParamsError::TrimmedMeanWeights.into())
+            }
+            CentroidUpdate::Median => return Err(KMeansParamsError::MedianWeights.into()),
+        }
+        self.fit_abortable(dataset, Some(weights.view()), true, |_| {
+            ControlFlow::Continue(())
+        })
+        .map(|(model, _)| model)
+    }
+
+    // Body of `fit`, calling `callback` after each iteration. Returns the fitted model and whether
+    // the callback aborted the fit, in which case the model is the best one so far. Restarts may
+    // only run concurrently, without calling `callback`, if `parallel_restarts`. The optional
+    // `weights` of the observations are checked by `fit_weighted`.
+    fn fit_abortable<T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+        weights: Option<ArrayView1<F>>,
+        parallel_restarts: bool,
+        mut callback: impl FnMut(&FitProgress<F>) -> ControlFlow<()>,
+    ) -> Result<(KMeans<F, D>, bool), KMeansError> {
+        let mut rng = self.rng().clone();
+        let observations = dataset.records().view();
+        let n_samples = dataset.nsamples();
+        self.check_observations(observations)?;
+        if weights.is_none() {
+            if let Some(batch_size) = self.mini_batch_size(n_samples, observations.ncols()) {
+                return self.run_mini_batch(observations, batch_size, callback);
+            }
+        }
+        let size_bounds = self.cluster_size_bounds();
+        if let Some((min, max)) = size_bounds {
+            let n_clusters = self.n_clusters();
+            if n_samples < n_clusters.saturating_mul(min)
+                || n_samples > n_clusters.saturating_mul(max)
+            {
+                return Err(KMeansError::InfeasibleClusterSizes {
+                    n_observations: n_samples,
+                    n_clusters,
+                    min,
+                    max,
+                });
+            }
+        }
+        // Total weight of the observations, by which the inertia and cluster counts are normalized
+        let total_weight = weights.map_or(F::cast(n_samples), |w| w.sum());
+
+        let mut min_inertia = F::infinity();
+        let mut best_centroids = None;
+        let mut best_cluster_count = None;
+        let mut best_feature_weights = None;
+        let mut best_member_dist_stats = Vec::new();
+        let mut best_training_summary = None;
+        let mut best_n_iterations = 0;
+        let mut best_stop_reason = StopReason::CentroidShift;
+        let mut best_trajectory = Vec::new();
+        let density_weights = self
+            .density_weighting()
+            .map(|weighting| weighting.sample_weights(self.dist_fn(), observations));
+        let density_weights = match (density_weights, weights) {
+            (Some(density), Some(weights)) => Some(density * weights),
+            (density, weights) => density.or_else(|| weights.map(|w| w.to_owned())),
+        };
+        // Unique observations, along with the index of the unique observation of each observation.
+        // The cluster sizes count the occurrences, which a unique observation can't split.
+        let unique = (self.deduplicate()
+            && size_bounds.is_none()
+            && matches!(
+                self.algorithm(),
+                KMeansAlgorithm::Lloyd | KMeansAlgorithm::Elkan | KMeansAlgorithm::Full
+            )
+            && matches!(self.centroid_update(), CentroidUpdate::Mean))
+        .then(|| unique_rows(&observations));
+        // The weight of each unique observation adds up those of its occurrences
+        let (records, sample_weights) = match &unique {
+            Some((unique, inverse)) => {
+                let mut weights = Array1::zeros(unique.nrows());
+                for (i, &u) in inverse.iter().enumerate() {
+                    weights[u] += density_weights.as_ref().map_or(F::one(), |w| w[i]);
+                }
+                (unique.view(), Some(weights))
+            }
+            None => (observations, density_weights.clone()),
+        };
+        // Global statistics, computed once for the hyperparameters that need them
+        let data_stats = (self.shrinkage() > F::zero()).then(|| fit_stats(&observations));
+        let update = UpdateStep {
+            sample_weights: sample_weights.as_ref(),
+            shrinkage: data_stats
+                .as_ref()
+                .map(|stats| (self.shrinkage(), stats.mean.clone())),
+            trim: match *self.centroid_update() {
+                CentroidUpdate::Mean => None,
+                CentroidUpdate::TrimmedMean { trim } => Some(trim),
+                // Trimming half of the values at each end leaves the middle one or two
+                CentroidUpdate::Median => Some(F::cast(0.5)),
+            },
+            accumulation: self.accumulation(),
+            prior_penalties: self.cluster_priors().map(ClusterPriors::penalties),
+            chunk_size: self.assignment_chunk_size(),
+            batched: matches!(self.algorithm(), KMeansAlgorithm::Full),
+            empty_clusters: self.empty_clusters(),
+            size_bounds,
+        };
+
+        let n_runs = self.n_runs();
+        let init = |rng: &mut R| {
+            self.init_method()
+                .run(
+                    self.dist_fn(),
+                    self.n_clusters(),
+                    records,
+                    rng,
+                    self.cross_platform_deterministic(),
+                )
+                .ok_or(KMeansError::DegenerateInit)
+        };
+        // Everything a run needs from the hyperparameters is bound up front, since they can't be
+        // shared between threads whatever the random number generator
+        let (dist_fn, algorithm, deterministic) = (
+            self.dist_fn(),
+            self.algorithm(),
+            self.cross_platform_deterministic(),
+        );
+        let (divergence_patience, stop_on_stable) = (
+            self.divergence_patience(),
+            self.stop_on_stable_assignments(),
+        );
+        let (convergence, tolerance, max_n_iterations) = (
+            self.convergence(),
+            self.tolerance(),
+            self.max_n_iterations(),
+        );
+        let tolerance_at =
+            |iteration| convergence.tolerance_at(tolerance, max_n_iterations, iteration);
+        let run_from = |centroids,
+                        on_iteration: &mut OnIteration<F>,
+                        memberships: &mut Array1<usize>,
+                        dists: &mut Array1<F>| {
+            run_once(
+                dist_fn,
+                algorithm,
+                centroids,
+                &records,
+                &update,
+                tolerance_at,
+                on_iteration,
+                max_n_iterations,
+                divergence_patience,
+                stop_on_stable,
+                deterministic,
+                memberships,
+                dists,
+            )
+        };
+        let (max_frames, inverse) = (
+            self.record_trajectory(),
+            unique.as_ref().map(|(_, inverse)| inverse),
+        );
+        let record_frame = |trajectory: &mut Vec<(Array2<F>, Array1<usize>)>,
+                            centroids: &Array2<F>,
+                            memberships: &Array1<usize>| {
+            if let Some(max_frames) = max_frames {
+                if trajectory.len() < max_frames {
+                    let labels = match inverse {
+                        Some(inverse) => inverse.mapv(|u| memberships[u]),
+                        None => memberships.clone(),
+                    };
+                    trajectory.push((centroids.clone(), labels));
+                }
+            }
+        };
+        // We keep the centroids which minimize the inertia (defined as the sum of
+        // the squared distances of the closest centroid for all observations)
+        // over the n runs of the KMeans algorithm.
+        let mut keep_best = |(centroids, inertia, n_iterations, feature_weights, stop_reason),
+                             trajectory,
+                             memberships: &Array1<usize>,
+                             dists: &Array1<F>| {
+            if inertia < min_inertia {
+                min_inertia = inertia;
+                best_centroids = Some(centroids);
+                // Back to one membership and distance per observation
+                let expanded;
+                let (memberships, dists) = match inverse {
+                    Some(inverse) => {
+                        expanded = (inverse.mapv(|u| memberships[u]), inverse.mapv(|u| dists[u]));
+                        (&expanded.0, &expanded.1)
+                    }
+                    None => (memberships, dists),
+                };
+                let mut cluster_count = Array1::zeros(self.n_clusters());
+                memberships
+                    .iter()
+                    .enumerate()
+                    .for_each(|(i, &c)| cluster_count[c] += weights.map_or(F::one(), |w| w[i]));
+                best_cluster_count = Some(cluster_count);
+                best_feature_weights = feature_weights;
+                best_n_iterations = n_iterations;
+                best_stop_reason = stop_reason;
+                best_trajectory = trajectory;
+                best_member_dist_stats =
+                    member_dist_stats(self.dist_fn(), self.n_clusters(), memberships, dists);
+                best_training_summary = Some(ClusterSummary::from_assignments(
+                    self.dist_fn(),
+                    self.n_clusters(),
+                    memberships,
+                    dists,
+                ));
+            }
+        };
+
+        let mut aborted = false;
+        if parallel_restarts
+            && self
+                .parallelism()
+                .parallel_restarts(n_runs, records.nrows())
+        {
+            // Drawing all the initial centroids up front keeps the random number generator out of
+            // the threads, and gives the runs the same centroids as one after the other
+            let inits = (0..n_runs)
+                .map(|_| init(&mut rng))
+                .collect::<Result<Vec<_>, _>>()?;
+            let best = inits
+                .into_par_iter()
+                .map(|centroids| {
+                    let mut memberships = Array1::zeros(records.nrows());
+                    let mut dists = Array1::zeros(records.nrows());
+                    let mut trajectory = Vec::new();
+                    let outcome = run_from(
+                        centroids,
+                        &mut |_, _, _, centroids, memberships| {
+                            record_frame(&mut trajectory, centroids, memberships);
+                            ControlFlow::Continue(())
+                        },
+                        &mut memberships,
+                        &mut dists,
+                    );
+                    (outcome, trajectory, memberships, dists)
+                })
+                // The first of the runs with the lowest inertia, as in the sequential loop
+                .reduce_with(|a, b| if b.0 .1 < a.0 .1 { b } else { a });
+            if let Some((outcome, trajectory, memberships, dists)) = best {
+                keep_best(outcome, trajectory, &memberships, &dists);
+            }
+        } else {
+            let mut memberships = Array1::zeros(records.nrows());
+            let mut dists = Array1::zeros(records.nrows());
+            for run in 0..n_runs {
+                let centroids = init(&mut rng)?;
+                let mut trajectory = Vec::new();
+                let outcome = run_from(
+                    centroids,
+                    &mut |iteration, shift, objective, centroids, memberships| {
+                        record_frame(&mut trajectory, centroids, memberships);
+                        let flow = callback(&FitProgress {
+                            run,
+                            iteration,
+                            shift,
+                            inertia: objective / total_weight,
+                        });
+                        aborted = flow.is_break();
+                        flow
+                    },
+                    &mut memberships,
+                    &mut dists,
+                );
+                keep_best(outcome, trajectory, &memberships, &dists);
+                if aborted {
+                    break;
+                }
+            }
+        }
+
+        match (best_centroids, best_cluster_count) {
+            (Some(centroids), Some(cluster_count)) => {
+                let mut model = KMeans {
+                    cluster_count,
+                    inertia: min_inertia / total_weight,
+                    feature_weights: best_feature_weights,
+                    member_dist_stats: best_member_dist_stats,
+                    training_summary: best_training_summary,
+                    n_iterations: best_n_iterations,
+                    trajectory: best_trajectory,
+                    data_stats,
+                    stop_reason: Some(best_stop_reason),
+                    random_seed: self.random_seed(),
+                    ..KMeans::from_centroids(centroids, self.dist_fn().clone())
+                };
+                if self.snap_to_data() {
+                    self.snap_centroids(
+                        &mut model,
+                        observations,
+                        density_weights.as_ref(),
+                        weights,
+                    );
+                }
+                if aborted {
+                    return Ok((model, true));
+                }
+                if self.empty_clusters() == EmptyClusters::Error {
+                    if let Some(index) = model.cluster_count.iter().position(|&c| c <= F::zero()) {
+                        return Err(KMeansError::EmptyCluster { index });
+                    }
+                }
+                if let Some(max_fraction) = self.max_cluster_fraction() {
+                    let fraction =
+                        model.cluster_count.fold(F::zero(), |max, &c| max.max(c)) / total_weight;
+                    if fraction > max_fraction {
+                        return Err(KMeansError::DegenerateClustering {
+                            fraction: fraction.to_f64().unwrap(),
+                        });
+                    }
+                }
+                Ok((model, false))
+            }
+            _ => Err(KMeansError::InertiaError),
+        }
+    }
+
+    // Single batch update of Mini-Batch K-means, see `fit_with`. Returns the updated model and
+    // the distance by which the batch moved the centroids, which is infinite for an empty batch
+    // since it tells nothing about convergence.
+    fn mini_batch_step(
+        &self,
+        model: Option<KMeans<F, D>>,
+        observations: ArrayView2<F>,
+    ) -> Result<(KMeans<F, D>, F), KMeansError> {
+        let n_samples = observations.nrows();
+
+        let mut model = match model {
+            // An empty batch cannot move the centroids
+            Some(model) if n_samples == 0 => return Ok((model, F::infinity())),
+            Some(model) => model,
+            None if n_samples == 0 => return Err(KMeansError::EmptyObservations),
+            None => {
+                self.init_method().check_features(observations.ncols())?;
+                let centroids = if let KMeansInit::Precomputed(centroids) = self.init_method() {
+                    // If using precomputed centroids, don't run the init algorithm multiple times
+                    centroids.clone()
+                } else {
+                    let mut rng = self.rng().clone();
+                    let mut dists = Array1::zeros(n_samples);
+                    // Initial centroids derived from the first batch by running the init algorithm
+                    // n_runs times and taking the centroids with the lowest inertia
+                    (0..self.n_runs())
+                        .map(|_| {
+                            let centroids = self.init_method().run(
+                                self.dist_fn(),
+                                self.n_clusters(),
+                                observations,
+                                &mut rng,
+                                self.cross_platform_deterministic(),
+                            )?;
+                            update_min_dists(self.dist_fn(), &centroids, &observations, &mut dists);
+                            Some((centroids, dists.sum()))
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or(KMeansError::DegenerateInit)?
+                        .into_iter()
+                        .min_by(|(_, d1), (_, d2)| {
+                            if d1 < d2 {
+                                Ordering::Less
+                            } else {
+                                Ordering::Greater
+                            }
+                        })
+                        .unwrap()
+                        .0
+                };
+                KMeans {
+                    random_seed: self.random_seed(),
+                    ..KMeans::from_centroids(centroids, self.dist_fn().clone())
+                }
+            }
+        };
+
+        let dist = model.incremental_step(&observations);
+        Ok((model, dist))
+    }
+
+    // Moves each centroid of `model` onto its closest observation, and updates the statistics of
+    // the model with an assignment step against the snapped centroids, see `snap_to_data`. The
+    // cluster counts and the inertia are normalized by the `weights` of `fit_weighted`, if any.
+    fn snap_centroids(
+        &self,
+        model: &mut KMeans<F, D>,
+        observations: ArrayView2<F>,
+        sample_weights: Option<&Array1<F>>,
+        weights: Option<ArrayView1<F>>,
+    ) {
+        model.centroid_tree.clear();
+        let scaled = model.scale_features(&observations);
+        let centroids = model.scale_features(&model.centroids).into_owned();
+        for (c, centroid) in centroids.rows().into_iter().enumerate() {
+            let (closest, _) = closest_centroid(&model.dist_fn, &scaled, &centroid);
+            model
+                .centroids
+                .row_mut(c)
+                .assign(&observations.row(closest));
+        }
+
+        let n_samples = observations.nrows();
+        let mut memberships = Array1::zeros(n_samples);
+        let mut dists = Array1::zeros(n_samples);
+        batch_update_memberships_and_dists(
+            &model.dist_fn,
+            &model.scale_features(&model.centroids),
+            &scaled,
+            &mut memberships,
+            &mut dists,
+        );
+        model.cluster_count.fill(F::zero());
+        for (i, &c) in memberships.iter().enumerate() {
+            model.cluster_count[c] += weights.map_or(F::one(), |w| w[i]);
+        }
+        model.inertia = total_dist(&dists, sample_weights, self.cross_platform_deterministic())
+            / weights.map_or(F::cast(n_samples), |w| w.sum());
+        model.member_dist_stats =
+            member_dist_stats(&model.dist_fn, self.n_clusters(), &memberships, &dists);
+        model.training_summary = Some(ClusterSummary::from_assignments(
+            &model.dist_fn,
+            self.n_clusters(),
+            &memberships,
+            &dists,
+        ));
+    }
+
+    // Rejects empty observations, fewer observations than clusters, observations without
+    // features or outside the domain of the distance metric, and precomputed initial centroids
+    // with another number of features
+    fn check_observations(&self, observations: ArrayView2<F>) -> Result<(), KMeansError> {
+        if observations.nrows() == 0 {
+            return Err(KMeansError::EmptyObservations);
+        }
+        if observations.nrows() < self.n_clusters() {
+            return Err(KMeansError::NotEnoughObservations {
+                n_observations: observations.nrows(),
+                n_clusters: self.n_clusters(),
+            });
+        }
+        if observations.ncols() == 0 {
+            return Err(KMeansError::NoFeatures);
+        }
+        self.init_method().check_features(observations.ncols())?;
+        if let Some(index) = non_finite_row(&observations) {
+            return Err(KMeansError::NonFiniteObservation { index });
+        }
+        match observations
+            .rows()
+            .into_iter()
+            .position(|observation| !self.dist_fn().is_valid_point(observation))
+        {
+            Some(index) => Err(KMeansError::InvalidObservation { index }),
+            None => Ok(()),
+        }
+    }
+
+    /// Update `model` with a chunk of a stream of observations, such as `dataset`, without keeping
+    /// the previous chunks in memory. Without a model, the centroids are initialized from the
+    /// chunk with the `init` method of these hyperparameters, as for the first batch of
+    /// [`fit_with`](linfa::traits::FitWith::fit_with).
+    ///
+    /// The chunk is then assigned to the centroids, and each centroid moves toward the mean of its
+    /// new members with a learning rate of one over the number of observations its cluster has
+    /// seen so far, kept in [`cluster_count`](KMeans::cluster_count), so that every observation of
+    /// the stream weighs the same. The counts are serialized with the model, which can be saved
+    /// and resumed between chunks. Unlike `fit_with`, this never reports the chunk as not
+    /// converged, since a stream has no end to converge at.
+    ///
+    /// Returns an error if there is no model and the chunk has no observations, or if the
+    /// initialization fails.
+    pub fn partial_fit<T>(
+        &self,
+        model: Option<KMeans<F, D>>,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+    ) -> Result<KMeans<F, D>, KMeansError> {
+        self.mini_batch_step(model, dataset.records().view())
+            .map(|(model, _)| model)
+    }
+
+    /// Fit the centroids of `dataset` by Mini-Batch K-means with batches of `batch_size`
+    /// observations, for datasets on which the full assignment step of `fit` at every iteration
+    /// is too expensive. Each batch moves the centroids toward the means of its members with a
+    /// per-cluster learning rate of one over the number of observations the cluster has seen so
+    /// far, as [`fit_with`](linfa::traits::FitWith::fit_with) does.
+    ///
+    /// The batches are drawn according to
+    /// [`mini_batch_sampling`](crate::KMeansParams::mini_batch_sampling), for up to
+    /// `max_n_iterations` epochs, until a batch moves the centroids by less than `tolerance`. This
+    /// is the fit that `memory_budget_bytes` switches to, with an explicit batch size instead of
+    /// one derived from the budget, and it ignores the same hyperparameters.
+    ///
+    /// Returns an error if `batch_size` is 0 or if there are no observations.
+    pub fn fit_mini_batch<T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+        batch_size: usize,
+    ) -> Result<KMeans<F, D>, KMeansError> {
+        if batch_size == 0 {
+            return Err(KMeansParamsError::BatchSize.into());
+        }
+        let observations = dataset.records().view();
+        self.check_observations(observations)?;
+        self.run_mini_batch(observations, batch_size, |_| ControlFlow::Continue(()))
+            .map(|(model, _)| model)
+    }
+
+    // Mini-Batch K-means on the whole of `observations`, see `fit_mini_batch`, calling
+    // `callback` after each batch, see `fit_abortable`
+    fn run_mini_batch(
+        &self,
+        observations: ArrayView2<F>,
+        batch_size: usize,
+        mut callback: impl FnMut(&FitProgress<F>) -> ControlFlow<()>,
+    ) -> Result<(KMeans<F, D>, bool), KMeansError> {
+        let n_samples = observations.nrows();
+        // `usize::div_ceil` is newer than the minimum supported Rust version
+        #[allow(clippy::manual_div_ceil)]
+        let batches_per_epoch = (n_samples + batch_size - 1) / batch_size;
+        let n_batches = (self.max_n_iterations() as usize).saturating_mul(batches_per_epoch);
+        let mut rng = self.rng().clone();
+        let mut model = None;
+        for (iteration, indices) in self
+            .mini_batch_sampling()
+            .batch_indices(n_samples, batch_size, &mut rng)
+            .take(n_batches)
+            .enumerate()
+        {
+            let batch = observations.select(Axis(0), &indices);
+            let (step, shift) = self.mini_batch_step(model, batch.view())?;
+            let progress = FitProgress {
+                run: 0,
+                iteration: iteration as u64,
+                shift,
+                inertia: step.inertia(),
+            };
+            if callback(&progress).is_break() {
+                return Ok((step, true));
+            }
+            if shift < self.tolerance() {
+                return Ok((step, false));
+            }
+            model = Some(step);
+        }
+        model
+            .map(|model| (model, false))
+            .ok_or(KMeansError::EmptyObservations)
+    }
+
+    /// Fit the centroids of `dataset` with group-exclusive clusters: observation `i` belongs to
+    /// group `group_ids[i]`, and can only be assigned to the clusters that `group_to_clusters`
+    /// allows for its group. This keeps the observations of different groups, such as the
+    /// customers of a multi-tenant dataset, in the clusters reserved for them, while groups
+    /// allowed the same clusters share them as usual.
+    ///
+    /// Each run starts from a K-means++ initialization in which each centroid is drawn among the
+    /// observations allowed in its cluster, and then runs the m_k-means loop with the assignment
+    /// step restricted to the allowed clusters. A cluster no group is allowed is placed on a
+    /// random observation and stays empty. Uses the `n_runs`, `tolerance`, `convergence`,
+    /// `max_n_iterations`, random number generator and distance metric of these hyperparameters,
+    /// and ignores the others.
+    ///
+    /// Returns an error if there are no observations, if `group_ids` doesn't have one group per
+    /// observation, if a group maps to no cluster or to an invalid cluster index, or if the group
+    /// of an observation is missing from `group_to_clusters`.
+    pub fn fit_grouped<G: Eq + Hash, T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+        group_ids: &[G],
+        group_to_clusters: &HashMap<G, Vec<usize>>,
+    ) -> Result<KMeans<F, D>, KMeansError> {
+        let observations = dataset.records();
+        let n_samples = observations.nrows();
+        let n_clusters = self.n_clusters();
+        if n_samples == 0 {
+            return Err(KMeansError::EmptyObservations);
+        }
+        if group_ids.len() != n_samples {
+            return Err(linfa::error::Error::MismatchedShapes(n_samples, group_ids.len()).into());
+        }
+        for clusters in group_to_clusters.values() {
+            if clusters.is_empty() {
+                return Err(KMeansError::EmptyGroup);
+            }
+            if let Some(&index) = clusters.iter().find(|&&c| c >= n_clusters) {
+                return Err(KMeansError::InvalidClusterIndex { index, n_clusters });
+            }
+        }
+        let allowed = group_ids
+            .iter()
+            .enumerate()
+            .map(|(index, group)| {
+                group_to_clusters
+                    .get(group)
+                    .map(Vec::as_slice)
+                    .ok_or(KMeansError::UnmappedGroup { index })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut rng = self.rng().clone();
+        let mut memberships = Array1::zeros(n_samples);
+        let mut dists = Array1::zeros(n_samples);
+        let mut min_inertia = F::infinity();
+        let mut best = None;
+        for _ in 0..self.n_runs() {
+            let mut centroids =
+                grouped_init(self.dist_fn(), n_clusters, observations, &allowed, &mut rng);
+            let dist_fn = self.dist_fn();
+            let mut n_iter = 0;
+            let mut inertia;
+            loop {
+                inertia = Zip::from(observations.rows())
+                    .and(&ArrayView1::from(&allowed))
+                    .and(&mut memberships)
+                    .and(&mut dists)
+                    .par_fold(
+                        F::zero,
+                        |acc, observation, allowed, membership, dist| {
+                            (*membership, *dist) = allowed
+                                .iter()
+                                .map(|&c| (c, dist_fn.rdistance(centroids.row(c), observation)))
+                                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+                                .expect("each group maps to at least one cluster");
+                            acc + *dist
+                        },
+                        |a, b| a + b,
+                    );
+                let new_centroids = compute_centroids(&centroids, observations, &memberships);
+                let distance = self
+                    .dist_fn()
+                    .distance(centroids.view(), new_centroids.view());
+                centroids = new_centroids;
+                if distance < self.tolerance_at(n_iter) || n_iter + 1 == self.max_n_iterations() {
+                    break;
+                }
+                n_iter += 1;
+            }
+
+            if inertia < min_inertia {
+                min_inertia = inertia;
+                best = Some((centroids, memberships.clone(), dists.clone(), n_iter + 1));
+            }
+        }
+
+        let (centroids, memberships, dists, n_iterations) =
+            best.ok_or(KMeansError::InertiaError)?;
+        let mut cluster_count = Array1::zeros(n_clusters);
+        memberships
+            .iter()
+            .for_each(|&c| cluster_count[c] += F::one());
+        Ok(KMeans {
+            cluster_count,
+            inertia: min_inertia / F::cast(n_samples),
+            member_dist_stats: member_dist_stats(self.dist_fn(), n_clusters, &memberships, &dists),
+            training_summary: Some(ClusterSummary::from_assignments(
+                self.dist_fn(),
+                n_clusters,
+                &memberships,
+                &dists,
+            )),
+            n_iterations,
+            random_seed: self.random_seed(),
+            ..KMeans::from_centroids(centroids, self.dist_fn().clone())
+        })
+    }
+}
+
+// K-means++ initialization of `fit_grouped`, where observation `i` may only join the clusters
+// `allowed[i]`. Centroids are drawn in order, each among the observations allowed in its
+// cluster: first those without any allowed centroid yet, then with a probability proportional to
+// their rdistance to their closest allowed centroid.
+fn grouped_init<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    n_clusters: usize,
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    allowed: &[&[usize]],
+    rng: &mut impl Rng,
+) -> Array2<F> {
+    let n_samples = observations.nrows();
+    let mut eligible = vec![Vec::new(); n_clusters];
+    for (i, clusters) in allowed.iter().enumerate() {
+        clusters.iter().for_each(|&c| eligible[c].push(i));
+    }
+
+    let mut centroids = Array2::zeros((n_clusters, observations.ncols()));
+    let mut closest = Array1::from_elem(n_samples, F::infinity());
+    for (c, candidates) in eligible.iter().enumerate() {
+        let unserved: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&i| closest[i] == F::infinity())
+            .collect();
+        let total = candidates
+            .iter()
+            .fold(F::zero(), |acc, &i| acc + closest[i]);
+        let pick = if candidates.is_empty() {
+            rng.gen_range(0..n_samples)
+        } else if !unserved.is_empty() {
+            unserved[rng.gen_range(0..unserved.len())]
+        } else if total > F::zero() {
+            let threshold = F::cast(rng.gen::<f64>()) * total;
+            let mut acc = F::zero();
+            *candidates
+                .iter()
+                .find(|&&i| {
+                    acc += closest[i];
+                    acc >= threshold
+                })
+                .unwrap_or(&candidates[candidates.len() - 1])
+        } else {
+            candidates[rng.gen_range(0..candidates.len())]
+        };
+
+        centroids.row_mut(c).assign(&observations.row(pick));
+        for &i in candidates {
+            let d = dist_fn.rdistance(centroids.row(c), observations.row(i));
+            closest[i] = closest[i].min(d);
+        }
+    }
+    centroids
+}
+
+#[cfg(feature = "sprs")]
+impl<F: Float, R: Rng + Clone> KMeansValidParams<F, R, L2Dist> {
+    /// Fit the centroids of the sparse matrix `observations`, in CSR or CSC storage, such as a
+    /// TF-IDF matrix that would not fit in memory once densified. CSC matrices are converted to
+    /// CSR first. The centroids are dense, and the assignment step computes the squared euclidean
+    /// distances as `|x|² - 2 x·c + |c|²` from the non-zero values of each observation only.
+    ///
+    /// Each run starts from a K-means++ initialization and then runs the Lloyd loop, in which an
+    /// empty cluster keeps its centroid. Uses the `n_runs`, `tolerance`, `convergence`,
+    /// `max_n_iterations` and random number generator of these hyperparameters, and ignores the
+    /// others.
+    ///
+    /// Returns an error if there are no observations, fewer observations than clusters, no
+    /// features, or a NaN or infinite value.
+    pub fn fit_sparse(&self, observations: CsMatView<F>) -> Result<KMeans<F, L2Dist>, KMeansError> {
+        let csr;
+        let observations = if observations.is_csr() {
+            observations
+        } else {
+            csr = observations.to_csr();
+            csr.view()
+        };
+        let (n_samples, n_features) = observations.shape();
+        let n_clusters = self.n_clusters();
+        if n_samples == 0 {
+            return Err(KMeansError::EmptyObservations);
+        }
+        if n_samples < n_clusters {
+            return Err(KMeansError::NotEnoughObservations {
+                n_observations: n_samples,
+                n_clusters,
+            });
+        }
+        if n_features == 0 {
+            return Err(KMeansError::NoFeatures);
+        }
+        if let Some(index) = observations
+            .outer_iterator()
+            .position(|row| row.iter().any(|(_, x)| !x.is_finite()))
+        {
+            return Err(KMeansError::NonFiniteObservation { index });
+        }
+
+        let mut rng = self.rng().clone();
+        let mut memberships = Array1::zeros(n_samples);
+        let mut dists = Array1::zeros(n_samples);
+        let mut min_inertia = F::infinity();
+        let mut best = None;
+        for _ in 0..self.n_runs() {
+            let mut centroids = sparse_k_means_plusplus(observations, n_clusters, &mut rng);
+            let mut n_iter = 0;
+            let stop_reason = loop {
+                sparse_update_memberships_and_dists(
+                    observations,
+                    None,
+                    centroids.view(),
+                    &mut memberships,
+                    &mut dists,
+                );
+                let new_centroids =
+                    sparse_compute_centroids(&centroids, observations, &memberships);
+                let shift = L2Dist.distance(centroids.view(), new_centroids.view());
+                centroids = new_centroids;
+                n_iter += 1;
+                if shift < self.tolerance_at(n_iter - 1) {
+                    break StopReason::CentroidShift;
+                }
+                if n_iter >= self.max_n_iterations() {
+                    break StopReason::MaxIterations;
+                }
+            };
+            // Assign the observations to the final centroids, to report their inertia
+            let inertia = sparse_update_memberships_and_dists(
+                observations,
+                None,
+                centroids.view(),
+                &mut memberships,
+                &mut dists,
+            );
+            if inertia < min_inertia {
+                min_inertia = inertia;
+                best = Some((
+                    centroids,
+                    memberships.clone(),
+                    dists.clone(),
+                    n_iter,
+                    stop_reason,
+                ));
+            }
+        }
+
+        let (centroids, memberships, dists, n_iterations, stop_reason) =
+            best.ok_or(KMeansError::InertiaError)?;
+        let mut cluster_count = Array1::zeros(n_clusters);
+        memberships
+            .iter()
+            .for_each(|&c| cluster_count[c] += F::one());
+        Ok(KMeans {
+            cluster_count,
+            inertia: min_inertia / F::cast(n_samples),
+            member_dist_stats: member_dist_stats(&L2Dist, n_clusters, &memberships, &dists),
+            training_summary: Some(ClusterSummary::from_assignments(
+                &L2Dist,
+                n_clusters,
+                &memberships,
+                &dists,
+            )),
+            n_iterations,
+            stop_reason: Some(stop_reason),
+            random_seed: self.random_seed(),
+            ..KMeans::from_centroids(centroids, L2Dist)
+        })
+    }
+}
+
+#[cfg(feature = "sprs")]
+impl<F: Float> KMeans<F, L2Dist> {
+    /// Return the index of the closest centroid of each row of the sparse matrix `observations`,
+    /// in CSR or CSC storage, as `predict` does for the same observations in a dense matrix, up
+    /// to rounding errors. The distances are computed from the non-zero values only, see
+    /// [`fit_sparse`](KMeansValidParams::fit_sparse), and with the learned feature weights, if
+    /// any.
+    ///
+    /// # Panics
+    ///
+    /// If `observations` doesn't have [`expects_features`](Self::expects_features) columns.
+    pub fn predict_sparse(&self, observations: CsMatView<F>) -> Array1<usize> {
+        assert_eq!(
+            observations.cols(),
+            self.expects_features(),
+            "observations and centroids have a different number of features"
+        );
+        let csr;
+        let observations = if observations.is_csr() {
+            observations
+        } else {
+            csr = observations.to_csr();
+            csr.view()
+        };
+        let scale = self.feature_scaling();
+        let mut memberships = Array1::zeros(observations.rows());
+        let mut dists = Array1::zeros(observations.rows());
+        sparse_update_memberships_and_dists(
+            observations,
+            scale.as_ref(),
+            self.scale_features(&self.centroids).view(),
+            &mut memberships,
+            &mut dists,
+        );
+        memberships
+    }
+}
+
+// K-means++ initialization of `fit_sparse`: the first centroid is a random row of the CSR
+// `observations`, and each next one is drawn with a probability proportional to the squared
+// distance of the rows to their closest centroid so far.
+#[cfg(feature = "sprs")]
+fn sparse_k_means_plusplus<F: Float>(
+    observations: CsMatView<F>,
+    n_clusters: usize,
+    rng: &mut impl Rng,
+) -> Array2<F> {
+    let n_samples = observations.rows();
+    let mut centroids = Array2::zeros((n_clusters, observations.cols()));
+    let mut pick = rng.gen_range(0..n_samples);
+    let mut min_dists = Array1::from_elem(n_samples, F::infinity());
+    for c in 0..n_clusters {
+        let mut centroid = centroids.row_mut(c);
+        if let Some(row) = observations.outer_view(pick) {
+            row.iter().for_each(|(j, &x)| centroid[j] = x);
+        }
+        let centroid = centroids.slice(s![c..c + 1, ..]);
+        let norms = centroid.map_axis(Axis(1), |c| c.dot(&c));
+        Zip::indexed(&mut min_dists).par_for_each(|i, min_dist| {
+            let row = observations.outer_view(i).expect("row index in bounds");
+            let (_, dist) = sparse_closest_centroid(row, None, centroid, norms.view());
+            *min_dist = min_dist.min(dist);
+        });
+        // This only errs if all of `min_dists` is 0, in which case the extra centroids don't
+        // matter and can be any row.
+        pick = WeightedIndex::new(min_dists.iter())
+            .map(|idx| idx.sample(rng))
+            .unwrap_or(0);
+    }
+    centroids
+}
+
+impl<F: Float, DA: Data<Elem = F>, D: Distance<F>> Transformer<&ArrayBase<DA, Ix2>, Array1<F>>
+    for KMeans<F, D>
+{
+    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`,
+    /// `transform` returns, for each observation, its squared distance to its centroid.
+    fn transform(&self, observations: &ArrayBase<DA, Ix2>) -> Array1<F> {
+        let mut memberships = Array1::zeros(observations.nrows());
+        let mut dists = Array1::zeros(observations.nrows());
+        batch_update_memberships_and_dists(
+            &self.dist_fn,
+            &self.scale_features(&self.centroids),
+            &self.scale_features(observations),
+            &mut memberships,
+            &mut dists,
+        );
+        dists
+    }
+}
+
+impl<F: Float, DA: Data<Elem = F>, D: Distance<F>> PredictInplace<ArrayBase<DA, Ix2>, Array1<usize>>
+    for KMeans<F, D>
+{
+    /// Given an input matrix `observations`, with shape `(n_observations, n_features)`,
+    /// `predict` returns, for each observation, the index of the closest cluster/centroid.
+    ///
+    /// The distances between blocks of observations and the centroids are computed at once with
+    /// [`Distance::rdistance_matrix`], which for
+    /// [`L2Dist`](linfa_nn::distance::L2Dist) boils down to a matrix product.
+    ///
+    /// You can retrieve the centroid associated to an index using the
+    /// [`centroids` method](#method.centroids).
+    fn predict_inplace(&self, observations: &ArrayBase<DA, Ix2>, memberships: &mut Array1<usize>) {
+        assert_eq!(
+            observations.nrows(),
+            memberships.len(),
+            "The number of data points must match the number of memberships."
+        );
+
+        let mut dists = Array1::zeros(observations.nrows());
+        batch_update_memberships_and_dists(
+            &self.dist_fn,
+            &self.scale_features(&self.centroids),
+            &self.scale_features(observations),
+            memberships,
+            &mut dists,
+        );
+    }
+
+    fn default_target(&self, x: &ArrayBase<DA, Ix2>) -> Array1<usize> {
+        Array1::zeros(x.nrows())
+    }
+}
+
+impl<F: Float, DA: Data<Elem = F>, D: Distance<F>> PredictInplace<ArrayBase<DA, Ix1>, usize>
+    for KMeans<F, D>
+{
+    /// Given one input observation, return the index of its closest cluster
+    ///
+    /// You can retrieve the centroid associated to an index using the
+    /// [`centroids` method](#method.centroids).
+    fn predict_inplace(&self, observation: &ArrayBase<DA, Ix1>, membership: &mut usize) {
+        *membership = closest_centroid(
+            &self.dist_fn,
+            &self.scale_features(&self.centroids),
+            &self.scale_features(observation),
+        )
+        .0;
+    }
+
+    fn default_target(&self, _x: &ArrayBase<DA, Ix1>) -> usize {
+        0
+    }
+}
+
+/// Runs the assignment and update steps of m_k-means, starting from `centroids`, until the
+/// centroids move by less than `tolerance(i)` at iteration `i` (from 0), `max_n_iterations` is
+/// reached, no observation changes cluster between two consecutive assignment steps if
+/// `stop_on_stable`, `on_iteration`, called with the iteration, the distance moved by the centroids,
+/// the weighted sum of the rdistances of the assignment step, and the centroids and memberships of
+/// that step, breaks, or the inertia rises for
+/// `divergence_patience` consecutive iterations. Returns the final centroids, along with the sum
+/// of the rdistances of the last assignment step, the number of iterations and why the loop
+/// stopped, while `memberships` and `dists` hold the closest centroid of each observation and
+/// its rdistance, as computed by that step. The update step follows `update`.
+///
+/// A diverging loop returns the centroids with the lowest inertia instead, as evaluated by one
+/// more assignment step. The assignment steps of the loop use the bounds of
+/// [`KMeansAlgorithm::Elkan`] if `elkan`.
+#[allow(clippy::too_many_arguments)]
+fn run_lloyd<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    mut centroids: Array2<F>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    update: &UpdateStep<F>,
+    tolerance: impl Fn(u64) -> F,
+    mut on_iteration: impl FnMut(u64, F, F, &Array2<F>, &Array1<usize>) -> ControlFlow<()>,
+    max_n_iterations: u64,
+    divergence_patience: Option<usize>,
+    stop_on_stable: bool,
+    elkan: bool,
+    memberships: &mut Array1<usize>,
+    dists: &mut Array1<F>,
+) -> (Array2<F>, F, u64, StopReason) {
+    let mut guard = DivergenceGuard::new(divergence_patience);
+    // Memberships of the previous assignment step, which the first one doesn't have
+    let mut previous = stop_on_stable.then(|| memberships.clone());
+    let mut bounds = elkan.then(|| ElkanBounds::new(observations.nrows(), centroids.nrows()));
+    let mut n_iter = 0;
+    loop {
+        let inertia = match &mut bounds {
+            Some(bounds) => bounds.assign(dist_fn, &centroids, observations, memberships, dists),
+            None => update.assign(dist_fn, &centroids, observations, memberships, dists),
+        };
+        if guard.diverged(|| update.objective(inertia, dists), || centroids.clone()) {
+            let centroids = guard.into_best();
+            let inertia = update.assign(dist_fn, &centroids, observations, memberships, dists);
+            break (centroids, inertia, n_iter, StopReason::Diverged);
+        }
+        let mut new_centroids = update.compute_centroids(&centroids, observations, memberships);
+        update.reseed_empty_clusters(&mut new_centroids, observations, memberships, dists);
+        let distance = dist_fn.distance(centroids.view(), new_centroids.view());
+        let objective = update.objective(inertia, dists);
+        let flow = on_iteration(n_iter, distance, objective, &centroids, memberships);
+        centroids = new_centroids;
+        let stable = matches!(&previous, Some(previous) if n_iter > 0 && memberships == previous);
+        if let Some(reason) = stop_reason(
+            flow,
+            distance < tolerance(n_iter),
+            stable,
+            n_iter,
+            max_n_iterations,
+        ) {
+            break (centroids, inertia, n_iter + 1, reason);
+        }
+        if let Some(previous) = &mut previous {
+            previous.assign(memberships);
+        }
+        n_iter += 1;
+    }
+}
+
+// Callback of the K-means loop, see `run_lloyd`
+type OnIteration<'a, F> = dyn FnMut(u64, F, F, &Array2<F>, &Array1<usize>) -> ControlFlow<()> + 'a;
+
+/// Runs the K-means loop of `algorithm` from `centroids`, see `run_lloyd`. Returns the final
+/// centroids, the inertia of the last assignment step, added up in order if `deterministic` or
+/// if the update step weights the observations, the number of iterations, for
+/// [`KMeansAlgorithm::WeightedFeatures`] the final feature weights along with `beta`, and why
+/// the loop stopped.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn run_once<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    algorithm: &KMeansAlgorithm<F>,
+    centroids: Array2<F>,
+    observations: &ArrayView2<F>,
+    update: &UpdateStep<F>,
+    tolerance: impl Fn(u64) -> F,
+    on_iteration: impl FnMut(u64, F, F, &Array2<F>, &Array1<usize>) -> ControlFlow<()>,
+    max_n_iterations: u64,
+    divergence_patience: Option<usize>,
+    stop_on_stable: bool,
+    deterministic: bool,
+    memberships: &mut Array1<usize>,
+    dists: &mut Array1<F>,
+) -> (Array2<F>, F, u64, Option<(Array1<F>, F)>, StopReason) {
+    let (centroids, inertia, n_iterations, feature_weights, stop_reason) = match *algorithm {
+        KMeansAlgorithm::Lloyd | KMeansAlgorithm::Elkan | KMeansAlgorithm::Full => {
+            let (centroids, inertia, n_iterations, stop_reason) = run_lloyd(
+                dist_fn,
+                centroids,
+                observations,
+                update,
+                tolerance,
+                on_iteration,
+                max_n_iterations,
+                divergence_patience,
+                stop_on_stable,
+                matches!(algorithm, KMeansAlgorithm::Elkan),
+                memberships,
+                dists,
+            );
+            (centroids, inertia, n_iterations, None, stop_reason)
+        }
+        KMeansAlgorithm::WeightedFeatures { beta } => {
+            let (centroids, inertia, n_iterations, weights, stop_reason) = run_weighted_lloyd(
+                dist_fn,
+                centroids,
+                observations,
+                update,
+                beta,
+                tolerance,
+                on_iteration,
+                max_n_iterations,
+                divergence_patience,
+                stop_on_stable,
+                memberships,
+                dists,
+            );
+            (
+                centroids,
+                inertia,
+                n_iterations,
+                Some((weights, beta)),
+                stop_reason,
+            )
+        }
+    };
+    // The assignment step already added up the unweighted rdistances, in no set order
+    let inertia = if update.sample_weights.is_none() && !deterministic {
+        inertia
+    } else {
+        total_dist(dists, update.sample_weights, deterministic)
+    };
+    (
+        centroids,
+        inertia,
+        n_iterations,
+        feature_weights,
+        stop_reason,
+    )
+}
+
+/// Same as `run_lloyd`, for [`KMeansAlgorithm::WeightedFeatures`]: observations are assigned to
+/// centroids after scaling their features according to the current feature weights, which are
+/// then updated from the per-feature dispersions after each update step. The weights start out
+/// uniform. Also returns the final feature weights, after the inertia and the number of
+/// iterations. A diverging loop falls back to the feature weights of the centroids with the
+/// lowest inertia.
+#[allow(clippy::too_many_arguments)]
+fn run_weighted_lloyd<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    mut centroids: Array2<F>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    update: &UpdateStep<F>,
+    beta: F,
+    tolerance: impl Fn(u64) -> F,
+    mut on_iteration: impl FnMut(u64, F, F, &Array2<F>, &Array1<usize>) -> ControlFlow<()>,
+    max_n_iterations: u64,
+    divergence_patience: Option<usize>,
+    stop_on_stable: bool,
+    memberships: &mut Array1<usize>,
+    dists: &mut Array1<F>,
+) -> (Array2<F>, F, u64, Array1<F>, StopReason) {
+    let n_features = observations.ncols();
+    let mut weights = Array1::from_elem(n_features, F::one() / F::cast(n_features));
+    let mut guard = DivergenceGuard::new(divergence_patience);
+    // Memberships of the previous assignment step, which the first one doesn't have
+    let mut previous = stop_on_stable.then(|| memberships.clone());
+    let mut n_iter = 0;
+    loop {
+        let scale = feature_scale(&weights, beta);
+        let inertia = update.assign(
+            dist_fn,
+            &(&centroids * &scale),
+            &(observations * &scale),
+            memberships,
+            dists,
+        );
+        if guard.diverged(
+            || update.objective(inertia, dists),
+            || (centroids.clone(), weights.clone()),
+        ) {
+            let (centroids, weights) = guard.into_best();
+            let scale = feature_scale(&weights, beta);
+            let inertia = update.assign(
+                dist_fn,
+                &(&centroids * &scale),
+                &(observations * &scale),
+                memberships,
+                dists,
+            );
+            break (centroids, inertia, n_iter, weights, StopReason::Diverged);
+        }
+        let mut new_centroids = update.compute_centroids(&centroids, observations, memberships);
+        update.reseed_empty_clusters(&mut new_centroids, observations, memberships, dists);
+        let distance = dist_fn.distance(centroids.view(), new_centroids.view());
+        weights = update_feature_weights(&new_centroids, observations, memberships, beta);
+        let objective = update.objective(inertia, dists);
+        let flow = on_iteration(n_iter, distance, objective, &centroids, memberships);
+        centroids = new_centroids;
+        let stable = matches!(&previous, Some(previous) if n_iter > 0 && memberships == previous);
+        if let Some(reason) = stop_reason(
+            flow,
+            distance < tolerance(n_iter),
+            stable,
+            n_iter,
+            max_n_iterations,
+        ) {
+            break (centroids, inertia, n_iter + 1, weights, reason);
+        }
+        if let Some(previous) = &mut previous {
+            previous.assign(memberships);
+        }
+        n_iter += 1;
+    }
+}
+
+// Bounds of the assignment steps of `KMeansAlgorithm::Elkan`, kept across the iterations of
+// `run_lloyd`
+struct ElkanBounds<F> {
+    // Lower bound on the distance between each observation and each centroid, with shape
+    // `(n_observations, n_clusters)`
+    lower: Array2<F>,
+    // Centroids of the previous assignment step, which the first one doesn't have
+    previous: Option<Array2<F>>,
+}
+
+impl<F: Float> ElkanBounds<F> {
+    fn new(n_observations: usize, n_clusters: usize) -> Self {
+        Self {
+            lower: Array2::zeros((n_observations, n_clusters)),
+            previous: None,
+        }
+    }
+
+    // Same as `update_memberships_and_dists`, only computing the rdistance between an observation
+    // and a centroid other than its current one if the bounds can't rule that centroid out. The
+    // first call computes all of them to set the bounds.
+    fn assign<D: Distance<F>>(
+        &mut self,
+        dist_fn: &D,
+        centroids: &Array2<F>,
+        observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+        memberships: &mut Array1<usize>,
+        dists: &mut Array1<F>,
+    ) -> F {
+        let n_clusters = centroids.nrows();
+        let moves = match &self.previous {
+            Some(previous) => {
+                let shifts = Array1::from_shape_fn(n_clusters, |c| {
+                    dist_fn.distance(previous.row(c), centroids.row(c))
+                });
+                // Half the distance between each pair of centroids: a centroid farther than twice
+                // the distance to the current one can't be closer
+                let half_gaps = Array2::from_shape_fn((n_clusters, n_clusters), |(a, b)| {
+                    dist_fn.distance(centroids.row(a), centroids.row(b)) / F::cast(2)
+                });
+                Some((shifts, half_gaps))
+            }
+            None => None,
+        };
+        self.previous = Some(centroids.clone());
+
+        Zip::from(observations.rows())
+            .and(self.lower.rows_mut())
+            .and(memberships)
+            .and(dists)
+            .par_fold(
+                F::zero,
+                |acc, observation, mut lower, membership, dist| {
+                    let (shifts, half_gaps) = match &moves {
+                        Some((shifts, half_gaps)) => (shifts, half_gaps),
+                        None => {
+                            let mut closest = (0, F::infinity());
+                            for (c, centroid) in centroids.rows().into_iter().enumerate() {
+                                let rdist = dist_fn.rdistance(centroid, observation.view());
+                                lower[c] = dist_fn.rdist_to_dist(rdist);
+                                if rdist < closest.1 {
+                                    closest = (c, rdist);
+                                }
+                            }
+                            *membership = closest.0;
+                            *dist = closest.1;
+                            return acc + closest.1;
+                        }
+                    };
+                    Zip::from(&mut lower)
+                        .and(shifts)
+                        .for_each(|bound, &shift| *bound = (*bound - shift).max(F::zero()));
+                    let mut closest = *membership;
+                    let mut rdist = dist_fn.rdistance(centroids.row(closest), observation.view());
+                    let mut upper = dist_fn.rdist_to_dist(rdist);
+                    lower[closest] = upper;
+                    for c in 0..n_clusters {
+                        if c == closest
+                            || upper < lower[c]
+                            || upper < half_gaps[(closest, c)]
+                            || (upper == lower[c].max(half_gaps[(closest, c)]) && c > closest)
+                        {
+                            continue;
+                        }
+                        let candidate = dist_fn.rdistance(centroids.row(c), observation.view());
+                        lower[c] = dist_fn.rdist_to_dist(candidate);
+                        // Ties go to the centroid with the lowest index, as with Lloyd
+                        if candidate < rdist || (candidate == rdist && c < closest) {
+                            closest = c;
+                            rdist = candidate;
+                            upper = lower[c];
+                        }
+                    }
+                    *membership = closest;
+                    *dist = rdist;
+                    acc + rdist
+                },
+                |a, b| a + b,
+            )
+    }
+}
+
+// Why the K-means loop stops after iteration `n_iter`, if it does, given the flow of the
+// callback, whether the centroids moved by less than the tolerance and whether the memberships
+// are those of the previous iteration
+fn stop_reason(
+    flow: ControlFlow<()>,
+    small_shift: bool,
+    stable: bool,
+    n_iter: u64,
+    max_n_iterations: u64,
+) -> Option<StopReason> {
+    if flow.is_break() {
+        Some(StopReason::Aborted)
+    } else if small_shift {
+        Some(StopReason::CentroidShift)
+    } else if stable {
+        Some(StopReason::StableAssignments)
+    } else if n_iter + 1 == max_n_iterations {
+        Some(StopReason::MaxIterations)
+    } else {
+        None
+    }
+}
+
+// Divergence guard of the K-means loop, see `KMeansParams::divergence_patience`: counts the
+// consecutive rises of the inertia, and keeps a snapshot `S` of the state with the lowest one
+struct DivergenceGuard<F, S> {
+    patience: Option<usize>,
+    rises: usize,
+    last: F,
+    best: Option<(F, S)>,
+}
+
+impl<F: Float, S> DivergenceGuard<F, S> {
+    fn new(patience: Option<usize>) -> Self {
+        Self {
+            patience,
+            rises: 0,
+            last: F::infinity(),
+            best: None,
+        }
+    }
+
+    // Records the inertia of the current assignment step, taking a snapshot of the state if the
+    // inertia is the lowest so far, and returns whether it rose for `patience` consecutive
+    // iterations. Neither closure is called without patience.
+    fn diverged(&mut self, inertia: impl FnOnce() -> F, snapshot: impl FnOnce() -> S) -> bool {
+        let patience = match self.patience {
+            Some(patience) => patience,
+            None => return false,
+        };
+        let inertia = inertia();
+        self.rises = if inertia > self.last {
+            self.rises + 1
+        } else {
+            0
+        };
+        self.last = inertia;
+        let lowest = match &self.best {
+            Some((best, _)) => inertia < *best,
+            None => true,
+        };
+        if lowest {
+            self.best = Some((inertia, snapshot()));
+        }
+        self.rises >= patience
+    }
+
+    // Snapshot of the state with the lowest inertia, once the loop diverged
+    fn into_best(self) -> S {
+        self.best
+            .expect("the first inertia is always the lowest so far")
+            .1
+    }
+}
+
+/// Factor applied to each feature so that the plain distance between scaled points is the
+/// distance weighted by `weights^beta`
+fn feature_scale<F: Float>(weights: &Array1<F>, beta: F) -> Array1<F> {
+    weights.mapv(|w| w.powf(beta / F::cast(2.)))
+}
+
+/// Feature weights minimizing the weighted dispersion of the observations around their
+/// centroids: `w_j ∝ D_j^(1 / (1 - beta))`, with a weight of 0 for features without dispersion
+fn update_feature_weights<F: Float>(
+    centroids: &Array2<F>,
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    memberships: &Array1<usize>,
+    beta: F,
+) -> Array1<F> {
+    let mut dispersions = Array1::<F>::zeros(observations.ncols());
+    Zip::from(observations.rows())
+        .and(memberships)
+        .for_each(|observation, &membership| {
+            Zip::from(&mut dispersions)
+                .and(&observation)
+                .and(&centroids.row(membership))
+                .for_each(|d, &x, &c| *d += (x - c) * (x - c));
+        });
+    let mut weights = dispersions.mapv(|d| {
+        if d > F::zero() {
+            d.powf(F::one() / (F::one() - beta))
+        } else {
+            F::zero()
+        }
+    });
+    let total = weights.sum();
+    if total > F::zero() {
+        weights /= total;
+    } else {
+        // All features are constant within their cluster
+        weights.fill(F::one() / F::cast(weights.len()));
+    }
+    weights
+}
+
+/// K-means is an iterative algorithm.
+/// We will perform the assignment and update steps until we are satisfied
+/// (according to our convergence criteria).
+///
+/// `compute_centroids` returns a 2-dimensional array,
+/// where the i-th row corresponds to the i-th cluster.
+fn compute_centroids<F: Float>(
+    old_centroids: &Array2<F>,
+    // (n_observations, n_features)
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    // (n_observations,)
+    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> Array2<F> {
+    let n_clusters = old_centroids.nrows();
+    let mut counts: Array1<usize> = Array1::ones(n_clusters);
+    let mut centroids = Array2::zeros((n_clusters, observations.ncols()));
+
+    Zip::from(observations.rows())
+        .and(cluster_memberships)
+        .for_each(|observation, &cluster_membership| {
+            let mut centroid = centroids.row_mut(cluster_membership);
+            centroid += &observation;
+            counts[cluster_membership] += 1;
+        });
+    // m_k-means: Treat the old centroid like another point in the cluster
+    centroids += old_centroids;
+
+    Zip::from(centroids.rows_mut())
+        .and(&counts)
+        .for_each(|mut centroid, &cnt| centroid /= F::cast(cnt));
+    centroids
+}
+
+// Options of the update step of `run_lloyd` and `run_weighted_lloyd`, and of their assignment
+// step for the cluster priors
+#[derive(Default)]
+struct UpdateStep<'a, F> {
+    // Weight of each observation, see `DensityWeighting`
+    sample_weights: Option<&'a Array1<F>>,
+    // Factor by which the centroids are pulled towards the mean of the observations, along with
+    // that mean
+    shrinkage: Option<(F, Array1<F>)>,
+    // Fraction trimmed at each end for `CentroidUpdate::TrimmedMean`, or 0.5 for
+    // `CentroidUpdate::Median`
+    trim: Option<F>,
+    // Summation of the members of each cluster for the mean update
+    accumulation: Accumulation,
+    // Penalty added to the rdistance to each centroid by the assignment step, see `ClusterPriors`
+    prior_penalties: Option<Array1<F>>,
+    // Number of consecutive observations assigned by each task of the assignment step, if set
+    chunk_size: Option<usize>,
+    // Whether the assignment step computes the distances by blocks, see `KMeansAlgorithm::Full`
+    batched: bool,
+    // What the update step does with the clusters the assignment step left empty
+    empty_clusters: EmptyClusters,
+    // Smallest and largest number of observations of each cluster, see `min_cluster_size`
+    size_bounds: Option<(usize, usize)>,
+}
+
+impl<'a, F: Float> UpdateStep<'a, F> {
+    // Assignment step of the loop, see `update_memberships_and_dists`, which with cluster size
+    // bounds keeps the size of each cluster within them, with cluster priors assigns each
+    // observation to the centroid with the lowest penalized rdistance instead, and otherwise
+    // computes the distances by blocks if `batched`, or splits the observations into chunks of
+    // `chunk_size` if set
+    fn assign<D: Distance<F>>(
+        &self,
+        dist_fn: &D,
+        centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+        observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+        memberships: &mut Array1<usize>,
+        dists: &mut Array1<F>,
+    ) -> F {
+        if let Some((min_size, max_size)) = self.size_bounds {
+            return balanced_update_memberships_and_dists(
+                dist_fn,
+                centroids,
+                observations,
+                min_size,
+                max_size,
+                memberships,
+                dists,
+            );
+        }
+        match (&self.prior_penalties, self.chunk_size) {
+            (Some(penalties), _) => update_penalized_memberships_and_dists(
+                dist_fn,
+                centroids,
+                observations,
+                penalties,
+                memberships,
+                dists,
+            ),
+            (None, _) if self.batched => batch_update_memberships_and_dists(
+                dist_fn,
+                centroids,
+                observations,
+                memberships,
+                dists,
+            ),
+            (None, Some(chunk_size)) => chunked_update_memberships_and_dists(
+                dist_fn,
+                centroids,
+                observations,
+                chunk_size,
+                memberships,
+                dists,
+            ),
+            (None, None) => {
+                update_memberships_and_dists(dist_fn, centroids, observations, memberships, dists)
+            }
+        }
+    }
+
+    // Objective minimized by the loop from the `inertia` of an assignment step and the `dists`
+    // it computed: the inertia itself, weighted by the sample weights if any
+    fn objective(&self, inertia: F, dists: &Array1<F>) -> F {
+        match self.sample_weights {
+            Some(weights) => total_dist(dists, Some(weights), false),
+            None => inertia,
+        }
+    }
+
+    fn compute_centroids(
+        &self,
+        old_centroids: &Array2<F>,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    ) -> Array2<F> {
+        let mut centroids = match (self.sample_weights, self.trim) {
+            (_, Some(trim)) => {
+                compute_trimmed_centroids(old_centroids, observations, cluster_memberships, trim)
+            }
+            (weights, None) if self.accumulation == Accumulation::Kahan => {
+                compute_kahan_centroids(old_centroids, observations, cluster_memberships, weights)
+            }
+            (weights, None) if self.accumulation == Accumulation::Pairwise => {
+                compute_pairwise_centroids(
+                    old_centroids,
+                    observations,
+                    cluster_memberships,
+                    weights,
+                )
+            }
+            (Some(weights), None) => compute_weighted_centroids(
+                old_centroids,
+                observations,
+                cluster_memberships,
+                weights,
+            ),
+            (None, None) => compute_centroids(old_centroids, observations, cluster_memberships),
+        };
+        if let Some((shrinkage, mean)) = &self.shrinkage {
+            centroids *= F::one() - *shrinkage;
+            centroids.scaled_add(*shrinkage, mean);
+        }
+        centroids
+    }
+
+    // Moves the updated centroids of the clusters without any member onto observations, according
+    // to `empty_clusters`, ranking the observations by their rdistance to their centroid in
+    // `dists`. No observation is picked twice, and the memberships are left as they are until the
+    // next assignment step.
+    fn reseed_empty_clusters(
+        &self,
+        centroids: &mut Array2<F>,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        memberships: &Array1<usize>,
+        dists: &Array1<F>,
+    ) {
+        let split_largest = match self.empty_clusters {
+            EmptyClusters::Keep | EmptyClusters::Error => return,
+            EmptyClusters::FarthestPoint => false,
+            EmptyClusters::SplitLargest => true,
+        };
+        let mut counts = vec![0usize; centroids.nrows()];
+        memberships.iter().for_each(|&c| counts[c] += 1);
+        let empty: Vec<_> = (0..counts.len()).filter(|&c| counts[c] == 0).collect();
+        let mut taken = vec![false; observations.nrows()];
+        for cluster in empty {
+            let largest = (0..counts.len())
+                .max_by_key(|&c| counts[c])
+                .expect("there is at least one cluster");
+            let farthest = match (0..observations.nrows())
+                .filter(|&i| !taken[i] && (!split_largest || memberships[i] == largest))
+                .max_by(|&a, &b| dists[a].partial_cmp(&dists[b]).unwrap())
+            {
+                Some(farthest) => farthest,
+                // Fewer distinct observations than clusters leaves the remaining ones empty
+                None => break,
+            };
+            taken[farthest] = true;
+            centroids
+                .row_mut(cluster)
+                .assign(&observations.row(farthest));
+            counts[memberships[farthest]] -= 1;
+            counts[cluster] = 1;
+        }
+    }
+}
+
+/// Same as `compute_centroids`, with each observation counting as `weights` observations. The
+/// old centroid keeps a weight of 1.
+fn compute_weighted_centroids<F: Float>(
+    old_centroids: &Array2<F>,
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    weights: &Array1<F>,
+) -> Array2<F> {
+    let n_clusters = old_centroids.nrows();
+    let mut totals = Array1::<F>::ones(n_clusters);
+    let mut centroids = old_centroids.clone();
+
+    Zip::from(observations.rows())
+        .and(cluster_memberships)
+        .and(weights)
+        .for_each(|observation, &cluster_membership, &weight| {
+            let mut centroid = centroids.row_mut(cluster_membership);
+            centroid.scaled_add(weight, &observation);
+            totals[cluster_membership] += weight;
+        });
+
+    Zip::from(centroids.rows_mut())
+        .and(&totals)
+        .for_each(|mut centroid, &total| centroid /= total);
+    centroids
+}
+
+/// Same as `compute_weighted_centroids`, or `compute_centroids` without `weights`, with Kahan
+/// compensated sums of the members of each cluster
+fn compute_kahan_centroids<F: Float>(
+    old_centroids: &Array2<F>,
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    weights: Option<&Array1<F>>,
+) -> Array2<F> {
+    // Adds `value` to `sum`, carrying the rounding error of the addition over in `compensation`
+    fn kahan_add<F: Float>(sum: &mut F, compensation: &mut F, value: F) {
+        let y = value - *compensation;
+        let t = *sum + y;
+        *compensation = (t - *sum) - y;
+        *sum = t;
+    }
+
+    let n_clusters = old_centroids.nrows();
+    // The old centroid is the first term of each sum, with a weight of 1
+    let mut centroids = old_centroids.clone();
+    let mut compensations = Array2::zeros(centroids.raw_dim());
+    let mut totals = Array1::<F>::ones(n_clusters);
+    let mut total_compensations = Array1::<F>::zeros(n_clusters);
+
+    for (i, (observation, &c)) in observations
+        .rows()
+        .into_iter()
+        .zip(cluster_memberships)
+        .enumerate()
+    {
+        let weight = weights.map_or(F::one(), |weights| weights[i]);
+        Zip::from(centroids.row_mut(c))
+            .and(compensations.row_mut(c))
+            .and(&observation)
+            .for_each(|sum, compensation, &x| kahan_add(sum, compensation, weight * x));
+        kahan_add(&mut totals[c], &mut total_compensations[c], weight);
+    }
+
+    Zip::from(centroids.rows_mut())
+        .and(&totals)
+        .for_each(|mut centroid, &total| centroid /= total);
+    centroids
+}
+
+// Number of members summed naively in each leaf of the pairwise summation
+const PAIRWISE_BLOCK_SIZE: usize = 128;
+
+/// Same as `compute_weighted_centroids`, or `compute_centroids` without `weights`, with pairwise
+/// sums of the members of each cluster: blocks of `PAIRWISE_BLOCK_SIZE` members are summed in
+/// parallel, then the block sums of each cluster are added up as a balanced tree.
+fn compute_pairwise_centroids<F: Float>(
+    old_centroids: &Array2<F>,
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    weights: Option<&Array1<F>>,
+) -> Array2<F> {
+    let n_clusters = old_centroids.nrows();
+    let mut members = vec![Vec::new(); n_clusters];
+    cluster_memberships
+        .iter()
+        .enumerate()
+        .for_each(|(i, &c)| members[c].push(i));
+
+    let observations = observations.view();
+    // The blocks are the same whatever the number of threads, and so are the sums
+    let blocks: Vec<(usize, &[usize])> = members
+        .iter()
+        .enumerate()
+        .flat_map(|(c, members)| members.chunks(PAIRWISE_BLOCK_SIZE).map(move |b| (c, b)))
+        .collect();
+    let block_sums: Vec<(usize, Array1<F>, F)> = blocks
+        .into_par_iter()
+        .map(|(c, block)| {
+            let mut sum = Array1::zeros(observations.ncols());
+            let mut total = F::zero();
+            for &i in block {
+                let weight = weights.map_or(F::one(), |weights| weights[i]);
+                sum.scaled_add(weight, &observations.row(i));
+                total += weight;
+            }
+            (c, sum, total)
+        })
+        .collect();
+
+    let mut cluster_sums = vec![Vec::new(); n_clusters];
+    for (c, sum, total) in block_sums {
+        cluster_sums[c].push((sum, total));
+    }
+    let mut centroids = old_centroids.clone();
+    for (mut centroid, mut sums) in centroids.rows_mut().into_iter().zip(cluster_sums) {
+        // Add up neighbouring sums until a single one is left
+        while sums.len() > 1 {
+            let mut pairs = sums.into_iter();
+            let mut merged = Vec::new();
+            while let Some((mut sum, mut total)) = pairs.next() {
+                if let Some((other, other_total)) = pairs.next() {
+                    sum += &other;
+                    total += other_total;
+                }
+                merged.push((sum, total));
+            }
+            sums = merged;
+        }
+        // The old centroid counts as one more member
+        if let Some((sum, total)) = sums.pop() {
+            centroid += &sum;
+            centroid /= total + F::one();
+        }
+    }
+    centroids
+}
+
+/// Same as `compute_centroids`, with per-feature trimmed means: for each cluster and feature, the
+/// values of the members and of the old centroid are sorted, and the `trim` fraction of the
+/// values is dropped at both ends before averaging.
+fn compute_trimmed_centroids<F: Float>(
+    old_centroids: &Array2<F>,
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    trim: F,
+) -> Array2<F> {
+    let mut members = vec![Vec::new(); old_centroids.nrows()];
+    cluster_memberships
+        .iter()
+        .enumerate()
+        .for_each(|(i, &c)| members[c].push(i));
+
+    let mut centroids = old_centroids.clone();
+    let mut values = Vec::new();
+    for (mut centroid, members) in centroids.rows_mut().into_iter().zip(&members) {
+        let n_values = members.len() + 1;
+        // Always keep at least one value, so that a `trim` of 0.5 computes the median
+        let n_trimmed = (trim * F::cast(n_values))
+            .floor()
+            .to_usize()
+            .unwrap()
+            .min((n_values - 1) / 2);
+        for (j, coord) in centroid.iter_mut().enumerate() {
+            values.clear();
+            values.push(*coord);
+            values.extend(members.iter().map(|&i| observations[[i, j]]));
+            values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            let kept = &values[n_trimmed..n_values - n_trimmed];
+            *coord = kept.iter().fold(F::zero(), |acc, &v| acc + v) / F::cast(kept.len());
+        }
+    }
+    centroids
+}
+
+// Unique rows of `observations`, in order of their first occurrence, along with the index of the
+// unique row of each observation
+fn unique_rows<F: Float>(observations: &ArrayView2<F>) -> (Array2<F>, Array1<usize>) {
+    let compare = |&a: &usize, &b: &usize| {
+        observations
+            .row(a)
+            .iter()
+            .zip(observations.row(b))
+            .map(|(x, y)| x.partial_cmp(y).unwrap_or(Ordering::Equal))
+            .find(|&ordering| ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    };
+    // The sort is stable, so each run of identical rows starts with its first occurrence
+    let mut order: Vec<usize> = (0..observations.nrows()).collect();
+    order.sort_by(compare);
+    let mut first_occurrence = vec![0; observations.nrows()];
+    let mut run_start = None;
+    for &i in &order {
+        match run_start {
+            Some(f) if compare(&f, &i) == Ordering::Equal => first_occurrence[i] = f,
+            _ => {
+                run_start = Some(i);
+                first_occurrence[i] = i;
+            }
+        }
+    }
+
+    // A first occurrence comes before the other occurrences of its row
+    let mut firsts = Vec::new();
+    let mut inverse = Array1::zeros(observations.nrows());
+    for i in 0..observations.nrows() {
+        let f = first_occurrence[i];
+        if f == i {
+            firsts.push(i);
+            inverse[i] = firsts.len() - 1;
+        } else {
+            inverse[i] = inverse[f];
+        }
+    }
+    (observations.select(Axis(0), &firsts), inverse)
+}
+
+// Sum of the rdistances `dists` of the observations to their centroid, weighted by
+// `sample_weights` if any. With `in_order`, the terms are added up in the order of the
+// observations, since ndarray doesn't specify the order in which `sum` adds up the elements.
+fn total_dist<F: Float>(
+    dists: &Array1<F>,
+    sample_weights: Option<&Array1<F>>,
+    in_order: bool,
+) -> F {
+    match (sample_weights, in_order) {
+        (None, false) => dists.sum(),
+        (Some(weights), false) => (dists * weights).sum(),
+        (None, true) => dists.iter().fold(F::zero(), |acc, &d| acc + d),
+        (Some(weights), true) => dists
+            .iter()
+            .zip(weights)
+            .fold(F::zero(), |acc, (&d, &w)| acc + d * w),
+    }
+}
+
+// Mean and standard deviation of the distances of the members of each cluster to its centroid,
+// from their `rdistances`, or `None` for empty clusters
+fn member_dist_stats<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    n_clusters: usize,
+    memberships: &Array1<usize>,
+    rdists: &Array1<F>,
+) -> Vec<Option<(F, F)>> {
+    // Welford's online algorithm: count, mean and sum of squared deviations of each cluster
+    let mut moments = vec![(F::zero(), F::zero(), F::zero()); n_clusters];
+    Zip::from(memberships).and(rdists).for_each(|&c, &rdist| {
+        let dist = dist_fn.rdist_to_dist(rdist);
+        let (count, mean, m2) = &mut moments[c];
+        *count += F::one();
+        let delta = dist - *mean;
+        *mean += delta / *count;
+        *m2 += delta * (dist - *mean);
+    });
+    moments
+        .into_iter()
+        .map(|(count, mean, m2)| (count > F::zero()).then(|| (mean, (m2 / count).sqrt())))
+        .collect()
+}
+
+/// Returns new centroids which has the moving average of all observations in each cluster added to
+/// the old centroids.
+/// Updates `counts` with the number of observations in each cluster.
+fn compute_centroids_incremental<F: Float>(
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    cluster_memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    old_centroids: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    counts: &mut ArrayBase<impl DataMut<Elem = F>, Ix1>,
+) -> Array2<F> {
+    let mut centroids = old_centroids.to_owned();
+    // We can parallelize this
+    Zip::from(observations.rows())
+        .and(cluster_memberships)
+        .for_each(|obs, &c| {
+            // Computes centroids[c] += (observation - centroids[c]) / counts[c]
+            // If cluster is empty for this batch, then this wouldn't even be called, so no
+            // chance of getting NaN.
+            counts[c] += F::one();
+            let shift = (&obs - &centroids.row(c)) / counts[c];
+            let mut centroid = centroids.row_mut(c);
+            centroid += &shift;
+        });
+    centroids
+}
+
+// Update `cluster_memberships` with the index of the cluster each observation belongs to.
+pub(crate) fn update_cluster_memberships<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    cluster_memberships: &mut ArrayBase<impl DataMut<Elem = usize>, Ix1>,
+) {
+    Zip::from(observations.axis_iter(Axis(0)))
+        .and(cluster_memberships)
+        .par_for_each(|observation, cluster_membership| {
+            *cluster_membership = closest_centroid(dist_fn, centroids, &observation).0
+        });
+}
+
+// Updates `dists` with the distance of each observation from its closest centroid.
+pub(crate) fn update_min_dists<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    dists: &mut ArrayBase<impl DataMut<Elem = F>, Ix1>,
+) {
+    Zip::from(observations.axis_iter(Axis(0)))
+        .and(dists)
+        .par_for_each(|observation, dist| {
+            *dist = closest_centroid(dist_fn, centroids, &observation).1
+        });
+}
+
+// Efficient combination of `update_cluster_memberships` and `update_min_dists`. Also returns
+// the sum of `dists`, added up while assigning the observations to save a pass over `dists` when
+// computing the inertia. The order of the additions depends on how the work is split between
+// threads, so the sum can differ from `dists.sum()` by rounding errors.
+pub(crate) fn update_memberships_and_dists<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    cluster_memberships: &mut ArrayBase<impl DataMut<Elem = usize>, Ix1>,
+    dists: &mut ArrayBase<impl DataMut<Elem = F>, Ix1>,
+) -> F {
+    Zip::from(observations.axis_iter(Axis(0)))
+        .and(cluster_memberships)
+        .and(dists)
+        .par_fold(
+            F::zero,
+            |acc, observation, cluster_membership, dist| {
+                // Starting from the previous membership gives a tight bound early on
+                let (m, d) =
+                    closest_centroid_from(dist_fn, centroids, &observation, *cluster_membership);
+                *cluster_membership = m;
+                *dist = d;
+                acc + d
+            },
+            |a, b| a + b,
+        )
+}
+
+// Same as `update_memberships_and_dists`, with one task per chunk of `chunk_size` consecutive
+// observations, which are assigned one after the other
+fn chunked_update_memberships_and_dists<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    chunk_size: usize,
+    cluster_memberships: &mut Array1<usize>,
+    dists: &mut Array1<F>,
+) -> F {
+    observations
+        .axis_chunks_iter(Axis(0), chunk_size)
+        .into_par_iter()
+        .zip(cluster_memberships.axis_chunks_iter_mut(Axis(0), chunk_size))
+        .zip(dists.axis_chunks_iter_mut(Axis(0), chunk_size))
+        .map(|((observations, mut cluster_memberships), mut dists)| {
+            Zip::from(observations.rows())
+                .and(&mut cluster_memberships)
+                .and(&mut dists)
+                .fold(F::zero(), |acc, observation, cluster_membership, dist| {
+                    let (m, d) = closest_centroid_from(
+                        dist_fn,
+                        centroids,
+                        &observation,
+                        *cluster_membership,
+                    );
+                    *cluster_membership = m;
+                    *dist = d;
+                    acc + d
+                })
+        })
+        .reduce(F::zero, |a, b| a + b)
+}
+
+// Same as `update_memberships_and_dists`, assigning each observation to the centroid minimizing
+// its rdistance plus the penalty of the centroid. The distances, and their sum, are the plain
+// rdistances to the assigned centroids.
+fn update_penalized_memberships_and_dists<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    penalties: &Array1<F>,
+    cluster_memberships: &mut Array1<usize>,
+    dists: &mut Array1<F>,
+) -> F {
+    Zip::from(observations.axis_iter(Axis(0)))
+        .and(cluster_memberships)
+        .and(dists)
+        .par_fold(
+            F::zero,
+            |acc, observation, cluster_membership, dist| {
+                let (mut closest, mut lowest_cost) = (0, F::infinity());
+                for (c, (centroid, &penalty)) in
+                    centroids.rows().into_iter().zip(penalties).enumerate()
+                {
+                    let d = dist_fn.rdistance(centroid, observation);
+                    if d + penalty < lowest_cost {
+                        (closest, lowest_cost) = (c, d + penalty);
+                        *dist = d;
+                    }
+                }
+                *cluster_membership = closest;
+                acc + *dist
+            },
+            |a, b| a + b,
+        )
+}
+
+// Same as `update_memberships_and_dists`, but gives each cluster between `min_size` and
+// `max_size` observations: going through all the (observation, centroid) pairs by increasing
+// rdistance, each observation goes to the first centroid with room left, then each cluster with
+// fewer than `min_size` members takes, one at a time, the observation of a cluster above
+// `min_size` whose move increases the rdistance the least. Requires
+// `n_clusters * min_size <= n_observations <= n_clusters * max_size`.
+fn balanced_update_memberships_and_dists<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    min_size: usize,
+    max_size: usize,
+    cluster_memberships: &mut Array1<usize>,
+    dists: &mut Array1<F>,
+) -> F {
+    let n_clusters = centroids.nrows();
+    let mut rdists = Array2::zeros((observations.nrows(), n_clusters));
+    Zip::from(rdists.rows_mut())
+        .and(observations.rows())
+        .par_for_each(|mut row, observation| {
+            for (rdist, centroid) in row.iter_mut().zip(centroids.rows()) {
+                *rdist = dist_fn.rdistance(centroid, observation);
+            }
+        });
+
+    let mut pairs: Vec<_> = rdists.indexed_iter().map(|(pair, _)| pair).collect();
+    pairs.sort_unstable_by(|&a, &b| {
+        rdists[a]
+            .partial_cmp(&rdists[b])
+            .unwrap_or(Ordering::Equal)
+            .then(a.cmp(&b))
+    });
+    let mut sizes = vec![0; n_clusters];
+    let mut assigned = vec![false; observations.nrows()];
+    for (i, c) in pairs {
+        if !assigned[i] && sizes[c] < max_size {
+            assigned[i] = true;
+            cluster_memberships[i] = c;
+            sizes[c] += 1;
+        }
+    }
+
+    // Moving an observation out of a cluster above `min_size` never takes it below
+    for c in 0..n_clusters {
+        while sizes[c] < min_size {
+            let (i, _) = cluster_memberships
+                .iter()
+                .enumerate()
+                .filter(|&(_, &m)| m != c && sizes[m] > min_size)
+                .map(|(i, &m)| (i, rdists[(i, c)] - rdists[(i, m)]))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+                .expect("at least n_clusters * min_size observations");
+            sizes[cluster_memberships[i]] -= 1;
+            cluster_memberships[i] = c;
+            sizes[c] += 1;
+        }
+    }
+
+    Zip::from(&mut *dists)
+        .and(&*cluster_memberships)
+        .and(rdists.rows())
+        .for_each(|dist, &c, row| *dist = row[c]);
+    dists.sum()
+}
+
+// Number of observations whose distances to all centroids are computed at once by
+// `batch_update_memberships_and_dists`.
+const BATCH_SIZE: usize = 256;
+
+// Same as `update_memberships_and_dists`, but computes the distances between blocks of
+// observations and all centroids at once with `Distance::rdistance_matrix`, which is much faster
+// for some metrics. The distances, and their sum, can differ from those of
+// `update_memberships_and_dists` by rounding errors.
+pub(crate) fn batch_update_memberships_and_dists<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    centroids: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    observations: &ArrayBase<impl Data<Elem = F> + Sync, Ix2>,
+    cluster_memberships: &mut ArrayBase<impl DataMut<Elem = usize>, Ix1>,
+    dists: &mut ArrayBase<impl DataMut<Elem = F>, Ix1>,
+) -> F {
+    observations
+        .axis_chunks_iter(Axis(0), BATCH_SIZE)
+        .into_par_iter()
+        .zip(cluster_memberships.axis_chunks_iter_mut(Axis(0), BATCH_SIZE))
+        .zip(dists.axis_chunks_iter_mut(Axis(0), BATCH_SIZE))
+        .map(|((observations, mut cluster_memberships), mut dists)| {
+            let batch_dists = dist_fn.rdistance_matrix(observations, centroids.view());
+            Zip::from(batch_dists.rows())
+                .and(&mut cluster_memberships)
+                .and(&mut dists)
+                .fold(F::zero(), |acc, batch_dists, cluster_membership, dist| {
+                    // Ties go to the first centroid, as in `closest_centroid`
+                    let (m, &d) = batch_dists
+                        .iter()
+                        .enumerate()
+                        .reduce(|min, other| if other.1 < min.1 { other } else { min })
+                        .expect("there is at least one centroid");
+                    *cluster_membership = m;
+                    *dist = d;
+                    acc + d
+                })
+        })
+        .reduce(F::zero, |a, b| a + b)
+}
+
+// Same as `batch_update_memberships_and_dists` with the squared euclidean distance, for the
+// rows of the CSR `observations`, with their features multiplied by `scale` if any. The distances
+// are computed as `|x|² - 2 x·c + |c|²` from the non-zero values of the rows, clamped at 0 against
+// rounding errors.
+#[cfg(feature = "sprs")]
+fn sparse_update_memberships_and_dists<F: Float>(
+    observations: CsMatView<F>,
+    scale: Option<&Array1<F>>,
+    centroids: ArrayView2<F>,
+    cluster_memberships: &mut Array1<usize>,
+    dists: &mut Array1<F>,
+) -> F {
+    let norms = centroids.map_axis(Axis(1), |c| c.dot(&c));
+    Zip::indexed(cluster_memberships).and(dists).par_fold(
+        F::zero,
+        |acc, i, cluster_membership, dist| {
+            let row = observations.outer_view(i).expect("row index in bounds");
+            (*cluster_membership, *dist) =
+                sparse_closest_centroid(row, scale, centroids, norms.view());
+            acc + *dist
+        },
+        |a, b| a + b,
+    )
+}
+
+// Index of the closest of the dense `centroids`, whose squared norms are `norms`, to the sparse
+// `row` with its features multiplied by `scale` if any, and its squared euclidean distance
+#[cfg(feature = "sprs")]
+fn sparse_closest_centroid<F: Float>(
+    row: CsVecView<F>,
+    scale: Option<&Array1<F>>,
+    centroids: ArrayView2<F>,
+    norms: ArrayView1<F>,
+) -> (usize, F) {
+    let scaled = |j: usize, x: F| scale.map_or(x, |scale| x * scale[j]);
+    let row_norm = row.iter().fold(F::zero(), |acc, (j, &x)| {
+        let x = scaled(j, x);
+        acc + x * x
+    });
+    centroids
+        .rows()
+        .into_iter()
+        .zip(norms)
+        .map(|(centroid, &norm)| {
+            let dot = row
+                .iter()
+                .fold(F::zero(), |acc, (j, &x)| acc + scaled(j, x) * centroid[j]);
+            (row_norm - F::cast(2.) * dot + norm).max(F::zero())
+        })
+        .enumerate()
+        // Ties go to the first centroid, as in `closest_centroid`
+        .reduce(|min, other| if other.1 < min.1 { other } else { min })
+        .expect("there is at least one centroid")
+}
+
+// Means of the rows of the CSR `observations` assigned to each cluster by `memberships`, keeping
+// the centroid in `old_centroids` of the empty clusters
+#[cfg(feature = "sprs")]
+fn sparse_compute_centroids<F: Float>(
+    old_centroids: &Array2<F>,
+    observations: CsMatView<F>,
+    memberships: &Array1<usize>,
+) -> Array2<F> {
+    let mut centroids = Array2::zeros(old_centroids.dim());
+    let mut counts = Array1::<usize>::zeros(old_centroids.nrows());
+    for (row, &c) in observations.outer_iterator().zip(memberships) {
+        counts[c] += 1;
+        row.iter().for_each(|(j, &x)| centroids[[c, j]] += x);
+    }
+    Zip::from(centroids.rows_mut())
+        .and(old_centroids.rows())
+        .and(&counts)
+        .for_each(|mut centroid, old_centroid, &count| {
+            if count == 0 {
+                centroid.assign(&old_centroid);
+            } else {
+                let count = F::cast(count);
+                centroid.mapv_inplace(|x| x / count);
+            }
+        });
+    centroids
+}
+
+// Index of the first row of `observations` with a NaN or infinite value, if any
+fn non_finite_row<F: Float>(observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> Option<usize> {
+    observations
+        .rows()
+        .into_iter()
+        .position(|row| row.iter().any(|x| !x.is_finite()))
+}
+
+/// Given a matrix of centroids with shape (n_centroids, n_features) and an observation,
+/// return the index of the closest centroid (the index of the corresponding row in `centroids`).
+pub(crate) fn closest_centroid<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    // (n_centroids, n_features)
+    centroids: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    // (n_features)
+    observation: &ArrayBase<impl Data<Elem = F>, Ix1>,
+) -> (usize, F) {
+    closest_centroid_from(dist_fn, centroids, observation, 0)
+}
+
+/// Same as `closest_centroid`, but computes the distance to the centroid at index `start` (if
+/// valid) first. Distances to the other centroids are only computed until they are known to be
+/// larger than the smallest distance so far, so starting from a centroid that is likely to be
+/// the closest one, such as the previous membership of the observation, saves work.
+fn closest_centroid_from<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    // (n_centroids, n_features)
+    centroids: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    // (n_features)
+    observation: &ArrayBase<impl Data<Elem = F>, Ix1>,
+    start: usize,
+) -> (usize, F) {
+    let start = if start < centroids.nrows() { start } else { 0 };
+    let (mut closest_index, mut minimum_distance) = (
+        start,
+        dist_fn.rdistance(centroids.row(start), observation.view()),
+    );
+
+    for (centroid_index, centroid) in centroids.rows().into_iter().enumerate() {
+        if centroid_index == start {
+            continue;
+        }
+        let distance = dist_fn.rdistance_bounded(centroid, observation.view(), minimum_distance);
+        // Ties go to the centroid with the lowest index, regardless of `start`
+        if distance < minimum_distance
+            || (distance == minimum_distance && centroid_index < closest_index)
+        {
+            closest_index = centroid_index;
+            minimum_distance = distance;
+        }
+    }
+    (closest_index, minimum_distance)
+}
+
+/// Like `closest_centroid`, but also returns the index and distance of the second closest centroid.
+/// If there is a single centroid, the second closest one is reported at an infinite distance.
+pub(crate) fn closest_two_centroids<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    // (n_centroids, n_features)
+    centroids: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    // (n_features)
+    observation: &ArrayBase<impl Data<Elem = F>, Ix1>,
+) -> ((usize, F), (usize, F)) {
+    let mut closest = (0, F::infinity());
+    let mut second = (0, F::infinity());
+
+    for (centroid_index, centroid) in centroids.rows().into_iter().enumerate() {
+        let distance = dist_fn.rdistance(centroid.view(), observation.view());
+        if distance < closest.1 {
+            second = closest;
+            closest = (centroid_index, distance);
+        } else if distance < second.1 {
+            second = (centroid_index, distance);
+        }
+    }
+    (closest, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::KMeansInit;
+    use super::*;
+    use crate::{
+        Accumulation, ClusterPriors, DensityWeighting, EmptyClusters, KMeansParamsError,
+        MiniBatchSampling, Parallelism, AUTO_RESTARTS_MAX_OBSERVATIONS,
+    };
+    use approx::assert_abs_diff_eq;
+    use linfa_nn::distance::{CosineDist, JensenShannonDist, L1Dist, LpDist};
+    use ndarray::{array, concatenate, s, stack, Array, Array1, Array2, Axis, Zip};
+    use ndarray_rand::rand::prelude::ThreadRng;
+    use ndarray_rand::rand::SeedableRng;
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use std::convert::TryInto;
+
+    #[test]
+    fn autotraits() {
+        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
+        has_autotraits::<KMeans<f64, L2Dist>>();
+        has_autotraits::<KMeans<f32, L1Dist>>();
+        has_autotraits::<KMeansParamsError>();
+        has_autotraits::<KMeansError>();
+        has_autotraits::<IncrKMeansError<String>>();
+    }
+
+    fn function_test_1d(x: &Array2<f64>) -> Array2<f64> {
+        let mut y = Array2::zeros(x.dim());
+        Zip::from(&mut y).and(x).for_each(|yi, &xi| {
+            if xi < 0.4 {
+                *yi = xi * xi;
+            } else if (0.4..0.8).contains(&xi) {
+                *yi = 3. * xi + 1.;
+            } else {
+                *yi = f64::sin(10. * xi);
+            }
+        });
+        y
+    }
+
+    macro_rules! calc_inertia {
+        ($dist:expr, $centroids:expr, $obs:expr, $memberships:expr) => {
+            $obs.rows()
+                .into_iter()
+                .zip($memberships.iter())
+                .map(|(row, &c)| $dist.rdistance(row.view(), $centroids.row(c).view()))
+                .sum::<f64>()
+        };
+    }
+
+    macro_rules! calc_memberships {
+        ($dist:expr, $centroids:expr, $obs:expr) => {{
+            let mut memberships = Array1::zeros($obs.nrows());
+            update_cluster_memberships(&$dist, &$centroids, &$obs, &mut memberships);
+            memberships
+        }};
+    }
+
+    #[test]
+    fn test_min_dists() {
+        let centroids = array![[0.0, 1.0], [40.0, 10.0]];
+        let observations = array![[3.0, 4.0], [1.0, 3.0], [25.0, 15.0]];
+        let mut dists = Array1::zeros(observations.nrows());
+
+        update_min_dists(&L2Dist, &centroids, &observations, &mut dists);
+        assert_abs_diff_eq!(dists, array![18.0, 5.0, 250.0]);
+        update_min_dists(&L1Dist, &centroids, &observations, &mut dists);
+        assert_abs_diff_eq!(dists, array![6.0, 3.0, 20.0]);
+    }
+
+    fn test_n_runs<D: Distance<f64>>(dist_fn: D) {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let xt = Array::random_using(100, Uniform::new(0., 1.0), &mut rng).insert_axis(Axis(1));
+        let yt = function_test_1d(&xt);
+        let data = concatenate(Axis(1), &[xt.view(), yt.view()]).unwrap();
+
+        for init in &[
+            KMeansInit::Random,
+            KMeansInit::KMeansPlusPlus,
+            KMeansInit::KMeansPara,
+        ] {
+            // First clustering with one iteration
+            let dataset = DatasetBase::from(data.clone());
+            let model = KMeans::params_with(3, rng.clone(), dist_fn.clone())
+                .n_runs(1)
+                .init_method(init.clone())
+                .fit(&dataset)
+                .expect("KMeans fitted");
+            let clusters = model.predict(dataset);
+            let inertia = calc_inertia!(
+                dist_fn,
+                model.centroids(),
+                clusters.records,
+                clusters.targets
+            );
+            let total_dist = model.transform(&clusters.records.view()).sum();
+            assert_abs_diff_eq!(inertia, total_dist, epsilon = 1e-5);
+
+            let single_cluster: usize = model.predict(&data.row(0));
+            assert_abs_diff_eq!(single_cluster, clusters.targets[0]);
+
+            // Second clustering with 10 iterations (default)
+            let dataset2 = DatasetBase::from(clusters.records().clone());
+            let model2 = KMeans::params_with(3, rng.clone(), dist_fn.clone())
+                .init_method(init.clone())
+                .fit(&dataset2)
+                .expect("KMeans fitted");
+            let clusters2 = model2.predict(dataset2);
+            let inertia2 = calc_inertia!(
+                dist_fn,
+                model2.centroids(),
+                clusters2.records,
+                clusters2.targets
+            );
+            let total_dist2 = model2.transform(&clusters2.records.view()).sum();
+            assert_abs_diff_eq!(inertia2, total_dist2, epsilon = 1e-5);
+
+            // Check we improve inertia (only really makes a difference for random init)
+            if *init == KMeansInit::Random {
+                assert!(inertia2 <= inertia);
+            }
+        }
+    }
+
+    #[test]
+    fn test_n_runs_keeps_the_best_run() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [5., 0.], [0., 5.], [5., 5.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations);
+        let fit = |n_runs| {
+            KMeans::params_with_rng(4, rng.clone())
+                .init_method(KMeansInit::Random)
+                .n_runs(n_runs)
+                .fit(&dataset)
+                .unwrap()
+        };
+        // The runs draw their initializations one after the other from the same generator, so
+        // the runs of a fit are the first runs of any fit with more runs
+        let inertias: Vec<_> = (1..=8).map(|n_runs| fit(n_runs).inertia()).collect();
+        assert!(inertias.windows(2).all(|w| w[1] <= w[0]));
+        assert!(inertias[7] < inertias[0]);
+        let (model, again) = (fit(8), fit(8));
+        assert_eq!(model.centroids(), again.centroids());
+        assert_eq!(model.n_iterations(), again.n_iterations());
+    }
+
+    #[test]
+    fn test_n_runs_l2dist() {
+        test_n_runs(L2Dist);
+    }
+
+    #[test]
+    fn test_n_runs_l1dist() {
+        test_n_runs(L1Dist);
+    }
+
+    #[test]
+    fn test_cosine_dist() {
+        // Observations along three directions, at norms spread between 1 and 100
+        let directions = array![[1., 0., 0.], [0., 1., 1.], [1., 1., 0.]];
+        let observations = Array2::from_shape_fn((60, 3), |(i, j)| {
+            let norm = 1. + (i % 20) as f64 * 5.;
+            let noise = 0.05 * ((i * 3 + j) as f64 * 1.3).sin();
+            norm * (directions[[i / 20, j]] + noise)
+        });
+        let unit = &observations
+            / &observations
+                .map_axis(Axis(1), |row| row.dot(&row).sqrt())
+                .insert_axis(Axis(1));
+        let model = KMeans::params_with(3, Xoshiro256Plus::seed_from_u64(42), CosineDist)
+            .fit(&DatasetBase::from(unit))
+            .unwrap();
+        // The clusters follow the directions whatever the norms
+        let labels = model.predict(&observations);
+        for group in labels.exact_chunks(20) {
+            assert!(group.iter().all(|&l| l == group[0]));
+        }
+        assert!(labels[0] != labels[20] && labels[20] != labels[40] && labels[0] != labels[40]);
+
+        let mut observations = observations;
+        observations.row_mut(5).fill(0.);
+        let res = KMeans::params_with(3, Xoshiro256Plus::seed_from_u64(42), CosineDist)
+            .fit(&DatasetBase::from(observations));
+        assert!(matches!(
+            res,
+            Err(KMeansError::InvalidObservation { index: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_jensen_shannon_dist() {
+        // Noisy distributions concentrated on the first, middle or last entries
+        let rng = Xoshiro256Plus::seed_from_u64(42);
+        let peaks = array![[0.8, 0.1, 0.1], [0.1, 0.8, 0.1], [0.1, 0.1, 0.8]];
+        let mut observations = Array2::from_shape_fn((150, 3), |(i, j)| {
+            peaks[[i / 50, j]] + 0.1 * ((i * 3 + j) as f64 * 1.7).sin().abs()
+        });
+        for mut row in observations.rows_mut() {
+            let sum = row.sum();
+            row /= sum;
+        }
+        let dataset = DatasetBase::from(observations.clone());
+        let model = KMeans::params_with(3, rng.clone(), JensenShannonDist)
+            .fit(&dataset)
+            .unwrap();
+        for centroid in model.centroids().rows() {
+            assert!(JensenShannonDist.is_valid_point(centroid));
+        }
+        let labels = model.predict(&observations);
+        for blob in labels.exact_chunks(50) {
+            assert!(blob.iter().all(|&l| l == blob[0]));
+        }
+        assert!(labels[0] != labels[50] && labels[50] != labels[100] && labels[0] != labels[100]);
+
+        observations[[7, 0]] += 0.5;
+        let res =
+            KMeans::params_with(3, rng, JensenShannonDist).fit(&DatasetBase::from(observations));
+        assert!(matches!(
+            res,
+            Err(KMeansError::InvalidObservation { index: 7 })
+        ));
+    }
+
+    #[test]
+    fn compute_centroids_works() {
+        let cluster_size = 100;
+        let n_features = 4;
+
+        // Let's setup a synthetic set of observations, composed of two clusters with known means
+        let cluster_1: Array2<f64> =
+            Array::random((cluster_size, n_features), Uniform::new(-100., 100.));
+        let memberships_1 = Array1::zeros(cluster_size);
+        let expected_centroid_1 = cluster_1.sum_axis(Axis(0)) / (cluster_size + 1) as f64;
+
+        let cluster_2: Array2<f64> =
+            Array::random((cluster_size, n_features), Uniform::new(-100., 100.));
+        let memberships_2 = Array1::ones(cluster_size);
+        let expected_centroid_2 = cluster_2.sum_axis(Axis(0)) / (cluster_size + 1) as f64;
+
+        // `concatenate` combines arrays along a given axis: https://docs.rs/ndarray/0.13.0/ndarray/fn.concatenate.html
+        let observations = concatenate(Axis(0), &[cluster_1.view(), cluster_2.view()]).unwrap();
+        let memberships =
+            concatenate(Axis(0), &[memberships_1.view(), memberships_2.view()]).unwrap();
+
+        // Does it work?
+        let old_centroids = Array2::zeros((2, n_features));
+        let centroids = compute_centroids(&old_centroids, &observations, &memberships);
+        assert_abs_diff_eq!(
+            centroids.index_axis(Axis(0), 0),
+            expected_centroid_1,
+            epsilon = 1e-5
+        );
+        assert_abs_diff_eq!(
+            centroids.index_axis(Axis(0), 1),
+            expected_centroid_2,
+            epsilon = 1e-5
+        );
+
+        assert_eq!(centroids.len_of(Axis(0)), 2);
+    }
+
+    #[test]
+    fn test_compute_extra_centroids() {
+        let observations = array![[1.0, 2.0]];
+        let memberships = array![0];
+        // Empty clusters keep their previous centroid
+        let old_centroids = Array2::ones((2, 2));
+        let centroids = compute_centroids(&old_centroids, &observations, &memberships);
+        assert_abs_diff_eq!(centroids, array![[1.0, 1.5], [1.0, 1.0]]);
+    }
+
+    #[test]
+    fn empty_clusters_keep_their_centroid() {
+        // More clusters than distinct observations, so some clusters end up empty
+        let observations = array![[1., 1.], [1., 1.], [5., 5.], [5., 5.], [5., 5.]];
+        let dataset = DatasetBase::from(observations);
+        for seed in 0..10 {
+            let model = KMeans::params_with_rng(4, Xoshiro256Plus::seed_from_u64(seed))
+                .init_method(KMeansInit::Random)
+                .n_runs(1)
+                .fit(&dataset)
+                .unwrap();
+            // m_k-means counts the previous centroid in each mean, so that an empty cluster keeps
+            // its centroid on an observation instead of collapsing to the origin
+            for centroid in model.centroids().rows() {
+                assert!(centroid == array![1., 1.] || centroid == array![5., 5.]);
+            }
+        }
+    }
+
+    #[test]
+    // An observation is closest to itself.
+    fn nothing_is_closer_than_self() {
+        let n_centroids = 20;
+        let n_features = 5;
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids: Array2<f64> = Array::random_using(
+            (n_centroids, n_features),
+            Uniform::new(-100., 100.),
+            &mut rng,
+        );
+
+        let expected_memberships = (0..n_centroids).into_iter().collect::<Array1<_>>();
+        assert_eq!(
+            calc_memberships!(L2Dist, centroids, centroids),
+            expected_memberships
+        );
+        assert_eq!(
+            calc_memberships!(L1Dist, centroids, centroids),
+            expected_memberships
+        );
+    }
+
+    #[test]
+    fn oracle_test_for_closest_centroid() {
+        let centroids = array![[0., 0.], [1., 2.], [20., 0.], [0., 20.],];
+        let observations = array![[1., 0.6], [20., 2.], [20., 0.], [7., 20.],];
+        let l2_memberships = array![0, 2, 2, 3];
+        let l1_memberships = array![1, 2, 2, 3];
+
+        assert_eq!(
+            calc_memberships!(L2Dist, centroids, observations),
+            l2_memberships
+        );
+        assert_eq!(
+            calc_memberships!(L1Dist, centroids, observations),
+            l1_memberships
+        );
+    }
+
+    #[test]
+    fn test_predict_exact_tree() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids: Array2<f64> =
+            Array::random_using((200, 3), Uniform::new(-100., 100.), &mut rng);
+        let observations: Array2<f64> =
+            Array::random_using((500, 3), Uniform::new(-100., 100.), &mut rng);
+        let model = KMeans::from_centroids(centroids.clone(), L2Dist);
+        let expected = calc_memberships!(L2Dist, centroids, observations);
+        assert_eq!(model.predict_exact_tree(&observations), expected);
+        // Non-contiguous rows are supported as well
+        let fortran_observations = observations.t().to_owned();
+        assert_eq!(
+            model.predict_exact_tree(&fortran_observations.t()),
+            expected
+        );
+
+        let model = KMeans::from_centroids(centroids.clone(), L1Dist);
+        assert_eq!(
+            model.predict_exact_tree(&observations),
+            calc_memberships!(L1Dist, centroids, observations)
+        );
+    }
+
+    #[test]
+    fn test_predict_approx() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids: Array2<f64> =
+            Array::random_using((200, 3), Uniform::new(-100., 100.), &mut rng);
+        let observations: Array2<f64> =
+            Array::random_using((500, 3), Uniform::new(-100., 100.), &mut rng);
+        let model = KMeans::params(200)
+            .init_method(KMeansInit::Precomputed(centroids))
+            .max_n_iterations(1)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let expected = model.predict(&observations);
+        assert_eq!(model.predict_approx(&observations, usize::MAX), expected);
+
+        // Visiting a single leaf can only find farther centroids, but most of them are right
+        let dists = |memberships: &Array1<usize>| {
+            Zip::from(observations.rows())
+                .and(memberships)
+                .map_collect(|observation, &c| {
+                    L2Dist.rdistance(model.centroids().row(c), observation)
+                })
+        };
+        let approx = model.predict_approx(&observations, 1);
+        Zip::from(&dists(&approx))
+            .and(&dists(&expected))
+            .for_each(|&approx, &exact| assert!(approx >= exact));
+        let n_exact = Zip::from(&approx)
+            .and(&expected)
+            .fold(0, |n, a, e| n + (a == e) as usize);
+        assert!(n_exact > 300, "{}", n_exact);
+        assert_eq!(model.predict_approx(&observations, 0), approx);
+    }
+
+    #[test]
+    fn test_centroid_tree_follows_centroids() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations: Array2<f64> =
+            Array::random_using((500, 2), Uniform::new(-10., 10.), &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let mut model = KMeans::params(30).fit(&dataset).unwrap();
+        assert_eq!(
+            model.predict_exact_tree(&observations),
+            model.predict(&observations)
+        );
+        // Clones share the tree, which is not compared
+        let cloned = model.clone();
+        assert_eq!(cloned, model);
+
+        let mut labels = model.predict(&observations);
+        model.remove_cluster(3, &mut labels).unwrap();
+        assert_eq!(
+            model.predict_exact_tree(&observations),
+            model.predict(&observations)
+        );
+        model.reseed_centroid(0, array![100., 100.]).unwrap();
+        assert_eq!(
+            model.predict_exact_tree(&observations),
+            model.predict(&observations)
+        );
+        model.update(&observations.slice(s![..100, ..])).unwrap();
+        assert_eq!(
+            model.predict_exact_tree(&observations),
+            model.predict(&observations)
+        );
+        // The clone still predicts with its own centroids
+        assert_eq!(
+            cloned.predict_exact_tree(&observations),
+            cloned.predict(&observations)
+        );
+    }
+
+    #[test]
+    fn test_centroid_tree_follows_fit_with() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations: Array2<f64> =
+            Array::random_using((2000, 2), Uniform::new(-10., 10.), &mut rng);
+        let params = KMeans::params_with_rng(40, rng).check().unwrap();
+        let mut model = match params.fit_with(None, &DatasetBase::from(observations.view())) {
+            Ok(model) | Err(IncrKMeansError::NotConverged(model)) => model,
+            Err(err) => panic!("{}", err),
+        };
+        assert_eq!(
+            model.predict_exact_tree(&observations),
+            model.predict(&observations)
+        );
+        for shift in 1..=20 {
+            let shifted = &observations + shift as f64 / 2.;
+            model = match params.fit_with(Some(model), &DatasetBase::from(shifted)) {
+                Ok(model) | Err(IncrKMeansError::NotConverged(model)) => model,
+                Err(err) => panic!("{}", err),
+            };
+        }
+        assert_eq!(
+            model.predict_exact_tree(&observations),
+            model.predict(&observations)
+        );
+    }
+
+    #[test]
+    fn test_predict_exact_tree_cosine() {
+        // The cosine distance can't prune the tree, so predictions fall back to the scan
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations: Array2<f64> =
+            Array::random_using((2000, 2), Uniform::new(-10., 10.), &mut rng);
+        let model = KMeans::params_with(60, rng, CosineDist)
+            .n_runs(1)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let expected = model.predict(&observations);
+        assert_eq!(model.predict_exact_tree(&observations), expected);
+        assert_eq!(model.predict_approx(&observations, 1), expected);
+    }
+
+    #[test]
+    fn test_batch_update_memberships_and_dists() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = Array2::random_using((20, 50), Uniform::new(-1., 1.), &mut rng);
+        let observations = Array2::random_using((1000, 50), Uniform::new(-1., 1.), &mut rng);
+
+        let mut memberships = Array1::zeros(1000);
+        let mut dists = Array1::zeros(1000);
+        let inertia = update_memberships_and_dists(
+            &L2Dist,
+            &centroids,
+            &observations,
+            &mut memberships,
+            &mut dists,
+        );
+        let mut batch_memberships = Array1::zeros(1000);
+        let mut batch_dists = Array1::zeros(1000);
+        let batch_inertia = batch_update_memberships_and_dists(
+            &L2Dist,
+            &centroids,
+            &observations,
+            &mut batch_memberships,
+            &mut batch_dists,
+        );
+        assert_eq!(memberships, batch_memberships);
+        assert_abs_diff_eq!(dists, batch_dists, epsilon = 1e-10);
+        // The sums accumulated during the assignment match a separate pass over the distances
+        let total = dists.iter().fold(0., |acc, &d| acc + d);
+        assert_abs_diff_eq!(inertia, total, epsilon = 1e-8);
+        assert_abs_diff_eq!(batch_inertia, total, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn test_accumulated_inertia() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(200, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params_with_rng(3, rng).n_runs(1);
+        // Deterministic fits add up the inertia in a separate in-order pass instead
+        let accumulated = params.clone().fit(&dataset).unwrap();
+        let in_order = params
+            .cross_platform_deterministic(true)
+            .fit(&dataset)
+            .unwrap();
+        assert_abs_diff_eq!(
+            accumulated.centroids(),
+            in_order.centroids(),
+            epsilon = 1e-10
+        );
+        assert_abs_diff_eq!(accumulated.inertia(), in_order.inertia(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_bounded_memberships_match_full_search() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = Array2::random_using((10, 100), Uniform::new(-1., 1.), &mut rng);
+        let observations = Array2::random_using((500, 100), Uniform::new(-1., 1.), &mut rng);
+        // Stale memberships, as in the middle of a fit
+        let mut memberships: Array1<usize> =
+            Array1::random_using(500, Uniform::new(0, 10), &mut rng);
+        let mut dists = Array1::zeros(500);
+        update_memberships_and_dists(
+            &L2Dist,
+            &centroids,
+            &observations,
+            &mut memberships,
+            &mut dists,
+        );
+
+        for ((obs, &m), &d) in observations
+            .rows()
+            .into_iter()
+            .zip(&memberships)
+            .zip(&dists)
+        {
+            let (expected_m, expected_d) = centroids
+                .rows()
+                .into_iter()
+                .map(|c| L2Dist.rdistance(c, obs))
+                .enumerate()
+                .fold((0, f64::INFINITY), |(best, min), (i, dist)| {
+                    if dist < min {
+                        (i, dist)
+                    } else {
+                        (best, min)
+                    }
+                });
+            assert_eq!(m, expected_m);
+            assert_eq!(d, expected_d);
+        }
+    }
+
+    #[test]
+    fn test_closest_two_centroids() {
+        let centroids = array![[0., 0.], [1., 2.], [20., 0.], [0., 20.]];
+        let ((c1, d1), (c2, d2)) = closest_two_centroids(&L2Dist, &centroids, &array![18., 1.]);
+        assert_eq!((c1, c2), (2, 1));
+        assert_abs_diff_eq!(d1, 5.);
+        assert_abs_diff_eq!(d2, 290.);
+
+        let ((c1, _), (_, d2)) =
+            closest_two_centroids(&L2Dist, &array![[1., 1.]], &array![0f64, 0.]);
+        assert_eq!(c1, 0);
+        assert!(d2.is_infinite());
+    }
+
+    #[test]
+    fn test_fit_f32() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 1.], [-10., 20.], [-1., 10.]];
+        // Embeddings loaded as `f32`, which the model keeps
+        let observations =
+            linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng).mapv(|x| x as f32);
+        let model = KMeans::params_with_rng(3, rng)
+            .tolerance(1e-3)
+            .fit(&DatasetBase::from(observations.view()))
+            .unwrap();
+        #[cfg(feature = "serde")]
+        let model: KMeans<f32, L2Dist> =
+            serde_json::from_str(&serde_json::to_string(&model).unwrap()).unwrap();
+
+        // Clusters ordered by their second coordinate
+        let mut order: Vec<_> = (0..3).collect();
+        order.sort_by(|&a, &b| {
+            model.centroids()[[a, 1]]
+                .partial_cmp(&model.centroids()[[b, 1]])
+                .unwrap()
+        });
+        let centroids = model.centroids().select(Axis(0), &order);
+        let expected = array![[0f32, 1.], [-1., 10.], [-10., 20.]];
+        assert_abs_diff_eq!(centroids, expected, epsilon = 0.5);
+        let labels: Array1<usize> = model.predict(&observations);
+        assert_eq!(labels.len(), 300);
+    }
+
+    #[test]
+    fn test_validate_input() {
+        let model = KMeans {
+            cluster_count: array![1., 1.],
+            ..KMeans::from_centroids(array![[0., 0., 0.], [4., 0., 1.]], L2Dist)
+        };
+        #[cfg(feature = "serde")]
+        let model: KMeans<f64, L2Dist> =
+            serde_json::from_str(&serde_json::to_string(&model).unwrap()).unwrap();
+        assert_eq!(model.expects_features(), 3);
+        assert!(model.validate_input(&array![[1., 2., 3.]]).is_ok());
+        assert!(model.validate_input(&Array2::<f64>::zeros((0, 3))).is_ok());
+        assert!(matches!(
+            model.validate_input(&array![[1., 2.], [3., 4.]]),
+            Err(KMeansError::FeatureMismatch {
+                expected: 3,
+                actual: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_predict_confidence() {
+        let model = KMeans {
+            cluster_count: array![1., 1.],
+            ..KMeans::from_centroids(array![[0., 0.], [4., 0.]], L2Dist)
+        };
+        let observations = array![[2., 0.], [0., 0.], [1., 0.], [5., 0.]];
+        let (memberships, confidences) = model.predict_confidence(&observations);
+        assert_eq!(memberships, array![0, 0, 0, 1]);
+        assert_abs_diff_eq!(confidences, array![0.5, 1., 0.75, 5. / 6.], epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_predict_with_counts() {
+        let model = KMeans::from_centroids(array![[0., 0.], [10., 0.], [0., 10.]], L2Dist);
+        let observations = array![[1., 0.], [9., 1.], [-1., 1.], [0., 0.5]];
+        let (labels, counts) = model.predict_with_counts(&observations);
+        assert_eq!(labels, model.predict(&observations));
+        assert_eq!(counts, array![3, 1, 0]);
+    }
+
+    #[test]
+    fn test_predict_with_zscore_rejection() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        // A tight cluster and a spread out one
+        let tight = linfa_datasets::generate::blobs(100, &array![[0., 0.]], &mut rng) * 0.1;
+        let spread = linfa_datasets::generate::blobs(100, &array![[10., 0.]], &mut rng) * 2.;
+        let observations = concatenate![Axis(0), tight, spread];
+        let mut model = KMeans::params_with_rng(2, rng)
+            .init_method(KMeansInit::Precomputed(array![[0., 0.], [20., 0.]]))
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+
+        let predictions = model.predict_with_zscore_rejection(&observations, 3.);
+        let accepted = predictions.iter().filter(|p| p.is_some()).count();
+        assert!(accepted > 190);
+        // The same distance to a centroid is an outlier for the tight cluster only
+        let [tight_centroid, spread_centroid] = [0, 1].map(|c| model.centroids().row(c).to_owned());
+        let novelties = stack![
+            Axis(0),
+            &tight_centroid + &array![1.5, 0.],
+            &spread_centroid + &array![1.5, 0.]
+        ];
+        assert_eq!(
+            model.predict_with_zscore_rejection(&novelties, 3.),
+            array![None, Some(1)]
+        );
+
+        // Removing the spread cluster merges its members into the tight one, whose statistics
+        // are now unknown
+        model.remove_cluster(1, &mut Array1::zeros(0)).unwrap();
+        assert_eq!(
+            model.predict_with_zscore_rejection(&novelties, 3.),
+            array![Some(0), Some(0)]
+        );
+    }
+
+    #[test]
+    fn test_cost_matrix() {
+        let model = KMeans {
+            cluster_count: array![1., 1.],
+            ..KMeans::from_centroids(array![[0., 0.], [3., 4.]], L2Dist)
+        };
+        let observations = array![[0., 0.], [3., 0.], [6., 8.]];
+        let costs = model.cost_matrix(&observations);
+        assert_abs_diff_eq!(
+            costs,
+            array![[0., 25.], [9., 16.], [100., 25.]],
+            epsilon = 1e-10
+        );
+        let large = Array2::random((BATCH_SIZE * 2 + 1, 2), Uniform::new(0., 1.));
+        assert_eq!(model.cost_matrix(&large).dim(), (BATCH_SIZE * 2 + 1, 2));
+    }
+
+    #[test]
+    fn test_fit_transform() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations = Array2::random_using((50, 2), Uniform::new(-10., 10.), &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params_with_rng(3, rng).check().unwrap();
+        let (model, costs) = params.fit_transform(&dataset).unwrap();
+        assert_eq!(model.centroids(), params.fit(&dataset).unwrap().centroids());
+        assert_eq!(costs.dim(), (50, 3));
+        // The closest centroid of each observation is its predicted cluster, at the distance of
+        // `transform`
+        let labels = model.predict(&observations);
+        let dists = model.transform(&observations);
+        for (i, row) in costs.rows().into_iter().enumerate() {
+            let (closest, &min) = row
+                .iter()
+                .enumerate()
+                .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .unwrap();
+            assert_eq!(closest, labels[i]);
+            assert_abs_diff_eq!(min, dists[i], epsilon = 1e-8);
+        }
+    }
+
+    #[test]
+    fn concurrent_predict() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations = Array2::random_using((200, 2), Uniform::new(-10., 10.), &mut rng);
+        let model = std::sync::Arc::new(
+            KMeans::params_with_rng(4, rng)
+                .fit(&DatasetBase::from(observations.clone()))
+                .unwrap(),
+        );
+        let expected = model.predict(&observations);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let model = model.clone();
+                let observations = observations.clone();
+                std::thread::spawn(move || model.predict(&observations))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_quantize() {
+        let model = KMeans::from_centroids(array![[0., 0.], [10., 0.]], L2Dist);
+        let observations = array![[1., 1.], [9., -2.], [-1., 0.]];
+        assert_abs_diff_eq!(
+            model.quantize(&observations),
+            array![[0., 0.], [10., 0.], [0., 0.]]
+        );
+        assert_abs_diff_eq!(model.quantization_error(&observations), (2. + 5. + 1.) / 3.);
+        assert_abs_diff_eq!(model.quantization_error(&Array2::zeros((0, 2))), 0.);
+    }
+
+    #[test]
+    fn test_predict_centroids() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(20, &expected_centroids, &mut rng);
+        let model = KMeans::params_with_rng(3, rng)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+
+        let centroids = model.predict_centroids(&observations);
+        assert_eq!(centroids.dim(), observations.dim());
+        let memberships: Array1<usize> = model.predict(&observations);
+        for (row, &membership) in centroids.rows().into_iter().zip(&memberships) {
+            assert_eq!(row, model.centroids().row(membership));
+        }
+    }
+
+    #[test]
+    fn test_predict_one() {
+        let model = KMeans::from_centroids(array![[0., 0.], [10., 0.], [0., 10.]], L2Dist);
+        assert_eq!(model.predict_one(&array![9., 1.]), 1);
+        assert_eq!(model.predict_one(&array![-1., 8.].view()), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "as many features as the centroids")]
+    fn test_predict_one_feature_mismatch() {
+        let model = KMeans::from_centroids(array![[0., 0.], [10., 0.]], L2Dist);
+        model.predict_one(&array![1., 2., 3.]);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let model = KMeans {
+            cluster_count: array![1., 1.],
+            ..KMeans::from_centroids(array![[0., 0.], [3., 4.]], L2Dist)
+        };
+        let observations = array![[0., 1.], [6., 8.], [3., 3.]];
+        let records: Vec<_> = model.assignments(&observations).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[1],
+            AssignmentRecord {
+                index: 1,
+                cluster: 1,
+                distance: 5.
+            }
+        );
+        let clusters: Array1<usize> = records.iter().map(|r| r.cluster).collect();
+        assert_eq!(clusters, model.predict(&observations));
+        assert_abs_diff_eq!(records[0].distance, 1.);
+        assert_abs_diff_eq!(records[2].distance, 1.);
+        // Records are computed on demand
+        assert_eq!(model.assignments(&observations).nth(2).unwrap().index, 2);
+    }
+
+    #[test]
+    fn test_predict_to_writer() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = Array2::random_using((5, 3), Uniform::new(-1., 1.), &mut rng);
+        let model = KMeans::from_centroids(centroids, L2Dist);
+        // More than one chunk
+        let observations = Array2::random_using((20000, 3), Uniform::new(-1., 1.), &mut rng);
+        let expected = model.predict(&observations);
+
+        let mut text = Vec::new();
+        model
+            .predict_to_writer(&observations, LabelFormat::Text, &mut text)
+            .unwrap();
+        let labels: Vec<usize> = String::from_utf8(text)
+            .unwrap()
+            .lines()
+            .map(|l| l.parse().unwrap())
+            .collect();
+        assert_eq!(labels, expected.to_vec());
+
+        let mut binary = Vec::new();
+        model
+            .predict_to_writer(&observations, LabelFormat::Binary, &mut binary)
+            .unwrap();
+        let words: Vec<u64> = binary
+            .chunks(8)
+            .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        assert_eq!(words[0], 20000);
+        assert!(words[1..]
+            .iter()
+            .zip(&expected)
+            .all(|(&w, &e)| w as usize == e));
+    }
+
+    #[test]
+    fn test_predict_chunked() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = Array2::random_using((5, 3), Uniform::new(-1., 1.), &mut rng);
+        let model = KMeans::from_centroids(centroids, L2Dist);
+        let observations = Array2::random_using((1000, 3), Uniform::new(-1., 1.), &mut rng);
+        let expected = model.predict(&observations);
+
+        let chunks: Vec<_> = model.predict_chunked(&observations, 300).collect();
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            vec![300, 300, 300, 100]
+        );
+        let labels: Vec<usize> = chunks.iter().flatten().copied().collect();
+        assert_eq!(labels, expected.to_vec());
+
+        // Owned batches, produced on the fly
+        let batches = (0..4).map(|i| {
+            observations
+                .slice(s![i * 250..(i + 1) * 250, ..])
+                .to_owned()
+        });
+        let labels: Vec<usize> = model.predict_batches(batches).flatten().collect();
+        assert_eq!(labels, expected.to_vec());
+    }
+
+    #[test]
+    fn test_centroid_mst() {
+        let model = KMeans {
+            cluster_count: Array1::ones(5),
+            ..KMeans::from_centroids(
+                array![[0., 0.], [10., 0.], [1., 0.], [10., 2.], [4., 0.]],
+                L2Dist,
+            )
+        };
+        let edges = model.centroid_mst();
+        assert_eq!(edges.len(), 4);
+        // The edges form a tree: each one joins two previously disconnected components
+        let mut component: Vec<usize> = (0..5).collect();
+        for &(a, b, _) in &edges {
+            let (ca, cb) = (component[a], component[b]);
+            assert_ne!(ca, cb);
+            component
+                .iter_mut()
+                .filter(|c| **c == cb)
+                .for_each(|c| *c = ca);
+        }
+        assert_eq!(edges, vec![(0, 2, 1.), (2, 4, 3.), (4, 1, 6.), (1, 3, 2.)]);
+
+        let single = KMeans::from_centroids(array![[1., 1.]], L2Dist);
+        assert!(single.centroid_mst().is_empty());
+    }
+
+    #[test]
+    fn test_cluster_overlap_matrix() {
+        let model = KMeans {
+            cluster_count: array![0., 0., 0.],
+            ..KMeans::from_centroids(array![[0., 0.], [2., 0.], [10., 0.]], L2Dist)
+        };
+        let observations = array![
+            [0., 0.],
+            [0.9, 0.],
+            [-1., 0.],
+            [2., 0.],
+            [5.5, 0.],
+            [8., 0.],
+            [10., 0.]
+        ];
+        let overlap = model.cluster_overlap_matrix(&observations);
+        assert_abs_diff_eq!(
+            overlap,
+            array![[0., 1., 0.], [0.5, 0., 0.5], [0., 1., 0.]],
+            epsilon = 1e-10
+        );
+
+        // Rows of empty clusters and of single cluster models are 0
+        let overlap = model.cluster_overlap_matrix(&array![[0., 0.]]);
+        assert_abs_diff_eq!(overlap.row(1), array![0., 0., 0.]);
+        let model = KMeans {
+            cluster_count: array![0.],
+            ..KMeans::from_centroids(array![[0., 0.]], L2Dist)
+        };
+        assert_abs_diff_eq!(model.cluster_overlap_matrix(&observations), array![[0.]]);
+    }
+
+    #[test]
+    fn test_remove_cluster() {
+        let mut model = KMeans {
+            cluster_count: array![2., 1., 1., 1.],
+            ..KMeans::from_centroids(array![[0., 0.], [10., 0.], [1., 0.], [20., 0.]], L2Dist)
+        };
+        let mut labels = array![0, 1, 2, 3, 0];
+        model.remove_cluster(2, &mut labels).unwrap();
+        assert_abs_diff_eq!(model.centroids(), &array![[0., 0.], [10., 0.], [20., 0.]]);
+        assert_abs_diff_eq!(model.cluster_count(), &array![3., 1., 1.]);
+        assert_eq!(labels, array![0, 1, 0, 2, 0]);
+
+        assert!(matches!(
+            model.remove_cluster(3, &mut labels),
+            Err(KMeansError::InvalidClusterIndex {
+                index: 3,
+                n_clusters: 3
+            })
+        ));
+        model.remove_cluster(0, &mut labels).unwrap();
+        model.remove_cluster(0, &mut labels).unwrap();
+        assert_eq!(labels, array![0, 0, 0, 0, 0]);
+        assert!(matches!(
+            model.remove_cluster(0, &mut labels),
+            Err(KMeansError::NoClusterLeft)
+        ));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[10., 0.], [0., 10.], [0., 0.], [10., 10.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+
+        let fit = |seed| {
+            let mut model = KMeans::params_with_rng(4, Xoshiro256Plus::seed_from_u64(seed))
+                .fit(&dataset)
+                .unwrap();
+            let mut labels = model.predict(&observations);
+            model.canonicalize(&mut labels);
+            (model, labels)
+        };
+        let (model1, labels1) = fit(1);
+        let (model2, labels2) = fit(2);
+        assert_abs_diff_eq!(*model1.centroids(), *model2.centroids(), epsilon = 1e-5);
+        assert_eq!(model1.cluster_count(), model2.cluster_count());
+        assert_eq!(labels1, labels2);
+        // The labels still match the reordered centroids
+        assert_eq!(model1.predict(&observations), labels1);
+        let xs = model1.centroids().column(0).to_vec();
+        assert!(xs.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_reseed_centroid() {
+        let observations = array![[0f64, 0.], [1., 0.], [10., 0.], [11., 0.]];
+        let dataset = DatasetBase::from(observations.clone());
+        let mut model = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![[0., 0.], [10., 0.]]))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model.predict(&observations), array![0, 0, 1, 1]);
+
+        assert!(matches!(
+            model.reseed_centroid(2, array![0., 0.]),
+            Err(KMeansError::InvalidClusterIndex {
+                index: 2,
+                n_clusters: 2
+            })
+        ));
+        assert!(matches!(
+            model.reseed_centroid(1, array![0.]),
+            Err(KMeansError::FeatureMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+
+        // Before the move, a far observation is rejected
+        let far = array![[-100., 0.], [100., 0.]];
+        assert_eq!(
+            model.predict_with_zscore_rejection(&far, 1.),
+            array![None, None]
+        );
+        model.reseed_centroid(1, array![0.9, 0.]).unwrap();
+        assert_eq!(model.centroids().row(1), array![0.9, 0.]);
+        assert_eq!(model.predict(&observations), array![0, 1, 1, 1]);
+        // The moved cluster has no statistics to reject observations with
+        assert_eq!(
+            model.predict_with_zscore_rejection(&far, 1.),
+            array![None, Some(1)]
+        );
+    }
+
+    #[test]
+    fn test_refit_clusters() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [40., 0.]];
+        let observations = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng);
+        let centroids = array![[1., 0.], [10., 0.], [30., 0.]];
+        let mut model = KMeans::from_centroids(centroids.clone(), L2Dist);
+
+        let memberships = model
+            .refit_clusters(&observations, &[2], 1e-4, 300, &mut rng)
+            .unwrap();
+        // The other centroids are frozen
+        assert_eq!(
+            model.centroids().slice(s![..2, ..]),
+            centroids.slice(s![..2, ..])
+        );
+        assert_abs_diff_eq!(
+            model.centroids().row(2),
+            expected_centroids.row(2),
+            epsilon = 0.5
+        );
+        assert_eq!(memberships, model.predict(&observations));
+        assert_abs_diff_eq!(model.cluster_count().sum(), 300.);
+
+        // A listed cluster without members is reseeded on an observation
+        model.reseed_centroid(0, array![-100., 0.]).unwrap();
+        model
+            .refit_clusters(&observations, &[0], 1e-4, 300, &mut rng)
+            .unwrap();
+        assert!(model.centroids()[[0, 0]] > -50.);
+        assert!(model.cluster_count()[0] > 0.);
+
+        assert!(matches!(
+            model.refit_clusters(&observations, &[0, 3], 1e-4, 300, &mut rng),
+            Err(KMeansError::InvalidClusterIndex {
+                index: 3,
+                n_clusters: 3
+            })
+        ));
+        assert!(matches!(
+            model.refit_clusters(&array![[0.]], &[0], 1e-4, 300, &mut rng),
+            Err(KMeansError::FeatureMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_perturb_and_refit() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [40., 0.]];
+        let observations = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng);
+        // One centroid in between the first two blobs and two within the last one is a local
+        // minimum
+        let mut model = KMeans::params(3)
+            .init_method(KMeansInit::Precomputed(array![
+                [5., 0.],
+                [39., 0.],
+                [41., 0.]
+            ]))
+            .n_runs(1)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let stuck = model.clone();
+
+        let mut inertia = model.inertia();
+        for _ in 0..20 {
+            let previous = model.clone();
+            if model.perturb_and_refit(&observations, 1., 1e-4, 300, &mut rng) {
+                assert!(model.inertia() < inertia);
+                inertia = model.inertia();
+            } else {
+                assert_eq!(model, previous);
+            }
+        }
+        assert!(model.inertia() < stuck.inertia() / 2.);
+        assert_abs_diff_eq!(model.cluster_count().sum(), 300.);
+        for expected in expected_centroids.rows() {
+            let (closest, _) = closest_centroid(&L2Dist, model.centroids(), &expected);
+            assert_abs_diff_eq!(model.centroids().row(closest), expected, epsilon = 0.5);
+        }
+    }
+
+    #[test]
+    fn test_split_until_radius() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.], [10., 10.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let mut model = KMeans::params_with_rng(1, rng.clone())
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+
+        let max_radius = 4.;
+        let memberships = model
+            .split_until_radius(&observations, max_radius, 10, &mut rng)
+            .unwrap();
+        assert_eq!(model.centroids().nrows(), 4);
+        assert_eq!(memberships, model.predict(&observations));
+        assert_abs_diff_eq!(model.cluster_count().sum(), 200.);
+        Zip::from(observations.rows())
+            .and(&memberships)
+            .for_each(|observation, &c| {
+                assert!(L2Dist.distance(observation, model.centroids().row(c)) <= max_radius);
+            });
+
+        // The number of clusters is capped
+        let mut model = KMeans::params_with_rng(1, rng.clone())
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let memberships = model
+            .split_until_radius(&observations, 0.1, 3, &mut rng)
+            .unwrap();
+        assert_eq!(model.centroids().nrows(), 3);
+        assert!(memberships.iter().all(|&c| c < 3));
+
+        // Copies of a point are covered by a single centroid, even with a negative radius
+        let observations = array![[5., 5.], [5., 5.], [5., 5.], [0., 0.], [0., 1.]];
+        let mut model = KMeans::params_with_rng(2, rng.clone())
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        model.reseed_centroid(0, array![4., 4.]).unwrap();
+        model.reseed_centroid(1, array![0., 3.]).unwrap();
+        let memberships = model
+            .split_until_radius(&observations, -1., 10, &mut rng)
+            .unwrap();
+        assert_eq!(model.centroids().nrows(), 3);
+        assert_eq!(model.centroids().row(0), array![5., 5.]);
+        assert_eq!(memberships, model.predict(&observations));
+        assert_eq!(model.cluster_count()[0], 3.);
+    }
+
+    #[test]
+    fn test_split_until_radius_errors() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations = array![[0., 0.], [1., 1.], [5., 5.]];
+        let mut model = KMeans::params_with_rng(1, rng.clone())
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let res = model.split_until_radius(&array![[0.], [1.]], 0.5, 3, &mut rng);
+        assert!(matches!(
+            res,
+            Err(KMeansError::FeatureMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+        let res = model.split_until_radius(&array![[0., 0.], [f64::NAN, 1.]], 0.5, 3, &mut rng);
+        assert!(matches!(
+            res,
+            Err(KMeansError::NonFiniteObservation { index: 1 })
+        ));
+
+        model.reseed_centroid(0, array![f64::NAN, 0.]).unwrap();
+        let res = model.split_until_radius(&observations, 0.5, 3, &mut rng);
+        assert!(matches!(
+            res,
+            Err(KMeansError::NonFiniteRadius { index: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_weighted_features() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [8., 0.], [0., 8.]];
+        let informative = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng);
+        let labels: Array1<usize> = (0..300).map(|i| i / 100).collect();
+        // Noise features with a larger spread than the clusters
+        let noise = Array2::random_using((300, 3), Uniform::new(-15., 15.), &mut rng);
+        let observations = concatenate![Axis(1), informative, noise];
+        let dataset = DatasetBase::from(observations.clone());
+
+        let model = KMeans::params_with_rng(3, rng.clone())
+            .algorithm(KMeansAlgorithm::WeightedFeatures { beta: 4. })
+            .fit(&dataset)
+            .unwrap();
+        let weights = model.feature_weights().unwrap();
+        assert_abs_diff_eq!(weights.sum(), 1., epsilon = 1e-10);
+        assert!(weights.slice(s![..2]).iter().all(|&w| w > 0.3));
+        assert!(weights.slice(s![2..]).iter().all(|&w| w < 0.1));
+
+        // The weighted model recovers the clusters, up to a permutation of the labels
+        let memberships = model.predict(&observations);
+        let agreement = |memberships: &Array1<usize>| {
+            let mut confusion = Array2::<usize>::zeros((3, 3));
+            Zip::from(memberships)
+                .and(&labels)
+                .for_each(|&m, &l| confusion[(m, l)] += 1);
+            confusion
+                .rows()
+                .into_iter()
+                .map(|r| *r.iter().max().unwrap())
+                .sum::<usize>()
+        };
+        assert_eq!(agreement(&memberships), 300);
+
+        let unweighted = KMeans::params_with_rng(3, rng).fit(&dataset).unwrap();
+        assert!(unweighted.feature_weights().is_none());
+        assert!(agreement(&unweighted.predict(&observations)) < 250);
+    }
+
+    #[test]
+    fn test_density_weighting() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        // A dense core around the origin and a sparse tail along the first feature
+        let core = Array2::random_using((200, 2), Uniform::new(-0.5, 0.5), &mut rng);
+        let mut tail = Array2::random_using((20, 2), Uniform::new(-0.5, 0.5), &mut rng);
+        tail.column_mut(0)
+            .assign(&Array1::random_using(20, Uniform::new(5., 15.), &mut rng));
+        let dataset = DatasetBase::from(concatenate![Axis(0), core, tail]);
+
+        let centroid = |weighting| -> f64 {
+            let model = KMeans::params_with_rng(1, rng.clone())
+                .density_weighting(weighting)
+                .fit(&dataset)
+                .unwrap();
+            model.centroids()[[0, 0]]
+        };
+        let unweighted = centroid(None);
+        let favor = centroid(Some(DensityWeighting::Favor { k: 5 }));
+        let penalize = centroid(Some(DensityWeighting::Penalize { k: 5 }));
+        // The dense core pulls the centroid back towards the origin
+        assert!(favor.abs() < unweighted / 2.);
+        assert!(penalize > unweighted * 2.);
+    }
+
+    #[test]
+    fn test_shrinkage() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        // Two large clusters and a tiny one far from the others
+        let expected_centroids = array![[0., 0.], [10., 0.], [5., 30.]];
+        let large =
+            linfa_datasets::generate::blobs(100, &expected_centroids.slice(s![..2, ..]), &mut rng);
+        let tiny =
+            linfa_datasets::generate::blobs(3, &expected_centroids.slice(s![2.., ..]), &mut rng);
+        let observations = concatenate![Axis(0), large, tiny];
+        let mean = observations.mean_axis(Axis(0)).unwrap();
+        let dataset = DatasetBase::from(observations);
+
+        let tiny_centroid = |shrinkage| {
+            let model = KMeans::params(3)
+                .init_method(KMeansInit::Precomputed(expected_centroids.clone()))
+                .n_runs(1)
+                .shrinkage(shrinkage)
+                .fit(&dataset)
+                .unwrap();
+            model.centroids().row(2).to_owned()
+        };
+        let unshrunk = tiny_centroid(0.);
+        let shrunk = tiny_centroid(0.2);
+        assert_abs_diff_eq!(unshrunk, expected_centroids.row(2), epsilon = 1.);
+        let dist_to_mean = |c: &Array1<f64>| L2Dist.distance(c.view(), mean.view());
+        assert!(dist_to_mean(&shrunk) < 0.9 * dist_to_mean(&unshrunk));
+    }
+
+    #[test]
+    fn test_data_stats() {
+        let observations = array![[0., 0.], [2., 0.], [4., 3.], [6., 1.]];
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params(2).n_runs(1);
+        assert!(params.clone().fit(&dataset).unwrap().data_stats().is_none());
+
+        // The stats cached by the fit are those computed on the fly
+        let model = params.shrinkage(0.1).fit(&dataset).unwrap();
+        assert_eq!(model.data_stats(), Some(&fit_stats(&observations)));
+        assert_abs_diff_eq!(model.data_stats().unwrap().mean, array![3., 1.]);
+    }
+
+    #[test]
+    fn test_trimmed_mean_centroids() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let mut observations = Array2::random_using((20, 2), Uniform::new(0., 1.), &mut rng);
+        // A single extreme value in the first feature
+        observations[[0, 0]] = 1000.;
+        let dataset = DatasetBase::from(observations.clone());
+        let fit = |centroid_update| {
+            KMeans::params(1)
+                .init_method(KMeansInit::Precomputed(array![[0.5, 0.5]]))
+                .n_runs(1)
+                .centroid_update(centroid_update)
+                .fit(&dataset)
+                .unwrap()
+        };
+
+        let mean = fit(CentroidUpdate::Mean);
+        assert!(mean.centroids()[[0, 0]] > 40.);
+        let trimmed = fit(CentroidUpdate::TrimmedMean { trim: 0.1 });
+        assert!(trimmed.centroids()[[0, 0]] > 0. && trimmed.centroids()[[0, 0]] < 1.);
+        // The other feature has no outlier and stays close to its mean
+        let expected = observations.column(1).mean().unwrap();
+        assert_abs_diff_eq!(trimmed.centroids()[[0, 1]], expected, epsilon = 0.1);
+    }
+
+    #[test]
+    fn test_minkowski_matches_euclidean() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = Array2::random_using((5, 4), Uniform::new(0., 10.), &mut rng);
+        let observations = Array2::random_using((200, 4), Uniform::new(0., 10.), &mut rng);
+        let minkowski = KMeans {
+            cluster_count: Array1::ones(5),
+            ..KMeans::from_centroids(centroids.clone(), LpDist(2.))
+        };
+        let euclidean = KMeans {
+            cluster_count: Array1::ones(5),
+            ..KMeans::from_centroids(centroids, L2Dist)
+        };
+        // The p-norm is a monotone transform of the squared euclidean distance for p = 2
+        assert_eq!(
+            minkowski.predict(&observations),
+            euclidean.predict(&observations)
+        );
+    }
+
+    #[test]
+    fn test_median_centroids() {
+        let observations = array![[0f64, 0.], [1., 10.], [2., 1.], [3., 2.], [90., 3.]];
+        let dataset = DatasetBase::from(observations);
+        let model = KMeans::params_with(1, Xoshiro256Plus::seed_from_u64(42), L1Dist)
+            .init_method(KMeansInit::Precomputed(array![[2., 2.]]))
+            .n_runs(1)
+            .centroid_update(CentroidUpdate::Median)
+            .fit(&dataset)
+            .unwrap();
+        // Even number of values per feature, whose middle two are both 2
+        assert_abs_diff_eq!(*model.centroids(), array![[2., 2.]]);
+
+        let observations = array![[0f64], [1.], [5.], [100.]];
+        let dataset = DatasetBase::from(observations);
+        let model = KMeans::params(1)
+            .init_method(KMeansInit::Precomputed(array![[2.]]))
+            .n_runs(1)
+            .max_n_iterations(1)
+            .centroid_update(CentroidUpdate::Median)
+            .fit(&dataset)
+            .unwrap();
+        // Middle value of the members and the previous centroid, unlike the mean of 21.6
+        assert_abs_diff_eq!(*model.centroids(), array![[2.]]);
+    }
+
+    #[test]
+    fn test_accumulation_precision() {
+        // The naive sum of 200000 `0.1f32` drifts by about 0.2%
+        let value = 0.1f32;
+        let dataset = DatasetBase::from(Array2::from_elem((200_000, 1), value));
+        let centroid = |accumulation| {
+            let model = KMeans::params(1)
+                .init_method(KMeansInit::Precomputed(array![[value]]))
+                .n_runs(1)
+                .max_n_iterations(1)
+                .accumulation(accumulation)
+                .fit(&dataset)
+                .unwrap();
+            model.centroids()[[0, 0]]
+        };
+        assert!((centroid(Accumulation::Naive) - value).abs() > 1e-4);
+        assert_abs_diff_eq!(centroid(Accumulation::Kahan), value, epsilon = 1e-7);
+        assert_abs_diff_eq!(centroid(Accumulation::Pairwise), value, epsilon = 1e-7);
+    }
+
+    #[test]
+    fn test_accumulation_strategies_agree() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(500, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations);
+        for density_weighting in [None, Some(DensityWeighting::Favor { k: 5 })] {
+            let centroids = |accumulation| {
+                let model = KMeans::params_with_rng(3, rng.clone())
+                    .init_method(KMeansInit::Precomputed(array![
+                        [1., 1.],
+                        [9., 1.],
+                        [1., 9.]
+                    ]))
+                    .n_runs(1)
+                    .max_n_iterations(1)
+                    .density_weighting(density_weighting.clone())
+                    .accumulation(accumulation)
+                    .fit(&dataset)
+                    .unwrap();
+                model.centroids().clone()
+            };
+            let naive = centroids(Accumulation::Naive);
+            assert_abs_diff_eq!(naive, centroids(Accumulation::Kahan), epsilon = 1e-10);
+            assert_abs_diff_eq!(naive, centroids(Accumulation::Pairwise), epsilon = 1e-10);
+        }
+    }
+
+    #[test]
+    fn test_fit_grouped() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        // Group "a" spans two blobs, and group "b" lies on the first one
+        let blobs = linfa_datasets::generate::blobs(50, &array![[0., 0.], [10., 0.]], &mut rng);
+        let shared = linfa_datasets::generate::blobs(50, &array![[0., 0.]], &mut rng);
+        let observations = concatenate![Axis(0), blobs, shared];
+        let group_ids: Vec<_> = (0..150).map(|i| if i < 100 { "a" } else { "b" }).collect();
+        let group_to_clusters = HashMap::from([("a", vec![0, 1]), ("b", vec![2])]);
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params_with_rng(3, rng).check().unwrap();
+
+        let model = params
+            .fit_grouped(&dataset, &group_ids, &group_to_clusters)
+            .unwrap();
+        assert_eq!(model.cluster_count(), &array![50., 50., 50.]);
+        let a = model.centroids().select(Axis(0), &[0, 1]);
+        let (low, high) = if a[[0, 0]] < a[[1, 0]] {
+            (a.row(0), a.row(1))
+        } else {
+            (a.row(1), a.row(0))
+        };
+        // m_k-means keeps the previous centroid in each mean, so allow for a little drift
+        assert_abs_diff_eq!(
+            low,
+            observations.slice(s![..50, ..]).mean_axis(Axis(0)).unwrap(),
+            epsilon = 0.1
+        );
+        assert_abs_diff_eq!(
+            high,
+            observations
+                .slice(s![50..100, ..])
+                .mean_axis(Axis(0))
+                .unwrap(),
+            epsilon = 0.1
+        );
+        assert_abs_diff_eq!(
+            model.centroids().row(2),
+            observations
+                .slice(s![100.., ..])
+                .mean_axis(Axis(0))
+                .unwrap(),
+            epsilon = 0.1
+        );
+
+        let fit = |group_to_clusters: &HashMap<&str, Vec<usize>>| {
+            params.fit_grouped(&dataset, &group_ids, group_to_clusters)
+        };
+        assert!(matches!(
+            fit(&HashMap::from([("a", vec![0, 1]), ("b", vec![])])),
+            Err(KMeansError::EmptyGroup)
+        ));
+        assert!(matches!(
+            fit(&HashMap::from([("a", vec![0, 3]), ("b", vec![2])])),
+            Err(KMeansError::InvalidClusterIndex {
+                index: 3,
+                n_clusters: 3
+            })
+        ));
+        assert!(matches!(
+            fit(&HashMap::from([("a", vec![0, 1])])),
+            Err(KMeansError::UnmappedGroup { index: 100 })
+        ));
+        assert!(matches!(
+            params.fit_grouped(&dataset, &group_ids[..10], &group_to_clusters),
+            Err(KMeansError::LinfaError(_))
+        ));
+    }
+
+    #[test]
+    fn test_predict_idw() {
+        let observations = array![[0., 0.], [10., 0.], [5., 0.], [1., 0.]];
+        let model = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![[0., 0.], [10., 0.]]))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(array![[0., 0.], [10., 0.]]))
+            .unwrap();
+        let memberships = model.predict_idw(&observations, 1e-9);
+        assert_eq!(memberships.dim(), (4, 2));
+        assert_abs_diff_eq!(
+            memberships.sum_axis(Axis(1)),
+            Array1::ones(4),
+            epsilon = 1e-12
+        );
+        // Observations on a centroid
+        assert!(memberships[[0, 0]] > 1. - 1e-6);
+        assert!(memberships[[1, 1]] > 1. - 1e-6);
+        // Halfway between the centroids
+        assert_abs_diff_eq!(memberships.row(2), array![0.5, 0.5], epsilon = 1e-9);
+        // Squared distances of 1 and 81
+        assert_abs_diff_eq!(memberships[[3, 0]], 81. / 82., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_drift_report() {
+        let training = array![[0., 0.], [0., 1.], [10., 0.], [10., 1.]];
+        let model = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![[0., 0.5], [10., 0.5]]))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(training))
+            .unwrap();
+        // Three quarters of the batch fall in the first cluster, shifted upwards
+        let batch = array![[0., 1.5], [1., 1.5], [-1., 1.5], [10., 0.5]];
+        let report = model.drift_report(&batch);
+        assert_eq!(report.batch_counts, array![3, 1]);
+        assert_eq!(&report.training_counts, model.cluster_count());
+        assert_abs_diff_eq!(report.ratios, array![1.5, 0.5], epsilon = 1e-10);
+        assert_abs_diff_eq!(
+            report.mean_shifts,
+            array![[0., 1.], [0., 0.]],
+            epsilon = 1e-10
+        );
+        assert_eq!(report.growing_clusters(1.2), vec![0]);
+
+        let report = model.drift_report(&Array2::zeros((0, 2)));
+        assert_eq!(report.batch_counts, array![0, 0]);
+        assert_abs_diff_eq!(report.ratios, array![0., 0.]);
+        assert!(report.growing_clusters(0.).is_empty());
+    }
+
+    #[test]
+    fn test_unique_rows() {
+        let observations = array![[1., 2.], [0., 1.], [1., 2.], [0., 1.], [3., 0.]];
+        let (unique, inverse) = unique_rows(&observations.view());
+        assert_eq!(unique, array![[1., 2.], [0., 1.], [3., 0.]]);
+        assert_eq!(inverse, array![0, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_deduplicate() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let distinct = linfa_datasets::generate::blobs(10, &expected_centroids, &mut rng);
+        // Each distinct observation occurs up to 5 times, interleaved
+        let indices: Vec<usize> = (0..100).map(|i| (i * 7) % 30).collect();
+        let observations = distinct.select(Axis(0), &indices);
+        let dataset = DatasetBase::from(observations.clone());
+
+        for density_weighting in [None, Some(DensityWeighting::Favor { k: 5 })] {
+            let params = KMeans::params_with_rng(3, rng.clone())
+                .init_method(KMeansInit::Precomputed(array![
+                    [1., 1.],
+                    [9., 1.],
+                    [1., 9.]
+                ]))
+                .density_weighting(density_weighting);
+            let plain = params.clone().fit(&dataset).unwrap();
+            let deduplicated = params.deduplicate(true).fit(&dataset).unwrap();
+            assert_abs_diff_eq!(plain.centroids(), deduplicated.centroids(), epsilon = 1e-10);
+            assert_eq!(plain.cluster_count(), deduplicated.cluster_count());
+            assert_abs_diff_eq!(plain.inertia(), deduplicated.inertia(), epsilon = 1e-10);
+            assert_eq!(
+                plain.predict(&observations),
+                deduplicated.predict(&observations)
+            );
+        }
+    }
+
+    #[test]
+    fn test_partial_fit() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng);
+        // Each half holds half of each blob
+        let (first, second): (Vec<usize>, Vec<usize>) = (0..300).partition(|i| i % 2 == 0);
+        let params = KMeans::params_with_rng(3, rng)
+            .init_method(KMeansInit::Precomputed(array![
+                [1., 1.],
+                [9., 1.],
+                [1., 9.]
+            ]))
+            .check()
+            .unwrap();
+
+        let model = params
+            .partial_fit(
+                None,
+                &DatasetBase::from(observations.select(Axis(0), &first)),
+            )
+            .unwrap();
+        #[cfg(feature = "serde")]
+        let model: KMeans<f64, L2Dist> =
+            serde_json::from_str(&serde_json::to_string(&model).unwrap()).unwrap();
+        assert_eq!(model.cluster_count().sum(), 150.);
+        let model = params
+            .partial_fit(
+                Some(model),
+                &DatasetBase::from(observations.select(Axis(0), &second)),
+            )
+            .unwrap();
+        assert_eq!(model.cluster_count(), &array![100., 100., 100.]);
+
+        let full = params.fit(&DatasetBase::from(observations)).unwrap();
+        assert_abs_diff_eq!(model.centroids(), full.centroids(), epsilon = 0.5);
+
+        let res = params.partial_fit(None, &DatasetBase::from(Array2::<f64>::zeros((0, 2))));
+        assert!(matches!(res, Err(KMeansError::EmptyObservations)));
+    }
+
+    #[test]
+    fn test_update() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng);
+        let params = KMeans::params_with_rng(3, rng).check().unwrap();
+        let first = DatasetBase::from(observations.slice(s![..150, ..]));
+        let second = observations.slice(s![150.., ..]);
+
+        // Same as a second batch of `fit_with`
+        let model = params
+            .fit_with(None, &first)
+            .unwrap_or_else(|err| match err {
+                IncrKMeansError::NotConverged(model) => model,
+                err => panic!("{}", err),
+            });
+        let mut updated = model.clone();
+        let shift = updated.update(&second).unwrap();
+        let expected = match params.fit_with(Some(model), &DatasetBase::from(second)) {
+            Ok(model) | Err(IncrKMeansError::NotConverged(model)) => model,
+            Err(err) => panic!("{}", err),
+        };
+        assert_eq!(updated, expected);
+        assert!(shift > 0.);
+        assert_eq!(updated.cluster_count().sum(), 300.);
+
+        assert_eq!(
+            updated.update(&Array2::zeros((0, 2))).unwrap(),
+            f64::INFINITY
+        );
+        assert_eq!(updated, expected);
+        assert!(matches!(
+            updated.update(&Array2::zeros((2, 3))),
+            Err(KMeansError::FeatureMismatch {
+                expected: 2,
+                actual: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_predict_with_distances() {
+        let model = KMeans {
+            cluster_count: array![1., 1.],
+            ..KMeans::from_centroids(array![[0., 0.], [10., 0.]], L1Dist)
+        };
+        let observations = array![[1., 2.], [7., -1.], [10., 0.]];
+        let (labels, dists) = model.predict_with_distances(&observations);
+        assert_eq!(labels, model.predict(&observations));
+        assert_abs_diff_eq!(dists, array![3., 4., 0.], epsilon = 1e-12);
+        let distances = model.centroid_distances(&observations);
+        assert_abs_diff_eq!(
+            distances,
+            array![[3., 11.], [8., 4.], [10., 0.]],
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_fit_predict() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params_with_rng(3, rng).check().unwrap();
+        let (model, labels) = params.fit_predict(&dataset).unwrap();
+        assert_eq!(model, params.fit(&dataset).unwrap());
+        assert_eq!(labels, model.predict(&observations));
+    }
+
+    #[test]
+    fn test_elkan() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = Array2::random_using((8, 20), Uniform::new(-3., 3.), &mut rng);
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let fit = |algorithm| {
+            KMeans::params_with(8, rng.clone(), L1Dist)
+                .init_method(KMeansInit::Random)
+                .n_runs(3)
+                .algorithm(algorithm)
+                .fit(&dataset)
+                .unwrap()
+        };
+        let lloyd = fit(KMeansAlgorithm::Lloyd);
+        let elkan = fit(KMeansAlgorithm::Elkan);
+        assert_eq!(lloyd.predict(&observations), elkan.predict(&observations));
+        assert_abs_diff_eq!(lloyd.centroids(), elkan.centroids(), epsilon = 1e-10);
+        assert_abs_diff_eq!(lloyd.inertia(), elkan.inertia(), epsilon = 1e-10);
+        assert_eq!(lloyd.n_iterations(), elkan.n_iterations());
+
+        let fit = |algorithm| {
+            KMeans::params_with_rng(8, rng.clone())
+                .init_method(KMeansInit::Random)
+                .algorithm(algorithm)
+                .fit(&dataset)
+                .unwrap()
+        };
+        let lloyd = fit(KMeansAlgorithm::Lloyd);
+        let elkan = fit(KMeansAlgorithm::Elkan);
+        assert_eq!(lloyd.predict(&observations), elkan.predict(&observations));
+        assert_abs_diff_eq!(lloyd.centroids(), elkan.centroids(), epsilon = 1e-10);
+    }
+
+    #[test]
+    fn test_full() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = Array2::random_using((8, 20), Uniform::new(-3., 3.), &mut rng);
+        // More observations than a block of the batched assignment step
+        let observations = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let fit = |algorithm| {
+            KMeans::params_with_rng(8, rng.clone())
+                .init_method(KMeansInit::Random)
+                .n_runs(3)
+                .algorithm(algorithm)
+                .fit(&dataset)
+                .unwrap()
+        };
+        let lloyd = fit(KMeansAlgorithm::Lloyd);
+        let full = fit(KMeansAlgorithm::Full);
+        assert_eq!(lloyd.predict(&observations), full.predict(&observations));
+        assert_abs_diff_eq!(lloyd.centroids(), full.centroids(), epsilon = 1e-8);
+        assert_abs_diff_eq!(lloyd.inertia(), full.inertia(), epsilon = 1e-6);
+        assert_eq!(lloyd.n_iterations(), full.n_iterations());
+    }
+
+    #[cfg(feature = "sprs")]
+    #[test]
+    fn test_fit_sparse() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[8., 0., 0., 0.], [0., 0., 8., 0.], [0., 8., 0., 8.]];
+        let observations = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng)
+            .mapv(|x| if x.abs() < 0.5 { 0. } else { x });
+        let csr = sprs::CsMat::csr_from_dense(observations.view(), 0.);
+        assert!(csr.nnz() < observations.len());
+        let params = KMeans::params_with_rng(3, rng).check().unwrap();
+
+        let model = params.fit_sparse(csr.view()).unwrap();
+        assert_eq!(model.cluster_count(), &array![100., 100., 100.]);
+        assert_eq!(model.stop_reason(), Some(StopReason::CentroidShift));
+        let labels = model.predict(&observations);
+        assert_eq!(model.predict_sparse(csr.view()), labels);
+        for (c, centroid) in model.centroids().rows().into_iter().enumerate() {
+            let members: Vec<_> = (0..300).filter(|&i| labels[i] == c).collect();
+            let mean = observations
+                .select(Axis(0), &members)
+                .mean_axis(Axis(0))
+                .unwrap();
+            assert_abs_diff_eq!(centroid, mean, epsilon = 1e-8);
+        }
+        let inertia = labels
+            .iter()
+            .zip(observations.rows())
+            .map(|(&c, x)| L2Dist.rdistance(model.centroids().row(c), x))
+            .sum::<f64>()
+            / 300.;
+        assert_abs_diff_eq!(model.inertia(), inertia, epsilon = 1e-8);
+
+        // CSC matrices are converted to CSR first
+        let csc = sprs::CsMat::csc_from_dense(observations.view(), 0.);
+        assert_eq!(model.predict_sparse(csc.view()), labels);
+        let csc_model = params.fit_sparse(csc.view()).unwrap();
+        assert_abs_diff_eq!(csc_model.centroids(), model.centroids(), epsilon = 1e-12);
+    }
+
+    #[cfg(feature = "sprs")]
+    #[test]
+    fn test_fit_sparse_errors() {
+        let params = KMeans::params(2).check().unwrap();
+        let sparse = |dense: Array2<f64>| sprs::CsMat::csr_from_dense(dense.view(), 0.);
+        assert!(matches!(
+            params.fit_sparse(sparse(Array2::zeros((0, 2))).view()),
+            Err(KMeansError::EmptyObservations)
+        ));
+        assert!(matches!(
+            params.fit_sparse(sparse(array![[1., 0.]]).view()),
+            Err(KMeansError::NotEnoughObservations {
+                n_observations: 1,
+                n_clusters: 2
+            })
+        ));
+        assert!(matches!(
+            params.fit_sparse(sparse(Array2::zeros((3, 0))).view()),
+            Err(KMeansError::NoFeatures)
+        ));
+        assert!(matches!(
+            params.fit_sparse(
+                sprs::CsMat::new(
+                    (3, 2),
+                    vec![0, 1, 2, 3],
+                    vec![0, 1, 0],
+                    vec![1., 1., f64::NAN]
+                )
+                .view()
+            ),
+            Err(KMeansError::NonFiniteObservation { index: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_fit_weighted() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let distinct = linfa_datasets::generate::blobs(10, &expected_centroids, &mut rng);
+        // Each distinct observation occurs between 0 and 4 times
+        let counts: Vec<usize> = (0..30).map(|i| (i * 7) % 5).collect();
+        let indices: Vec<usize> = (0..30)
+            .flat_map(|i| std::iter::repeat_n(i, counts[i]))
+            .collect();
+        let weights = Array1::from_iter(counts.iter().map(|&c| c as f64));
+        let params = KMeans::params_with_rng(3, rng)
+            .init_method(KMeansInit::Precomputed(array![
+                [1., 1.],
+                [9., 1.],
+                [1., 9.]
+            ]))
+            .check()
+            .unwrap();
+
+        let repeated = params
+            .fit(&DatasetBase::from(distinct.select(Axis(0), &indices)))
+            .unwrap();
+        let weighted = params
+            .fit_weighted(&DatasetBase::from(distinct.clone()), &weights)
+            .unwrap();
+        assert_abs_diff_eq!(repeated.centroids(), weighted.centroids(), epsilon = 1e-10);
+        assert_abs_diff_eq!(
+            repeated.cluster_count(),
+            weighted.cluster_count(),
+            epsilon = 1e-10
+        );
+        assert_abs_diff_eq!(repeated.inertia(), weighted.inertia(), epsilon = 1e-10);
+
+        let dataset = DatasetBase::from(distinct);
+        let res = params.fit_weighted(&dataset, &Array1::ones(29));
+        assert!(matches!(
+            res,
+            Err(KMeansError::SampleWeightsLength {
+                expected: 30,
+                actual: 29
+            })
+        ));
+        let mut negative = Array1::ones(30);
+        negative[3] = -1.;
+        for weights in [negative, Array1::zeros(30)] {
+            let res = params.fit_weighted(&dataset, &weights);
+            assert!(matches!(res, Err(KMeansError::InvalidSampleWeights)));
+        }
+        let res = KMeans::params(3)
+            .centroid_update(CentroidUpdate::Median)
+            .check()
+            .unwrap()
+            .fit_weighted(&dataset, &Array1::ones(30));
+        assert!(matches!(
+            res,
+            Err(KMeansError::InvalidParams(KMeansParamsError::MedianWeights))
+        ));
+    }
+
+    #[test]
+    fn test_max_n_iterations_is_exact() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations);
+        for algorithm in [
+            KMeansAlgorithm::Lloyd,
+            KMeansAlgorithm::WeightedFeatures { beta: 2. },
+        ] {
+            for max_n_iterations in [1, 5, 8] {
+                // No shift is below the smallest positive tolerance before the cap
+                let mut n_steps = 0;
+                let model = KMeans::params_with_rng(3, rng.clone())
+                    .n_runs(1)
+                    .algorithm(algorithm.clone())
+                    .tolerance(f64::MIN_POSITIVE)
+                    .max_n_iterations(max_n_iterations)
+                    .check()
+                    .unwrap()
+                    .fit_with_callback(&dataset, |_| {
+                        n_steps += 1;
+                        ControlFlow::Continue(())
+                    })
+                    .unwrap();
+                assert_eq!(n_steps, max_n_iterations);
+                assert_eq!(model.n_iterations(), max_n_iterations);
+                assert_eq!(model.stop_reason(), Some(StopReason::MaxIterations));
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_seed() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [5., 5.], [10., 0.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations);
+        let params = |rng| {
+            KMeans::params_with_rng(3, rng)
+                .init_method(KMeansInit::Random)
+                .n_runs(1)
+        };
+
+        // The seed replaces the generator the hyperparameters were given
+        let fit = |rng| params(rng).random_seed(7).fit(&dataset).unwrap();
+        let model = fit(Xoshiro256Plus::seed_from_u64(1));
+        assert_eq!(model.random_seed(), Some(7));
+        assert_eq!(
+            model.centroids(),
+            fit(Xoshiro256Plus::seed_from_u64(2)).centroids()
+        );
+        let unseeded = params(Xoshiro256Plus::seed_from_u64(7))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(unseeded.random_seed(), None);
+        assert_eq!(unseeded.centroids(), model.centroids());
+        assert_eq!(
+            KMeans::params(3).fit(&dataset).unwrap().random_seed(),
+            Some(42)
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let loaded: KMeans<f64, L2Dist> =
+                serde_json::from_str(&serde_json::to_string(&model).unwrap()).unwrap();
+            assert_eq!(loaded.random_seed(), Some(7));
+        }
+    }
+
+    #[test]
+    fn test_n_iterations_and_score() {
+        let observations = array![[0.], [2.]];
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params(1)
+            .init_method(KMeansInit::Precomputed(array![[10.]]))
+            .n_runs(1);
+        // The first update step moves the centroid by 6, and each following one by a third of
+        // the previous step
+        let model = params.clone().tolerance(7.).fit(&dataset).unwrap();
+        assert_eq!(model.n_iterations(), 1);
+        let model = params.clone().tolerance(1.).fit(&dataset).unwrap();
+        assert_eq!(model.n_iterations(), 3);
+        let model = params
+            .clone()
+            .tolerance(1e-12)
+            .max_n_iterations(5)
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model.n_iterations(), 5);
+
+        let model = params.fit(&dataset).unwrap();
+        // Squared distances of 1 to the centroid at 1
+        assert_abs_diff_eq!(model.score(&observations), -1., epsilon = 1e-6);
+        assert_abs_diff_eq!(model.score(&array![[1.], [4.]]), -4.5, epsilon = 1e-3);
+        assert_eq!(model.score(&Array2::zeros((0, 1))), 0.);
+
+        // Each batch of `fit_with` counts as an iteration
+        let params = KMeans::params(1).check().unwrap();
+        let model = params
+            .fit_with(None, &dataset)
+            .unwrap_or_else(|err| match err {
+                IncrKMeansError::NotConverged(model) => model,
+                err => panic!("{}", err),
+            });
+        let model = params
+            .fit_with(Some(model), &dataset)
+            .unwrap_or_else(|err| match err {
+                IncrKMeansError::NotConverged(model) => model,
+                err => panic!("{}", err),
+            });
+        assert_eq!(model.n_iterations(), 2);
+    }
+
+    #[test]
+    fn test_snap_to_data() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params_with_rng(3, rng);
+        let model = params.clone().fit(&dataset).unwrap();
+        let snapped = params.snap_to_data(true).fit(&dataset).unwrap();
+
+        for centroid in snapped.centroids().rows() {
+            assert!(observations.rows().into_iter().any(|row| row == centroid));
+        }
+        assert_abs_diff_eq!(*snapped.centroids(), *model.centroids(), epsilon = 1.);
+        // The statistics are those of the snapped centroids
+        assert_eq!(snapped.cluster_count().sum(), 150.);
+        let dists = snapped.transform(&observations);
+        assert_abs_diff_eq!(snapped.inertia(), dists.mean().unwrap(), epsilon = 1e-10);
+        assert!(snapped.inertia() >= model.inertia());
+    }
+
+    #[test]
+    fn test_record_trajectory() {
+        let observations = array![[0.], [1.], [9.], [10.]];
+        let dataset = DatasetBase::from(observations);
+        let params = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![[0.], [1.]]))
+            .n_runs(1);
+        assert!(params.fit(&dataset).unwrap().trajectory().is_empty());
+
+        let model = params
+            .clone()
+            .record_trajectory(Some(2))
+            .fit(&dataset)
+            .unwrap();
+        let trajectory = model.trajectory();
+        assert_eq!(trajectory.len(), 2);
+        assert_eq!(trajectory[0].0, array![[0.], [1.]]);
+        assert_eq!(trajectory[0].1, array![0, 1, 1, 1]);
+        // The second observation moves to the first cluster once the second centroid moves away
+        assert_abs_diff_eq!(trajectory[1].0, array![[0.], [5.25]], epsilon = 1e-10);
+        assert_eq!(trajectory[1].1, array![0, 0, 1, 1]);
+
+        let model = params
+            .clone()
+            .record_trajectory(Some(1000))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model.trajectory().len() as u64, model.n_iterations());
+
+        // Deduplicated fits still label every observation
+        let dataset = DatasetBase::from(array![[0.], [0.], [1.], [9.], [10.], [10.]]);
+        let model = params
+            .record_trajectory(Some(1))
+            .deduplicate(true)
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model.trajectory()[0].1, array![0, 0, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(1000, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations);
+        let params = KMeans::params_with_rng(3, rng).init_method(KMeansInit::Precomputed(array![
+            [1., 1.],
+            [9., 1.],
+            [1., 9.]
+        ]));
 
-    let first_centroid = centroids.row(0);
-    let (mut closest_index, mut minimum_distance) = (
-        0,
-        dist_fn.rdistance(first_centroid.view(), observation.view()),
-    );
+        // Room for the full working set
+        let full = params.clone().fit(&dataset).unwrap();
+        let large = params
+            .clone()
+            .memory_budget_bytes(Some(1 << 20))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(full.centroids(), large.centroids());
 
-    for (centroid_index, centroid) in iterator.enumerate() {
-        let distance = dist_fn.rdistance(centroid.view(), observation.view());
-        if distance < minimum_distance {
-            closest_index = centroid_index;
-            minimum_distance = distance;
-        }
+        // Room for batches of about 300 observations
+        let small = params
+            .memory_budget_bytes(Some(300 * 32))
+            .fit(&dataset)
+            .unwrap();
+        assert_abs_diff_eq!(*small.centroids(), expected_centroids, epsilon = 0.5);
+        // The training point counts accumulate over the batches
+        assert!(small.cluster_count().sum() > 300.);
     }
-    (closest_index, minimum_distance)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::super::KMeansInit;
-    use super::*;
-    use crate::KMeansParamsError;
-    use approx::assert_abs_diff_eq;
-    use linfa_nn::distance::L1Dist;
-    use ndarray::{array, concatenate, Array, Array1, Array2, Axis};
-    use ndarray_rand::rand::prelude::ThreadRng;
-    use ndarray_rand::rand::SeedableRng;
-    use ndarray_rand::rand_distr::Uniform;
-    use ndarray_rand::RandomExt;
 
     #[test]
-    fn autotraits() {
-        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
-        has_autotraits::<KMeans<f64, L2Dist>>();
-        has_autotraits::<KMeansParamsError>();
-        has_autotraits::<KMeansError>();
-        has_autotraits::<IncrKMeansError<String>>();
+    fn test_fit_mini_batch() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(1000, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations);
+        let params = KMeans::params_with_rng(3, rng)
+            .init_method(KMeansInit::Precomputed(array![
+                [1., 1.],
+                [9., 1.],
+                [1., 9.]
+            ]))
+            .check()
+            .unwrap();
+
+        let full = params.fit(&dataset).unwrap();
+        let mini_batch = params.fit_mini_batch(&dataset, 100).unwrap();
+        assert_abs_diff_eq!(*mini_batch.centroids(), *full.centroids(), epsilon = 0.5);
+        assert!(mini_batch.n_iterations() >= 1);
+        // Independent batches, as in scikit-learn, converge to the same centroids
+        let with_replacement = KMeans::params_with_rng(3, Xoshiro256Plus::seed_from_u64(42))
+            .init_method(KMeansInit::Precomputed(array![
+                [1., 1.],
+                [9., 1.],
+                [1., 9.]
+            ]))
+            .mini_batch_sampling(MiniBatchSampling::WithReplacement)
+            .check()
+            .unwrap()
+            .fit_mini_batch(&dataset, 100)
+            .unwrap();
+        assert_abs_diff_eq!(
+            *with_replacement.centroids(),
+            *full.centroids(),
+            epsilon = 0.5
+        );
+        assert_ne!(with_replacement.centroids(), mini_batch.centroids());
+        assert!(matches!(
+            params.fit_mini_batch(&dataset, 0),
+            Err(KMeansError::InvalidParams(KMeansParamsError::BatchSize))
+        ));
+        assert!(matches!(
+            params.fit_mini_batch(&DatasetBase::from(Array2::<f64>::zeros((0, 2))), 10),
+            Err(KMeansError::EmptyObservations)
+        ));
     }
 
-    fn function_test_1d(x: &Array2<f64>) -> Array2<f64> {
-        let mut y = Array2::zeros(x.dim());
-        Zip::from(&mut y).and(x).for_each(|yi, &xi| {
-            if xi < 0.4 {
-                *yi = xi * xi;
-            } else if (0.4..0.8).contains(&xi) {
-                *yi = 3. * xi + 1.;
+    #[test]
+    fn test_fit_with_callback() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(100, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        let params = KMeans::params_with_rng(3, rng)
+            .n_runs(2)
+            .tolerance(1e-12)
+            .check()
+            .unwrap();
+
+        // A callback that never aborts leaves the fit unchanged
+        let mut progress = Vec::new();
+        let model = params
+            .fit_with_callback(&dataset, |p| {
+                progress.push(*p);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(model.centroids(), params.fit(&dataset).unwrap().centroids());
+        assert_eq!(progress[0].run, 0);
+        assert_eq!(progress[0].iteration, 0);
+        assert_eq!(progress.last().unwrap().run, 1);
+        // The inertia of Lloyd's algorithm never rises within a run, and ends at that of the model
+        let first_run: Vec<_> = progress.iter().filter(|p| p.run == 0).collect();
+        assert!(first_run
+            .windows(2)
+            .all(|w| w[1].inertia <= w[0].inertia + 1e-12));
+        let last = progress.last().unwrap();
+        assert!(progress[0].inertia > last.inertia);
+        assert!(first_run.last().unwrap().inertia.min(last.inertia) >= model.inertia() - 1e-9);
+
+        // Abort at the third iteration of the first run
+        let mut n_calls = 0;
+        let res = params.fit_with_callback(&dataset, |_| {
+            n_calls += 1;
+            if n_calls == 3 {
+                ControlFlow::Break(())
             } else {
-                *yi = f64::sin(10. * xi);
+                ControlFlow::Continue(())
             }
         });
-        y
-    }
-
-    macro_rules! calc_inertia {
-        ($dist:expr, $centroids:expr, $obs:expr, $memberships:expr) => {
-            $obs.rows()
-                .into_iter()
-                .zip($memberships.iter())
-                .map(|(row, &c)| $dist.rdistance(row.view(), $centroids.row(c).view()))
-                .sum::<f64>()
+        let partial = match res {
+            Err(AbortableKMeansError::Aborted { partial }) => partial,
+            res => panic!("expected an aborted fit, got {:?}", res),
         };
-    }
+        assert_eq!(n_calls, 3);
+        assert_eq!(partial.n_iterations(), 3);
+        assert_eq!(partial.predict(&observations).len(), 300);
 
-    macro_rules! calc_memberships {
-        ($dist:expr, $centroids:expr, $obs:expr) => {{
-            let mut memberships = Array1::zeros($obs.nrows());
-            update_cluster_memberships(&$dist, &$centroids, &$obs, &mut memberships);
-            memberships
-        }};
+        // Mini-batch fits call the callback after each batch
+        let params = KMeans::params_with_rng(3, Xoshiro256Plus::seed_from_u64(42))
+            .memory_budget_bytes(Some(50 * 32))
+            .check()
+            .unwrap();
+        let res = params.fit_with_callback(&dataset, |p| {
+            if p.iteration == 1 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert!(
+            matches!(res, Err(AbortableKMeansError::Aborted { partial }) if partial.n_iterations() == 2)
+        );
     }
 
     #[test]
-    fn test_min_dists() {
-        let centroids = array![[0.0, 1.0], [40.0, 10.0]];
-        let observations = array![[3.0, 4.0], [1.0, 3.0], [25.0, 15.0]];
-        let mut dists = Array1::zeros(observations.nrows());
+    fn test_max_cluster_fraction() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let big = linfa_datasets::generate::blobs(90, &array![[0., 0.]], &mut rng);
+        let small = linfa_datasets::generate::blobs(10, &array![[10., 10.]], &mut rng);
+        let dataset = DatasetBase::from(concatenate![Axis(0), big, small]);
 
-        update_min_dists(&L2Dist, &centroids, &observations, &mut dists);
-        assert_abs_diff_eq!(dists, array![18.0, 5.0, 250.0]);
-        update_min_dists(&L1Dist, &centroids, &observations, &mut dists);
-        assert_abs_diff_eq!(dists, array![6.0, 3.0, 20.0]);
+        let model = KMeans::params_with_rng(2, rng.clone())
+            .max_cluster_fraction(Some(0.95))
+            .fit(&dataset)
+            .unwrap();
+        // The counts are those of the best run
+        let mut counts = model.cluster_count().to_vec();
+        counts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(counts, vec![10., 90.]);
+
+        let res = KMeans::params_with_rng(2, rng)
+            .max_cluster_fraction(Some(0.8))
+            .fit(&dataset);
+        match res {
+            Err(KMeansError::DegenerateClustering { fraction }) => {
+                assert_abs_diff_eq!(fraction, 0.9)
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
     }
 
-    fn test_n_runs<D: Distance<f64>>(dist_fn: D) {
+    #[test]
+    fn test_empty_clusters() {
         let mut rng = Xoshiro256Plus::seed_from_u64(42);
-        let xt = Array::random_using(100, Uniform::new(0., 1.0), &mut rng).insert_axis(Axis(1));
-        let yt = function_test_1d(&xt);
-        let data = concatenate(Axis(1), &[xt.view(), yt.view()]).unwrap();
-
-        for init in &[
-            KMeansInit::Random,
-            KMeansInit::KMeansPlusPlus,
-            KMeansInit::KMeansPara,
-        ] {
-            // First clustering with one iteration
-            let dataset = DatasetBase::from(data.clone());
-            let model = KMeans::params_with(3, rng.clone(), dist_fn.clone())
+        let observations =
+            linfa_datasets::generate::blobs(50, &array![[0., 0.], [10., 0.]], &mut rng);
+        let dataset = DatasetBase::from(observations);
+        // The third centroid is too far from the data to win any observation
+        let fit = |empty_clusters| {
+            KMeans::params_with_rng(3, rng.clone())
                 .n_runs(1)
-                .init_method(init.clone())
+                .init_method(KMeansInit::Precomputed(array![
+                    [0., 0.],
+                    [10., 0.],
+                    [1000., 1000.]
+                ]))
+                .empty_clusters(empty_clusters)
                 .fit(&dataset)
-                .expect("KMeans fitted");
-            let clusters = model.predict(dataset);
-            let inertia = calc_inertia!(
-                dist_fn,
-                model.centroids(),
-                clusters.records,
-                clusters.targets
-            );
-            let total_dist = model.transform(&clusters.records.view()).sum();
-            assert_abs_diff_eq!(inertia, total_dist, epsilon = 1e-5);
+        };
 
-            let single_cluster: usize = model.predict(&data.row(0));
-            assert_abs_diff_eq!(single_cluster, clusters.targets[0]);
+        let model = fit(EmptyClusters::Keep).unwrap();
+        assert_eq!(model.cluster_count()[2], 0.);
+        assert_abs_diff_eq!(model.centroids().row(2), array![1000., 1000.]);
 
-            // Second clustering with 10 iterations (default)
-            let dataset2 = DatasetBase::from(clusters.records().clone());
-            let model2 = KMeans::params_with(3, rng.clone(), dist_fn.clone())
-                .init_method(init.clone())
-                .fit(&dataset2)
-                .expect("KMeans fitted");
-            let clusters2 = model2.predict(dataset2);
-            let inertia2 = calc_inertia!(
-                dist_fn,
-                model2.centroids(),
-                clusters2.records,
-                clusters2.targets
-            );
-            let total_dist2 = model2.transform(&clusters2.records.view()).sum();
-            assert_abs_diff_eq!(inertia2, total_dist2, epsilon = 1e-5);
+        for empty_clusters in [EmptyClusters::FarthestPoint, EmptyClusters::SplitLargest] {
+            let model = fit(empty_clusters).unwrap();
+            assert!(model.cluster_count().iter().all(|&c| c > 0.));
+            assert_abs_diff_eq!(model.cluster_count().sum(), 100.);
+            // The reseeded centroid ends up splitting one of the blobs
+            assert!(model.centroids().row(2).iter().all(|&x| x.abs() < 20.));
+        }
 
-            // Check we improve inertia (only really makes a difference for random init)
-            if *init == KMeansInit::Random {
-                assert!(inertia2 <= inertia);
-            }
+        match fit(EmptyClusters::Error) {
+            Err(KMeansError::EmptyCluster { index }) => assert_eq!(index, 2),
+            res => panic!("unexpected result: {:?}", res),
         }
     }
 
     #[test]
-    fn test_n_runs_l2dist() {
-        test_n_runs(L2Dist);
-    }
+    fn test_cross_platform_deterministic() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations = Array2::random_using((1000, 3), Uniform::new(-10., 10.), &mut rng);
+        let dataset = DatasetBase::from(observations);
+        let fit = |n_threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(n_threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    KMeans::params_with_rng(5, rng.clone())
+                        .init_method(KMeansInit::KMeansPara)
+                        .n_runs(3)
+                        .cross_platform_deterministic(true)
+                        .fit(&dataset)
+                        .unwrap()
+                })
+        };
 
-    #[test]
-    fn test_n_runs_l1dist() {
-        test_n_runs(L1Dist);
+        let model = fit(1);
+        assert_eq!(model.centroids(), fit(4).centroids());
+        // Golden value, which must not change with the platform
+        assert_abs_diff_eq!(model.inertia(), 39.69448783298282, epsilon = 1e-12);
     }
 
     #[test]
-    fn compute_centroids_works() {
-        let cluster_size = 100;
-        let n_features = 4;
-
-        // Let's setup a synthetic set of observations, composed of two clusters with known means
-        let cluster_1: Array2<f64> =
-            Array::random((cluster_size, n_features), Uniform::new(-100., 100.));
-        let memberships_1 = Array1::zeros(cluster_size);
-        let expected_centroid_1 = cluster_1.sum_axis(Axis(0)) / (cluster_size + 1) as f64;
+    fn test_cluster_sizes() {
+        // Eight observations around 0, among which two duplicates, and two around 10
+        let dataset = DatasetBase::from(array![
+            [-1.5],
+            [-1.],
+            [-0.5],
+            [0.],
+            [0.],
+            [0.5],
+            [1.],
+            [1.5],
+            [9.],
+            [11.]
+        ]);
+        let params = KMeans::params(2)
+            .n_runs(1)
+            .deduplicate(true)
+            .init_method(KMeansInit::Precomputed(array![[0.], [10.]]));
 
-        let cluster_2: Array2<f64> =
-            Array::random((cluster_size, n_features), Uniform::new(-100., 100.));
-        let memberships_2 = Array1::ones(cluster_size);
-        let expected_centroid_2 = cluster_2.sum_axis(Axis(0)) / (cluster_size + 1) as f64;
+        let plain = params.clone().fit(&dataset).unwrap();
+        assert_eq!(plain.cluster_count(), &array![8., 2.]);
+        // Three observations of the large cluster move to the small one
+        let model = params
+            .clone()
+            .min_cluster_size(Some(5))
+            .max_cluster_size(Some(5))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model.cluster_count(), &array![5., 5.]);
+        assert_eq!(model.cluster_sizes(), Some(&array![5, 5]));
+        assert!(model.centroids()[[1, 0]] < 5.);
+        // A bound alone leaves the other side free
+        let model = params
+            .clone()
+            .max_cluster_size(Some(6))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model.cluster_count(), &array![6., 4.]);
+        let model = params
+            .clone()
+            .min_cluster_size(Some(2))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model, plain);
 
-        // `concatenate` combines arrays along a given axis: https://docs.rs/ndarray/0.13.0/ndarray/fn.concatenate.html
-        let observations = concatenate(Axis(0), &[cluster_1.view(), cluster_2.view()]).unwrap();
-        let memberships =
-            concatenate(Axis(0), &[memberships_1.view(), memberships_2.view()]).unwrap();
+        for (min, max) in [(Some(6), None), (None, Some(4))] {
+            let res = params
+                .clone()
+                .min_cluster_size(min)
+                .max_cluster_size(max)
+                .fit(&dataset);
+            assert!(matches!(
+                res,
+                Err(KMeansError::InfeasibleClusterSizes {
+                    n_observations: 10,
+                    n_clusters: 2,
+                    ..
+                })
+            ));
+        }
+    }
 
-        // Does it work?
-        let old_centroids = Array2::zeros((2, n_features));
-        let centroids = compute_centroids(&old_centroids, &observations, &memberships);
-        assert_abs_diff_eq!(
-            centroids.index_axis(Axis(0), 0),
-            expected_centroid_1,
-            epsilon = 1e-5
-        );
-        assert_abs_diff_eq!(
-            centroids.index_axis(Axis(0), 1),
-            expected_centroid_2,
-            epsilon = 1e-5
-        );
+    #[test]
+    fn test_cluster_priors() {
+        // A large cluster around 0, a small one around 10, and a borderline observation closer to
+        // the small one
+        let dataset = DatasetBase::from(array![
+            [-1.],
+            [-0.5],
+            [0.],
+            [0.],
+            [0.5],
+            [1.],
+            [9.],
+            [10.],
+            [11.],
+            [5.5]
+        ]);
+        let params = KMeans::params(2)
+            .n_runs(1)
+            .init_method(KMeansInit::Precomputed(array![[0.], [10.]]));
+        let priors = |variance| {
+            Some(ClusterPriors {
+                priors: array![0.9, 0.1],
+                variance,
+            })
+        };
 
-        assert_eq!(centroids.len_of(Axis(0)), 2);
+        let plain = params.clone().fit(&dataset).unwrap();
+        assert_eq!(plain.cluster_count(), &array![6., 4.]);
+        // The penalty of the small cluster outweighs the distance gap of the borderline point
+        let model = params
+            .clone()
+            .cluster_priors(priors(3.))
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(model.cluster_count(), &array![7., 3.]);
+        assert_abs_diff_eq!(model.centroids()[[1, 0]], 10., epsilon = 1e-3);
+        // Without variance, the priors don't matter
+        let model = params.cluster_priors(priors(0.)).fit(&dataset).unwrap();
+        assert_eq!(model, plain);
     }
 
     #[test]
-    fn test_compute_extra_centroids() {
-        let observations = array![[1.0, 2.0]];
-        let memberships = array![0];
-        // Should return an average of 0 for empty clusters
-        let old_centroids = Array2::ones((2, 2));
-        let centroids = compute_centroids(&old_centroids, &observations, &memberships);
-        assert_abs_diff_eq!(centroids, array![[1.0, 1.5], [1.0, 1.0]]);
+    fn test_stop_on_stable_assignments() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [10., 0.], [0., 10.]];
+        let observations = linfa_datasets::generate::blobs(50, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        // A tolerance the centroids only reach long after the memberships settle
+        let params = KMeans::params_with_rng(3, rng).n_runs(1).tolerance(1e-12);
+
+        let full = params.clone().fit(&dataset).unwrap();
+        let stable = params
+            .stop_on_stable_assignments(true)
+            .fit(&dataset)
+            .unwrap();
+        assert_eq!(stable.stop_reason(), Some(StopReason::StableAssignments));
+        assert!(stable.converged());
+        assert!(stable.n_iterations() < full.n_iterations());
+        let full_labels: Array1<usize> = full.predict(&observations);
+        let stable_labels: Array1<usize> = stable.predict(&observations);
+        assert_eq!(full_labels, stable_labels);
     }
 
     #[test]
-    // An observation is closest to itself.
-    fn nothing_is_closer_than_self() {
-        let n_centroids = 20;
-        let n_features = 5;
+    fn test_assignment_chunk_size() {
         let mut rng = Xoshiro256Plus::seed_from_u64(42);
-        let centroids: Array2<f64> = Array::random_using(
-            (n_centroids, n_features),
-            Uniform::new(-100., 100.),
-            &mut rng,
-        );
+        let observations = Array2::random_using((500, 4), Uniform::new(-10., 10.), &mut rng);
+        let dataset = DatasetBase::from(observations);
+        let fit = |chunk_size| {
+            KMeans::params_with_rng(6, rng.clone())
+                .n_runs(2)
+                .assignment_chunk_size(chunk_size)
+                .cross_platform_deterministic(true)
+                .fit(&dataset)
+                .unwrap()
+        };
 
-        let expected_memberships = (0..n_centroids).into_iter().collect::<Array1<_>>();
-        assert_eq!(
-            calc_memberships!(L2Dist, centroids, centroids),
-            expected_memberships
-        );
-        assert_eq!(
-            calc_memberships!(L1Dist, centroids, centroids),
-            expected_memberships
-        );
+        let model = fit(None);
+        // Including chunks that don't divide the observations, and a single chunk
+        for chunk_size in [1, 7, 128, 1000] {
+            assert_eq!(model, fit(Some(chunk_size)));
+        }
     }
 
     #[test]
-    fn oracle_test_for_closest_centroid() {
-        let centroids = array![[0., 0.], [1., 2.], [20., 0.], [0., 20.],];
-        let observations = array![[1., 0.6], [20., 2.], [20., 0.], [7., 20.],];
-        let l2_memberships = array![0, 2, 2, 3];
-        let l1_memberships = array![1, 2, 2, 3];
+    fn test_divergence_patience() {
+        // The mean minimizes squared distances, not the L1 distances of the assignment step: from
+        // the median of the cluster, the first update already raises the inertia from 10 to 12.5
+        let dataset = DatasetBase::from(array![[0.], [0.], [10.]]);
+        let params = KMeans::params_with(1, Xoshiro256Plus::seed_from_u64(42), L1Dist)
+            .n_runs(1)
+            .init_method(KMeansInit::Precomputed(array![[0.]]));
 
-        assert_eq!(
-            calc_memberships!(L2Dist, centroids, observations),
-            l2_memberships
-        );
-        assert_eq!(
-            calc_memberships!(L1Dist, centroids, observations),
-            l1_memberships
-        );
+        let model = params.clone().fit(&dataset).unwrap();
+        assert!(model.converged());
+        assert_eq!(model.stop_reason(), Some(StopReason::CentroidShift));
+        assert_abs_diff_eq!(model.centroids()[[0, 0]], 10. / 3., epsilon = 1e-3);
+
+        for patience in [1, 2] {
+            let model = params
+                .clone()
+                .divergence_patience(Some(patience))
+                .fit(&dataset)
+                .unwrap();
+            assert!(!model.converged());
+            assert_eq!(model.stop_reason(), Some(StopReason::Diverged));
+            // Back to the starting centroid, with its inertia
+            assert_eq!(model.centroids(), &array![[0.]]);
+            assert_abs_diff_eq!(model.inertia(), 10. / 3.);
+            assert_eq!(model.n_iterations(), patience as u64);
+        }
+
+        let model = params.max_n_iterations(1).fit(&dataset).unwrap();
+        assert_eq!(model.stop_reason(), Some(StopReason::MaxIterations));
+    }
+
+    #[test]
+    fn test_parallelism() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations = Array2::random_using((300, 3), Uniform::new(-10., 10.), &mut rng);
+        let dataset = DatasetBase::from(observations);
+        let fit = |parallelism, n_threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(n_threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    KMeans::params_with_rng(4, rng.clone())
+                        .n_runs(6)
+                        .parallelism(parallelism)
+                        .record_trajectory(Some(3))
+                        .cross_platform_deterministic(true)
+                        .fit(&dataset)
+                        .unwrap()
+                })
+        };
+
+        // The restarts start from the same centroids whether they run concurrently or not
+        let model = fit(Parallelism::Observations, 1);
+        for n_threads in [1, 4] {
+            assert_eq!(model, fit(Parallelism::Restarts, n_threads));
+            assert_eq!(model, fit(Parallelism::Auto, n_threads));
+        }
+        assert_eq!(model.trajectory().len(), 3);
+
+        assert!(Parallelism::Restarts.parallel_restarts(2, 1_000_000));
+        assert!(!Parallelism::Restarts.parallel_restarts(1, 10));
+        assert!(!Parallelism::Observations.parallel_restarts(10, 10));
+        assert!(!Parallelism::Auto.parallel_restarts(10, AUTO_RESTARTS_MAX_OBSERVATIONS + 1));
     }
 
     #[test]
@@ -812,10 +6536,8 @@ mod tests {
         let dataset1 = DatasetBase::from(array![[-1.0, -3.0], [0., 0.], [3., 5.], [5., 5.]]);
         let dataset2 = DatasetBase::from(array![[-5.0, -5.0], [0., 0.], [10., 10.]]);
         let model = KMeans {
-            centroids: array![[-1., -1.], [3., 4.], [7., 8.]],
             cluster_count: array![0., 0., 0.],
-            inertia: 0.0,
-            dist_fn: L2Dist,
+            ..KMeans::from_centroids(array![[-1., -1.], [3., 4.], [7., 8.]], L2Dist)
         };
         let rng = Xoshiro256Plus::seed_from_u64(45);
         let params = KMeans::params_with_rng(3, rng).tolerance(100.0);
@@ -861,6 +6583,99 @@ mod tests {
             .expect("KMeans fitted");
     }
 
+    #[test]
+    fn test_empty_observations() {
+        let observations = DatasetBase::from(Array2::<f64>::zeros((0, 2)));
+        let params = KMeans::params(2).init_method(KMeansInit::Random);
+        assert!(matches!(
+            params.fit(&observations),
+            Err(KMeansError::EmptyObservations)
+        ));
+        assert!(matches!(
+            params.fit_with(None, &observations),
+            Err(IncrKMeansError::EmptyObservations)
+        ));
+
+        let model = KMeans {
+            cluster_count: array![1., 1.],
+            ..KMeans::from_centroids(array![[0., 0.], [1., 1.]], L2Dist)
+        };
+        // Predicting nothing is valid
+        let memberships: Array1<usize> = model.predict(observations.records());
+        assert_eq!(memberships.len(), 0);
+        assert_eq!(model.transform(observations.records()).len(), 0);
+        // An empty batch leaves the model untouched
+        match params.fit_with(Some(model.clone()), &observations) {
+            Err(IncrKMeansError::NotConverged(m)) => assert_eq!(m, model),
+            _ => panic!("expected unchanged model"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_observations_shape() {
+        let params = KMeans::params(3);
+        assert!(matches!(
+            params.fit(&DatasetBase::from(array![[1., 2.], [3., 4.]])),
+            Err(KMeansError::NotEnoughObservations {
+                n_observations: 2,
+                n_clusters: 3
+            })
+        ));
+        assert!(matches!(
+            params.fit(&DatasetBase::from(Array2::<f64>::zeros((5, 0)))),
+            Err(KMeansError::NoFeatures)
+        ));
+        assert!(matches!(
+            params
+                .check()
+                .unwrap()
+                .fit_mini_batch(&DatasetBase::from(Array2::<f64>::zeros((5, 0))), 2),
+            Err(KMeansError::NoFeatures)
+        ));
+    }
+
+    #[test]
+    fn test_non_finite_observations() {
+        let params = KMeans::params(2);
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let observations = array![[1., 2.], [3., 4.], [5., value]];
+            assert!(matches!(
+                params.fit(&DatasetBase::from(observations.clone())),
+                Err(KMeansError::NonFiniteObservation { index: 2 })
+            ));
+            let model = params
+                .fit(&DatasetBase::from(observations.slice(s![..2, ..])))
+                .unwrap();
+            assert!(matches!(
+                model.validate_input(&observations),
+                Err(KMeansError::NonFiniteObservation { index: 2 })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_fit_on_windows() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let data: Array2<f64> = Array::random_using((300, 3), Uniform::new(-10., 10.), &mut rng);
+
+        for init in [KMeansInit::Random, KMeansInit::KMeansPlusPlus] {
+            let params = KMeans::params_with_rng(4, rng.clone())
+                .n_runs(2)
+                .init_method(init);
+            // Overlapping windows over the same data
+            for start in (0..200).step_by(50) {
+                let window = data.slice(s![start..start + 100, ..]);
+                let from_view = params.fit(&DatasetBase::from(window)).unwrap();
+                let from_owned = params.fit(&DatasetBase::from(window.to_owned())).unwrap();
+                assert_abs_diff_eq!(from_view.centroids(), from_owned.centroids());
+                assert_abs_diff_eq!(from_view.inertia(), from_owned.inertia());
+
+                let memberships: Array1<usize> = from_view.predict(&window);
+                assert_eq!(memberships, from_owned.predict(&window.to_owned()));
+            }
+        }
+    }
+
     fn fittable<T: Fit<Array2<f64>, (), KMeansError>>(_: T) {}
     #[test]
     fn thread_rng_fittable() {
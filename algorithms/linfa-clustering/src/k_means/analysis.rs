@@ -0,0 +1,1023 @@
+use super::algorithm::KMeans;
+use super::errors::KMeansError;
+use linfa::Float;
+use linfa_nn::distance::{Distance, L2Dist};
+use ndarray::{Array1, Array2, ArrayBase, Axis, Data, Ix1, Ix2, Zip};
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// Global statistics of a set of observations, as computed by [`fit_stats`]
+pub struct DataStats<F> {
+    /// Mean of the observations, with shape `(n_features)`
+    pub mean: Array1<F>,
+    /// Total sum of squares, the sum of the squared euclidean distances of the observations to
+    /// their mean
+    pub total_ss: F,
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// Statistics of the members of each cluster, as computed by [`KMeans::cluster_summary`] or kept
+/// from the fit, see [`KMeans::training_summary`]
+pub struct ClusterSummary<F> {
+    /// Number of members of each cluster
+    pub sizes: Array1<usize>,
+    /// Sum of the reduced distances of the members of each cluster to its centroid, the squared
+    /// euclidean distances for `L2Dist`, which add up to the inertia of the members
+    pub inertia: Array1<F>,
+    /// Mean reduced distance of the members of each cluster to its centroid, i.e. the
+    /// within-cluster variance summed over the features for `L2Dist`, NaN for empty clusters
+    pub variance: Array1<F>,
+    /// Largest distance of a member of each cluster to its centroid, in the metric of the model,
+    /// NaN for empty clusters
+    pub radius: Array1<F>,
+}
+
+impl<F: Float> ClusterSummary<F> {
+    // Summary of observations assigned to clusters according to `labels`, at the reduced
+    // distances `rdists` of their centroids
+    pub(crate) fn from_assignments<D: Distance<F>>(
+        dist_fn: &D,
+        n_clusters: usize,
+        labels: &Array1<usize>,
+        rdists: &Array1<F>,
+    ) -> Self {
+        let mut sizes = Array1::zeros(n_clusters);
+        let mut inertia = Array1::zeros(n_clusters);
+        let mut max_rdists = Array1::from_elem(n_clusters, F::nan());
+        Zip::from(labels).and(rdists).for_each(|&c, &rdist| {
+            sizes[c] += 1;
+            inertia[c] += rdist;
+            // `max` ignores the NaN of clusters without members so far
+            max_rdists[c] = max_rdists[c].max(rdist);
+        });
+        let variance = Zip::from(&inertia)
+            .and(&sizes)
+            .map_collect(|&inertia, &size| inertia / F::cast(size));
+        Self {
+            sizes,
+            inertia,
+            variance,
+            radius: max_rdists.mapv(|rdist| dist_fn.rdist_to_dist(rdist)),
+        }
+    }
+
+    // Follows a renumbering of the clusters, cluster `c` taking the place of cluster `order[c]`
+    pub(crate) fn reorder(&mut self, order: &[usize]) {
+        self.sizes = self.sizes.select(Axis(0), order);
+        self.inertia = self.inertia.select(Axis(0), order);
+        self.variance = self.variance.select(Axis(0), order);
+        self.radius = self.radius.select(Axis(0), order);
+    }
+}
+
+impl<F: Float, D: Distance<F>> KMeans<F, D> {
+    /// Returns the statistics of the members of each cluster among `observations`, assigned to
+    /// their closest centroid as by `predict`: their number, the sum and mean of their reduced
+    /// distances to the centroid, and the largest distance, see [`ClusterSummary`]. With
+    /// feature weights, the weighted distance of `predict` is used.
+    ///
+    /// The same statistics on the training observations, as of the end of the fit, are kept by
+    /// the model, see [`training_summary`](KMeans::training_summary).
+    pub fn cluster_summary(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> ClusterSummary<F> {
+        let (labels, dists) = self.predict_with_distances(observations);
+        let rdists = dists.mapv(|d| self.dist_fn().dist_to_rdist(d));
+        ClusterSummary::from_assignments(self.dist_fn(), self.centroids().nrows(), &labels, &rdists)
+    }
+
+    /// Returns the number of training points assigned to each cluster by the last assignment
+    /// step of the fit, see [`training_summary`](KMeans::training_summary). Unlike
+    /// [`cluster_count`](KMeans::cluster_count), these are plain counts, whatever the weights of
+    /// the observations.
+    pub fn cluster_sizes(&self) -> Option<&Array1<usize>> {
+        self.training_summary().map(|summary| &summary.sizes)
+    }
+
+    /// Returns the share of each cluster of the inertia of the training points, i.e. the sum of
+    /// the reduced distances of its members to its centroid, see
+    /// [`training_summary`](KMeans::training_summary). Without sample weights, they add up to
+    /// [`inertia`](KMeans::inertia) times the number of training points.
+    pub fn per_cluster_inertia(&self) -> Option<&Array1<F>> {
+        self.training_summary().map(|summary| &summary.inertia)
+    }
+
+    /// Returns the [Bayesian information criterion](https://en.wikipedia.org/wiki/Bayesian_information_criterion)
+    /// of the model on `observations`, which are assigned to clusters according to `labels`
+    /// (typically the output of `predict`). Lower is better.
+    ///
+    /// K-means is treated as a mixture of spherical gaussians, one per cluster, each with its own
+    /// variance (the mean squared euclidean distance of its members to the centroid, per feature)
+    /// and a weight proportional to its size. This has `n_clusters * (n_features + 1)` free
+    /// parameters. The spherical covariance assumption only holds if features have similar scales
+    /// and clusters are roughly isotropic, so the criterion should only be used to compare models
+    /// fitted on the same data, for example to select `n_clusters`.
+    pub fn bic(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        labels: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    ) -> F {
+        let (log_likelihood, n_params) = self.spherical_log_likelihood(observations, labels);
+        n_params * F::cast(observations.nrows()).ln() - F::cast(2.) * log_likelihood
+    }
+
+    /// Returns the [Akaike information criterion](https://en.wikipedia.org/wiki/Akaike_information_criterion)
+    /// of the model on `observations`, which are assigned to clusters according to `labels`.
+    /// Lower is better.
+    ///
+    /// See [`bic`](KMeans::bic) for the underlying spherical gaussian mixture model.
+    pub fn aic(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        labels: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    ) -> F {
+        let (log_likelihood, n_params) = self.spherical_log_likelihood(observations, labels);
+        F::cast(2.) * n_params - F::cast(2.) * log_likelihood
+    }
+
+    /// Returns, for each feature, the ratio of its between-cluster variance to its within-cluster
+    /// variance on `observations`, which are assigned to clusters according to `labels`
+    /// (typically the output of `predict`).
+    ///
+    /// This is the [F-statistic](https://en.wikipedia.org/wiki/F-test#Multiple-comparison_ANOVA_problems)
+    /// of a one-way ANOVA of the feature over the clusters, with the centroids as the cluster
+    /// means. Higher values mean the feature is more discriminative, i.e. it varies more between
+    /// clusters than inside them, so it drives the clustering more. Values are only comparable
+    /// between features of the same model.
+    ///
+    /// Empty clusters are ignored. A feature with no within-cluster variance gets an infinite
+    /// importance if it varies between clusters, and 0 otherwise. All importances are 0 if less
+    /// than 2 clusters have members.
+    pub fn feature_importances(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        labels: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    ) -> Array1<F> {
+        let (n_clusters, n_features) = self.centroids().dim();
+        let mut counts = Array1::<F>::zeros(n_clusters);
+        let mut within = Array1::<F>::zeros(n_features);
+        Zip::from(observations.rows())
+            .and(labels)
+            .for_each(|observation, &label| {
+                counts[label] += F::one();
+                Zip::from(&mut within)
+                    .and(&observation)
+                    .and(self.centroids().row(label))
+                    .for_each(|w, &x, &c| *w += (x - c) * (x - c));
+            });
+
+        let n_non_empty = counts.iter().filter(|&&c| c > F::zero()).count();
+        if n_non_empty < 2 {
+            return Array1::zeros(n_features);
+        }
+        let mean = fit_stats(observations).mean;
+        let mut between = Array1::<F>::zeros(n_features);
+        Zip::from(self.centroids().rows())
+            .and(&counts)
+            .for_each(|centroid, &count| {
+                Zip::from(&mut between)
+                    .and(&centroid)
+                    .and(&mean)
+                    .for_each(|b, &c, &m| *b += count * (c - m) * (c - m));
+            });
+
+        let between_dof = F::cast(n_non_empty - 1);
+        let within_dof = F::cast(observations.nrows().saturating_sub(n_non_empty));
+        Zip::from(&between)
+            .and(&within)
+            .map_collect(|&between, &within| {
+                if within == F::zero() || within_dof == F::zero() {
+                    if between > F::zero() {
+                        F::infinity()
+                    } else {
+                        F::zero()
+                    }
+                } else {
+                    (between / between_dof) / (within / within_dof)
+                }
+            })
+    }
+
+    /// Returns the indices of the features whose centroid spread, i.e. the difference between the
+    /// largest and the smallest value of the feature among the centroids, is below `threshold`.
+    ///
+    /// Such features hardly tell the clusters apart and are candidates for pruning before the
+    /// next fit. Unlike [`feature_importances`](KMeans::feature_importances), this only looks at
+    /// the centroids, which makes it cheap but blind to the spread of the observations: the
+    /// threshold should be chosen relative to the scale of the features. With a single cluster,
+    /// every feature has a spread of 0.
+    pub fn uninformative_features(&self, threshold: F) -> Vec<usize> {
+        self.centroids()
+            .columns()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, column)| {
+                let (min, max) = column
+                    .iter()
+                    .fold((F::infinity(), F::neg_infinity()), |(min, max), &c| {
+                        (min.min(c), max.max(c))
+                    });
+                max - min < threshold
+            })
+            .map(|(j, _)| j)
+            .collect()
+    }
+
+    /// Returns the axis-aligned bounding box of the members of each cluster among `observations`,
+    /// which are assigned to clusters according to `labels` (typically the output of `predict`),
+    /// as the matrices of the lowest and of the highest value of each feature in each cluster,
+    /// both with shape `(n_clusters, n_features)`. This gives quick range filters per cluster,
+    /// for example to build a spatial index over the clusters.
+    ///
+    /// The bounds of an empty cluster are NaN.
+    pub fn cluster_bounding_boxes(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        labels: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    ) -> (Array2<F>, Array2<F>) {
+        let dim = self.centroids().dim();
+        let mut mins = Array2::from_elem(dim, F::infinity());
+        let mut maxes = Array2::from_elem(dim, F::neg_infinity());
+        let mut non_empty = vec![false; dim.0];
+        Zip::from(observations.rows())
+            .and(labels)
+            .for_each(|observation, &label| {
+                non_empty[label] = true;
+                Zip::from(mins.row_mut(label))
+                    .and(maxes.row_mut(label))
+                    .and(&observation)
+                    .for_each(|min, max, &x| {
+                        *min = min.min(x);
+                        *max = max.max(x);
+                    });
+            });
+        for (c, _) in non_empty.iter().enumerate().filter(|(_, &n)| !n) {
+            mins.row_mut(c).fill(F::nan());
+            maxes.row_mut(c).fill(F::nan());
+        }
+        (mins, maxes)
+    }
+
+    /// Returns the one-to-one matching of the clusters of the model with those of `other` that
+    /// minimizes the total distance between matched centroids, along with that total distance.
+    /// Cluster `c` of the model is matched with cluster `matching[c]` of `other`.
+    ///
+    /// The matching is solved exactly by the [Hungarian algorithm](https://en.wikipedia.org/wiki/Hungarian_algorithm)
+    /// over the matrix of distances between the centroids of both models, in `O(n_clusters³)`.
+    /// Unlike a greedy matching, the total distance doesn't depend on how the clusters of either
+    /// model are numbered, so it can compare models fitted at different times or on different
+    /// samples, with the distance metric of the model.
+    ///
+    /// Returns an error if the models don't have the same number of clusters and of features.
+    pub fn optimal_matching(&self, other: &KMeans<F, D>) -> Result<(Vec<usize>, F), KMeansError> {
+        let n_clusters = self.centroids().nrows();
+        if other.centroids().nrows() != n_clusters {
+            return Err(KMeansError::ClusterCountMismatch {
+                expected: n_clusters,
+                actual: other.centroids().nrows(),
+            });
+        }
+        other.validate_input(self.centroids())?;
+        let costs = Array2::from_shape_fn((n_clusters, n_clusters), |(a, b)| {
+            self.dist_fn()
+                .distance(self.centroids().row(a), other.centroids().row(b))
+        });
+        let matching = min_cost_matching(&costs);
+        let total = matching
+            .iter()
+            .enumerate()
+            .fold(F::zero(), |acc, (a, &b)| acc + costs[[a, b]]);
+        Ok((matching, total))
+    }
+
+    /// Returns the log-likelihood of the observations under a (hard assignment) mixture of
+    /// spherical gaussians centered on the centroids, along with the number of free parameters.
+    fn spherical_log_likelihood(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        labels: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    ) -> (F, F) {
+        let (n_clusters, n_features) = self.centroids().dim();
+        let mut counts = Array1::<F>::zeros(n_clusters);
+        let mut sq_dists = Array1::<F>::zeros(n_clusters);
+        Zip::from(observations.rows())
+            .and(labels)
+            .for_each(|observation, &label| {
+                counts[label] += F::one();
+                sq_dists[label] +=
+                    L2Dist.rdistance(observation.view(), self.centroids().row(label));
+            });
+
+        let n_samples = F::cast(observations.nrows());
+        let n_features = F::cast(n_features);
+        let two_pi = F::cast(2. * std::f64::consts::PI);
+        let log_likelihood =
+            Zip::from(&counts)
+                .and(&sq_dists)
+                .fold(F::zero(), |acc, &count, &sq_dist| {
+                    if count == F::zero() {
+                        return acc;
+                    }
+                    // Clusters with a null variance (e.g. singletons) would make the likelihood
+                    // unbounded
+                    let variance = (sq_dist / (count * n_features)).max(F::epsilon());
+                    acc + count * (count / n_samples).ln()
+                        - count * n_features / F::cast(2.) * (two_pi * variance).ln()
+                        - count * n_features / F::cast(2.)
+                });
+        let n_params = F::cast(n_clusters) * (n_features + F::one());
+        (log_likelihood, n_params)
+    }
+}
+
+/// Returns the global mean and total sum of squares of `observations`, the statistics that
+/// variance-based helpers such as [`feature_importances`](KMeans::feature_importances) and the
+/// `shrinkage` of [`fit`](crate::KMeans) compare clusters to. `fit` keeps those of the training
+/// observations in [`KMeans::data_stats`] when it needs them, and this computes them for
+/// metrics evaluated on other observations.
+///
+/// The mean is made of NaN and the total sum of squares is 0 if there are no observations.
+pub fn fit_stats<F: Float>(observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> DataStats<F> {
+    let mean = observations
+        .mean_axis(Axis(0))
+        .unwrap_or_else(|| Array1::from_elem(observations.ncols(), F::nan()));
+    let total_ss = observations.rows().into_iter().fold(F::zero(), |acc, row| {
+        acc + L2Dist.rdistance(row, mean.view())
+    });
+    DataStats { mean, total_ss }
+}
+
+/// Returns the assignment of each row of the square matrix `costs` to a distinct column with the
+/// lowest total cost, by the Hungarian algorithm with potentials: rows are added one at a time,
+/// each along the shortest augmenting path of reduced costs `costs[i][j] - u[i] - v[j]`.
+pub(crate) fn min_cost_matching<F: Float>(costs: &Array2<F>) -> Vec<usize> {
+    let n = costs.nrows();
+    // Row and column potentials, and the row assigned to each column, shifted by one so that
+    // column 0 is a virtual column holding the row being added
+    let mut u = vec![F::zero(); n + 1];
+    let mut v = vec![F::zero(); n + 1];
+    let mut row_of = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+    for i in 1..=n {
+        row_of[0] = i;
+        let mut col = 0;
+        let mut min_reduced = vec![F::infinity(); n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[col] = true;
+            let row = row_of[col];
+            let mut delta = F::infinity();
+            let mut next = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced = costs[[row - 1, j - 1]] - u[row] - v[j];
+                if reduced < min_reduced[j] {
+                    min_reduced[j] = reduced;
+                    way[j] = col;
+                }
+                // Infinite costs still pick a column instead of looping forever
+                if min_reduced[j] < delta || next == 0 {
+                    delta = min_reduced[j];
+                    next = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_reduced[j] -= delta;
+                }
+            }
+            col = next;
+            if row_of[col] == 0 {
+                break;
+            }
+        }
+        // Flip the augmenting path back to the virtual column
+        while col != 0 {
+            let prev = way[col];
+            row_of[col] = row_of[prev];
+            col = prev;
+        }
+    }
+    let mut matching = vec![0; n];
+    for j in 1..=n {
+        matching[row_of[j] - 1] = j - 1;
+    }
+    matching
+}
+
+/// Returns the contingency matrix of two labelings of the same observations, such as the outputs
+/// of two clusterings: the entry `(a, b)` is the number of observations labeled `a` in
+/// `labels_a` and `b` in `labels_b`. This is the building block of comparisons between
+/// clusterings like the adjusted Rand index.
+///
+/// The matrix has shape `(n_clusters_a, n_clusters_b)`, where the number of clusters of each
+/// labeling is its largest label plus one, so that labels don't need to be contiguous.
+///
+/// Panics if the labelings have different lengths.
+pub fn contingency_matrix(
+    labels_a: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    labels_b: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> Array2<usize> {
+    assert_eq!(
+        labels_a.len(),
+        labels_b.len(),
+        "The labelings must have the same number of observations."
+    );
+    let n_clusters_a = labels_a.iter().max().map_or(0, |&max| max + 1);
+    let n_clusters_b = labels_b.iter().max().map_or(0, |&max| max + 1);
+    let mut contingency = Array2::zeros((n_clusters_a, n_clusters_b));
+    Zip::from(labels_a)
+        .and(labels_b)
+        .for_each(|&a, &b| contingency[[a, b]] += 1);
+    contingency
+}
+
+/// Returns the mean [silhouette coefficient](https://en.wikipedia.org/wiki/Silhouette_(clustering))
+/// of `observations`, which are assigned to clusters according to `memberships`, to compare
+/// clusterings without ground-truth labels, for example to choose `n_clusters`. Higher is better.
+///
+/// The coefficient of an observation is `(b - a) / max(a, b)`, where `a` is its mean euclidean
+/// distance to the other members of its cluster and `b` its mean euclidean distance to the
+/// members of the nearest other cluster. It lies between -1 and 1, and is 0 for the members of
+/// singleton clusters, or if all the observations are in the same cluster. The mean is NaN
+/// without observations.
+///
+/// This computes the distance between every pair of observations, which takes
+/// `O(n_observations^2 * n_features)` time, spread across threads by observation.
+///
+/// Panics if `observations` and `memberships` don't have the same number of observations.
+pub fn silhouette_score<F: Float>(
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> F {
+    assert_eq!(
+        observations.nrows(),
+        memberships.len(),
+        "There must be one membership per observation."
+    );
+    let observations = observations.view();
+    let memberships = memberships.view();
+    let n_clusters = memberships.iter().max().map_or(0, |&max| max + 1);
+    let mut counts = vec![0usize; n_clusters];
+    memberships.iter().for_each(|&c| counts[c] += 1);
+
+    let total = Zip::from(observations.rows()).and(memberships).par_fold(
+        F::zero,
+        |acc, observation, &cluster| {
+            if counts[cluster] == 1 {
+                return acc;
+            }
+            // Total distance of the observation to the members of each cluster
+            let mut totals = vec![F::zero(); n_clusters];
+            Zip::from(observations.rows())
+                .and(memberships)
+                .for_each(|other, &c| totals[c] += L2Dist.distance(observation, other));
+            let a = totals[cluster] / F::cast(counts[cluster] - 1);
+            let b = (0..n_clusters)
+                .filter(|&c| c != cluster && counts[c] > 0)
+                .map(|c| totals[c] / F::cast(counts[c]))
+                .fold(None, |min: Option<F>, mean| {
+                    Some(min.map_or(mean, |min| min.min(mean)))
+                });
+            match b {
+                Some(b) if a.max(b) > F::zero() => acc + (b - a) / a.max(b),
+                _ => acc,
+            }
+        },
+        |a, b| a + b,
+    );
+    total / F::cast(observations.nrows())
+}
+
+// Centroid and number of members of each cluster of `memberships`, the centroids of clusters
+// without members being zero
+fn cluster_means<F: Float>(
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> (Array2<F>, Vec<usize>) {
+    assert_eq!(
+        observations.nrows(),
+        memberships.len(),
+        "There must be one membership per observation."
+    );
+    let n_clusters = memberships.iter().max().map_or(0, |&max| max + 1);
+    let mut counts = vec![0usize; n_clusters];
+    let mut centroids = Array2::zeros((n_clusters, observations.ncols()));
+    Zip::from(observations.rows())
+        .and(memberships)
+        .for_each(|observation, &c| {
+            counts[c] += 1;
+            let mut centroid = centroids.row_mut(c);
+            centroid += &observation;
+        });
+    Zip::from(centroids.rows_mut())
+        .and(&counts)
+        .for_each(|mut centroid, &count| {
+            if count > 0 {
+                centroid /= F::cast(count)
+            }
+        });
+    (centroids, counts)
+}
+
+/// Returns the [Davies-Bouldin index](https://en.wikipedia.org/wiki/Davies%E2%80%93Bouldin_index)
+/// of `observations`, which are assigned to clusters according to `memberships`. Lower is
+/// better, with 0 for clusters reduced to points.
+///
+/// The index is the mean over the clusters of the similarity to their most similar other
+/// cluster, where the similarity of two clusters is the sum of the mean euclidean distances of
+/// their members to their centroid, divided by the euclidean distance between the centroids.
+/// Labels without members are skipped, and like scikit-learn, pairs of clusters with the same
+/// centroid have a similarity of 0. It is 0 if all the observations are in the same cluster,
+/// and NaN without observations.
+///
+/// This takes `O(n_observations * n_features + n_clusters^2 * n_features)` time.
+///
+/// Panics if `observations` and `memberships` don't have the same number of observations.
+pub fn davies_bouldin_score<F: Float>(
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> F {
+    let (centroids, counts) = cluster_means(observations, memberships);
+    let mut scatters = vec![F::zero(); counts.len()];
+    Zip::from(observations.rows())
+        .and(memberships)
+        .for_each(|observation, &c| {
+            scatters[c] += L2Dist.distance(observation, centroids.row(c)) / F::cast(counts[c])
+        });
+
+    let clusters: Vec<_> = (0..counts.len()).filter(|&c| counts[c] > 0).collect();
+    let total = clusters.iter().fold(F::zero(), |acc, &a| {
+        let similarity = clusters
+            .iter()
+            .filter(|&&b| b != a)
+            .map(|&b| {
+                let gap = L2Dist.distance(centroids.row(a), centroids.row(b));
+                if gap > F::zero() {
+                    (scatters[a] + scatters[b]) / gap
+                } else {
+                    F::zero()
+                }
+            })
+            .fold(F::zero(), F::max);
+        acc + similarity
+    });
+    total / F::cast(clusters.len())
+}
+
+/// Returns the [Calinski-Harabasz index](https://en.wikipedia.org/wiki/Calinski%E2%80%93Harabasz_index),
+/// or variance ratio criterion, of `observations`, which are assigned to clusters according to
+/// `memberships`. Higher is better.
+///
+/// The index is the ratio of the dispersion between the clusters, the sum over the clusters of
+/// their number of members times the squared euclidean distance between their centroid and the
+/// mean of the observations, to the dispersion within the clusters, the sum of the squared
+/// euclidean distances between the observations and their centroid, each divided by its
+/// degrees of freedom: `(B / (k - 1)) / (W / (n - k))` for `k` clusters with members and `n`
+/// observations. Like scikit-learn, it is 1 for clusters reduced to points. It is NaN with a
+/// single cluster, or with as many clusters as observations.
+///
+/// This takes `O(n_observations * n_features)` time.
+///
+/// Panics if `observations` and `memberships` don't have the same number of observations.
+pub fn calinski_harabasz_score<F: Float>(
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    memberships: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> F {
+    let (centroids, counts) = cluster_means(observations, memberships);
+    let n_samples = observations.nrows();
+    let n_clusters = counts.iter().filter(|&&count| count > 0).count();
+    if n_clusters < 2 || n_clusters >= n_samples {
+        return F::nan();
+    }
+    let mean = observations
+        .mean_axis(Axis(0))
+        .expect("there are more observations than clusters");
+
+    let between = Zip::from(centroids.rows())
+        .and(&counts)
+        .fold(F::zero(), |acc, centroid, &count| {
+            acc + F::cast(count) * L2Dist.rdistance(centroid, mean.view())
+        });
+    let within = Zip::from(observations.rows())
+        .and(memberships)
+        .fold(F::zero(), |acc, observation, &c| {
+            acc + L2Dist.rdistance(observation, centroids.row(c))
+        });
+    if within == F::zero() {
+        return F::one();
+    }
+    (between / F::cast(n_clusters - 1)) / (within / F::cast(n_samples - n_clusters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        calinski_harabasz_score, contingency_matrix, davies_bouldin_score, fit_stats,
+        silhouette_score,
+    };
+    use crate::{KMeans, KMeansError, KMeansInit};
+    use approx::assert_abs_diff_eq;
+    use linfa::prelude::*;
+    use linfa_datasets::generate;
+    use linfa_nn::distance::{Distance, L2Dist};
+    use ndarray::{array, s, Array1, Array2};
+    use ndarray_rand::rand::SeedableRng;
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    #[test]
+    fn information_criteria_hand_computed() {
+        let observations = array![[0., 0.], [2., 0.], [10., 1.], [10., -1.]];
+        let labels = array![0, 0, 1, 1];
+        let model = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![[1., 0.], [10., 0.]]))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        // Each cluster has 2 members with a total squared distance of 2 to their centroid, over 2
+        // features, which gives a variance of 0.5 per cluster
+        let log_likelihood = 2. * (2. * 0.5f64.ln() - 2. * std::f64::consts::PI.ln() - 2.);
+        assert_abs_diff_eq!(
+            model.aic(&observations, &labels),
+            12. - 2. * log_likelihood,
+            epsilon = 1e-10
+        );
+        assert_abs_diff_eq!(
+            model.bic(&observations, &labels),
+            6. * 4f64.ln() - 2. * log_likelihood,
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn cluster_summary() {
+        let observations = array![[0., 0.], [2., 0.], [10., 1.], [10., -1.], [11., 0.]];
+        let model = KMeans::params(3)
+            .init_method(KMeansInit::Precomputed(array![
+                [1., 0.],
+                [10., 0.],
+                [100., 100.]
+            ]))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        // m_k-means keeps the previous centroid in each mean, which moves the second centroid to
+        // (10.25, 0)
+        let summary = model.cluster_summary(&observations);
+        assert_eq!(summary.sizes, array![2, 3, 0]);
+        assert_abs_diff_eq!(
+            summary.inertia.slice(s![..2]),
+            array![2., 2. * 1.0625 + 0.5625],
+            epsilon = 1e-10
+        );
+        assert_eq!(summary.inertia[2], 0.);
+        assert_abs_diff_eq!(summary.variance[0], 1., epsilon = 1e-10);
+        assert_abs_diff_eq!(summary.variance[1], 2.6875 / 3., epsilon = 1e-10);
+        assert_abs_diff_eq!(
+            summary.radius.slice(s![..2]),
+            array![1., 1.0625f64.sqrt()],
+            epsilon = 1e-10
+        );
+        assert!(summary.variance[2].is_nan() && summary.radius[2].is_nan());
+
+        // The fit keeps the summary of its last assignment step, before its last update step
+        let summary = model.training_summary().unwrap();
+        assert_eq!(model.cluster_sizes(), Some(&array![2, 3, 0]));
+        assert_abs_diff_eq!(summary.inertia, array![2., 3., 0.], epsilon = 1e-10);
+        assert_eq!(model.per_cluster_inertia(), Some(&summary.inertia));
+        assert_abs_diff_eq!(
+            summary.radius.slice(s![..2]),
+            array![1., 1.],
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn training_summary_follows_the_clusters() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = array![[10., 10.], [0., 0.], [-10., 10.]];
+        let observations = generate::blobs(50, &centroids, &mut rng);
+        let mut model = KMeans::params_with_rng(3, rng)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let summary = model.training_summary().unwrap().clone();
+        assert_eq!(summary.sizes.sum(), 150);
+        assert_abs_diff_eq!(
+            summary.inertia.sum(),
+            model.inertia() * 150.,
+            epsilon = 1e-8
+        );
+        assert_abs_diff_eq!(
+            model.cluster_summary(&observations).inertia,
+            summary.inertia,
+            epsilon = 1e-6
+        );
+
+        let mut labels = model.predict(&observations);
+        let before = labels.clone();
+        model.canonicalize(&mut labels);
+        let reordered = model.training_summary().unwrap();
+        for (old, &new) in before.iter().zip(&labels) {
+            assert_eq!(reordered.sizes[new], summary.sizes[*old]);
+            assert_eq!(reordered.radius[new], summary.radius[*old]);
+        }
+        model.reseed_centroid(0, array![0., 0.]).unwrap();
+        assert_eq!(model.training_summary(), None);
+        assert_eq!(model.cluster_sizes(), None);
+    }
+
+    #[test]
+    fn feature_importances() {
+        let observations = array![[0., 0., 1.], [2., 1., 1.], [10., 1., 1.], [12., 0., 1.]];
+        let labels = array![0, 0, 1, 1];
+        let model = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![
+                [1., 0.5, 1.],
+                [11., 0.5, 1.]
+            ]))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let importances = model.feature_importances(&observations, &labels);
+        // Feature 0: between = 4 * 25 over 1 dof, within = 4 * 1 over 2 dofs
+        assert_abs_diff_eq!(importances[0], 50., epsilon = 1e-10);
+        // Feature 1 doesn't separate the clusters
+        assert_abs_diff_eq!(importances[1], 0., epsilon = 1e-10);
+        // Feature 2 is constant
+        assert_eq!(importances[2], 0.);
+
+        // Perfectly separated clusters
+        let observations = array![[0f64], [0.], [1.], [1.]];
+        let model = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![[0.], [1.]]))
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let importances = model.feature_importances(&observations, &labels);
+        assert!(importances[0].is_infinite());
+        // A single non-empty cluster
+        let importances = model.feature_importances(&observations, &array![1, 1, 1, 1]);
+        assert_eq!(importances[0], 0.);
+    }
+
+    #[test]
+    fn uninformative_features() {
+        let observations = array![
+            [0., 5., 1.],
+            [0., 5.05, 2.],
+            [10., 5.1, 1.],
+            [10., 5.15, 2.]
+        ];
+        let model = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(array![
+                [0., 5., 1.5],
+                [10., 5., 1.5]
+            ]))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(observations))
+            .unwrap();
+        // Centroid spreads of about 10, 0.07 and 0
+        assert_eq!(model.uninformative_features(0.01), vec![2]);
+        assert_eq!(model.uninformative_features(0.5), vec![1, 2]);
+        assert!(model.uninformative_features(0.).is_empty());
+    }
+
+    #[test]
+    fn optimal_matching() {
+        // Fitting on the centroids themselves leaves them in place
+        let model_at = |centroids: Array2<f64>| {
+            KMeans::params(centroids.nrows())
+                .init_method(KMeansInit::Precomputed(centroids.clone()))
+                .n_runs(1)
+                .fit(&DatasetBase::from(centroids))
+                .unwrap()
+        };
+        let model = model_at(array![[0., 0.], [10., 0.], [0., 10.]]);
+        let other = model_at(array![[0., 10.5], [0.5, 0.], [10., 0.5]]);
+        let (matching, total) = model.optimal_matching(&other).unwrap();
+        assert_eq!(matching, vec![1, 2, 0]);
+        assert_abs_diff_eq!(total, 1.5, epsilon = 1e-10);
+        let (matching, total) = model.optimal_matching(&model).unwrap();
+        assert_eq!((matching, total), (vec![0, 1, 2], 0.));
+
+        // Matching the closest pair first, as a greedy matching would, costs more here
+        let model = model_at(array![[0.], [2.]]);
+        let other = model_at(array![[1.9], [10.]]);
+        let (matching, total) = model.optimal_matching(&other).unwrap();
+        assert_eq!(matching, vec![0, 1]);
+        assert_abs_diff_eq!(total, 9.9, epsilon = 1e-10);
+
+        // Same total as a brute force search over all the matchings
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let model = model_at(Array2::random_using((5, 3), Uniform::new(0., 1.), &mut rng));
+        let other = model_at(Array2::random_using((5, 3), Uniform::new(0., 1.), &mut rng));
+        fn permutations(items: Vec<usize>) -> Vec<Vec<usize>> {
+            if items.len() <= 1 {
+                return vec![items];
+            }
+            (0..items.len())
+                .flat_map(|i| {
+                    let mut rest = items.clone();
+                    let first = rest.remove(i);
+                    permutations(rest).into_iter().map(move |mut p| {
+                        p.insert(0, first);
+                        p
+                    })
+                })
+                .collect()
+        }
+        let best = permutations((0..5).collect())
+            .iter()
+            .map(|p| {
+                p.iter()
+                    .enumerate()
+                    .map(|(a, &b)| {
+                        L2Dist.distance(model.centroids().row(a), other.centroids().row(b))
+                    })
+                    .sum::<f64>()
+            })
+            .fold(f64::INFINITY, f64::min);
+        let (matching, total) = model.optimal_matching(&other).unwrap();
+        let mut sorted = matching.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, vec![0, 1, 2, 3, 4]);
+        assert_abs_diff_eq!(total, best, epsilon = 1e-10);
+
+        assert!(matches!(
+            model.optimal_matching(&model_at(array![[0., 0., 0.]])),
+            Err(KMeansError::ClusterCountMismatch {
+                expected: 5,
+                actual: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn cluster_bounding_boxes() {
+        let observations = array![[0., 5.], [2., 1.], [1., 3.], [10., 0.], [12., 2.]];
+        let model = KMeans::params(3)
+            .init_method(KMeansInit::Precomputed(array![
+                [1., 3.],
+                [11., 1.],
+                [50., 50.]
+            ]))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let (mins, maxes) = model.cluster_bounding_boxes(&observations, &array![0, 0, 0, 1, 1]);
+        assert_eq!(
+            mins.slice(ndarray::s![..2, ..]),
+            array![[0., 1.], [10., 0.]]
+        );
+        assert_eq!(
+            maxes.slice(ndarray::s![..2, ..]),
+            array![[2., 5.], [12., 2.]]
+        );
+        // The third cluster is empty
+        assert!(mins
+            .row(2)
+            .iter()
+            .chain(maxes.row(2))
+            .all(|b: &f64| b.is_nan()));
+    }
+
+    #[test]
+    fn data_stats() {
+        let stats = fit_stats(&array![[0., 0.], [2., 0.], [4., 3.], [6., 1.]]);
+        assert_abs_diff_eq!(stats.mean, array![3., 1.]);
+        // Squared distances of 10, 2, 5 and 9 to the mean
+        assert_abs_diff_eq!(stats.total_ss, 26.);
+        let empty = fit_stats(&Array2::<f64>::zeros((0, 2)));
+        assert!(empty.mean.iter().all(|m| m.is_nan()));
+        assert_eq!(empty.total_ss, 0.);
+    }
+
+    #[test]
+    fn contingency() {
+        let labels_a = array![0, 0, 1, 1, 1, 3];
+        let labels_b = array![1, 1, 0, 1, 0, 0];
+        let contingency = contingency_matrix(&labels_a, &labels_b);
+        assert_eq!(contingency, array![[0, 2], [2, 1], [0, 0], [1, 0]]);
+        assert_eq!(contingency.sum_axis(ndarray::Axis(1)), array![2, 3, 0, 1]);
+        assert_eq!(contingency.sum(), labels_a.len());
+
+        let empty = Array1::<usize>::zeros(0);
+        assert_eq!(contingency_matrix(&empty, &empty).dim(), (0, 0));
+    }
+
+    #[test]
+    fn bic_selects_n_clusters() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [20., 0.], [0., 20.]];
+        let observations = generate::blobs(100, &expected_centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+
+        let bics: Vec<f64> = (1..6)
+            .map(|k| {
+                let model = KMeans::params_with_rng(k, rng.clone())
+                    .fit(&dataset)
+                    .unwrap();
+                let labels = model.predict(&observations);
+                model.bic(&observations, &labels)
+            })
+            .collect();
+        let best_k = bics
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap()
+            .0
+            + 1;
+        assert_eq!(best_k, 3);
+    }
+
+    #[test]
+    fn silhouette() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = array![[0., 0.], [100., 0.]];
+        let observations = generate::blobs(50, &centroids, &mut rng);
+        let memberships = Array1::from_shape_fn(100, |i| i / 50);
+        let score = silhouette_score(&observations, &memberships);
+        assert!(score > 0.95 && score <= 1., "{}", score);
+        // Same as the silhouette score of linfa's datasets, which has no singleton clusters
+        let dataset = DatasetBase::new(observations.clone(), memberships.clone());
+        assert_abs_diff_eq!(score, dataset.silhouette_score().unwrap(), epsilon = 1e-10);
+        // Splitting a blob in two makes a worse clustering
+        let split = Array1::from_shape_fn(100, |i| if i < 50 { i % 2 } else { 2 });
+        assert!(silhouette_score(&observations, &split) < score);
+
+        // The singleton 2 contributes 0. Observation 0 has a = 1 and b = 10, observation 1 has
+        // a = 1 and b = 9
+        let observations = array![[0.], [1.], [10.]];
+        let score = silhouette_score(&observations, &array![0, 0, 1]);
+        assert_abs_diff_eq!(score, (9. / 10. + 8. / 9.) / 3., epsilon = 1e-10);
+        assert_eq!(silhouette_score(&observations, &array![0, 0, 0]), 0.);
+    }
+
+    #[test]
+    fn davies_bouldin_and_calinski_harabasz() {
+        // Centroids 1 and 11, members at distance 1 of their centroid, and overall mean 6
+        let observations = array![[0.], [2.], [10.], [12.]];
+        let memberships = array![0, 0, 1, 1];
+        assert_abs_diff_eq!(
+            davies_bouldin_score(&observations, &memberships),
+            2. / 10.,
+            epsilon = 1e-10
+        );
+        // B = 4 * 5^2 and W = 4 * 1^2
+        assert_abs_diff_eq!(
+            calinski_harabasz_score(&observations, &memberships),
+            (100. / 1.) / (4. / 2.),
+            epsilon = 1e-10
+        );
+        // An unused label changes nothing
+        let gapped = array![0, 0, 2, 2];
+        assert_abs_diff_eq!(davies_bouldin_score(&observations, &gapped), 0.2);
+        assert_abs_diff_eq!(calinski_harabasz_score(&observations, &gapped), 50.);
+
+        let single = array![0, 0, 0, 0];
+        assert_eq!(davies_bouldin_score(&observations, &single), 0.);
+        assert!(calinski_harabasz_score::<f64>(&observations, &single).is_nan());
+        let points = array![[0.], [0.], [10.], [10.]];
+        assert_eq!(davies_bouldin_score(&points, &memberships), 0.);
+        assert_eq!(calinski_harabasz_score(&points, &memberships), 1.);
+
+        // Splitting a blob in two makes a worse clustering
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = array![[0., 0.], [100., 0.]];
+        let observations = generate::blobs(50, &centroids, &mut rng);
+        let memberships = Array1::from_shape_fn(100, |i| i / 50);
+        let split = Array1::from_shape_fn(100, |i| if i < 50 { i % 2 } else { 2 });
+        assert!(
+            davies_bouldin_score(&observations, &split)
+                > davies_bouldin_score(&observations, &memberships)
+        );
+        assert!(
+            calinski_harabasz_score(&observations, &split)
+                < calinski_harabasz_score(&observations, &memberships)
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use super::algorithm::KMeans;
+use super::errors::KMeansError;
+use super::hyperparams::KMeansValidParams;
+use linfa::prelude::*;
+use linfa::Float;
+use linfa_nn::distance::Distance;
+use ndarray::{Array1, Array2, ArrayBase, ArrayView1, Axis, Data, Ix2};
+use ndarray_rand::rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// A hierarchy of clusterings computed by bisecting K-means, see
+/// [`fit_bisecting`](KMeansValidParams::fit_bisecting).
+///
+/// Cluster `c > 0` was created by splitting its parent cluster in two, and the clusters are
+/// numbered in the order of the splits, so the clustering into `k` clusters is obtained by merging
+/// every cluster `c >= k` back into its parent. The clusterings at all resolutions are thus nested,
+/// and can be queried with [`labels_at`](BisectingKMeans::labels_at) without refitting.
+pub struct BisectingKMeans<F: Float, D: Distance<F>> {
+    /// Centroids of the finest clustering
+    centroids: Array2<F>,
+    /// Cluster split to create each cluster, the first cluster being its own parent
+    parents: Vec<usize>,
+    /// Labels of the training observations in the finest clustering
+    labels: Array1<usize>,
+    dist_fn: D,
+}
+
+impl<F: Float, D: Distance<F>> BisectingKMeans<F, D> {
+    /// Return the centroids of the finest clustering, with shape `(n_clusters, n_features)`
+    pub fn centroids(&self) -> &Array2<F> {
+        &self.centroids
+    }
+
+    /// Return the number of clusters of the finest clustering. This is less than the requested
+    /// `n_clusters` if the training observations ran out of distinct values to split.
+    pub fn n_clusters(&self) -> usize {
+        self.parents.len()
+    }
+
+    /// Return the cluster whose split created each cluster. The first cluster is its own parent,
+    /// and every other cluster has a parent with a lower index.
+    pub fn parents(&self) -> &[usize] {
+        &self.parents
+    }
+
+    /// Return the distance metric of the model
+    pub fn dist_fn(&self) -> &D {
+        &self.dist_fn
+    }
+
+    /// Return the labels of the training observations when the hierarchy is cut at `k` clusters,
+    /// as given by the first `k - 1` splits. Labels lie between 0 and `k - 1`.
+    ///
+    /// Panics if `k` is 0 or greater than [`n_clusters`](BisectingKMeans::n_clusters).
+    pub fn labels_at(&self, k: usize) -> Array1<usize> {
+        assert!(
+            k >= 1 && k <= self.n_clusters(),
+            "The resolution must lie between 1 and {}, got {}.",
+            self.n_clusters(),
+            k
+        );
+        self.labels.mapv(|mut label| {
+            while label >= k {
+                label = self.parents[label];
+            }
+            label
+        })
+    }
+}
+
+impl<F: Float, R: Rng + Clone, D: Distance<F>> KMeansValidParams<F, R, D> {
+    /// Fit a hierarchy of up to `n_clusters` clusters on `dataset` by bisecting K-means: starting
+    /// from a single cluster, the cluster with the largest inertia (sum of `rdistance` of its
+    /// members to its centroid) is split in two by 2-means until there are `n_clusters` clusters.
+    ///
+    /// Unlike [`KMeans::split_until_radius`], observations are never reassigned to another branch
+    /// of the hierarchy, so that the clusterings at every resolution are nested. Each 2-means
+    /// uses the `n_runs`, `tolerance`, `convergence`, `max_n_iterations`, `init` and distance
+    /// metric of these hyperparameters, and the other hyperparameters are ignored.
+    ///
+    /// Returns an error if there are no observations or if a split fails.
+    pub fn fit_bisecting<T>(
+        &self,
+        dataset: &DatasetBase<ArrayBase<impl Data<Elem = F>, Ix2>, T>,
+    ) -> Result<BisectingKMeans<F, D>, KMeansError> {
+        let observations = dataset.records();
+        let n_samples = observations.nrows();
+        if n_samples == 0 {
+            return Err(KMeansError::EmptyObservations);
+        }
+
+        let mut rng = self.rng().clone();
+        let mut labels = Array1::zeros(n_samples);
+        let mut centroids = observations
+            .mean_axis(Axis(0))
+            .expect("there is at least one observation")
+            .insert_axis(Axis(0));
+        let mut parents = vec![0];
+        let inertia = |centroid: ArrayView1<F>, members: &[usize]| {
+            members.iter().fold(F::zero(), |acc, &i| {
+                acc + self.dist_fn().rdistance(centroid, observations.row(i))
+            })
+        };
+        let all: Vec<_> = (0..n_samples).collect();
+        let mut inertias = vec![inertia(centroids.row(0), &all)];
+
+        while parents.len() < self.n_clusters() {
+            // Clusters without inertia have all their members at the centroid, and can't be split
+            let (widest, &max_inertia) = inertias
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .expect("there is at least one cluster");
+            if max_inertia <= F::zero() {
+                break;
+            }
+
+            let members: Vec<usize> = (0..n_samples).filter(|&i| labels[i] == widest).collect();
+            let member_obs = observations.select(Axis(0), &members);
+            let halves = KMeans::params_with(
+                2,
+                Xoshiro256Plus::seed_from_u64(rng.gen()),
+                self.dist_fn().clone(),
+            )
+            .n_runs(self.n_runs())
+            .tolerance(self.tolerance())
+            .convergence(self.convergence().clone())
+            .max_n_iterations(self.max_n_iterations())
+            .init_method(self.init_method().clone())
+            .fit(&DatasetBase::from(member_obs.view()))?;
+            let half_labels = halves.predict(&member_obs);
+            let (first, second): (Vec<_>, Vec<_>) = members
+                .iter()
+                .zip(&half_labels)
+                .partition(|(_, &half)| half == 0);
+            if first.is_empty() || second.is_empty() {
+                // Members too close to be told apart, never try to split them again
+                inertias[widest] = F::zero();
+                continue;
+            }
+
+            let new = parents.len();
+            let first: Vec<usize> = first.into_iter().map(|(&i, _)| i).collect();
+            let second: Vec<usize> = second.into_iter().map(|(&i, _)| i).collect();
+            second.iter().for_each(|&i| labels[i] = new);
+            centroids.row_mut(widest).assign(&halves.centroids().row(0));
+            centroids
+                .push_row(halves.centroids().row(1))
+                .expect("centroids have the same number of features");
+            inertias[widest] = inertia(halves.centroids().row(0), &first);
+            inertias.push(inertia(halves.centroids().row(1), &second));
+            parents.push(widest);
+        }
+
+        Ok(BisectingKMeans {
+            centroids,
+            parents,
+            labels,
+            dist_fn: self.dist_fn().clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linfa_datasets::generate;
+    use ndarray::{array, Array2};
+
+    #[test]
+    fn nested_resolutions() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        // Two far apart groups of two blobs each
+        let expected_centroids = array![[0., 0.], [10., 0.], [100., 0.], [110., 0.]];
+        let observations = generate::blobs(50, &expected_centroids, &mut rng);
+        let model = KMeans::params_with_rng(4, rng)
+            .check()
+            .unwrap()
+            .fit_bisecting(&DatasetBase::from(observations))
+            .unwrap();
+        assert_eq!(model.n_clusters(), 4);
+        assert_eq!(model.parents()[0], 0);
+        assert!((1..4).all(|c| model.parents()[c] < c));
+
+        assert!(model.labels_at(1).iter().all(|&l| l == 0));
+        // `blobs` generates the observations of each blob contiguously
+        let blob_labels = |k| {
+            let labels = model.labels_at(k);
+            labels
+                .exact_chunks(50)
+                .into_iter()
+                .map(|blob| {
+                    assert!(blob.iter().all(|&l| l == blob[0]));
+                    blob[0]
+                })
+                .collect::<Vec<_>>()
+        };
+        let coarse = blob_labels(2);
+        assert_eq!(coarse[0], coarse[1]);
+        assert_eq!(coarse[2], coarse[3]);
+        assert_ne!(coarse[0], coarse[2]);
+        let fine = blob_labels(4);
+        let mut sorted = fine.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn stops_without_distinct_values() {
+        let observations = array![[1., 1.], [1., 1.], [3., 1.]];
+        let model = KMeans::params(3)
+            .check()
+            .unwrap()
+            .fit_bisecting(&DatasetBase::from(observations))
+            .unwrap();
+        assert_eq!(model.n_clusters(), 2);
+        let labels = model.labels_at(2);
+        assert!(labels[0] == labels[1] && labels[1] != labels[2]);
+
+        let res = KMeans::params(3)
+            .check()
+            .unwrap()
+            .fit_bisecting(&DatasetBase::from(Array2::<f64>::zeros((0, 2))));
+        assert!(matches!(res, Err(KMeansError::EmptyObservations)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn labels_beyond_the_hierarchy() {
+        let observations = array![[1., 1.], [3., 1.]];
+        let model = KMeans::params(2)
+            .check()
+            .unwrap()
+            .fit_bisecting(&DatasetBase::from(observations))
+            .unwrap();
+        model.labels_at(3);
+    }
+}
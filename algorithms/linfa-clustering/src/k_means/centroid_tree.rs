@@ -0,0 +1,298 @@
+use linfa::Float;
+use linfa_nn::distance::Distance;
+use ndarray::{Array2, ArrayView1};
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+
+// Number of centroids below which a node of the tree stops splitting
+const LEAF_SIZE: usize = 8;
+
+#[derive(Debug)]
+enum Node<F> {
+    // Positions in `order` of the centroids of the leaf
+    Leaf { start: usize, end: usize },
+    // Centroids whose feature `dim` is at most `value` are in the left child, which is the next
+    // node, and those where it's at least `value` in the `right` node
+    Split { dim: usize, value: F, right: usize },
+}
+
+/// KD tree over the centroids of a model, owning them so that it can be kept on the model.
+///
+/// Pruning a subtree relies on the distance between two points being at least the distance along
+/// any single feature, which only holds if [`Distance::is_minkowski`].
+#[derive(Debug)]
+pub(crate) struct CentroidTree<F> {
+    centroids: Array2<F>,
+    // Indices of the centroids, grouped by leaf
+    order: Vec<usize>,
+    nodes: Vec<Node<F>>,
+}
+
+impl<F: Float> CentroidTree<F> {
+    pub(crate) fn new(centroids: Array2<F>) -> Self {
+        let mut tree = CentroidTree {
+            order: (0..centroids.nrows()).collect(),
+            centroids,
+            nodes: Vec::new(),
+        };
+        tree.build(0, tree.order.len());
+        tree
+    }
+
+    fn build(&mut self, start: usize, end: usize) {
+        let centroids = &self.centroids;
+        let order = &mut self.order[start..end];
+        // Split on the feature with the largest spread, if the centroids aren't all the same
+        let split = (end - start > LEAF_SIZE)
+            .then(|| {
+                (0..centroids.ncols())
+                    .map(|dim| {
+                        let (min, max) = order.iter().fold(
+                            (F::infinity(), F::neg_infinity()),
+                            |(min, max), &c| {
+                                (
+                                    F::min(min, centroids[(c, dim)]),
+                                    F::max(max, centroids[(c, dim)]),
+                                )
+                            },
+                        );
+                        (dim, max - min)
+                    })
+                    .fold((0, F::zero()), |best, spread| {
+                        if spread.1 > best.1 {
+                            spread
+                        } else {
+                            best
+                        }
+                    })
+            })
+            .filter(|&(_, spread)| spread > F::zero());
+        let dim = match split {
+            Some((dim, _)) => dim,
+            None => {
+                self.nodes.push(Node::Leaf { start, end });
+                return;
+            }
+        };
+
+        let mid = (end - start) / 2;
+        order.select_nth_unstable_by(mid, |&a, &b| {
+            centroids[(a, dim)]
+                .partial_cmp(&centroids[(b, dim)])
+                .unwrap_or(Ordering::Equal)
+        });
+        let value = centroids[(order[mid], dim)];
+        let node = self.nodes.len();
+        self.nodes.push(Node::Split {
+            dim,
+            value,
+            right: 0,
+        });
+        self.build(start, start + mid);
+        let right_child = self.nodes.len();
+        if let Node::Split { right, .. } = &mut self.nodes[node] {
+            *right = right_child;
+        }
+        self.build(start + mid, end);
+    }
+
+    /// Returns the index of the closest centroid to `observation`, with the reduced distance
+    /// between them, among the centroids of the first `max_leaves` leaves of the tree visited by
+    /// a depth-first search going to the side of `observation` first. Leaves which can't hold a
+    /// closer centroid are skipped, so with enough leaves the closest centroid is always found,
+    /// with ties going to the lowest index like `closest_centroid`.
+    pub(crate) fn closest_centroid<D: Distance<F>>(
+        &self,
+        dist_fn: &D,
+        observation: ArrayView1<F>,
+        max_leaves: usize,
+    ) -> (usize, F) {
+        let mut closest = (0, F::infinity());
+        let mut leaves = max_leaves.max(1);
+        self.search(0, dist_fn, observation, &mut closest, &mut leaves);
+        closest
+    }
+
+    fn search<D: Distance<F>>(
+        &self,
+        node: usize,
+        dist_fn: &D,
+        observation: ArrayView1<F>,
+        closest: &mut (usize, F),
+        leaves: &mut usize,
+    ) {
+        match self.nodes[node] {
+            Node::Leaf { start, end } => {
+                *leaves -= 1;
+                for &c in &self.order[start..end] {
+                    let dist = dist_fn.rdistance(self.centroids.row(c), observation);
+                    if dist < closest.1 || (dist == closest.1 && c < closest.0) {
+                        *closest = (c, dist);
+                    }
+                }
+            }
+            Node::Split { dim, value, right } => {
+                let diff = observation[dim] - value;
+                let (near, far) = if diff < F::zero() {
+                    (node + 1, right)
+                } else {
+                    (right, node + 1)
+                };
+                self.search(near, dist_fn, observation, closest, leaves);
+                // Centroids on the other side are at least `|diff|` away. Equal distances are
+                // still searched, for ties to go to the lowest index.
+                if *leaves > 0 && dist_fn.dist_to_rdist(diff.abs()) <= closest.1 {
+                    self.search(far, dist_fn, observation, closest, leaves);
+                }
+            }
+        }
+    }
+}
+
+/// Lazily built [`CentroidTree`] of a model, shared between threads.
+///
+/// The cache is not part of the state of the model: it is ignored by comparisons, and models
+/// cloned from one another share the tree until either of them changes its centroids.
+pub(crate) struct CentroidTreeCache<F>(RwLock<Option<Arc<CentroidTree<F>>>>);
+
+impl<F> CentroidTreeCache<F> {
+    /// Returns the cached tree, building it with `build` if there is none
+    pub(crate) fn get_or_build(
+        &self,
+        build: impl FnOnce() -> CentroidTree<F>,
+    ) -> Arc<CentroidTree<F>> {
+        if let Some(tree) = &*self.0.read().unwrap_or_else(PoisonError::into_inner) {
+            return tree.clone();
+        }
+        let mut cache = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        // Another thread may have built the tree while this one waited for the lock
+        cache.get_or_insert_with(|| Arc::new(build())).clone()
+    }
+
+    /// Drops the cached tree, to be called whenever the centroids change
+    pub(crate) fn clear(&mut self) {
+        *self.0.get_mut().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    fn get(&self) -> Option<Arc<CentroidTree<F>>> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl<F> Default for CentroidTreeCache<F> {
+    fn default() -> Self {
+        Self(RwLock::new(None))
+    }
+}
+
+impl<F> Clone for CentroidTreeCache<F> {
+    fn clone(&self) -> Self {
+        Self(RwLock::new(self.get()))
+    }
+}
+
+impl<F> PartialEq for CentroidTreeCache<F> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<F> fmt::Debug for CentroidTreeCache<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CentroidTreeCache")
+            .field("built", &self.get().is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linfa_nn::distance::{L1Dist, L2Dist};
+    use ndarray::{array, Array};
+    use ndarray_rand::rand::SeedableRng;
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    fn linear_scan<D: Distance<f64>>(
+        dist_fn: &D,
+        centroids: &Array2<f64>,
+        observation: ArrayView1<f64>,
+    ) -> (usize, f64) {
+        centroids
+            .rows()
+            .into_iter()
+            .map(|c| dist_fn.rdistance(c, observation))
+            .enumerate()
+            .fold((0, f64::INFINITY), |best, (c, dist)| {
+                if dist < best.1 {
+                    (c, dist)
+                } else {
+                    best
+                }
+            })
+    }
+
+    #[test]
+    fn exact_search_matches_linear_scan() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = Array::random_using((300, 4), Uniform::new(-10., 10.), &mut rng);
+        let observations = Array::random_using((200, 4), Uniform::new(-12., 12.), &mut rng);
+        let tree = CentroidTree::new(centroids.clone());
+        for observation in observations.rows() {
+            assert_eq!(
+                tree.closest_centroid(&L2Dist, observation, usize::MAX),
+                linear_scan(&L2Dist, &centroids, observation)
+            );
+            assert_eq!(
+                tree.closest_centroid(&L1Dist, observation, usize::MAX),
+                linear_scan(&L1Dist, &centroids, observation)
+            );
+            // A single leaf gives a centroid that is at best as close
+            let (_, dist) = tree.closest_centroid(&L2Dist, observation, 1);
+            assert!(dist >= linear_scan(&L2Dist, &centroids, observation).1);
+        }
+    }
+
+    #[test]
+    fn ties_and_duplicates() {
+        // Duplicated centroids can't be split, and must end up in a single leaf
+        let centroids = Array2::from_elem((20, 2), 1.);
+        let tree = CentroidTree::new(centroids);
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(
+            tree.closest_centroid(&L2Dist, array![0., 0.].view(), 1),
+            (0, 2.)
+        );
+
+        // (0, 0) is as close to the centroids on the left as those on the right
+        let mut centroids = Array2::zeros((2 * LEAF_SIZE, 2));
+        for i in 0..LEAF_SIZE {
+            centroids.row_mut(i).assign(&array![1., 0.]);
+            centroids.row_mut(LEAF_SIZE + i).assign(&array![-1., 0.]);
+        }
+        let tree = CentroidTree::new(centroids);
+        assert_eq!(
+            tree.closest_centroid(&L2Dist, array![0., 0.].view(), usize::MAX),
+            (0, 1.)
+        );
+    }
+
+    #[test]
+    fn cache_is_shared_and_cleared() {
+        let cache = CentroidTreeCache::default();
+        let tree = cache.get_or_build(|| CentroidTree::new(array![[0., 0.]]));
+        let same = cache.get_or_build(|| panic!("the tree is already built"));
+        assert!(Arc::ptr_eq(&tree, &same));
+        let mut cloned = cache.clone();
+        assert!(Arc::ptr_eq(&tree, &cloned.get().unwrap()));
+        cloned.clear();
+        assert!(cloned.get().is_none());
+        assert!(cache.get().is_some());
+    }
+}
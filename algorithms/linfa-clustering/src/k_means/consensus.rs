@@ -0,0 +1,325 @@
+use super::algorithm::KMeans;
+use super::analysis::contingency_matrix;
+use super::errors::{KMeansError, KMeansParamsError};
+use super::hyperparams::KMeansParams;
+use super::init::cut_dendrogram;
+use linfa::prelude::*;
+use linfa::Float;
+use linfa_nn::distance::Distance;
+use ndarray::{Array1, Array2, ArrayBase, Axis, Data, Ix2};
+use ndarray_rand::rand::seq::SliceRandom;
+use ndarray_rand::rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
+
+/// Computes a consensus clustering of `observations` into `n_clusters` clusters from `n_runs`
+/// independent K-means fits, which is more stable than the labels of any single fit.
+///
+/// Each run fits a [`KMeans`] model with default hyperparameters and a single initialization,
+/// seeded from `rng`. The co-association of two observations is the fraction of runs in which
+/// they land in the same cluster. The observations are then clustered by an average linkage
+/// agglomerative clustering on the distance `1 - co-association`, cut at `n_clusters` clusters.
+/// Labels are numbered in order of the first observation of each cluster.
+///
+/// The co-association matrix takes `O(n_observations²)` memory and time, so this is only suitable
+/// for small to medium datasets.
+///
+/// Returns an error if `n_runs` is 0, if there are no observations, or if a fit fails.
+pub fn consensus_fit<F: Float>(
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    n_runs: usize,
+    n_clusters: usize,
+    rng: &mut impl Rng,
+) -> Result<Array1<usize>, KMeansError> {
+    if n_runs == 0 {
+        return Err(KMeansParamsError::NRuns.into());
+    }
+    let n_samples = observations.nrows();
+    if n_samples == 0 {
+        return Err(KMeansError::EmptyObservations);
+    }
+
+    // Number of runs in which each pair of observations shares a cluster, as a condensed matrix
+    let dataset = DatasetBase::from(observations.view());
+    let mut co_association = vec![0usize; n_samples * (n_samples - 1) / 2];
+    for _ in 0..n_runs {
+        let model = KMeans::params_with_rng(n_clusters, Xoshiro256Plus::seed_from_u64(rng.gen()))
+            .n_runs(1)
+            .fit(&dataset)?;
+        let labels = model.predict(observations);
+        let mut pairs = co_association.iter_mut();
+        for i in 0..n_samples {
+            for j in (i + 1)..n_samples {
+                let count = pairs.next().expect("one entry per pair");
+                if labels[i] == labels[j] {
+                    *count += 1;
+                }
+            }
+        }
+    }
+
+    let mut condensed: Vec<f64> = co_association
+        .into_iter()
+        .map(|count| 1. - count as f64 / n_runs as f64)
+        .collect();
+    let dendrogram = kodama::linkage(&mut condensed, n_samples, kodama::Method::Average);
+    let mut clusters = cut_dendrogram(&dendrogram, n_samples, n_clusters);
+    clusters
+        .iter_mut()
+        .for_each(|cluster| cluster.sort_unstable());
+    clusters.sort_unstable_by_key(|cluster| cluster[0]);
+
+    let mut labels = Array1::zeros(n_samples);
+    for (label, cluster) in clusters.iter().enumerate() {
+        for &i in cluster {
+            labels[i] = label;
+        }
+    }
+    Ok(labels)
+}
+
+/// Computes the [prediction strength](https://doi.org/10.1198/106186005X59243) of K-means on
+/// `observations` for each number of clusters of `k_range`, which measures how reproducible a
+/// clustering into `k` clusters is.
+///
+/// The observations are shuffled with `rng` and split into a training and a test half, and each
+/// half is fitted with a [`KMeans`] model with default hyperparameters and `k` clusters. For each
+/// cluster of the test fit, the strength is the fraction of pairs of its members that the model
+/// of the training half also puts in the same cluster, and the prediction strength is the lowest
+/// such fraction. Clusters with less than two members are ignored, and a `k` without any larger
+/// cluster gets a strength of 0. The prediction strength of a single cluster is always 1.
+///
+/// Returns the pairs of `k` and prediction strength, in the order of `k_range`. The recommended
+/// number of clusters is the largest `k` with a strength above about 0.8.
+///
+/// Returns an error if there are less than two observations, or if a fit fails.
+pub fn prediction_strength<F: Float>(
+    observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    k_range: impl IntoIterator<Item = usize>,
+    rng: &mut impl Rng,
+) -> Result<Vec<(usize, f64)>, KMeansError> {
+    if observations.nrows() < 2 {
+        return Err(KMeansError::EmptyObservations);
+    }
+    let mut indices: Vec<usize> = (0..observations.nrows()).collect();
+    indices.shuffle(rng);
+    let (train, test) = indices.split_at(indices.len() / 2);
+    let train = DatasetBase::from(observations.select(Axis(0), train));
+    let test = DatasetBase::from(observations.select(Axis(0), test));
+
+    k_range
+        .into_iter()
+        .map(|k| {
+            let mut fit = |dataset| {
+                KMeans::params_with_rng(k, Xoshiro256Plus::seed_from_u64(rng.gen())).fit(dataset)
+            };
+            let train_model = fit(&train)?;
+            let test_model = fit(&test)?;
+            let test_labels = test_model.predict(test.records());
+            let predicted = train_model.predict(test.records());
+
+            // Pairs of members of each test cluster, and those which share a predicted cluster
+            let pairs = |n: usize| n * n.saturating_sub(1) / 2;
+            let strength = contingency_matrix(&test_labels, &predicted)
+                .rows()
+                .into_iter()
+                .filter(|row| row.sum() >= 2)
+                .map(|row| {
+                    let co_clustered: usize = row.iter().map(|&n| pairs(n)).sum();
+                    co_clustered as f64 / pairs(row.sum()) as f64
+                })
+                .fold(None, |min: Option<f64>, s| {
+                    Some(min.map_or(s, |m| m.min(s)))
+                })
+                .unwrap_or(0.);
+            Ok((k, strength))
+        })
+        .collect()
+}
+
+/// A fit of [`KMeans::fit_range`] for one number of clusters
+#[derive(Clone, Debug)]
+pub struct RangeFit<F: Float, D: Distance<F>> {
+    /// Number of clusters of the fit
+    pub n_clusters: usize,
+    /// Model fitted on the observations with `n_clusters` clusters
+    pub model: KMeans<F, D>,
+    /// Inertia of the model, see [`KMeans::inertia`]
+    pub inertia: F,
+    /// Gap statistic of the fit, if computed, along with its standard error
+    pub gap: Option<(F, F)>,
+}
+
+impl<F: Float, D: Distance<F>> KMeans<F, D> {
+    /// Fits K-means on `observations` for each number of clusters of `k_range`, with every other
+    /// hyperparameter taken from `template`, so that `n_clusters` can be picked from the results
+    /// instead of refitting by hand. The inertia of each fit gives the elbow curve.
+    ///
+    /// With `n_references > 0`, also computes the [gap statistic](https://doi.org/10.1111/1467-9868.00293)
+    /// of each fit: `n_references` reference datasets are drawn with `rng` uniformly over the
+    /// bounding box of the observations, and fitted with the same hyperparameters. The gap is the
+    /// mean log-inertia of the reference fits minus the log-inertia of the fit, and its standard
+    /// error is the standard deviation of the reference log-inertias times
+    /// `sqrt(1 + 1 / n_references)`. See [`select_by_gap`](KMeans::select_by_gap) for the usual
+    /// choice of `n_clusters` from the gaps. The references make this `n_references + 1` times
+    /// slower than the inertias alone.
+    ///
+    /// Returns the fits in the order of `k_range`, or an error if the hyperparameters are invalid
+    /// for a `k`, or if a fit fails.
+    pub fn fit_range<R: Rng + Clone>(
+        template: &KMeansParams<F, R, D>,
+        k_range: impl IntoIterator<Item = usize>,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        n_references: usize,
+        rng: &mut impl Rng,
+    ) -> Result<Vec<RangeFit<F, D>>, KMeansError> {
+        let dataset = DatasetBase::from(observations.view());
+        let min = observations.fold_axis(Axis(0), F::infinity(), |&min, &x| min.min(x));
+        let max = observations.fold_axis(Axis(0), F::neg_infinity(), |&max, &x| max.max(x));
+        let references: Vec<_> = (0..n_references)
+            .map(|_| {
+                let shape = (observations.nrows(), observations.ncols());
+                let reference = Array2::from_shape_fn(shape, |(_, j)| {
+                    min[j] + (max[j] - min[j]) * F::cast(rng.gen::<f64>())
+                });
+                DatasetBase::from(reference)
+            })
+            .collect();
+
+        k_range
+            .into_iter()
+            .map(|k| {
+                let params = template.clone().n_clusters(k).check()?;
+                let model = params.fit(&dataset)?;
+                let inertia = model.inertia();
+                let gap = match references.len() {
+                    0 => None,
+                    n => {
+                        let log_inertias = references
+                            .iter()
+                            .map(|reference| Ok(params.fit(reference)?.inertia().ln()))
+                            .collect::<Result<Vec<F>, KMeansError>>()?;
+                        let n = F::cast(n);
+                        let mean = log_inertias.iter().fold(F::zero(), |acc, &l| acc + l) / n;
+                        let variance = log_inertias
+                            .iter()
+                            .fold(F::zero(), |acc, &l| acc + (l - mean) * (l - mean))
+                            / n;
+                        let std_err = (variance * (F::one() + F::one() / n)).sqrt();
+                        Some((mean - inertia.ln(), std_err))
+                    }
+                };
+                Ok(RangeFit {
+                    n_clusters: k,
+                    model,
+                    inertia,
+                    gap,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the index in `fits` of the first fit whose gap is at least the gap of the next fit
+    /// minus its standard error, the rule of Tibshirani et al. for the gap statistic, or `None`
+    /// if there is no such fit or the fits have no gap. `fits` are expected in increasing order
+    /// of `n_clusters`, as returned by [`fit_range`](KMeans::fit_range) on an increasing range.
+    pub fn select_by_gap(fits: &[RangeFit<F, D>]) -> Option<usize> {
+        fits.windows(2)
+            .position(|pair| match (pair[0].gap, pair[1].gap) {
+                (Some((gap, _)), Some((next_gap, next_std_err))) => gap >= next_gap - next_std_err,
+                _ => false,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linfa_datasets::generate;
+    use ndarray::{array, Array2};
+
+    #[test]
+    fn consensus_recovers_blobs() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [20., 0.], [0., 20.]];
+        let observations = generate::blobs(30, &expected_centroids, &mut rng);
+
+        let labels = consensus_fit(&observations, 10, 3, &mut rng).unwrap();
+        // `blobs` generates the observations of each blob contiguously
+        for (blob, expected) in labels.exact_chunks(30).into_iter().zip(0..) {
+            assert!(blob.iter().all(|&l| l == expected));
+        }
+    }
+
+    #[test]
+    fn prediction_strength_selects_k() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [20., 0.], [0., 20.]];
+        let observations = generate::blobs(100, &expected_centroids, &mut rng);
+
+        let strengths = prediction_strength(&observations, 1..=6, &mut rng).unwrap();
+        assert_eq!(
+            strengths.iter().map(|&(k, _)| k).collect::<Vec<_>>(),
+            (1..=6).collect::<Vec<_>>()
+        );
+        assert_eq!(strengths[0].1, 1.);
+        let best_k = strengths
+            .iter()
+            .filter(|&&(_, strength)| strength > 0.8)
+            .map(|&(k, _)| k)
+            .max();
+        assert_eq!(best_k, Some(3));
+
+        assert!(matches!(
+            prediction_strength(&array![[0., 0.]], 1..3, &mut rng),
+            Err(KMeansError::EmptyObservations)
+        ));
+    }
+
+    #[test]
+    fn fit_range_selects_k() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [20., 0.], [0., 20.]];
+        let observations = generate::blobs(100, &expected_centroids, &mut rng);
+        let template = KMeans::params_with_rng(1, rng.clone()).n_runs(3);
+
+        let fits = KMeans::fit_range(&template, 1..=6, &observations, 10, &mut rng).unwrap();
+        assert_eq!(
+            fits.iter().map(|fit| fit.n_clusters).collect::<Vec<_>>(),
+            (1..=6).collect::<Vec<_>>()
+        );
+        assert!(fits
+            .iter()
+            .all(|fit| fit.model.centroids().nrows() == fit.n_clusters));
+        assert!(fits
+            .windows(2)
+            .all(|pair| pair[1].inertia < pair[0].inertia));
+        assert!(fits.iter().all(|fit| fit.gap.is_some()));
+        assert_eq!(
+            KMeans::select_by_gap(&fits).map(|i| fits[i].n_clusters),
+            Some(3)
+        );
+
+        let fits = KMeans::fit_range(&template, 1..=3, &observations, 0, &mut rng).unwrap();
+        assert!(fits.iter().all(|fit| fit.gap.is_none()));
+        assert_eq!(KMeans::select_by_gap(&fits), None);
+
+        assert!(matches!(
+            KMeans::fit_range(&template, 0..2, &observations, 0, &mut rng),
+            Err(KMeansError::InvalidParams(KMeansParamsError::NClusters))
+        ));
+    }
+
+    #[test]
+    fn consensus_errors() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let observations = array![[0., 0.], [1., 1.]];
+        assert!(matches!(
+            consensus_fit(&observations, 0, 2, &mut rng),
+            Err(KMeansError::InvalidParams(KMeansParamsError::NRuns))
+        ));
+        assert!(matches!(
+            consensus_fit(&Array2::<f64>::zeros((0, 2)), 5, 2, &mut rng),
+            Err(KMeansError::EmptyObservations)
+        ));
+    }
+}
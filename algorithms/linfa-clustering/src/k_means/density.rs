@@ -0,0 +1,154 @@
+use linfa::Float;
+use linfa_nn::distance::Distance;
+use linfa_nn::{CommonNearestNeighbour, NearestNeighbour};
+use ndarray::{Array1, ArrayView2, Zip};
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+/// Weighs each observation by an estimate of the density of the data around it during
+/// [`fit`](crate::KMeans), so that dense or sparse regions get more influence on the centroids.
+///
+/// The local density around an observation is estimated as the inverse of the distance to its
+/// `k`-th nearest neighbour (excluding itself), using the distance metric of K-means. A small `k`
+/// follows the data closely but is sensitive to noise, while a larger `k` gives a smoother
+/// estimate at a higher cost. `k` must be at least 1, and is capped to the number of observations
+/// minus one.
+///
+/// The weights are scaled to average to 1, and weigh the observations in the update step and in
+/// the inertia.
+pub enum DensityWeighting {
+    /// The weight of an observation is proportional to its local density: centroids gravitate to
+    /// the dense cores of the clusters and outliers have little influence.
+    Favor { k: usize },
+    /// The weight of an observation is inversely proportional to its local density: centroids
+    /// spread into the sparse regions of the data.
+    Penalize { k: usize },
+}
+
+impl DensityWeighting {
+    /// Number of neighbours of the density estimate
+    pub fn k(&self) -> usize {
+        match *self {
+            Self::Favor { k } | Self::Penalize { k } => k,
+        }
+    }
+
+    /// Computes the weight of each observation, averaging to 1
+    pub(crate) fn sample_weights<F: Float, D: Distance<F>>(
+        &self,
+        dist_fn: &D,
+        observations: ArrayView2<F>,
+    ) -> Array1<F> {
+        let n_samples = observations.nrows();
+        let k = self.k().min(n_samples.saturating_sub(1));
+        // The tree can only be built from and queried with contiguous points
+        let observations = observations.as_standard_layout();
+        let index = match CommonNearestNeighbour::KdTree.from_batch(&observations, dist_fn.clone())
+        {
+            Ok(index) if k > 0 => index,
+            // Without features or neighbours, all observations are alike
+            _ => return Array1::ones(n_samples),
+        };
+
+        // Distance of each observation to its k-th nearest neighbour
+        let mut radii = Array1::zeros(n_samples);
+        Zip::from(observations.rows())
+            .and(&mut radii)
+            .par_for_each(|observation, radius| {
+                // The closest point is the observation itself
+                let neighbours = index
+                    .k_nearest(observation, k + 1)
+                    .expect("the observation comes from the indexed batch");
+                let (neighbour, _) = neighbours.last().expect("the batch is not empty");
+                *radius = dist_fn.distance(observation, neighbour.view());
+            });
+
+        // Observations with duplicates have a null radius, treat them like the densest other ones
+        let min_radius = radii
+            .iter()
+            .filter(|&&r| r > F::zero())
+            .fold(F::infinity(), |min, &r| min.min(r));
+        if min_radius.is_infinite() {
+            return Array1::ones(n_samples);
+        }
+        let mut weights = radii.mapv(|r| {
+            let r = r.max(min_radius);
+            match self {
+                Self::Favor { .. } => F::one() / r,
+                Self::Penalize { .. } => r,
+            }
+        });
+        let mean = weights.sum() / F::cast(n_samples);
+        weights /= mean;
+        weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KMeans;
+    use approx::assert_abs_diff_eq;
+    use linfa::prelude::*;
+    use linfa_nn::distance::L2Dist;
+    use ndarray::array;
+
+    #[test]
+    fn sample_weights() {
+        // A dense group of 3 points and an outlier
+        let observations = array![[0f64, 0.], [0., 1.], [1., 0.], [10., 10.]];
+        let favor = DensityWeighting::Favor { k: 1 }.sample_weights(&L2Dist, observations.view());
+        assert_abs_diff_eq!(favor.sum(), 4., epsilon = 1e-10);
+        assert_abs_diff_eq!(favor[0], favor[1], epsilon = 1e-10);
+        assert!(favor[3] < favor[0] / 5.);
+
+        let penalize =
+            DensityWeighting::Penalize { k: 1 }.sample_weights(&L2Dist, observations.view());
+        assert_abs_diff_eq!(penalize.sum(), 4., epsilon = 1e-10);
+        assert!(penalize[3] > penalize[0] * 5.);
+
+        // k is capped to the number of other observations
+        let capped = DensityWeighting::Favor { k: 10 }.sample_weights(&L2Dist, observations.view());
+        assert!(capped.iter().all(|w| w.is_finite()));
+        // Duplicates and single observations
+        let duplicates = array![[1f64, 1.], [1., 1.], [2., 1.]];
+        let weights = DensityWeighting::Favor { k: 1 }.sample_weights(&L2Dist, duplicates.view());
+        assert!(weights.iter().all(|w| w.is_finite()));
+        let single = array![[1., 1.]];
+        let weights = DensityWeighting::Favor { k: 3 }.sample_weights(&L2Dist, single.view());
+        assert_eq!(weights, array![1.]);
+    }
+    #[test]
+    fn column_major_observations() {
+        let observations = array![
+            [0f64, 0.],
+            [0., 1.],
+            [1., 0.],
+            [10., 10.],
+            [10., 11.],
+            [5., 5.]
+        ];
+        let fortran = observations
+            .t()
+            .as_standard_layout()
+            .into_owned()
+            .reversed_axes();
+        let weighting = DensityWeighting::Favor { k: 2 };
+        assert_eq!(
+            weighting.sample_weights(&L2Dist, fortran.view()),
+            weighting.sample_weights(&L2Dist, observations.view())
+        );
+
+        let params = KMeans::params(2).density_weighting(Some(weighting));
+        let expected = params.fit(&DatasetBase::from(observations)).unwrap();
+        let model = params.fit(&DatasetBase::from(fortran)).unwrap();
+        assert_eq!(model.centroids(), expected.centroids());
+    }
+}
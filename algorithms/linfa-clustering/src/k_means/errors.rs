@@ -1,3 +1,4 @@
+use crate::k_means::MAX_WARD_SUBSAMPLE_SIZE;
 use thiserror::Error;
 
 /// An error when fitting with an invalid hyperparameter
@@ -9,8 +10,46 @@ pub enum KMeansParamsError {
     NRuns,
     #[error("tolerance must be greater than 0")]
     Tolerance,
+    #[error("end of the annealed shift must be greater than 0 and at most start")]
+    AnnealedShift,
     #[error("max_n_iterations cannot be 0")]
     MaxIterations,
+    #[error("subsample_size of the Ward initialization must be between n_clusters and {MAX_WARD_SUBSAMPLE_SIZE}")]
+    WardSubsampleSize,
+    #[error("beta of the feature weighting must be greater than 1")]
+    FeatureWeightingBeta,
+    #[error("max_cluster_fraction must be between 0 (excluded) and 1")]
+    MaxClusterFraction,
+    #[error("k of the density weighting cannot be 0")]
+    DensityWeightingK,
+    #[error("shrinkage must be between 0 and 1")]
+    Shrinkage,
+    #[error("trim of the trimmed mean centroids must be between 0 and 0.5 (excluded)")]
+    TrimmedMeanTrim,
+    #[error("trimmed mean centroids cannot be combined with density or sample weights")]
+    TrimmedMeanWeights,
+    #[error("median centroids cannot be combined with density or sample weights")]
+    MedianWeights,
+    #[error("the strategies of a fallback initialization cannot be empty")]
+    EmptyFallback,
+    #[error("the maximum number of frames of the trajectory cannot be 0")]
+    TrajectoryFrames,
+    #[error("batch_size of Mini-Batch K-means cannot be 0")]
+    BatchSize,
+    #[error("the precomputed centroids must have n_clusters rows")]
+    PrecomputedCentroids,
+    #[error("divergence_patience cannot be 0")]
+    DivergencePatience,
+    #[error("assignment_chunk_size cannot be 0")]
+    AssignmentChunkSize,
+    #[error("cluster priors must be n_clusters positive values summing to 1, with a non-negative variance")]
+    ClusterPriors,
+    #[error("Elkan's algorithm cannot be combined with cluster priors")]
+    ElkanPriors,
+    #[error("max_cluster_size must be positive and at least min_cluster_size")]
+    ClusterSizes,
+    #[error("cluster size bounds cannot be combined with Elkan's algorithm or cluster priors")]
+    ClusterSizesCombination,
 }
 
 /// An error when modeling a KMeans algorithm
@@ -22,10 +61,131 @@ pub enum KMeansError {
     /// When inertia computation fails
     #[error("Fitting failed: No inertia improvement (-inf)")]
     InertiaError,
+    /// When fitting on a dataset without any observation
+    #[error("Fitting failed: No observations")]
+    EmptyObservations,
+    /// When fitting on fewer observations than clusters
+    #[error("Fitting failed: {n_observations} observations for {n_clusters} clusters")]
+    NotEnoughObservations {
+        n_observations: usize,
+        n_clusters: usize,
+    },
+    /// When fitting on observations without any feature
+    #[error("Fitting failed: the observations have no features")]
+    NoFeatures,
+    /// When the sample weights of a weighted fit don't have one weight per observation
+    #[error("Invalid sample weights: expected {expected} weights, got {actual}")]
+    SampleWeightsLength { expected: usize, actual: usize },
+    /// When a sample weight is negative or not finite, or all the sample weights are zero
+    #[error("Invalid sample weights: weights must be finite, non-negative and not all zero")]
+    InvalidSampleWeights,
+    /// When referring to a cluster the model doesn't have
+    #[error("Invalid cluster index {index}, the model has {n_clusters} clusters")]
+    InvalidClusterIndex { index: usize, n_clusters: usize },
+    /// When two models compared cluster by cluster don't have the same number of clusters
+    #[error("Invalid model: expected {expected} clusters, got {actual}")]
+    ClusterCountMismatch { expected: usize, actual: usize },
+    /// When the observations don't have the number of features the model was trained on
+    #[error("Invalid observations: the model expects {expected} features, got {actual}")]
+    FeatureMismatch { expected: usize, actual: usize },
+    /// When an observation has a NaN or infinite feature
+    #[error("Invalid observations: observation {index} has a non-finite value")]
+    NonFiniteObservation { index: usize },
+    /// When an observation lies outside the domain of the distance metric, such as an observation
+    /// that isn't a probability distribution with `JensenShannonDist`
+    #[error(
+        "Invalid observations: observation {index} is not a valid point of the distance metric"
+    )]
+    InvalidObservation { index: usize },
+    /// When the precomputed initial centroids don't have the number of features of the
+    /// observations
+    #[error("Invalid initialization: the observations have {expected} features, the precomputed centroids {actual}")]
+    InitFeatureMismatch { expected: usize, actual: usize },
+    /// When a cluster of the fitted model holds more than `max_cluster_fraction` of the
+    /// observations
+    #[error("Fitting failed: a cluster holds a fraction {fraction} of the observations")]
+    DegenerateClustering { fraction: f64 },
+    /// When a cluster of the fitted model has no observation, with
+    /// [`EmptyClusters::Error`](crate::EmptyClusters::Error)
+    #[error("Fitting failed: cluster {index} has no observation")]
+    EmptyCluster { index: usize },
+    /// When none of the strategies of a fallback initialization produced distinct centroids
+    #[error("Initialization failed: no strategy produced distinct centroids")]
+    DegenerateInit,
+    /// When a group of a grouped fit is not allowed any cluster
+    #[error("Invalid groups: a group maps to no cluster")]
+    EmptyGroup,
+    /// When an observation of a grouped fit belongs to a group missing from the group mapping
+    #[error("Invalid groups: the group of observation {index} maps to no cluster")]
+    UnmappedGroup { index: usize },
+    /// When the observations cannot be split into `n_clusters` clusters within the bounds of
+    /// `min_cluster_size` and `max_cluster_size`
+    #[error("Fitting failed: {n_observations} observations cannot form {n_clusters} clusters of {min} to {max} observations")]
+    InfeasibleClusterSizes {
+        n_observations: usize,
+        n_clusters: usize,
+        min: usize,
+        max: usize,
+    },
+    /// When the radius of a cluster is NaN, as happens when the model has non-finite centroids
+    #[error("Invalid model: cluster {index} has a NaN radius")]
+    NonFiniteRadius { index: usize },
+    /// When an operation would leave the model without any cluster
+    #[error("The model must keep at least one cluster")]
+    NoClusterLeft,
     #[error(transparent)]
     LinfaError(#[from] linfa::error::Error),
 }
 
+/// An error when fitting with [`fit_with_callback`](crate::KMeansValidParams::fit_with_callback)
+#[derive(Error, Debug)]
+pub enum AbortableKMeansError<M: std::fmt::Debug> {
+    /// When the fit itself fails
+    #[error(transparent)]
+    KMeansError(#[from] KMeansError),
+    /// When the callback aborted the fit, along with the best model found so far
+    #[error("Fitting aborted by the callback")]
+    Aborted { partial: Box<M> },
+}
+
+/// An error when saving or loading a model with [`KMeans::save`](crate::KMeans::save) and
+/// [`KMeans::load`](crate::KMeans::load)
+#[cfg(feature = "serde")]
+#[derive(Error, Debug)]
+pub enum PersistenceError {
+    /// When the file cannot be read or written
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// When the JSON file is malformed or doesn't describe a model
+    #[error("Invalid JSON model file: {0}")]
+    Json(#[from] serde_json::Error),
+    /// When the bincode file is malformed or doesn't describe a model
+    #[error("Invalid bincode model file: {0}")]
+    Bincode(#[from] bincode::Error),
+    /// When the file has no header, such as a model serialized with serde directly
+    #[error("Not a saved model: the file has no header")]
+    MissingHeader,
+    /// When the header describes another kind of model
+    #[error("Not a saved K-means model: the schema is {0:?}")]
+    UnknownSchema(String),
+    /// When the file was saved with a format version this version of the crate cannot read
+    #[error("Unsupported format version {found}, expected 1 to {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+    /// When the centroids don't have the shape `(n_clusters, n_features)` recorded in the header
+    #[error("The centroids have shape {actual:?} but the header declares {expected:?}")]
+    ShapeMismatch {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+    /// When an array of the model doesn't have one value per cluster or per feature
+    #[error("The {field} of the model have {actual} values instead of {expected}")]
+    LengthMismatch {
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+}
+
 #[derive(Error, Debug)]
 pub enum IncrKMeansError<M: std::fmt::Debug> {
     /// When any of the hyperparameters are set the wrong value
@@ -35,6 +195,15 @@ pub enum IncrKMeansError<M: std::fmt::Debug> {
     /// actual error, just there to signal that the algorithm should keep running.
     #[error("Algorithm has not yet converged, Keep on running the algorithm.")]
     NotConverged(M),
+    /// When initializing the model from a batch without any observation
+    #[error("Fitting failed: No observations")]
+    EmptyObservations,
+    /// When none of the strategies of a fallback initialization produced distinct centroids
+    #[error("Initialization failed: no strategy produced distinct centroids")]
+    DegenerateInit,
+    /// When the precomputed initial centroids don't have the number of features of the batch
+    #[error("Invalid initialization: the observations have {expected} features, the precomputed centroids {actual}")]
+    InitFeatureMismatch { expected: usize, actual: usize },
     #[error(transparent)]
     LinfaError(#[from] linfa::error::Error),
 }
@@ -1,13 +1,318 @@
 use crate::KMeansParamsError;
 
-use super::init::KMeansInit;
+use super::density::DensityWeighting;
+use super::init::{KMeansInit, MAX_WARD_SUBSAMPLE_SIZE};
+use super::mini_batch::MiniBatchSampling;
 use linfa::prelude::*;
 use linfa::Float;
 use linfa_nn::distance::Distance;
-use ndarray_rand::rand::Rng;
+use ndarray::Array1;
+use ndarray_rand::rand::{Rng, SeedableRng};
 #[cfg(feature = "serde")]
 use serde_crate::{Deserialize, Serialize};
 
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+/// Variant of the K-means loop run by [`fit`](crate::KMeans)
+pub enum KMeansAlgorithm<F: Float> {
+    /// m_k-means: the standard algorithm, with the modified update step described in
+    /// [`KMeans`](crate::KMeans)
+    Lloyd,
+    /// Automated feature weighting K-means
+    /// ([W-k-means](https://doi.org/10.1109/TPAMI.2005.95)).
+    ///
+    /// Each feature `j` is given a weight `w_j`, and the assignment step compares observations to
+    /// centroids after scaling feature `j` by `w_j^(beta / 2)`, so that the squared euclidean
+    /// distance becomes `sum_j w_j^beta * (x_j - c_j)^2`. After each update step, the weights are
+    /// recomputed as `w_j ∝ D_j^(1 / (1 - beta))`, where `D_j` is the sum over all observations of
+    /// the squared difference between feature `j` of the observation and of its centroid. Features
+    /// that don't separate clusters have a large dispersion and end up with a small weight, while
+    /// constant features get a weight of 0. The weights sum to 1.
+    ///
+    /// `beta` must be greater than 1. The larger it is, the more uniform the weights, while values
+    /// close to 1 tend to put all the weight on a single feature.
+    WeightedFeatures { beta: F },
+    /// [Elkan's algorithm](https://cdn.aaai.org/ICML/2003/ICML03-022.pdf): the same loop as
+    /// [`Lloyd`](KMeansAlgorithm::Lloyd), whose assignment step skips the distances that the
+    /// triangle inequality proves unneeded.
+    ///
+    /// Each observation keeps a lower bound on its distance to each centroid, which shrinks by
+    /// the distance the centroid moved at each iteration, and the distance to a centroid is only
+    /// computed when its bound, or half the distance between that centroid and the current one,
+    /// is smaller than the distance to the current centroid. Once the clusters settle, most
+    /// distances are skipped, which pays off with many clusters and features. The bounds take
+    /// `n_observations * n_clusters` values.
+    ///
+    /// The memberships are those of `Lloyd` up to rounding errors, as long as the distance
+    /// metric satisfies the triangle inequality, like `L2Dist`, `L1Dist` and `LInfDist`. Elkan's
+    /// algorithm can't be combined with cluster priors, and ignores `assignment_chunk_size`.
+    Elkan,
+    /// The same loop as [`Lloyd`](KMeansAlgorithm::Lloyd), whose assignment step computes the
+    /// distances between blocks of observations and all the centroids at once with
+    /// [`Distance::rdistance_matrix`], like `predict`.
+    ///
+    /// With `L2Dist`, the squared distances of a block are `|x|² + |c|² - 2 x·c` for all pairs,
+    /// computed with a single matrix product, which is BLAS-backed with the `blas` feature of
+    /// `ndarray`. This is much faster than comparing observations to centroids one by one with
+    /// many features and clusters, at the cost of rounding errors that can flip the membership of
+    /// observations almost equidistant from two centroids. Other distance metrics compute the
+    /// same distances as `Lloyd`. Cluster priors fall back to the assignment step of `Lloyd`, and
+    /// `assignment_chunk_size` is ignored.
+    Full,
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// Prior proportions of the clusters, biasing the assignment step of [`fit`](crate::KMeans)
+/// towards the clusters expected to be large, see
+/// [`KMeansParams::cluster_priors`](crate::KMeansParams::cluster_priors).
+///
+/// Each observation `x` is assigned to the centroid `c_j` minimizing
+/// `rdistance(x, c_j) - 2 * variance * ln(priors[j])`. With `L2Dist`, whose `rdistance` is the
+/// squared euclidean distance, this is the most likely component of a mixture of isotropic
+/// Gaussians with mixing proportions `priors` and a shared variance `variance` per feature, so
+/// the assignment of a cluster with a higher prior reaches further into its neighbours. A
+/// `variance` of 0 is plain K-means.
+///
+/// A good `variance` is the spread of the observations around their centroids, which a first
+/// fit without priors estimates as `inertia() / n_features`.
+pub struct ClusterPriors<F: Float> {
+    /// Prior proportion of each cluster, positive and summing to 1
+    pub priors: Array1<F>,
+    /// Shared variance of the clusters, per feature
+    pub variance: F,
+}
+
+// Largest difference between 1 and the sum of the cluster priors
+const PRIORS_SUM_TOLERANCE: f64 = 1e-6;
+
+impl<F: Float> ClusterPriors<F> {
+    // Whether there is a positive prior per cluster, summing to 1, and a non-negative variance
+    fn is_valid(&self, n_clusters: usize) -> bool {
+        self.priors.len() == n_clusters
+            && self.priors.iter().all(|&prior| prior > F::zero())
+            && (self.priors.sum() - F::one()).abs() <= F::cast(PRIORS_SUM_TOLERANCE)
+            && self.variance >= F::zero()
+            && self.variance.is_finite()
+    }
+
+    // Penalty added to the rdistance to each centroid in the assignment step
+    pub(crate) fn penalties(&self) -> Array1<F> {
+        self.priors
+            .mapv(|prior| -F::cast(2.) * self.variance * prior.ln())
+    }
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+/// How the update step of [`fit`](crate::KMeans) computes each centroid from the members of its
+/// cluster
+pub enum CentroidUpdate<F: Float> {
+    /// The mean of the members, along with the previous centroid (m_k-means). The mean minimizes
+    /// the sum of squared euclidean distances to the members, so it is only optimal with
+    /// [`L2Dist`](linfa_nn::distance::L2Dist), or [`LpDist`](linfa_nn::distance::LpDist) with
+    /// `p = 2`. Other distances still converge, but to centroids that don't minimize their
+    /// distance to the members.
+    Mean,
+    /// Per-feature trimmed mean: for each feature, the values of the members and of the previous
+    /// centroid are sorted, the `trim` fraction of the lowest and of the highest values are
+    /// dropped, and the remaining values are averaged. Unlike trimmed K-means, which drops whole
+    /// observations, this is robust to outliers in single features.
+    ///
+    /// `trim` must lie between 0 and 0.5 (excluded). Each value of a cluster is sorted once per
+    /// update step, which takes `O(n_observations * n_features)` extra memory and
+    /// `O(n log n)` time per cluster and feature, against a single pass for the mean. Cannot be
+    /// combined with a [`DensityWeighting`].
+    TrimmedMean { trim: F },
+    /// Per-feature median of the members and of the previous centroid, the middle two values
+    /// being averaged for an even count. The median minimizes the sum of manhattan distances to
+    /// the members (K-medians), which makes it the optimal update for
+    /// [`L1Dist`](linfa_nn::distance::L1Dist), or [`LpDist`](linfa_nn::distance::LpDist) with
+    /// `p = 1`.
+    ///
+    /// Has the cost of a [`TrimmedMean`](Self::TrimmedMean), and cannot be combined with a
+    /// [`DensityWeighting`] either.
+    Median,
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+/// Convergence criterion of the K-means loop of [`fit`](crate::KMeans), which stops once the
+/// centroids move by less than a tolerance between two iterations
+pub enum Convergence<F: Float> {
+    /// The tolerance is the `tolerance` hyperparameter at every iteration
+    Shift,
+    /// The tolerance decays exponentially from `start` at the first iteration to `end` at the
+    /// last one allowed by `max_n_iterations`: iteration `i` (from 0) uses
+    /// `start * (end / start)^(i / (max_n_iterations - 1))`. Early iterations stop on rough
+    /// centroids, and the loop only goes on refining them while they still move by more than the
+    /// current tolerance, so `end` is the final accuracy target.
+    ///
+    /// `start` must be greater or equal than `end`, which must be greater than 0. The
+    /// `tolerance` hyperparameter is ignored.
+    AnnealedShift { start: F, end: F },
+}
+
+impl<F: Float> Convergence<F> {
+    // Tolerance at iteration `iteration`, counted from 0, see `KMeansValidParams::tolerance_at`
+    pub(crate) fn tolerance_at(&self, tolerance: F, max_n_iterations: u64, iteration: u64) -> F {
+        match *self {
+            Convergence::Shift => tolerance,
+            Convergence::AnnealedShift { start, end } => {
+                if max_n_iterations <= 1 {
+                    return end;
+                }
+                let progress =
+                    F::cast(iteration.min(max_n_iterations - 1)) / F::cast(max_n_iterations - 1);
+                start * (end / start).powf(progress)
+            }
+        }
+    }
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// How the mean update step of [`fit`](crate::KMeans) adds up the members of each cluster. The
+/// rounding error of a sum grows with the number of terms, so on clusters of millions of
+/// observations, or `f32` records, the naive sum can shift centroids noticeably.
+///
+/// The update step is cheap next to the assignment step, so the accurate strategies only slow
+/// `fit` down a little: on a single cluster of a million `f32` observations with 4 features
+/// around 1000, the `k_means_accumulation` benchmark measures a centroid error of about 9 for
+/// `Naive` against about 5e-5 (the precision of `f32`) for the others, for a fit about 5% slower
+/// with `Kahan` and 15% slower with `Pairwise`.
+///
+/// Only applies to [`CentroidUpdate::Mean`], with or without a [`DensityWeighting`], and not to
+/// [`fit_with`](linfa::traits::FitWith::fit_with), which averages small batches.
+pub enum Accumulation {
+    /// Adds up the members in order of the observations. The fastest strategy, in a single pass,
+    /// but its error grows linearly with the size of the cluster.
+    #[default]
+    Naive,
+    /// Kahan compensated summation: a running compensation per cluster and feature recovers the
+    /// low-order bits lost by each addition, which makes the error independent of the size of the
+    /// cluster. Still a single sequential pass, with four times the floating point operations of
+    /// the naive sum and an extra copy of the centroids.
+    Kahan,
+    /// Pairwise summation: the members of each cluster are split into blocks which are summed in
+    /// parallel, and the block sums are added up as a balanced tree. The error only grows
+    /// logarithmically with the size of the cluster, and the result doesn't depend on the number
+    /// of threads. Gathering the members of each cluster and allocating the block sums outweigh
+    /// the parallel sums in the benchmark, which makes it the slowest strategy there.
+    Pairwise,
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// How [`fit`](crate::KMeans) spreads its work over the threads of the current `rayon` thread
+/// pool. Both strategies give the same model: the initial centroids of all runs are drawn in the
+/// same order from the random number generator, and ties between runs go to the first one.
+///
+/// The assignment step of a run splits the observations between threads, which pays off on large
+/// datasets but leaves threads waiting on every step of a small one. Many restarts on a small
+/// dataset are better run concurrently, at the cost of holding the memberships and distances of
+/// one run per thread at once. Since the restarts run as tasks of the same pool as the assignment
+/// steps, nested parallelism never starts more threads than the pool has: a thread idle after
+/// its last restart helps with the assignment steps of the others. The `k_means_parallelism`
+/// benchmark compares both strategies on a small and a large dataset.
+///
+/// Only applies to the full-batch [`Fit::fit`](linfa::traits::Fit::fit).
+/// [`fit_with_callback`](crate::KMeansValidParams::fit_with_callback) calls its callback from the
+/// calling thread, and so always runs the restarts one after the other.
+pub enum Parallelism {
+    /// `Restarts` when there are at least as many runs as threads in the pool and at most
+    /// [`AUTO_RESTARTS_MAX_OBSERVATIONS`] observations, `Observations` otherwise
+    #[default]
+    Auto,
+    /// The `n_runs` restarts run concurrently
+    Restarts,
+    /// The restarts run one after the other, and each assignment step splits the observations
+    /// between threads
+    Observations,
+}
+
+/// Largest number of observations on which [`Parallelism::Auto`] runs restarts concurrently
+pub const AUTO_RESTARTS_MAX_OBSERVATIONS: usize = 10_000;
+
+impl Parallelism {
+    // Whether `fit` runs `n_runs` restarts on `n_samples` observations concurrently
+    pub(crate) fn parallel_restarts(&self, n_runs: usize, n_samples: usize) -> bool {
+        match self {
+            Parallelism::Auto => {
+                n_runs > 1
+                    && n_runs >= rayon::current_num_threads()
+                    && n_samples <= AUTO_RESTARTS_MAX_OBSERVATIONS
+            }
+            Parallelism::Restarts => n_runs > 1,
+            Parallelism::Observations => false,
+        }
+    }
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// What the update step of [`fit`](crate::KMeans) does with a cluster the assignment step left
+/// without any observation.
+///
+/// The m_k-means update of `fit` counts the previous centroid as a member of its cluster, so an
+/// empty cluster keeps its centroid where it was instead of becoming undefined. That centroid
+/// still has a chance to win observations back as the others move, but one stranded far from
+/// the data stays empty for good, and the model effectively has one cluster less. The other
+/// strategies move such a centroid onto an observation, which then joins its cluster at the next
+/// assignment step. Each observation reseeds at most one cluster per update step.
+///
+/// Only applies to the full-batch [`Fit::fit`](linfa::traits::Fit::fit), and not to the
+/// mini-batch fits.
+pub enum EmptyClusters {
+    /// The centroid of an empty cluster stays where it was
+    #[default]
+    Keep,
+    /// The centroid of an empty cluster moves onto the observation farthest from its centroid,
+    /// the one the current model explains worst
+    FarthestPoint,
+    /// The centroid of an empty cluster moves onto the member of the largest cluster farthest
+    /// from its centroid, splitting that cluster in two
+    SplitLargest,
+    /// `fit` fails with [`KMeansError::EmptyCluster`](crate::KMeansError::EmptyCluster) if a
+    /// cluster of the fitted model has no observation. Empty clusters are kept as with `Keep`
+    /// until then, since they may fill up again before the end of the fit.
+    Error,
+}
+
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -31,6 +336,50 @@ pub struct KMeansValidParams<F: Float, R: Rng, D: Distance<F>> {
     n_clusters: usize,
     /// The initialization strategy used to initialize the centroids.
     init: KMeansInit<F>,
+    /// The variant of the K-means loop
+    algorithm: KMeansAlgorithm<F>,
+    /// Largest fraction of the observations a single cluster may hold
+    max_cluster_fraction: Option<F>,
+    /// Whether `fit` must give the same results on every platform and for any number of threads
+    cross_platform_deterministic: bool,
+    /// Weighting of the observations by their local density
+    density_weighting: Option<DensityWeighting>,
+    /// Factor by which updated centroids are pulled towards the mean of the observations
+    shrinkage: F,
+    /// How centroids are computed from the members of their cluster
+    centroid_update: CentroidUpdate<F>,
+    /// Memory budget above which `fit` switches to Mini-Batch K-means
+    memory_budget_bytes: Option<usize>,
+    /// Convergence criterion of the K-means loop
+    convergence: Convergence<F>,
+    /// How the mean update step adds up the members of each cluster
+    accumulation: Accumulation,
+    /// Whether `fit` collapses identical observations into weighted unique ones
+    deduplicate: bool,
+    /// Maximum number of frames of the trajectory recorded by `fit`, if any
+    record_trajectory: Option<usize>,
+    /// Whether `fit` moves the final centroids onto their closest observations
+    snap_to_data: bool,
+    /// How `fit` spreads its work over threads
+    parallelism: Parallelism,
+    /// Number of consecutive rises of the inertia after which `fit` stops a run, if any
+    divergence_patience: Option<usize>,
+    /// Whether `fit` also stops a run once its assignments stop changing
+    stop_on_stable_assignments: bool,
+    /// Number of consecutive observations assigned by each task of the assignment step of `fit`
+    assignment_chunk_size: Option<usize>,
+    /// Prior proportions of the clusters in the assignment step of `fit`, if any
+    cluster_priors: Option<ClusterPriors<F>>,
+    /// How the mini-batch fits draw their batches
+    mini_batch_sampling: MiniBatchSampling,
+    /// What `fit` does with clusters left without any observation
+    empty_clusters: EmptyClusters,
+    /// Smallest number of observations the assignment step of `fit` gives each cluster, if any
+    min_cluster_size: Option<usize>,
+    /// Largest number of observations the assignment step of `fit` gives each cluster, if any
+    max_cluster_size: Option<usize>,
+    /// The seed the random number generator was built from, if set with `random_seed`
+    random_seed: Option<u64>,
     /// The random number generator
     rng: R,
     /// Distance metric used in the centroid assignment step
@@ -61,6 +410,28 @@ impl<F: Float, R: Rng, D: Distance<F>> KMeansParams<F, R, D> {
     /// * `max_n_iterations = 300`
     /// * `n_runs = 10`
     /// * `init = KMeansPlusPlus`
+    /// * `algorithm = Lloyd`
+    /// * `max_cluster_fraction = None`
+    /// * `cross_platform_deterministic = false`
+    /// * `density_weighting = None`
+    /// * `shrinkage = 0`
+    /// * `centroid_update = Mean`
+    /// * `memory_budget_bytes = None`
+    /// * `convergence = Shift`
+    /// * `accumulation = Naive`
+    /// * `deduplicate = false`
+    /// * `record_trajectory = None`
+    /// * `snap_to_data = false`
+    /// * `parallelism = Auto`
+    /// * `divergence_patience = None`
+    /// * `stop_on_stable_assignments = false`
+    /// * `assignment_chunk_size = None`
+    /// * `cluster_priors = None`
+    /// * `mini_batch_sampling = EpochShuffle`
+    /// * `empty_clusters = Keep`
+    /// * `min_cluster_size = None`
+    /// * `max_cluster_size = None`
+    /// * `random_seed = None`
     pub fn new(n_clusters: usize, rng: R, dist_fn: D) -> Self {
         Self(KMeansValidParams {
             n_runs: 10,
@@ -68,11 +439,39 @@ impl<F: Float, R: Rng, D: Distance<F>> KMeansParams<F, R, D> {
             max_n_iterations: 300,
             n_clusters,
             init: KMeansInit::KMeansPlusPlus,
+            algorithm: KMeansAlgorithm::Lloyd,
+            max_cluster_fraction: None,
+            cross_platform_deterministic: false,
+            density_weighting: None,
+            shrinkage: F::zero(),
+            centroid_update: CentroidUpdate::Mean,
+            memory_budget_bytes: None,
+            convergence: Convergence::Shift,
+            accumulation: Accumulation::Naive,
+            deduplicate: false,
+            record_trajectory: None,
+            snap_to_data: false,
+            parallelism: Parallelism::Auto,
+            divergence_patience: None,
+            stop_on_stable_assignments: false,
+            assignment_chunk_size: None,
+            cluster_priors: None,
+            mini_batch_sampling: MiniBatchSampling::EpochShuffle,
+            empty_clusters: EmptyClusters::Keep,
+            min_cluster_size: None,
+            max_cluster_size: None,
+            random_seed: None,
             rng,
             dist_fn,
         })
     }
 
+    /// Change the value of `n_clusters`
+    pub fn n_clusters(mut self, n_clusters: usize) -> Self {
+        self.0.n_clusters = n_clusters;
+        self
+    }
+
     /// Change the value of `n_runs`
     pub fn n_runs(mut self, n_runs: usize) -> Self {
         self.0.n_runs = n_runs;
@@ -96,6 +495,274 @@ impl<F: Float, R: Rng, D: Distance<F>> KMeansParams<F, R, D> {
         self.0.init = init;
         self
     }
+
+    /// Change the value of `algorithm`
+    pub fn algorithm(mut self, algorithm: KMeansAlgorithm<F>) -> Self {
+        self.0.algorithm = algorithm;
+        self
+    }
+
+    /// Change the value of `max_cluster_fraction`. When set, `fit` fails with
+    /// [`KMeansError::DegenerateClustering`](crate::KMeansError::DegenerateClustering) if a
+    /// cluster of the final model holds more than this fraction of the observations.
+    pub fn max_cluster_fraction(mut self, max_cluster_fraction: Option<F>) -> Self {
+        self.0.max_cluster_fraction = max_cluster_fraction;
+        self
+    }
+
+    /// Change the value of `cross_platform_deterministic`. When enabled, `fit` returns
+    /// bit-identical centroids and inertia for the same seed and data on every platform, whatever
+    /// the number of threads:
+    /// * the candidates of [`KMeansInit::KMeansPara`] are sampled sequentially from a single
+    ///   random generator, instead of one generator per thread;
+    /// * the inertia is summed sequentially, in the order of the observations.
+    ///
+    /// The other steps of `fit` only use additions, multiplications, divisions and square roots,
+    /// which are never fused into FMA instructions and are correctly rounded everywhere. The
+    /// guarantee doesn't cover steps relying on the platform's math library, namely
+    /// [`KMeansAlgorithm::WeightedFeatures`] and `LpDist`, nor the batched `predict` of the
+    /// fitted model, whose matrix product may use FMA instructions when the CPU supports them.
+    /// Predicting observations one at a time is exact.
+    ///
+    /// This makes `KMeansPara` slower on multi-core machines.
+    pub fn cross_platform_deterministic(mut self, cross_platform_deterministic: bool) -> Self {
+        self.0.cross_platform_deterministic = cross_platform_deterministic;
+        self
+    }
+
+    /// Change the value of `density_weighting`. When set, `fit` weighs each observation by an
+    /// estimate of the local density of the data, see [`DensityWeighting`].
+    pub fn density_weighting(mut self, density_weighting: Option<DensityWeighting>) -> Self {
+        self.0.density_weighting = density_weighting;
+        self
+    }
+
+    /// Change the value of `shrinkage`, which must lie between 0 and 1. After each update step of
+    /// `fit`, every centroid is pulled towards the mean of all observations as
+    /// `(1 - shrinkage) * centroid + shrinkage * mean`. This regularizes the centroids of small
+    /// clusters on noisy data, at the cost of biasing all centroids towards the center. A value
+    /// of 0 disables it.
+    pub fn shrinkage(mut self, shrinkage: F) -> Self {
+        self.0.shrinkage = shrinkage;
+        self
+    }
+
+    /// Change the value of `centroid_update`
+    pub fn centroid_update(mut self, centroid_update: CentroidUpdate<F>) -> Self {
+        self.0.centroid_update = centroid_update;
+        self
+    }
+
+    /// Change the value of `memory_budget_bytes`. When the estimated working set of `fit` exceeds
+    /// the budget, `fit` runs Mini-Batch K-means instead, with the largest batch size that fits
+    /// in the budget (but never less than `n_clusters` observations).
+    ///
+    /// The estimate is approximate: it counts the observations, one distance and one label per
+    /// observation, plus two copies of the centroids, that is
+    /// `(n_observations * (n_features + 1) + 2 * n_clusters * n_features) * size_of::<F>()`
+    /// `+ n_observations * size_of::<usize>()` bytes for a full-batch fit, with `n_observations`
+    /// replaced by the batch size for a mini-batch fit. Allocations of the initialization and of
+    /// the parallel assignment step are not accounted for.
+    ///
+    /// Like [`fit_with`](linfa::traits::FitWith::fit_with), the mini-batch fit uses the m_k-means
+    /// loop with the mean update step, and ignores `algorithm`, `density_weighting`, `shrinkage`,
+    /// `centroid_update` and `accumulation`. The batches are drawn according to
+    /// `mini_batch_sampling` for up to `max_n_iterations` epochs, until a batch moves the centroids by less than `tolerance`.
+    /// [`fit_mini_batch`](KMeansValidParams::fit_mini_batch) runs the same fit with an explicit
+    /// batch size.
+    pub fn memory_budget_bytes(mut self, memory_budget_bytes: Option<usize>) -> Self {
+        self.0.memory_budget_bytes = memory_budget_bytes;
+        self
+    }
+
+    /// Change the value of `convergence`. It applies to the loop of `fit`, and not to
+    /// `fit_with`, which compares each batch update to `tolerance`.
+    pub fn convergence(mut self, convergence: Convergence<F>) -> Self {
+        self.0.convergence = convergence;
+        self
+    }
+
+    /// Change the value of `accumulation`
+    pub fn accumulation(mut self, accumulation: Accumulation) -> Self {
+        self.0.accumulation = accumulation;
+        self
+    }
+
+    /// Change the value of `deduplicate`. When enabled, `fit` collapses identical observations
+    /// into unique ones, weighted by their number of occurrences (times their
+    /// [`DensityWeighting`] weights, if any), so that distances are computed once per unique
+    /// observation. This can speed up fits a lot on data with many duplicates, such as encoded
+    /// categorical data, at the cost of sorting the observations once.
+    ///
+    /// The K-means loop gives the same centroids as without deduplication, and the cluster
+    /// counts, inertia and distance statistics still account for every observation. The
+    /// initialization however sees each unique observation once, so random initializations
+    /// are drawn differently. Only applies to the [`Lloyd`](KMeansAlgorithm::Lloyd) algorithm
+    /// with [`CentroidUpdate::Mean`], and is ignored otherwise.
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.0.deduplicate = deduplicate;
+        self
+    }
+
+    /// Change the value of `record_trajectory`. With `Some(max_frames)`, `fit` records the
+    /// centroids and the cluster labels of the observations at each of the first `max_frames`
+    /// iterations of the best run, see [`KMeans::trajectory`](crate::KMeans::trajectory), for
+    /// example to animate the convergence frame by frame.
+    ///
+    /// Each frame takes `n_clusters * n_features * size_of::<F>()` bytes for the centroids and
+    /// `n_observations * size_of::<usize>()` bytes for the labels, and every run records its own
+    /// frames until it's compared with the best one, so the cap should be set with the size of
+    /// the dataset in mind. The mini-batch fit of `memory_budget_bytes` records no trajectory.
+    pub fn record_trajectory(mut self, max_frames: Option<usize>) -> Self {
+        self.0.record_trajectory = max_frames;
+        self
+    }
+
+    /// Change the value of `snap_to_data`. When enabled, `fit` moves each centroid of the best
+    /// run onto its closest observation once the K-means loop is over, so that the centroids of
+    /// the model are actual observations, as the medoids of K-medoids are, at the cost of K-means.
+    /// One more assignment step then recomputes the cluster counts, the inertia and the distance
+    /// statistics of the model against the snapped centroids.
+    ///
+    /// The snapped centroids no longer minimize the distances to the members of their clusters,
+    /// so the inertia is usually slightly higher than without snapping. Centroids sharing the
+    /// same closest observation are snapped onto the same point, which leaves all but one of
+    /// their clusters empty. The mini-batch fit of `memory_budget_bytes` doesn't snap.
+    pub fn snap_to_data(mut self, snap_to_data: bool) -> Self {
+        self.0.snap_to_data = snap_to_data;
+        self
+    }
+
+    /// Change the value of `parallelism`, see [`Parallelism`]
+    pub fn parallelism(mut self, parallelism: Parallelism) -> Self {
+        self.0.parallelism = parallelism;
+        self
+    }
+
+    /// Change the value of `divergence_patience`. With `Some(patience)`, a run of `fit` whose
+    /// inertia rises for `patience` consecutive iterations is stopped and falls back to the
+    /// centroids with the lowest inertia it has seen, with a
+    /// [`StopReason::Diverged`](crate::StopReason::Diverged) stop reason.
+    ///
+    /// The standard K-means loop never increases the inertia, but it can rise when the update
+    /// step doesn't minimize the distance metric, such as the mean update with `L1Dist` or
+    /// `CosineDist`, or with `shrinkage` and feature weighting. The guard keeps such a mismatch
+    /// from wandering off for `max_n_iterations`, at the cost of a copy of the centroids whenever
+    /// the inertia reaches a new low. The mini-batch fit of `memory_budget_bytes` ignores it.
+    pub fn divergence_patience(mut self, patience: Option<usize>) -> Self {
+        self.0.divergence_patience = patience;
+        self
+    }
+
+    /// Change the value of `stop_on_stable_assignments`. When enabled, a run of `fit` also stops
+    /// as soon as no observation changed cluster between two consecutive assignment steps, with
+    /// a [`StopReason::StableAssignments`](crate::StopReason::StableAssignments) stop reason, on
+    /// top of the `tolerance` on the shift of the centroids. This saves the iterations of
+    /// discrete data on which the centroids keep wiggling without changing any membership, for
+    /// the cost of comparing the memberships of each iteration to the previous ones.
+    ///
+    /// Stable memberships are the fixed point of Lloyd's algorithm, but the m_k-means update of
+    /// `fit` counts the previous centroid as a member of its cluster, so the centroids still
+    /// drift towards the means of their members after the memberships settle. Stopping on stable
+    /// assignments leaves the centroids short of these means, which is why it isn't the default.
+    pub fn stop_on_stable_assignments(mut self, stop_on_stable_assignments: bool) -> Self {
+        self.0.stop_on_stable_assignments = stop_on_stable_assignments;
+        self
+    }
+
+    /// Change the value of `assignment_chunk_size`. By default, the assignment step of `fit`
+    /// leaves the split of the observations between threads to `rayon`, which may schedule them
+    /// a few rows at a time. With `Some(chunk_size)`, each task instead assigns a block of
+    /// `chunk_size` consecutive observations, comparing each of them to all the centroids before
+    /// moving on to the next, which keeps the centroids in cache for the whole block when there
+    /// are many of them in high dimension.
+    ///
+    /// A good chunk size gives each thread several blocks for load balancing, so at most
+    /// `n_observations / (4 * n_threads)`, while being large enough for the scheduling cost to
+    /// vanish next to the `chunk_size * n_clusters * n_features` operations of a block, usually a
+    /// few hundred to a few thousand rows. The `k_means_assignment_chunk` benchmark compares a
+    /// few sizes on 100 clusters in 100 dimensions. The results don't depend on the chunk size,
+    /// except for the rounding of the inertia without `cross_platform_deterministic`. Ignored with
+    /// `cluster_priors`, and by the mini-batch fit of `memory_budget_bytes`.
+    pub fn assignment_chunk_size(mut self, chunk_size: Option<usize>) -> Self {
+        self.0.assignment_chunk_size = chunk_size;
+        self
+    }
+
+    /// Change the value of `cluster_priors`. When set, the assignment step of `fit` trades the
+    /// distance to the centroids off against the prior proportion of each cluster, so that
+    /// borderline observations go to the clusters expected to be larger and rare clusters keep
+    /// their share, see [`ClusterPriors`]. This turns the K-means loop into a hard-assignment
+    /// version of the EM algorithm of a Gaussian mixture with fixed proportions and a shared
+    /// variance.
+    ///
+    /// The priors must have `n_clusters` positive values summing to 1, and the variance must be
+    /// non-negative. Cluster `j` of the model is the one with prior `priors[j]` only with an
+    /// initialization that orders the clusters, such as [`KMeansInit::Precomputed`]. The priors
+    /// only apply to the fit: [`predict`](linfa::traits::Predict::predict) still assigns
+    /// observations to their closest centroid. The mini-batch fit of `memory_budget_bytes`
+    /// ignores them.
+    pub fn cluster_priors(mut self, cluster_priors: Option<ClusterPriors<F>>) -> Self {
+        self.0.cluster_priors = cluster_priors;
+        self
+    }
+
+    /// Change the value of `mini_batch_sampling`, how
+    /// [`fit_mini_batch`](KMeansValidParams::fit_mini_batch) and the mini-batch fit of
+    /// `memory_budget_bytes` draw their batches. [`MiniBatchSampling::WithReplacement`] draws
+    /// each batch independently, like scikit-learn's `MiniBatchKMeans`, while the default
+    /// `EpochShuffle` sees every observation once per epoch. Either way, the fit runs for up to
+    /// `max_n_iterations` epochs of `n_observations / batch_size` batches (rounded up).
+    pub fn mini_batch_sampling(mut self, mini_batch_sampling: MiniBatchSampling) -> Self {
+        self.0.mini_batch_sampling = mini_batch_sampling;
+        self
+    }
+
+    /// Change the value of `empty_clusters`, see [`EmptyClusters`]
+    pub fn empty_clusters(mut self, empty_clusters: EmptyClusters) -> Self {
+        self.0.empty_clusters = empty_clusters;
+        self
+    }
+
+    /// Change the value of `min_cluster_size`. When set, the assignment step of `fit` gives every
+    /// cluster at least this many observations, turning the K-means loop into a balanced
+    /// K-means: observations go to their closest centroid with room left, in increasing order of
+    /// distance, then the clusters still too small take the observations that cost the least to
+    /// move from the others. Sizes count observations, whatever their sample weights.
+    ///
+    /// `fit` fails with
+    /// [`KMeansError::InfeasibleClusterSizes`](crate::KMeansError::InfeasibleClusterSizes) if
+    /// there are fewer than `n_clusters * min_cluster_size` observations. The bounds only apply
+    /// to the fit: [`predict`](linfa::traits::Predict::predict) still assigns observations to
+    /// their closest centroid. They cannot be combined with Elkan's algorithm or cluster priors,
+    /// disable `deduplicate`, and the mini-batch fit of `memory_budget_bytes` ignores them.
+    pub fn min_cluster_size(mut self, min_cluster_size: Option<usize>) -> Self {
+        self.0.min_cluster_size = min_cluster_size;
+        self
+    }
+
+    /// Change the value of `max_cluster_size`. When set, the assignment step of `fit` gives no
+    /// cluster more than this many observations, and `fit` fails with
+    /// [`KMeansError::InfeasibleClusterSizes`](crate::KMeansError::InfeasibleClusterSizes) if
+    /// there are more than `n_clusters * max_cluster_size` observations. See
+    /// [`min_cluster_size`](Self::min_cluster_size) for how the bounded assignment works.
+    pub fn max_cluster_size(mut self, max_cluster_size: Option<usize>) -> Self {
+        self.0.max_cluster_size = max_cluster_size;
+        self
+    }
+}
+
+impl<F: Float, R: Rng + SeedableRng, D: Distance<F>> KMeansParams<F, R, D> {
+    /// Replace the random number generator with one seeded from `seed` with
+    /// [`SeedableRng::seed_from_u64`], so that fitting the same data with the same
+    /// hyperparameters gives the same model without building and passing a generator. The seed
+    /// is kept in the fitted model, see [`KMeans::random_seed`](crate::KMeans::random_seed), to
+    /// record how to reproduce it.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.0.rng = R::seed_from_u64(seed);
+        self.0.random_seed = Some(seed);
+        self
+    }
 }
 
 impl<F: Float, R: Rng, D: Distance<F>> ParamGuard for KMeansParams<F, R, D> {
@@ -109,8 +776,69 @@ impl<F: Float, R: Rng, D: Distance<F>> ParamGuard for KMeansParams<F, R, D> {
             Err(KMeansParamsError::NRuns)
         } else if self.0.tolerance <= F::zero() {
             Err(KMeansParamsError::Tolerance)
+        } else if matches!(
+            self.0.convergence,
+            Convergence::AnnealedShift { start, end } if !(end > F::zero() && start >= end)
+        ) {
+            Err(KMeansParamsError::AnnealedShift)
         } else if self.0.max_n_iterations == 0 {
             Err(KMeansParamsError::MaxIterations)
+        } else if let Err(err) = check_init(&self.0.init, self.0.n_clusters) {
+            Err(err)
+        } else if matches!(
+            self.0.algorithm,
+            KMeansAlgorithm::WeightedFeatures { beta } if beta <= F::one() || beta.is_nan()
+        ) {
+            Err(KMeansParamsError::FeatureWeightingBeta)
+        } else if matches!(
+            self.0.max_cluster_fraction,
+            Some(fraction) if !(fraction > F::zero() && fraction <= F::one())
+        ) {
+            Err(KMeansParamsError::MaxClusterFraction)
+        } else if matches!(&self.0.density_weighting, Some(weighting) if weighting.k() == 0) {
+            Err(KMeansParamsError::DensityWeightingK)
+        } else if !(self.0.shrinkage >= F::zero() && self.0.shrinkage <= F::one()) {
+            Err(KMeansParamsError::Shrinkage)
+        } else if matches!(
+            self.0.centroid_update,
+            CentroidUpdate::TrimmedMean { trim } if !(trim >= F::zero() && trim < F::cast(0.5))
+        ) {
+            Err(KMeansParamsError::TrimmedMeanTrim)
+        } else if matches!(self.0.centroid_update, CentroidUpdate::TrimmedMean { .. })
+            && self.0.density_weighting.is_some()
+        {
+            Err(KMeansParamsError::TrimmedMeanWeights)
+        } else if matches!(self.0.centroid_update, CentroidUpdate::Median)
+            && self.0.density_weighting.is_some()
+        {
+            Err(KMeansParamsError::MedianWeights)
+        } else if self.0.record_trajectory == Some(0) {
+            Err(KMeansParamsError::TrajectoryFrames)
+        } else if self.0.divergence_patience == Some(0) {
+            Err(KMeansParamsError::DivergencePatience)
+        } else if self.0.assignment_chunk_size == Some(0) {
+            Err(KMeansParamsError::AssignmentChunkSize)
+        } else if matches!(
+            &self.0.cluster_priors,
+            Some(priors) if !priors.is_valid(self.0.n_clusters)
+        ) {
+            Err(KMeansParamsError::ClusterPriors)
+        } else if matches!(self.0.algorithm, KMeansAlgorithm::Elkan)
+            && self.0.cluster_priors.is_some()
+        {
+            Err(KMeansParamsError::ElkanPriors)
+        } else if self.0.max_cluster_size == Some(0)
+            || matches!(
+                (self.0.min_cluster_size, self.0.max_cluster_size),
+                (Some(min), Some(max)) if min > max
+            )
+        {
+            Err(KMeansParamsError::ClusterSizes)
+        } else if (self.0.min_cluster_size.is_some() || self.0.max_cluster_size.is_some())
+            && (matches!(self.0.algorithm, KMeansAlgorithm::Elkan)
+                || self.0.cluster_priors.is_some())
+        {
+            Err(KMeansParamsError::ClusterSizesCombination)
         } else {
             Ok(&self.0)
         }
@@ -122,6 +850,27 @@ impl<F: Float, R: Rng, D: Distance<F>> ParamGuard for KMeansParams<F, R, D> {
     }
 }
 
+/// Checks the parameters of `init`, including the strategies of a fallback
+fn check_init<F: Float>(init: &KMeansInit<F>, n_clusters: usize) -> Result<(), KMeansParamsError> {
+    match init {
+        KMeansInit::AgglomerativeWard { subsample_size }
+            if *subsample_size < n_clusters || *subsample_size > MAX_WARD_SUBSAMPLE_SIZE =>
+        {
+            Err(KMeansParamsError::WardSubsampleSize)
+        }
+        KMeansInit::Precomputed(centroids) if centroids.nrows() != n_clusters => {
+            Err(KMeansParamsError::PrecomputedCentroids)
+        }
+        KMeansInit::Fallback(strategies) if strategies.is_empty() => {
+            Err(KMeansParamsError::EmptyFallback)
+        }
+        KMeansInit::Fallback(strategies) => strategies
+            .iter()
+            .try_for_each(|strategy| check_init(strategy, n_clusters)),
+        _ => Ok(()),
+    }
+}
+
 impl<F: Float, R: Rng, D: Distance<F>> KMeansValidParams<F, R, D> {
     /// The final results will be the best output of n_runs consecutive runs in terms of inertia.
     pub fn n_runs(&self) -> usize {
@@ -152,6 +901,156 @@ impl<F: Float, R: Rng, D: Distance<F>> KMeansValidParams<F, R, D> {
         &self.init
     }
 
+    /// Variant of the K-means loop
+    pub fn algorithm(&self) -> &KMeansAlgorithm<F> {
+        &self.algorithm
+    }
+
+    /// Largest fraction of the observations a single cluster may hold, if any
+    pub fn max_cluster_fraction(&self) -> Option<F> {
+        self.max_cluster_fraction
+    }
+
+    /// Whether `fit` gives the same results on every platform
+    pub fn cross_platform_deterministic(&self) -> bool {
+        self.cross_platform_deterministic
+    }
+
+    /// Weighting of the observations by their local density, if any
+    pub fn density_weighting(&self) -> Option<&DensityWeighting> {
+        self.density_weighting.as_ref()
+    }
+
+    /// Factor by which updated centroids are pulled towards the mean of the observations
+    pub fn shrinkage(&self) -> F {
+        self.shrinkage
+    }
+
+    /// How centroids are computed from the members of their cluster
+    pub fn centroid_update(&self) -> &CentroidUpdate<F> {
+        &self.centroid_update
+    }
+
+    /// Memory budget above which `fit` switches to Mini-Batch K-means, if any
+    pub fn memory_budget_bytes(&self) -> Option<usize> {
+        self.memory_budget_bytes
+    }
+
+    /// Convergence criterion of the K-means loop
+    pub fn convergence(&self) -> &Convergence<F> {
+        &self.convergence
+    }
+
+    /// How the mean update step adds up the members of each cluster
+    pub fn accumulation(&self) -> Accumulation {
+        self.accumulation
+    }
+
+    /// Whether `fit` collapses identical observations into weighted unique ones
+    pub fn deduplicate(&self) -> bool {
+        self.deduplicate
+    }
+
+    /// Maximum number of frames of the trajectory recorded by `fit`, if any
+    pub fn record_trajectory(&self) -> Option<usize> {
+        self.record_trajectory
+    }
+
+    /// Whether `fit` moves the final centroids onto their closest observations
+    pub fn snap_to_data(&self) -> bool {
+        self.snap_to_data
+    }
+
+    /// How `fit` spreads its work over threads
+    pub fn parallelism(&self) -> Parallelism {
+        self.parallelism
+    }
+
+    /// Number of consecutive rises of the inertia after which `fit` stops a run, if any
+    pub fn divergence_patience(&self) -> Option<usize> {
+        self.divergence_patience
+    }
+
+    /// Whether `fit` also stops a run once its assignments stop changing
+    pub fn stop_on_stable_assignments(&self) -> bool {
+        self.stop_on_stable_assignments
+    }
+
+    /// Number of consecutive observations assigned by each task of the assignment step of `fit`,
+    /// or `None` to leave the split to `rayon`
+    pub fn assignment_chunk_size(&self) -> Option<usize> {
+        self.assignment_chunk_size
+    }
+
+    /// Prior proportions of the clusters in the assignment step of `fit`, if any
+    pub fn cluster_priors(&self) -> Option<&ClusterPriors<F>> {
+        self.cluster_priors.as_ref()
+    }
+
+    /// How the mini-batch fits draw their batches
+    pub fn mini_batch_sampling(&self) -> MiniBatchSampling {
+        self.mini_batch_sampling
+    }
+
+    /// What `fit` does with clusters left without any observation
+    pub fn empty_clusters(&self) -> EmptyClusters {
+        self.empty_clusters
+    }
+
+    /// Smallest number of observations the assignment step of `fit` gives each cluster, if any
+    pub fn min_cluster_size(&self) -> Option<usize> {
+        self.min_cluster_size
+    }
+
+    /// Largest number of observations the assignment step of `fit` gives each cluster, if any
+    pub fn max_cluster_size(&self) -> Option<usize> {
+        self.max_cluster_size
+    }
+
+    /// Bounds on the number of observations the assignment step of `fit` gives each cluster, or
+    /// `None` for the plain closest-centroid assignment
+    pub(crate) fn cluster_size_bounds(&self) -> Option<(usize, usize)> {
+        if self.min_cluster_size.is_none() && self.max_cluster_size.is_none() {
+            return None;
+        }
+        Some((
+            self.min_cluster_size.unwrap_or(0),
+            self.max_cluster_size.unwrap_or(usize::MAX),
+        ))
+    }
+
+    /// Tolerance of the K-means loop of `fit` at iteration `iteration`, counted from 0, according
+    /// to `convergence`
+    pub fn tolerance_at(&self, iteration: u64) -> F {
+        self.convergence
+            .tolerance_at(self.tolerance, self.max_n_iterations, iteration)
+    }
+
+    /// Batch size of the mini-batch fit when the estimated working set of a full-batch fit on
+    /// `n_samples` observations with `n_features` features exceeds the memory budget, or `None`
+    /// to run a full-batch fit
+    pub(crate) fn mini_batch_size(&self, n_samples: usize, n_features: usize) -> Option<usize> {
+        let budget = self.memory_budget_bytes?;
+        let float = std::mem::size_of::<F>();
+        let per_sample = (n_features + 1) * float + std::mem::size_of::<usize>();
+        let centroids = 2 * self.n_clusters * n_features * float;
+        if n_samples
+            .saturating_mul(per_sample)
+            .saturating_add(centroids)
+            <= budget
+        {
+            return None;
+        }
+        let batch_size = budget.saturating_sub(centroids) / per_sample;
+        Some(batch_size.max(self.n_clusters).min(n_samples))
+    }
+
+    /// The seed the random number generator was built from with
+    /// [`random_seed`](KMeansParams::random_seed), or `None` if the generator was passed as is
+    pub fn random_seed(&self) -> Option<u64> {
+        self.random_seed
+    }
+
     /// Returns the random generator
     pub fn rng(&self) -> &R {
         &self.rng
@@ -165,9 +1064,14 @@ impl<F: Float, R: Rng, D: Distance<F>> KMeansValidParams<F, R, D> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{KMeans, KMeansParams, KMeansParamsError, KMeansValidParams};
+    use crate::{
+        CentroidUpdate, ClusterPriors, Convergence, DensityWeighting, KMeans, KMeansAlgorithm,
+        KMeansInit, KMeansParams, KMeansParamsError, KMeansValidParams, MAX_WARD_SUBSAMPLE_SIZE,
+    };
+    use approx::assert_abs_diff_eq;
     use linfa::ParamGuard;
     use linfa_nn::distance::L2Dist;
+    use ndarray::array;
     use rand_xoshiro::Xoshiro256Plus;
 
     #[test]
@@ -189,6 +1093,32 @@ mod tests {
         assert!(matches!(res, Err(KMeansParamsError::Tolerance)))
     }
 
+    #[test]
+    fn annealed_shift() {
+        let params = KMeans::params(1)
+            .max_n_iterations(5)
+            .convergence(Convergence::AnnealedShift {
+                start: 1.,
+                end: 1e-4,
+            })
+            .check()
+            .unwrap();
+        let tolerances: Vec<f64> = (0..6).map(|i| params.tolerance_at(i)).collect();
+        for (tolerance, expected) in tolerances.iter().zip([1., 0.1, 1e-2, 1e-3, 1e-4, 1e-4]) {
+            assert_abs_diff_eq!(*tolerance, expected, epsilon = 1e-12);
+        }
+        let params = KMeans::params(1).tolerance(0.5).check().unwrap();
+        assert_eq!(params.tolerance_at(0), 0.5);
+        assert_eq!(params.tolerance_at(100), 0.5);
+
+        for (start, end) in [(1e-4, 1.), (1., 0.), (f64::NAN, 1.)] {
+            let res = KMeans::params(1)
+                .convergence(Convergence::AnnealedShift { start, end })
+                .check();
+            assert!(matches!(res, Err(KMeansParamsError::AnnealedShift)));
+        }
+    }
+
     #[test]
     fn tolerance_cannot_be_zero() {
         let res = KMeans::params(1).tolerance(0.).check();
@@ -201,6 +1131,243 @@ mod tests {
         assert!(matches!(res, Err(KMeansParamsError::MaxIterations)))
     }
 
+    #[test]
+    fn ward_subsample_size_is_bounded() {
+        let res = KMeans::<f64, _>::params(10)
+            .init_method(KMeansInit::AgglomerativeWard { subsample_size: 5 })
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::WardSubsampleSize)));
+        let res = KMeans::<f64, _>::params(10)
+            .init_method(KMeansInit::AgglomerativeWard {
+                subsample_size: MAX_WARD_SUBSAMPLE_SIZE + 1,
+            })
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::WardSubsampleSize)));
+        let res = KMeans::<f64, _>::params(10)
+            .init_method(KMeansInit::AgglomerativeWard { subsample_size: 10 })
+            .check();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn feature_weighting_beta_must_be_greater_than_one() {
+        for beta in [1., 0.5, f64::NAN] {
+            let res = KMeans::params(2)
+                .algorithm(KMeansAlgorithm::WeightedFeatures { beta })
+                .check();
+            assert!(matches!(res, Err(KMeansParamsError::FeatureWeightingBeta)));
+        }
+        let res = KMeans::params(2)
+            .algorithm(KMeansAlgorithm::WeightedFeatures { beta: 2. })
+            .check();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn max_cluster_fraction_is_a_fraction() {
+        for fraction in [0., -0.5, 1.5, f64::NAN] {
+            let res = KMeans::params(2)
+                .max_cluster_fraction(Some(fraction))
+                .check();
+            assert!(matches!(res, Err(KMeansParamsError::MaxClusterFraction)));
+        }
+        let res = KMeans::params(2).max_cluster_fraction(Some(1.)).check();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn density_weighting_k_cannot_be_zero() {
+        let res = KMeans::params(2)
+            .tolerance(1.)
+            .density_weighting(Some(DensityWeighting::Favor { k: 0 }))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::DensityWeightingK)));
+        let res = KMeans::params(2)
+            .tolerance(1.)
+            .density_weighting(Some(DensityWeighting::Penalize { k: 1 }))
+            .check();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn shrinkage_is_a_fraction() {
+        for shrinkage in [-0.1, 1.5, f64::NAN] {
+            let res = KMeans::params(2).shrinkage(shrinkage).check();
+            assert!(matches!(res, Err(KMeansParamsError::Shrinkage)));
+        }
+        for shrinkage in [0., 0.5, 1.] {
+            assert!(KMeans::params(2).shrinkage(shrinkage).check().is_ok());
+        }
+    }
+
+    #[test]
+    fn trimmed_mean_trim_is_bounded() {
+        for trim in [-0.1, 0.5, f64::NAN] {
+            let res = KMeans::params(2)
+                .centroid_update(CentroidUpdate::TrimmedMean { trim })
+                .check();
+            assert!(matches!(res, Err(KMeansParamsError::TrimmedMeanTrim)));
+        }
+        let res = KMeans::params(2)
+            .centroid_update(CentroidUpdate::TrimmedMean { trim: 0.2 })
+            .density_weighting(Some(DensityWeighting::Favor { k: 1 }))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::TrimmedMeanWeights)));
+        let res = KMeans::params(2)
+            .centroid_update(CentroidUpdate::TrimmedMean { trim: 0.2 })
+            .check();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn median_excludes_density_weighting() {
+        let res = KMeans::params(2)
+            .centroid_update(CentroidUpdate::<f64>::Median)
+            .density_weighting(Some(DensityWeighting::Penalize { k: 3 }))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::MedianWeights)));
+    }
+
+    #[test]
+    fn mini_batch_size() {
+        let params = KMeans::<f64, _>::params(4).check().unwrap();
+        assert_eq!(params.mini_batch_size(1_000_000, 10), None);
+        // 10 features and a label take 96 bytes per observation, the centroids 640 bytes
+        let params = KMeans::<f64, _>::params(4)
+            .memory_budget_bytes(Some(96 * 1000 + 640))
+            .check()
+            .unwrap();
+        assert_eq!(params.mini_batch_size(1000, 10), None);
+        assert_eq!(params.mini_batch_size(1001, 10), Some(1000));
+        assert_eq!(params.mini_batch_size(1_000_000, 10), Some(1000));
+        // Batches hold at least one observation per cluster
+        let params = KMeans::<f64, _>::params(4)
+            .memory_budget_bytes(Some(0))
+            .check()
+            .unwrap();
+        assert_eq!(params.mini_batch_size(1000, 10), Some(4));
+    }
+
+    #[test]
+    fn trajectory_frames_are_checked() {
+        let res = KMeans::<f64, _>::params(2)
+            .record_trajectory(Some(0))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::TrajectoryFrames)));
+        let res = KMeans::<f64, _>::params(2)
+            .record_trajectory(Some(1))
+            .check();
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn cluster_priors_are_checked() {
+        let check = |priors, variance| {
+            KMeans::<f64, _>::params(2)
+                .cluster_priors(Some(ClusterPriors { priors, variance }))
+                .check()
+        };
+        assert!(check(array![0.3, 0.7], 1.).is_ok());
+        assert!(check(array![0.3, 0.7], 0.).is_ok());
+        for res in [
+            check(array![1.], 1.),
+            check(array![0.3, 0.6], 1.),
+            check(array![0., 1.], 1.),
+            check(array![-0.5, 1.5], 1.),
+            check(array![0.3, 0.7], -1.),
+            check(array![0.3, 0.7], f64::NAN),
+        ] {
+            assert!(matches!(res, Err(KMeansParamsError::ClusterPriors)));
+        }
+    }
+
+    #[test]
+    fn elkan_priors_are_checked() {
+        let priors = Some(ClusterPriors {
+            priors: array![0.3, 0.7],
+            variance: 1.,
+        });
+        let res = KMeans::<f64, _>::params(2)
+            .algorithm(KMeansAlgorithm::Elkan)
+            .cluster_priors(priors)
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::ElkanPriors)));
+    }
+
+    #[test]
+    fn cluster_sizes_are_checked() {
+        let check = |min, max| {
+            KMeans::<f64, _>::params(2)
+                .min_cluster_size(min)
+                .max_cluster_size(max)
+                .check()
+        };
+        assert!(check(Some(2), Some(2)).is_ok());
+        assert!(check(Some(0), None).is_ok());
+        assert!(check(None, Some(1)).is_ok());
+        for res in [check(Some(3), Some(2)), check(None, Some(0))] {
+            assert!(matches!(res, Err(KMeansParamsError::ClusterSizes)));
+        }
+
+        let res = KMeans::<f64, _>::params(2)
+            .algorithm(KMeansAlgorithm::Elkan)
+            .max_cluster_size(Some(5))
+            .check();
+        assert!(matches!(
+            res,
+            Err(KMeansParamsError::ClusterSizesCombination)
+        ));
+        let res = KMeans::<f64, _>::params(2)
+            .min_cluster_size(Some(1))
+            .cluster_priors(Some(ClusterPriors {
+                priors: array![0.3, 0.7],
+                variance: 1.,
+            }))
+            .check();
+        assert!(matches!(
+            res,
+            Err(KMeansParamsError::ClusterSizesCombination)
+        ));
+    }
+
+    #[test]
+    fn assignment_chunk_size_is_checked() {
+        let res = KMeans::<f64, _>::params(2)
+            .assignment_chunk_size(Some(0))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::AssignmentChunkSize)));
+    }
+
+    #[test]
+    fn divergence_patience_is_checked() {
+        let res = KMeans::<f64, _>::params(2)
+            .divergence_patience(Some(0))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::DivergencePatience)));
+    }
+
+    #[test]
+    fn fallback_init_is_checked() {
+        let res = KMeans::params(2)
+            .init_method(KMeansInit::<f64>::Fallback(vec![]))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::EmptyFallback)));
+        let res = KMeans::<f64, _>::params(2)
+            .init_method(KMeansInit::Fallback(vec![
+                KMeansInit::KMeansPlusPlus,
+                KMeansInit::AgglomerativeWard { subsample_size: 1 },
+            ]))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::WardSubsampleSize)));
+        let res = KMeans::<f64, _>::params(2)
+            .init_method(KMeansInit::Fallback(vec![
+                KMeansInit::KMeansPlusPlus,
+                KMeansInit::Random,
+            ]))
+            .check();
+        assert!(res.is_ok());
+    }
+
     #[test]
     fn n_runs_cannot_be_zero() {
         let res = KMeans::params(1).tolerance(1.).n_runs(0).check();
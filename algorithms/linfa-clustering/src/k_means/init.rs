@@ -1,6 +1,7 @@
 use super::algorithm::{update_cluster_memberships, update_min_dists};
+use super::errors::KMeansError;
 use linfa::Float;
-use linfa_nn::distance::Distance;
+use linfa_nn::distance::{Distance, L2Dist};
 use ndarray::parallel::prelude::*;
 use ndarray::{s, Array1, Array2, ArrayBase, ArrayView1, ArrayView2, Axis, Data, Ix2};
 use ndarray_rand::rand::distributions::{Distribution, WeightedIndex};
@@ -22,7 +23,10 @@ use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
 pub enum KMeansInit<F: Float> {
     /// Pick random points as centroids.
     Random,
-    /// Precomputed list of centroids, represented as an array of (n_centroids, n_features).
+    /// Precomputed list of centroids, represented as an array of (n_centroids, n_features), for
+    /// example the centroids of a previous model to warm-start a fit on new data. The number of
+    /// centroids must be `n_clusters`, which is checked with the other hyperparameters, and the
+    /// number of features that of the observations, which is checked by the fit.
     Precomputed(Array2<F>),
     /// K-means++ algorithm. Using this over random initialization causes K-means to converge
     /// faster for almost all cases, since K-means++ produces better centroids.
@@ -32,31 +36,105 @@ pub enum KMeansInit<F: Float> {
     /// use this for larger datasets.  Details on the algorithm can be found
     /// [here](http://vldb.org/pvldb/vol5/p622_bahmanbahmani_vldb2012.pdf).
     KMeansPara,
+    /// Runs an agglomerative clustering with Ward linkage on `subsample_size` randomly picked
+    /// observations, cuts the resulting dendrogram at `n_clusters` clusters and uses the means of
+    /// those clusters as centroids. This gives excellent starting centroids for small to medium
+    /// datasets.
+    ///
+    /// Ward linkage needs the pairwise euclidean distances of the subsample, so this takes
+    /// `O(subsample_size²)` time and memory, regardless of the distance metric used by K-means.
+    /// For this reason `subsample_size` must lie between `n_clusters` and
+    /// [`MAX_WARD_SUBSAMPLE_SIZE`].
+    AgglomerativeWard { subsample_size: usize },
+    /// Farthest-first traversal: picks a random observation as the first centroid, then
+    /// repeatedly picks the observation farthest from all centroids picked so far. This spreads
+    /// the centroids out as much as possible, which also makes it sensitive to outliers.
+    FarthestFirst,
+    /// Tries each strategy in order, each up to [`FALLBACK_ATTEMPTS`] times, until one of them
+    /// produces `n_clusters` distinct centroids with finite coordinates. This hardens fitting on
+    /// data with many duplicate observations, on which most strategies pick coincident
+    /// centroids. For example `Fallback(vec![KMeansPlusPlus, FarthestFirst, Random])`.
+    ///
+    /// [`fit`](crate::KMeans) fails with
+    /// [`KMeansError::DegenerateInit`](crate::KMeansError::DegenerateInit) if all of them fail.
+    /// The list must not be empty.
+    Fallback(Vec<KMeansInit<F>>),
 }
 
+/// Number of times each strategy of a [`KMeansInit::Fallback`] is tried before moving on to the
+/// next one.
+pub const FALLBACK_ATTEMPTS: usize = 3;
+
+/// Largest subsample size accepted by [`KMeansInit::AgglomerativeWard`], which keeps the pairwise
+/// distance matrix of the subsample at a few megabytes.
+pub const MAX_WARD_SUBSAMPLE_SIZE: usize = 2000;
+
 impl<F: Float> KMeansInit<F> {
-    /// Runs the chosen initialization routine
+    /// Checks that the precomputed centroids, including those of a `Fallback`, have `n_features`
+    /// features, so that `run` doesn't panic on the observations
+    pub(crate) fn check_features(&self, n_features: usize) -> Result<(), KMeansError> {
+        match self {
+            Self::Precomputed(centroids) if centroids.ncols() != n_features => {
+                Err(KMeansError::InitFeatureMismatch {
+                    expected: n_features,
+                    actual: centroids.ncols(),
+                })
+            }
+            Self::Fallback(strategies) => strategies
+                .iter()
+                .try_for_each(|strategy| strategy.check_features(n_features)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs the chosen initialization routine. With `deterministic`, the result doesn't depend on
+    /// the number of threads. Only returns `None` if all the strategies of a `Fallback` failed.
     pub(crate) fn run<R: Rng, D: Distance<F>>(
         &self,
         dist_fn: &D,
         n_clusters: usize,
         observations: ArrayView2<F>,
         rng: &mut R,
-    ) -> Array2<F> {
-        match self {
+        deterministic: bool,
+    ) -> Option<Array2<F>> {
+        let centroids = match self {
             Self::Random => random_init(n_clusters, observations, rng),
             Self::KMeansPlusPlus => k_means_plusplus(dist_fn, n_clusters, observations, rng),
-            Self::KMeansPara => k_means_para(dist_fn, n_clusters, observations, rng),
+            Self::KMeansPara => k_means_para(dist_fn, n_clusters, observations, rng, deterministic),
+            Self::AgglomerativeWard { subsample_size } => {
+                agglomerative_ward(n_clusters, *subsample_size, observations, rng)
+            }
+            Self::FarthestFirst => farthest_first(dist_fn, n_clusters, observations, rng),
+            Self::Fallback(strategies) => {
+                return strategies.iter().find_map(|strategy| {
+                    (0..FALLBACK_ATTEMPTS)
+                        .filter_map(|_| {
+                            strategy.run(dist_fn, n_clusters, observations, rng, deterministic)
+                        })
+                        .find(|centroids| distinct_centroids(centroids))
+                })
+            }
             Self::Precomputed(centroids) => {
-                // Check centroid dimensions
+                // Checked by `check_init` and `check_features`
                 assert_eq!(centroids.nrows(), n_clusters);
                 assert_eq!(centroids.ncols(), observations.ncols());
                 centroids.clone()
             }
-        }
+        };
+        Some(centroids)
     }
 }
 
+/// Whether all centroids have finite coordinates and no two of them are equal
+fn distinct_centroids<F: Float>(centroids: &Array2<F>) -> bool {
+    centroids.iter().all(|c| c.is_finite())
+        && centroids
+            .rows()
+            .into_iter()
+            .enumerate()
+            .all(|(i, a)| centroids.rows().into_iter().skip(i + 1).all(|b| a != b))
+}
+
 /// Pick random points from the input matrix as centroids
 fn random_init<F: Float>(
     n_clusters: usize,
@@ -129,6 +207,45 @@ fn k_means_plusplus<F: Float, D: Distance<F>>(
     )
 }
 
+/// Farthest-first traversal initialization algorithm
+fn farthest_first<F: Float, D: Distance<F>>(
+    dist_fn: &D,
+    n_clusters: usize,
+    observations: ArrayView2<F>,
+    rng: &mut impl Rng,
+) -> Array2<F> {
+    let (n_samples, n_features) = observations.dim();
+    let mut centroids = Array2::zeros((n_clusters, n_features));
+    let first_idx = rng.gen_range(0..n_samples);
+    centroids.row_mut(0).assign(&observations.row(first_idx));
+
+    let mut dists = Array1::zeros(n_samples);
+    for c_cnt in 1..n_clusters {
+        update_min_dists(
+            dist_fn,
+            &centroids.slice(s![0..c_cnt, ..]),
+            &observations,
+            &mut dists,
+        );
+        // Ties go to the first observation
+        let farthest_idx = dists
+            .iter()
+            .enumerate()
+            .fold((0, F::neg_infinity()), |(best, max), (i, &d)| {
+                if d > max {
+                    (i, d)
+                } else {
+                    (best, max)
+                }
+            })
+            .0;
+        centroids
+            .row_mut(c_cnt)
+            .assign(&observations.row(farthest_idx));
+    }
+    centroids
+}
+
 /// KMeans|| initialization algorithm
 /// In each iteration, pick some new "candidate centroids" by sampling the probabilities of each
 /// input point in parallel. The probability of a point becoming a centroid is the same as with
@@ -139,6 +256,7 @@ fn k_means_para<R: Rng, F: Float, D: Distance<F>>(
     n_clusters: usize,
     observations: ArrayView2<F>,
     rng: &mut R,
+    deterministic: bool,
 ) -> Array2<F> {
     // The product of these parameters must exceed n_clusters. The higher they are, the more
     // candidates are selected, which improves the quality of the centroids but increases running
@@ -165,6 +283,7 @@ fn k_means_para<R: Rng, F: Float, D: Distance<F>>(
             &dists,
             F::cast(candidates_per_round),
             rng.gen_range(0..std::u64::MAX),
+            deterministic,
         );
 
         // Append the newly generated candidates to the current cadidates, breaking out of the loop
@@ -189,15 +308,95 @@ fn k_means_para<R: Rng, F: Float, D: Distance<F>>(
     weighted_k_means_plusplus(dist_fn, n_clusters, final_candidates, weights.view(), rng)
 }
 
+/// Agglomerative Ward initialization algorithm
+/// Builds a Ward dendrogram over a random subsample of the observations, then replays its first
+/// merge steps until only `n_clusters` clusters are left. The means of these clusters are the
+/// centroids.
+fn agglomerative_ward<F: Float>(
+    n_clusters: usize,
+    subsample_size: usize,
+    observations: ArrayView2<F>,
+    rng: &mut impl Rng,
+) -> Array2<F> {
+    let subsample = random_init(subsample_size.min(observations.nrows()), observations, rng);
+    let n_samples = subsample.nrows();
+
+    // Condensed matrix of the pairwise euclidean distances, as expected by `kodama`
+    let mut condensed = Vec::with_capacity(n_samples * n_samples.saturating_sub(1) / 2);
+    for i in 0..n_samples {
+        for j in (i + 1)..n_samples {
+            let dist = L2Dist.distance(subsample.row(i), subsample.row(j));
+            condensed.push(dist.to_f64().unwrap());
+        }
+    }
+    let dendrogram = kodama::linkage(&mut condensed, n_samples, kodama::Method::Ward);
+    let clusters = cut_dendrogram(&dendrogram, n_samples, n_clusters);
+
+    let mut centroids = Array2::zeros((n_clusters, observations.ncols()));
+    // With fewer observations than clusters, the extra centroids are duplicates
+    for (mut centroid, cluster) in centroids
+        .rows_mut()
+        .into_iter()
+        .zip(clusters.iter().cycle())
+    {
+        for &i in cluster {
+            centroid += &subsample.row(i);
+        }
+        centroid /= F::cast(cluster.len());
+    }
+    centroids
+}
+
+/// Replays the first merge steps of `dendrogram`, built over `n_samples` points, until only
+/// `n_clusters` clusters are left (or all the points if there are fewer). Returns the indices of
+/// the points of each cluster.
+pub(crate) fn cut_dendrogram(
+    dendrogram: &kodama::Dendrogram<f64>,
+    n_samples: usize,
+    n_clusters: usize,
+) -> Vec<Vec<usize>> {
+    // Members of every node of the dendrogram, the first `n_samples` nodes being the points
+    // themselves and the following ones the clusters created at each step.
+    let mut members: Vec<Vec<usize>> = (0..n_samples).map(|i| vec![i]).collect();
+    let n_steps = n_samples.saturating_sub(n_clusters);
+    for step in &dendrogram.steps()[..n_steps] {
+        let mut merged = std::mem::take(&mut members[step.cluster1]);
+        merged.append(&mut members[step.cluster2]);
+        members.push(merged);
+    }
+    members.retain(|m| !m.is_empty());
+    members
+}
+
 /// Generate candidate centroids by sampling each observation in parallel using a seedable RNG in
 /// every thread. Average number of generated candidates should equal `multiplier`.
+///
+/// With `deterministic`, the observations are sampled sequentially from a single RNG, so that the
+/// candidates don't depend on how the work is split between threads.
 fn sample_subsequent_candidates<R: Rng, F: Float>(
     dists: &Array1<F>,
     multiplier: F,
     seed: u64,
+    deterministic: bool,
 ) -> Vec<usize> {
     // This sum can also be parallelized
     let cost = dists.sum();
+    if deterministic {
+        let mut rng = Xoshiro256Plus::seed_from_u64(seed);
+        return dists
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &d)| {
+                let rand = F::cast(rng.gen_range(0.0..1.0));
+                let prob = multiplier * d / cost;
+                if rand < prob {
+                    Some(i)
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
     // Using an atomic allows the seed to be modified while seeding RNGs in parallel
     let seed = AtomicU64::new(seed);
 
@@ -242,11 +441,13 @@ fn cluster_membership_counts<F: Float, D: Distance<F>>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{IncrKMeansError, KMeans, KMeansError, KMeansParamsError};
     use approx::{abs_diff_eq, assert_abs_diff_eq, assert_abs_diff_ne};
+    use linfa::prelude::*;
     use linfa_nn::distance::{L1Dist, L2Dist};
     use ndarray::{array, concatenate, Array};
     use ndarray_rand::rand::SeedableRng;
-    use ndarray_rand::rand_distr::Normal;
+    use ndarray_rand::rand_distr::{Normal, Uniform};
     use ndarray_rand::RandomExt;
     use rand_xoshiro::Xoshiro256Plus;
     use std::collections::HashSet;
@@ -257,24 +458,76 @@ mod tests {
         has_autotraits::<KMeansInit<f64>>();
     }
 
+    macro_rules! calc_loss {
+        ($dist_fn:expr, $centroids:expr, $observations:expr) => {{
+            let mut dists = Array1::zeros($observations.nrows());
+            update_min_dists(&$dist_fn, &$centroids, &$observations, &mut dists);
+            dists.sum()
+        }};
+    }
+
     #[test]
     fn test_precomputed() {
         let mut rng = Xoshiro256Plus::seed_from_u64(40);
         let centroids = array![[0.0, 1.0], [40.0, 10.0]];
         let observations = array![[3.0, 4.0], [1.0, 3.0], [25.0, 15.0]];
-        let c = KMeansInit::Precomputed(centroids.clone()).run(
-            &L2Dist,
-            2,
-            observations.view(),
-            &mut rng,
-        );
+        let c = KMeansInit::Precomputed(centroids.clone())
+            .run(&L2Dist, 2, observations.view(), &mut rng, false)
+            .unwrap();
         assert_abs_diff_eq!(c, centroids);
     }
 
+    #[test]
+    fn test_precomputed_shapes() {
+        let observations = array![[3.0, 4.0], [1.0, 3.0], [25.0, 15.0], [30.0, 12.0]];
+        let dataset = DatasetBase::from(observations.clone());
+        let first = KMeans::params(2).fit(&dataset).unwrap();
+        // Warm-start a fit on new observations from the centroids of the first model
+        let next = DatasetBase::from(&observations + 1.);
+        let second = KMeans::params(2)
+            .init_method(KMeansInit::Precomputed(first.centroids().clone()))
+            .n_runs(1)
+            .fit(&next)
+            .unwrap();
+        assert_abs_diff_eq!(*second.centroids(), first.centroids() + 1., epsilon = 1e-3);
+
+        let res = KMeans::params(3)
+            .init_method(KMeansInit::Precomputed(first.centroids().clone()))
+            .check();
+        assert!(matches!(res, Err(KMeansParamsError::PrecomputedCentroids)));
+        let init = KMeansInit::Precomputed(array![[0.0], [1.0]]);
+        let res = KMeans::params(2).init_method(init.clone()).fit(&dataset);
+        assert!(matches!(
+            res,
+            Err(KMeansError::InitFeatureMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+        let res = KMeans::params(2)
+            .init_method(KMeansInit::Fallback(vec![KMeansInit::Random, init.clone()]))
+            .fit(&dataset);
+        assert!(matches!(res, Err(KMeansError::InitFeatureMismatch { .. })));
+        let res = KMeans::params(2)
+            .init_method(init)
+            .check()
+            .unwrap()
+            .fit_with(None, &dataset);
+        assert!(matches!(
+            res,
+            Err(IncrKMeansError::InitFeatureMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+    }
+
     #[test]
     fn test_sample_subsequent_candidates() {
         let dists = array![0.0, 0.4, 0.5];
-        let candidates = sample_subsequent_candidates::<Xoshiro256Plus, _>(&dists, 8.0, 0);
+        let candidates = sample_subsequent_candidates::<Xoshiro256Plus, _>(&dists, 8.0, 0, false);
+        assert_eq!(candidates, vec![1, 2]);
+        let candidates = sample_subsequent_candidates::<Xoshiro256Plus, _>(&dists, 8.0, 0, true);
         assert_eq!(candidates, vec![1, 2]);
     }
 
@@ -317,6 +570,86 @@ mod tests {
         verify_init(KMeansInit::KMeansPlusPlus, L1Dist);
     }
 
+    #[test]
+    fn test_agglomerative_ward() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let init = KMeansInit::AgglomerativeWard {
+            subsample_size: 100,
+        };
+        // Make sure we don't panic on degenerate data (n_clusters > n_samples)
+        let degenerate_data = array![[1.0, 2.0]];
+        let out = init
+            .run(&L2Dist, 2, degenerate_data.view(), &mut rng, false)
+            .unwrap();
+        assert_abs_diff_eq!(out, concatenate![Axis(0), degenerate_data, degenerate_data]);
+
+        let centroids = [20.0, -1000.0, 1000.0];
+        let obs: Array2<f64> = centroids.iter().fold(Array2::default((0, 2)), |a, &c| {
+            let cluster = Array::random_using((50, 2), Normal::new(c, 1.).unwrap(), &mut rng);
+            concatenate(Axis(0), &[a.view(), cluster.view()]).unwrap()
+        });
+        let out = init
+            .run(&L2Dist, centroids.len(), obs.view(), &mut rng, false)
+            .unwrap();
+        // Each centroid is the mean of a whole blob
+        let mut found: Vec<f64> = out.column(0).to_vec();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_abs_diff_eq!(
+            Array1::from(found),
+            array![-1000., 20., 1000.],
+            epsilon = 1.
+        );
+
+        let out_rand = random_init(3, obs.view(), &mut rng.clone());
+        assert!(calc_loss!(L2Dist, out, obs) < calc_loss!(L2Dist, out_rand, obs));
+    }
+
+    #[test]
+    fn test_farthest_first() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let obs = array![[0., 0.], [0.1, 0.], [10., 0.], [10., 0.1], [0., 10.]];
+        let out = KMeansInit::FarthestFirst
+            .run(&L2Dist, 3, obs.view(), &mut rng, false)
+            .unwrap();
+        // One centroid from each group, whichever observation comes first
+        let mut corners: Vec<_> = out
+            .rows()
+            .into_iter()
+            .map(|c| (c[0] > 5., c[1] > 5.))
+            .collect();
+        corners.sort_unstable();
+        assert_eq!(corners, vec![(false, false), (false, true), (true, false)]);
+    }
+
+    #[test]
+    fn test_fallback() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        // Mostly duplicates, with only 3 distinct observations
+        let mut obs = Array2::zeros((50, 2));
+        obs.row_mut(10).assign(&array![5., 5.]);
+        obs.row_mut(20).assign(&array![10., 10.]);
+
+        // The coincident precomputed centroids are rejected
+        let init = KMeansInit::Fallback(vec![
+            KMeansInit::Precomputed(Array2::zeros((3, 2))),
+            KMeansInit::FarthestFirst,
+        ]);
+        let out = init.run(&L2Dist, 3, obs.view(), &mut rng, false).unwrap();
+        assert!(distinct_centroids(&out));
+
+        // Not enough distinct observations for any strategy
+        let init = KMeansInit::Fallback(vec![
+            KMeansInit::KMeansPlusPlus,
+            KMeansInit::FarthestFirst,
+            KMeansInit::Random,
+        ]);
+        assert!(init.run(&L2Dist, 4, obs.view(), &mut rng, false).is_none());
+        let res = KMeans::params_with_rng(4, rng)
+            .init_method(init)
+            .fit(&DatasetBase::from(obs));
+        assert!(matches!(res, Err(KMeansError::DegenerateInit)));
+    }
+
     #[test]
     fn test_k_means_para() {
         verify_init(KMeansInit::KMeansPara, L2Dist);
@@ -328,7 +661,9 @@ mod tests {
         let mut rng = Xoshiro256Plus::seed_from_u64(42);
         // Make sure we don't panic on degenerate data (n_clusters > n_samples)
         let degenerate_data = array![[1.0, 2.0]];
-        let out = init.run(&dist_fn, 2, degenerate_data.view(), &mut rng);
+        let out = init
+            .run(&dist_fn, 2, degenerate_data.view(), &mut rng, false)
+            .unwrap();
         assert_abs_diff_eq!(out, concatenate![Axis(0), degenerate_data, degenerate_data]);
 
         // Build 3 separated clusters of points
@@ -342,7 +677,9 @@ mod tests {
         });
 
         // Look for the right number of centroids
-        let out = init.run(&dist_fn, centroids.len(), obs.view(), &mut rng);
+        let out = init
+            .run(&dist_fn, centroids.len(), obs.view(), &mut rng, false)
+            .unwrap();
         let mut cluster_ids = HashSet::new();
         for row in out.rows() {
             // Centroid should not be 0
@@ -365,14 +702,6 @@ mod tests {
         assert_eq!(cluster_ids, [0, 1, 2].iter().copied().collect());
     }
 
-    macro_rules! calc_loss {
-        ($dist_fn:expr, $centroids:expr, $observations:expr) => {{
-            let mut dists = Array1::zeros($observations.nrows());
-            update_min_dists(&$dist_fn, &$centroids, &$observations, &mut dists);
-            dists.sum()
-        }};
-    }
-
     fn test_compare<D: Distance<f64>>(dist_fn: D) {
         let mut rng = Xoshiro256Plus::seed_from_u64(42);
         let centroids = [20.0, -1000.0, 1000.0];
@@ -386,7 +715,7 @@ mod tests {
 
         let out_rand = random_init(3, obs.view(), &mut rng.clone());
         let out_pp = k_means_plusplus(&dist_fn, 3, obs.view(), &mut rng.clone());
-        let out_para = k_means_para(&dist_fn, 3, obs.view(), &mut rng);
+        let out_para = k_means_para(&dist_fn, 3, obs.view(), &mut rng, false);
         // Loss of Kmeans++ should be better than using random_init
         assert!(calc_loss!(dist_fn, out_pp, obs) < calc_loss!(dist_fn, out_rand, obs));
         // Loss of Kmeans|| should be better than using random_init
@@ -402,4 +731,36 @@ mod tests {
     fn test_compare_l1() {
         test_compare(L1Dist);
     }
+
+    #[test]
+    fn test_compare_fit_on_blobs() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let centroids = Array::random_using((10, 2), Uniform::new(-50., 50.), &mut rng);
+        let observations = linfa_datasets::generate::blobs(30, &centroids, &mut rng);
+        let dataset = DatasetBase::from(observations.clone());
+        // Total over a few seeds, since a single random initialization can get lucky
+        let total = |init: KMeansInit<f64>| {
+            (0..5)
+                .map(|seed| {
+                    let rng = Xoshiro256Plus::seed_from_u64(seed);
+                    let init_loss = calc_loss!(
+                        L2Dist,
+                        init.run(&L2Dist, 10, observations.view(), &mut rng.clone(), false)
+                            .unwrap(),
+                        observations
+                    );
+                    let model = KMeans::params_with_rng(10, rng)
+                        .init_method(init.clone())
+                        .n_runs(1)
+                        .fit(&dataset)
+                        .unwrap();
+                    (init_loss, model.inertia())
+                })
+                .fold((0., 0.), |(a, b), (c, d)| (a + c, b + d))
+        };
+        let (pp_init, pp_fit) = total(KMeansInit::KMeansPlusPlus);
+        let (random_init, random_fit) = total(KMeansInit::Random);
+        assert!(pp_init < random_init);
+        assert!(pp_fit < random_fit);
+    }
 }
@@ -0,0 +1,133 @@
+use ndarray_rand::rand::seq::SliceRandom;
+use ndarray_rand::rand::Rng;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+/// How the observations of the batches of Mini-Batch K-means are drawn from a dataset, see
+/// [`batch_indices`](MiniBatchSampling::batch_indices).
+pub enum MiniBatchSampling {
+    /// Each batch is drawn uniformly at random with replacement, independently of the previous
+    /// batches: within an epoch some observations are seen several times and others not at all.
+    WithReplacement,
+    /// The observations are shuffled once per epoch and split into consecutive batches, so that
+    /// each observation is seen exactly once per epoch. The last batch of an epoch is smaller if
+    /// the batch size doesn't divide the number of observations.
+    EpochShuffle,
+}
+
+impl MiniBatchSampling {
+    /// Returns an endless iterator over the indices of the observations of each batch, for a
+    /// dataset of `n_samples` observations. Batches can then be extracted with
+    /// `records.select(Axis(0), &indices)` and fed to
+    /// [`fit_with`](linfa::traits::FitWith::fit_with).
+    ///
+    /// The iterator is empty if `n_samples` or `batch_size` is 0.
+    pub fn batch_indices<R: Rng>(
+        self,
+        n_samples: usize,
+        batch_size: usize,
+        rng: R,
+    ) -> MiniBatchIndices<R> {
+        MiniBatchIndices {
+            sampling: self,
+            batch_size: if n_samples == 0 { 0 } else { batch_size },
+            permutation: (0..n_samples).collect(),
+            position: n_samples,
+            rng,
+        }
+    }
+}
+
+/// Iterator over the indices of the observations of Mini-Batch K-means batches, created by
+/// [`MiniBatchSampling::batch_indices`]
+#[derive(Clone, Debug)]
+pub struct MiniBatchIndices<R: Rng> {
+    sampling: MiniBatchSampling,
+    batch_size: usize,
+    // Order in which the observations are visited in the current epoch
+    permutation: Vec<usize>,
+    // Start of the next batch in `permutation`
+    position: usize,
+    rng: R,
+}
+
+impl<R: Rng> Iterator for MiniBatchIndices<R> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        if self.batch_size == 0 {
+            return None;
+        }
+        let n_samples = self.permutation.len();
+        match self.sampling {
+            MiniBatchSampling::WithReplacement => Some(
+                (0..self.batch_size)
+                    .map(|_| self.rng.gen_range(0..n_samples))
+                    .collect(),
+            ),
+            MiniBatchSampling::EpochShuffle => {
+                if self.position == n_samples {
+                    self.permutation.shuffle(&mut self.rng);
+                    self.position = 0;
+                }
+                let end = (self.position + self.batch_size).min(n_samples);
+                let batch = self.permutation[self.position..end].to_vec();
+                self.position = end;
+                Some(batch)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    #[test]
+    fn epoch_shuffle_visits_each_index_once() {
+        let rng = Xoshiro256Plus::seed_from_u64(42);
+        let mut batches = MiniBatchSampling::EpochShuffle.batch_indices(10, 4, rng);
+        let mut epochs = Vec::new();
+        for _ in 0..3 {
+            let epoch: Vec<_> = batches.by_ref().take(3).collect();
+            assert_eq!(
+                epoch.iter().map(Vec::len).collect::<Vec<_>>(),
+                vec![4, 4, 2]
+            );
+            let mut visited = epoch.concat();
+            epochs.push(visited.clone());
+            visited.sort_unstable();
+            assert_eq!(visited, (0..10).collect::<Vec<_>>());
+        }
+        // Each epoch is shuffled anew
+        assert!(epochs[0] != epochs[1] || epochs[1] != epochs[2]);
+    }
+
+    #[test]
+    fn with_replacement_batches() {
+        let rng = Xoshiro256Plus::seed_from_u64(42);
+        let batches: Vec<_> = MiniBatchSampling::WithReplacement
+            .batch_indices(10, 4, rng)
+            .take(5)
+            .collect();
+        assert!(batches.iter().all(|batch| batch.len() == 4));
+        assert!(batches.iter().flatten().all(|&i| i < 10));
+
+        let rng = Xoshiro256Plus::seed_from_u64(42);
+        assert_eq!(
+            MiniBatchSampling::EpochShuffle
+                .batch_indices(0, 4, rng)
+                .next(),
+            None
+        );
+    }
+}
@@ -1,9 +1,37 @@
 mod algorithm;
+mod analysis;
+mod bisecting;
+mod centroid_tree;
+mod consensus;
+mod density;
 mod errors;
 mod hyperparams;
 mod init;
+mod mini_batch;
+#[cfg(feature = "onnx")]
+mod onnx;
+#[cfg(feature = "serde")]
+mod persistence;
+mod point;
+mod predictor;
+mod voronoi;
 
 pub use algorithm::*;
+pub(crate) use analysis::min_cost_matching;
+pub use analysis::{
+    calinski_harabasz_score, contingency_matrix, davies_bouldin_score, fit_stats, silhouette_score,
+    ClusterSummary, DataStats,
+};
+pub use bisecting::*;
+pub use consensus::*;
+pub use density::*;
 pub use errors::*;
 pub use hyperparams::*;
 pub use init::*;
+pub use mini_batch::*;
+#[cfg(feature = "onnx")]
+pub use onnx::*;
+#[cfg(feature = "serde")]
+pub use persistence::*;
+pub use point::*;
+pub use predictor::*;
@@ -0,0 +1,376 @@
+// Export of fitted K-means models to ONNX, encoded by hand since an ONNX model is a plain
+// protocol buffers message and the graph only needs a handful of its fields
+use super::algorithm::KMeans;
+use linfa::Float;
+use linfa_nn::distance::L2Dist;
+use ndarray::{Array1, Array2, Axis};
+use std::io::{self, Write};
+
+/// ONNX operator set the exported graph is written against
+pub const ONNX_OPSET_VERSION: i64 = 13;
+// IR version of the ONNX release that introduced `ONNX_OPSET_VERSION`
+const IR_VERSION: u64 = 7;
+
+// Values of `TensorProto.DataType`
+const FLOAT: u64 = 1;
+const INT64: u64 = 7;
+// Values of `AttributeProto.AttributeType`
+const ATTRIBUTE_INT: u64 = 2;
+const ATTRIBUTE_INTS: u64 = 7;
+
+impl<F: Float> KMeans<F, L2Dist> {
+    /// Return the model as a serialized ONNX model, which runtimes such as onnxruntime can serve
+    /// without Rust. The graph takes a `float` tensor `X` of shape `(N, n_features)` and returns
+    /// the `int64` tensor `label` of shape `(N)`, the labels of `predict`, and the `float` tensor
+    /// `scores` of shape `(N, n_clusters)`, the squared distances of
+    /// [`cost_matrix`](KMeans::cost_matrix).
+    ///
+    /// The distances are computed as `|x|² - 2 x·c + |c|²` with `ReduceSumSquare`, `MatMul` and
+    /// `Add` nodes, followed by an `ArgMin`, and learned feature weights scale `X` with a `Mul`
+    /// node first. The centroids are stored in single precision, so the labels of observations
+    /// almost equidistant to two centroids can differ from those of `predict`.
+    pub fn to_onnx(&self) -> Vec<u8> {
+        let scale = self.feature_scaling();
+        let centroids = match &scale {
+            Some(scale) => self.centroids() * scale,
+            None => self.centroids().clone(),
+        };
+        let (n_clusters, n_features) = centroids.dim();
+        let neg_twice_centroids_t: Array2<F> = centroids.t().mapv(|x| -F::cast(2.) * x);
+        let centroid_norms: Array1<F> = centroids.map_axis(Axis(1), |c| c.dot(&c));
+
+        let mut graph = Message::default();
+        let mut input = "X";
+        if let Some(scale) = &scale {
+            graph.message(1, node("Mul", &["X", "scale"], "X_scaled", &[]));
+            graph.message(5, tensor("scale", &[n_features], scale.iter()));
+            input = "X_scaled";
+        }
+        graph.message(
+            1,
+            node(
+                "ReduceSumSquare",
+                &[input],
+                "X_norms",
+                &[
+                    ("axes", Attribute::Ints(&[1])),
+                    ("keepdims", Attribute::Int(1)),
+                ],
+            ),
+        );
+        graph.message(1, node("MatMul", &[input, "centroids"], "cross_terms", &[]));
+        graph.message(
+            1,
+            node("Add", &["X_norms", "cross_terms"], "partial_scores", &[]),
+        );
+        graph.message(
+            1,
+            node("Add", &["partial_scores", "centroid_norms"], "scores", &[]),
+        );
+        graph.message(
+            1,
+            node(
+                "ArgMin",
+                &["scores"],
+                "label",
+                &[("axis", Attribute::Int(1)), ("keepdims", Attribute::Int(0))],
+            ),
+        );
+        graph.string(2, "linfa_kmeans");
+        graph.message(
+            5,
+            tensor(
+                "centroids",
+                &[n_features, n_clusters],
+                neg_twice_centroids_t.iter(),
+            ),
+        );
+        graph.message(
+            5,
+            tensor("centroid_norms", &[n_clusters], centroid_norms.iter()),
+        );
+        graph.message(11, value_info("X", FLOAT, &[None, Some(n_features)]));
+        graph.message(12, value_info("label", INT64, &[None]));
+        graph.message(12, value_info("scores", FLOAT, &[None, Some(n_clusters)]));
+
+        let mut opset = Message::default();
+        opset.string(1, "");
+        opset.varint(2, ONNX_OPSET_VERSION as u64);
+
+        let mut model = Message::default();
+        model.varint(1, IR_VERSION);
+        model.string(2, "linfa-clustering");
+        model.string(3, env!("CARGO_PKG_VERSION"));
+        model.message(7, graph);
+        model.message(8, opset);
+        model.0
+    }
+
+    /// Write the ONNX model of [`to_onnx`](KMeans::to_onnx) to `writer`, such as a `.onnx` file
+    pub fn write_onnx<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.to_onnx())
+    }
+}
+
+enum Attribute<'a> {
+    Int(i64),
+    Ints(&'a [i64]),
+}
+
+// `NodeProto` of the default domain with a single output, named after it
+fn node(op_type: &str, inputs: &[&str], output: &str, attributes: &[(&str, Attribute)]) -> Message {
+    let mut node = Message::default();
+    inputs.iter().for_each(|input| node.string(1, input));
+    node.string(2, output);
+    node.string(3, output);
+    node.string(4, op_type);
+    for (name, value) in attributes {
+        let mut attribute = Message::default();
+        attribute.string(1, name);
+        match value {
+            Attribute::Int(i) => {
+                attribute.varint(3, *i as u64);
+                attribute.varint(20, ATTRIBUTE_INT);
+            }
+            Attribute::Ints(ints) => {
+                ints.iter().for_each(|&i| attribute.varint(8, i as u64));
+                attribute.varint(20, ATTRIBUTE_INTS);
+            }
+        }
+        node.message(5, attribute);
+    }
+    node
+}
+
+// `TensorProto` of `float` values, stored as little-endian raw data
+fn tensor<'a, F: Float>(
+    name: &str,
+    dims: &[usize],
+    values: impl Iterator<Item = &'a F>,
+) -> Message {
+    let mut tensor = Message::default();
+    dims.iter().for_each(|&dim| tensor.varint(1, dim as u64));
+    tensor.varint(2, FLOAT);
+    tensor.string(8, name);
+    let raw: Vec<u8> = values
+        .flat_map(|x| x.to_f32().unwrap_or(f32::NAN).to_le_bytes())
+        .collect();
+    tensor.bytes(9, &raw);
+    tensor
+}
+
+// `ValueInfoProto` of a tensor, with a symbolic dimension `N` for each `None` of `dims`
+fn value_info(name: &str, elem_type: u64, dims: &[Option<usize>]) -> Message {
+    let mut shape = Message::default();
+    for dim in dims {
+        let mut dimension = Message::default();
+        match dim {
+            Some(value) => dimension.varint(1, *value as u64),
+            None => dimension.string(2, "N"),
+        }
+        shape.message(1, dimension);
+    }
+    let mut tensor_type = Message::default();
+    tensor_type.varint(1, elem_type);
+    tensor_type.message(2, shape);
+    let mut type_proto = Message::default();
+    type_proto.message(1, tensor_type);
+    let mut value_info = Message::default();
+    value_info.string(1, name);
+    value_info.message(2, type_proto);
+    value_info
+}
+
+// Encoded fields of a protocol buffers message
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.raw_varint(((field as u64) << 3) | wire_type as u64);
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn varint(&mut self, field: u32, value: u64) {
+        self.key(field, 0);
+        self.raw_varint(value);
+    }
+
+    fn bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.key(field, 2);
+        self.raw_varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    fn message(&mut self, field: u32, message: Message) {
+        self.bytes(field, &message.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KMeans, KMeansAlgorithm, KMeansInit};
+    use linfa::prelude::*;
+    use ndarray::{array, Array1, ArrayView1};
+    use std::collections::HashMap;
+    use std::convert::TryInto;
+
+    // Value of a field of a decoded protocol buffers message
+    #[derive(Debug)]
+    enum Value<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+    }
+
+    fn read_varint(bytes: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = bytes.split_first().expect("truncated varint");
+            *bytes = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+        }
+        panic!("varint too long")
+    }
+
+    // Fields of a message by field number, in order
+    fn decode(mut bytes: &[u8]) -> HashMap<u64, Vec<Value<'_>>> {
+        let mut fields: HashMap<u64, Vec<Value>> = HashMap::new();
+        while !bytes.is_empty() {
+            let key = read_varint(&mut bytes);
+            let value = match key & 7 {
+                0 => Value::Varint(read_varint(&mut bytes)),
+                2 => {
+                    let len = read_varint(&mut bytes) as usize;
+                    let (value, rest) = bytes.split_at(len);
+                    bytes = rest;
+                    Value::Bytes(value)
+                }
+                wire_type => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.entry(key >> 3).or_default().push(value);
+        }
+        fields
+    }
+
+    fn bytes<'a>(fields: &HashMap<u64, Vec<Value<'a>>>, field: u64) -> Vec<&'a [u8]> {
+        fields.get(&field).map_or(Vec::new(), |values| {
+            values
+                .iter()
+                .map(|value| match value {
+                    Value::Bytes(bytes) => *bytes,
+                    Value::Varint(_) => panic!("field {} is not length-delimited", field),
+                })
+                .collect()
+        })
+    }
+
+    fn string<'a>(fields: &HashMap<u64, Vec<Value<'a>>>, field: u64) -> &'a str {
+        std::str::from_utf8(bytes(fields, field)[0]).unwrap()
+    }
+
+    // Runs the decoded graph on `observation`, checking that its nodes are the expected ones
+    fn run_graph(model: &[u8], observation: ArrayView1<f64>) -> (usize, Array1<f32>) {
+        let model = decode(model);
+        assert!(matches!(model[&1][..], [Value::Varint(IR_VERSION)]));
+        let graph = decode(bytes(&model, 7)[0]);
+        let tensors: HashMap<_, _> = bytes(&graph, 5)
+            .into_iter()
+            .map(|tensor| {
+                let tensor = decode(tensor);
+                let values: Vec<f32> = bytes(&tensor, 9)[0]
+                    .chunks(4)
+                    .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                (string(&tensor, 8).to_string(), values)
+            })
+            .collect();
+        let op_types: Vec<_> = bytes(&graph, 1)
+            .into_iter()
+            .map(|node| string(&decode(node), 4).to_string())
+            .collect();
+
+        let mut x: Vec<f32> = observation.iter().map(|&x| x as f32).collect();
+        if let Some(scale) = tensors.get("scale") {
+            assert_eq!(op_types[0], "Mul");
+            x.iter_mut().zip(scale).for_each(|(x, s)| *x *= s);
+        }
+        assert_eq!(
+            op_types[op_types.len() - 5..],
+            ["ReduceSumSquare", "MatMul", "Add", "Add", "ArgMin"]
+        );
+        let norms = &tensors["centroid_norms"];
+        let centroids = &tensors["centroids"];
+        let x_norm: f32 = x.iter().map(|x| x * x).sum();
+        let scores: Array1<f32> = (0..norms.len())
+            .map(|c| {
+                let cross: f32 = (0..x.len())
+                    .map(|j| x[j] * centroids[j * norms.len() + c])
+                    .sum();
+                x_norm + cross + norms[c]
+            })
+            .collect();
+        let label = (0..scores.len())
+            .reduce(|min, c| if scores[c] < scores[min] { c } else { min })
+            .unwrap();
+        (label, scores)
+    }
+
+    #[test]
+    fn encodes_the_distance_graph() {
+        let observations = array![[0., 0.], [1., 0.], [10., 10.], [11., 10.], [0., 9.]];
+        let model = KMeans::params(3)
+            .init_method(KMeansInit::Precomputed(array![
+                [0., 0.],
+                [10., 10.],
+                [0., 9.]
+            ]))
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        let onnx = model.to_onnx();
+        let mut written = Vec::new();
+        model.write_onnx(&mut written).unwrap();
+        assert_eq!(written, onnx);
+
+        let labels = model.predict(&observations);
+        let cost_matrix = model.cost_matrix(&observations);
+        for (i, observation) in observations.rows().into_iter().enumerate() {
+            let (label, scores) = run_graph(&onnx, observation);
+            assert_eq!(label, labels[i]);
+            approx::assert_abs_diff_eq!(
+                scores,
+                cost_matrix.row(i).mapv(|x| x as f32),
+                epsilon = 1e-4
+            );
+        }
+    }
+
+    #[test]
+    fn scales_weighted_features() {
+        // The second feature separates the clusters, the first one is noise
+        let observations = array![[0., 0.], [5., 0.1], [2., 10.], [4., 10.1], [1., 0.2]];
+        let model = KMeans::params(2)
+            .algorithm(KMeansAlgorithm::WeightedFeatures { beta: 2. })
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+        assert!(model.feature_weights().is_some());
+        let labels = model.predict(&observations);
+        for (i, observation) in observations.rows().into_iter().enumerate() {
+            assert_eq!(run_graph(&model.to_onnx(), observation).0, labels[i]);
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use super::algorithm::KMeans;
+use super::errors::PersistenceError;
+use linfa::Float;
+use linfa_nn::distance::Distance;
+use serde_crate::de::DeserializeOwned;
+use serde_crate::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Schema tag of the files written by [`KMeans::save`]
+const SCHEMA: &str = "linfa-clustering/KMeans";
+/// Version of the layout of the files written by [`KMeans::save`], to bump whenever a change to
+/// the model cannot be read by older versions of the crate
+pub const MODEL_FORMAT_VERSION: u32 = 1;
+
+/// File format of [`KMeans::save`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModelFormat {
+    /// Human readable JSON
+    Json,
+    /// Compact binary encoding of [bincode](https://docs.rs/bincode)
+    Bincode,
+}
+
+// Written before the model, so that `load` can reject files it doesn't understand before
+// decoding the model itself
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "serde_crate")]
+struct Header {
+    schema: String,
+    version: u32,
+    n_clusters: usize,
+    n_features: usize,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "serde_crate")]
+struct Envelope<'a, M> {
+    header: Header,
+    model: &'a M,
+}
+
+impl<F, D> KMeans<F, D>
+where
+    F: Float + Serialize + DeserializeOwned,
+    D: Distance<F> + Serialize + DeserializeOwned,
+{
+    /// Save the model to the file at `path` in the given `format`, overwriting it if it exists.
+    ///
+    /// The model is preceded by a header with a schema tag, the version of the file layout, see
+    /// [`MODEL_FORMAT_VERSION`], and the number of clusters and of features of the model, which
+    /// [`load`](KMeans::load) checks before trusting the file.
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        format: ModelFormat,
+    ) -> Result<(), PersistenceError> {
+        let envelope = Envelope {
+            header: Header {
+                schema: SCHEMA.to_string(),
+                version: MODEL_FORMAT_VERSION,
+                n_clusters: self.centroids().nrows(),
+                n_features: self.centroids().ncols(),
+            },
+            model: self,
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            ModelFormat::Json => serde_json::to_writer(&mut writer, &envelope)?,
+            ModelFormat::Bincode => bincode::serialize_into(&mut writer, &envelope)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a model saved with [`save`](KMeans::save) in either format, which is detected from
+    /// the content of the file.
+    ///
+    /// Returns an error if the file cannot be read, has no header or the header of another kind
+    /// of model, was saved with a newer format version, fails to decode, or if the centroids and
+    /// the other arrays of the model don't have the shape recorded in the header.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+        let bytes = std::fs::read(path)?;
+        // A bincode file starts with the little-endian length of the schema tag, a JSON one with
+        // an opening brace, possibly after some whitespace
+        let is_json = bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{');
+        let (header, model): (Header, Self) = if is_json {
+            let mut value: serde_json::Value = serde_json::from_slice(&bytes)?;
+            let header = value
+                .get_mut("header")
+                .map(serde_json::Value::take)
+                .ok_or(PersistenceError::MissingHeader)?;
+            let header: Header = serde_json::from_value(header)?;
+            check_header(&header)?;
+            let model = value
+                .get_mut("model")
+                .map(serde_json::Value::take)
+                .unwrap_or_default();
+            (header, serde_json::from_value(model)?)
+        } else {
+            let mut reader = bytes.as_slice();
+            let header: Header = bincode::deserialize_from(&mut reader)?;
+            check_header(&header)?;
+            (header, bincode::deserialize_from(&mut reader)?)
+        };
+
+        let expected = (header.n_clusters, header.n_features);
+        let actual = model.centroids().dim();
+        if actual != expected {
+            return Err(PersistenceError::ShapeMismatch { expected, actual });
+        }
+        let lengths = [
+            (
+                "cluster counts",
+                header.n_clusters,
+                model.cluster_count().len(),
+            ),
+            (
+                "feature weights",
+                header.n_features,
+                model
+                    .feature_weights()
+                    .map_or(header.n_features, |weights| weights.len()),
+            ),
+        ];
+        match lengths
+            .iter()
+            .find(|(_, expected, actual)| expected != actual)
+        {
+            Some(&(field, expected, actual)) => Err(PersistenceError::LengthMismatch {
+                field,
+                expected,
+                actual,
+            }),
+            None => Ok(model),
+        }
+    }
+}
+
+fn check_header(header: &Header) -> Result<(), PersistenceError> {
+    if header.schema != SCHEMA {
+        return Err(PersistenceError::UnknownSchema(header.schema.clone()));
+    }
+    if header.version == 0 || header.version > MODEL_FORMAT_VERSION {
+        return Err(PersistenceError::UnsupportedVersion {
+            found: header.version,
+            supported: MODEL_FORMAT_VERSION,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KMeans;
+    use linfa::prelude::*;
+    use linfa_nn::distance::L2Dist;
+    use ndarray::array;
+    use std::path::PathBuf;
+
+    // A file of the temporary directory, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!(
+                "linfa-clustering-{}-{}",
+                std::process::id(),
+                name
+            )))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn model() -> KMeans<f64, L2Dist> {
+        let observations = array![[0., 0.], [0., 1.], [10., 0.], [10., 1.]];
+        KMeans::params(2)
+            .fit(&DatasetBase::from(observations))
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let model = model();
+        for (name, format) in [
+            ("round-trip.json", ModelFormat::Json),
+            ("round-trip.bin", ModelFormat::Bincode),
+        ] {
+            let file = TempFile::new(name);
+            model.save(&file.0, format).unwrap();
+            let loaded = KMeans::<f64, L2Dist>::load(&file.0).unwrap();
+            assert_eq!(loaded.centroids(), model.centroids());
+            assert_eq!(loaded.cluster_count(), model.cluster_count());
+            assert_eq!(loaded.inertia(), model.inertia());
+            assert_eq!(loaded.random_seed(), model.random_seed());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let model = model();
+        let file = TempFile::new("invalid.json");
+        let load = |json: serde_json::Value| {
+            std::fs::write(&file.0, json.to_string()).unwrap();
+            KMeans::<f64, L2Dist>::load(&file.0)
+        };
+        model.save(&file.0, ModelFormat::Json).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&file.0).unwrap()).unwrap();
+
+        assert!(matches!(
+            load(serde_json::to_value(&model).unwrap()),
+            Err(PersistenceError::MissingHeader)
+        ));
+        let mut json = saved.clone();
+        json["header"]["schema"] = "linfa-clustering/Dbscan".into();
+        assert!(matches!(
+            load(json),
+            Err(PersistenceError::UnknownSchema(schema)) if schema == "linfa-clustering/Dbscan"
+        ));
+        let mut json = saved.clone();
+        json["header"]["version"] = (MODEL_FORMAT_VERSION + 1).into();
+        assert!(matches!(
+            load(json),
+            Err(PersistenceError::UnsupportedVersion {
+                found: 2,
+                supported: 1
+            })
+        ));
+        let mut json = saved.clone();
+        json["header"]["n_features"] = 3.into();
+        assert!(matches!(
+            load(json),
+            Err(PersistenceError::ShapeMismatch {
+                expected: (2, 3),
+                actual: (2, 2)
+            })
+        ));
+        let mut json = saved.clone();
+        json["model"] = serde_json::Value::Null;
+        assert!(matches!(load(json), Err(PersistenceError::Json(_))));
+
+        std::fs::write(&file.0, [1, 2, 3]).unwrap();
+        assert!(matches!(
+            KMeans::<f64, L2Dist>::load(&file.0),
+            Err(PersistenceError::Bincode(_))
+        ));
+        let missing = TempFile::new("missing.json");
+        assert!(matches!(
+            KMeans::<f64, L2Dist>::load(&missing.0),
+            Err(PersistenceError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+use linfa::Float;
+use ndarray::parallel::prelude::*;
+use ndarray::{Array1, Zip};
+
+/// A point that can be clustered by [`fit_points`], for point representations other than
+/// `ndarray` arrays, such as fixed-size arrays or custom vector types.
+///
+/// Distances are squared euclidean distances.
+pub trait Point {
+    /// Squared euclidean distance between `self` and `other`
+    fn sq_distance(&self, other: &Self) -> f64;
+    /// Computes `self += scale * other`, component-wise
+    fn add_assign_scaled(&mut self, other: &Self, scale: f64);
+}
+
+impl<F: Float> Point for Array1<F> {
+    fn sq_distance(&self, other: &Self) -> f64 {
+        Zip::from(self)
+            .and(other)
+            .fold(F::zero(), |acc, &a, &b| acc + (a - b) * (a - b))
+            .to_f64()
+            .unwrap()
+    }
+
+    fn add_assign_scaled(&mut self, other: &Self, scale: f64) {
+        self.scaled_add(F::cast(scale), other);
+    }
+}
+
+impl Point for Vec<f64> {
+    fn sq_distance(&self, other: &Self) -> f64 {
+        slice_sq_distance(self, other)
+    }
+
+    fn add_assign_scaled(&mut self, other: &Self, scale: f64) {
+        slice_add_assign_scaled(self, other, scale)
+    }
+}
+
+impl<const N: usize> Point for [f64; N] {
+    fn sq_distance(&self, other: &Self) -> f64 {
+        slice_sq_distance(self, other)
+    }
+
+    fn add_assign_scaled(&mut self, other: &Self, scale: f64) {
+        slice_add_assign_scaled(self, other, scale)
+    }
+}
+
+fn slice_sq_distance(a: &[f64], b: &[f64]) -> f64 {
+    assert_eq!(a.len(), b.len(), "points must have the same dimension");
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+fn slice_add_assign_scaled(a: &mut [f64], b: &[f64], scale: f64) {
+    assert_eq!(a.len(), b.len(), "points must have the same dimension");
+    a.iter_mut().zip(b).for_each(|(a, b)| *a += scale * b);
+}
+
+/// Runs the same m_k-means loop as [`KMeans`](crate::KMeans) on any [`Point`] type, starting from
+/// `centroids`, and returns the final centroids along with the index of the closest centroid of
+/// each point.
+///
+/// The loop stops once the euclidean distance between two consecutive sets of centroids is below
+/// `tolerance`, or after `max_n_iterations` iterations.
+///
+/// Panics if `centroids` is empty.
+pub fn fit_points<P: Point + Clone + Send + Sync>(
+    points: &[P],
+    mut centroids: Vec<P>,
+    max_n_iterations: u64,
+    tolerance: f64,
+) -> (Vec<P>, Vec<usize>) {
+    assert!(!centroids.is_empty(), "at least one centroid is required");
+    let mut memberships = closest_points(points, &centroids);
+    for n_iter in 1.. {
+        let new_centroids = update_points(points, &centroids, &memberships);
+        let shift = centroids
+            .iter()
+            .zip(&new_centroids)
+            .map(|(old, new)| old.sq_distance(new))
+            .sum::<f64>()
+            .sqrt();
+        centroids = new_centroids;
+        if shift < tolerance || n_iter >= max_n_iterations {
+            break;
+        }
+        memberships = closest_points(points, &centroids);
+    }
+    let memberships = closest_points(points, &centroids);
+    (centroids, memberships)
+}
+
+/// Index of the closest centroid of each point
+fn closest_points<P: Point + Sync>(points: &[P], centroids: &[P]) -> Vec<usize> {
+    points
+        .par_iter()
+        .map(|point| {
+            centroids
+                .iter()
+                .enumerate()
+                .fold((0, f64::INFINITY), |(best, min), (i, centroid)| {
+                    let dist = point.sq_distance(centroid);
+                    if dist < min {
+                        (i, dist)
+                    } else {
+                        (best, min)
+                    }
+                })
+                .0
+        })
+        .collect()
+}
+
+/// m_k-means update step: the old centroid counts as one more member of its cluster
+fn update_points<P: Point + Clone>(points: &[P], centroids: &[P], memberships: &[usize]) -> Vec<P> {
+    let mut counts = vec![1usize; centroids.len()];
+    memberships.iter().for_each(|&c| counts[c] += 1);
+
+    // Scale the old centroids down to their share of the new mean
+    let mut new_centroids = centroids.to_vec();
+    for ((new, old), &count) in new_centroids.iter_mut().zip(centroids).zip(&counts) {
+        new.add_assign_scaled(old, 1. / count as f64 - 1.);
+    }
+    for (point, &c) in points.iter().zip(memberships) {
+        new_centroids[c].add_assign_scaled(point, 1. / counts[c] as f64);
+    }
+    new_centroids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KMeans, KMeansInit};
+    use approx::assert_abs_diff_eq;
+    use linfa::prelude::*;
+    use linfa_datasets::generate;
+    use ndarray::{array, Array2};
+    use ndarray_rand::rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    #[test]
+    fn point_impls() {
+        let mut a = [1., 2.];
+        a.add_assign_scaled(&[2., 2.], 0.5);
+        assert_abs_diff_eq!(a.sq_distance(&[0., 0.]), 13.);
+
+        let mut v = vec![1., 2.];
+        v.add_assign_scaled(&vec![2., 2.], 0.5);
+        assert_abs_diff_eq!(v.sq_distance(&vec![0., 0.]), 13.);
+
+        let mut arr = array![1f32, 2.];
+        arr.add_assign_scaled(&array![2., 2.], 0.5);
+        assert_abs_diff_eq!(arr.sq_distance(&array![0., 0.]), 13.);
+    }
+
+    #[test]
+    fn fit_points_matches_kmeans() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 1.], [-10., 20.], [-1., 10.]];
+        let observations = generate::blobs(50, &expected_centroids, &mut rng);
+        let initial = array![[0., 0.], [-5., 15.], [5., 5.]];
+
+        let model = KMeans::params(3)
+            .init_method(KMeansInit::Precomputed(initial.clone()))
+            .n_runs(1)
+            .tolerance(1e-6)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+
+        let points: Vec<[f64; 2]> = observations
+            .rows()
+            .into_iter()
+            .map(|r| [r[0], r[1]])
+            .collect();
+        let initial: Vec<[f64; 2]> = initial.rows().into_iter().map(|r| [r[0], r[1]]).collect();
+        let (centroids, memberships) = fit_points(&points, initial, 300, 1e-6);
+
+        let centroids = Array2::from(centroids);
+        assert_abs_diff_eq!(&centroids, model.centroids(), epsilon = 1e-8);
+        let expected_memberships: ndarray::Array1<usize> = model.predict(&observations);
+        assert_eq!(memberships, expected_memberships.to_vec());
+    }
+}
@@ -0,0 +1,75 @@
+use super::algorithm::KMeans;
+use linfa::Float;
+use linfa_nn::distance::L2Dist;
+
+pub use linfa_kmeans_inference::{KMeansPredictor, PredictorError};
+
+impl<F: Float> KMeans<F, L2Dist> {
+    /// Return the centroids of the model, and its learned feature scaling if any, as a
+    /// [`KMeansPredictor`] of `linfa-kmeans-inference`, which assigns observations to the same
+    /// clusters as [`predict`](linfa::traits::Predict::predict), up to rounding errors, without
+    /// depending on the standard library or on the training dependencies of this crate. This lets
+    /// a model fitted here serve predictions in WebAssembly or on embedded targets, for example
+    /// by saving the predictor with `serde`.
+    pub fn to_predictor(&self) -> KMeansPredictor<F> {
+        KMeansPredictor::new(self.centroids().clone(), self.feature_scaling())
+            .expect("a fitted model has centroids with the features of its feature weights")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{KMeans, KMeansAlgorithm};
+    use linfa::prelude::*;
+    use linfa_datasets::generate;
+    use ndarray::Array;
+    use ndarray_rand::rand::SeedableRng;
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    #[test]
+    fn predictor_agrees_with_predict() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        for n_features in [1, 2, 5, 12] {
+            for n_clusters in [1, 3, 8] {
+                let centroids = Array::random_using(
+                    (n_clusters, n_features),
+                    Uniform::new(-20., 20.),
+                    &mut rng,
+                );
+                let observations = generate::blobs(40, &centroids, &mut rng);
+                let dataset = DatasetBase::from(observations.clone());
+                // Observations away from the training ones too
+                let new_observations =
+                    Array::random_using((200, n_features), Uniform::new(-30., 30.), &mut rng);
+
+                for algorithm in [
+                    KMeansAlgorithm::Lloyd,
+                    KMeansAlgorithm::WeightedFeatures { beta: 2. },
+                ] {
+                    let model = KMeans::params_with_rng(n_clusters, rng.clone())
+                        .algorithm(algorithm.clone())
+                        .fit(&dataset)
+                        .unwrap();
+                    let predictor = model.to_predictor();
+                    assert_eq!(predictor.centroids(), model.centroids());
+                    assert_eq!(
+                        predictor.scale().is_some(),
+                        model.feature_weights().is_some()
+                    );
+                    for observations in [&observations, &new_observations] {
+                        assert_eq!(
+                            predictor.predict(observations),
+                            model.predict(observations),
+                            "{} features, {} clusters, {:?}",
+                            n_features,
+                            n_clusters,
+                            algorithm
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use super::algorithm::KMeans;
+use linfa::Float;
+use linfa_nn::distance::L2Dist;
+
+impl<F: Float> KMeans<F, L2Dist> {
+    /// Returns the [Voronoi cell](https://en.wikipedia.org/wiki/Voronoi_diagram) of each centroid
+    /// of a model with 2 features, clipped to the box going from the `(x, y)` corner `bounds.0` to
+    /// the `(x, y)` corner `bounds.1`.
+    ///
+    /// The cell of a centroid is the region of the plane in which observations are assigned to
+    /// that centroid by `predict`. Each cell is a convex polygon, returned as a list of vertices in
+    /// counter-clockwise order, and the cells of all centroids tile the bounding box. A cell is
+    /// empty if its centroid is a duplicate of a previous centroid, or if it lies entirely outside
+    /// the bounding box.
+    ///
+    /// Panics if the model doesn't have exactly 2 features.
+    pub fn voronoi_cells(&self, bounds: ((F, F), (F, F))) -> Vec<Vec<(F, F)>> {
+        let centroids = self.centroids();
+        assert_eq!(
+            centroids.ncols(),
+            2,
+            "Voronoi cells can only be computed for 2-dimensional models."
+        );
+        let ((x_min, y_min), (x_max, y_max)) = bounds;
+        let (x_min, x_max) = (x_min.min(x_max), x_min.max(x_max));
+        let (y_min, y_max) = (y_min.min(y_max), y_min.max(y_max));
+        let bounding_box = vec![
+            (x_min, y_min),
+            (x_max, y_min),
+            (x_max, y_max),
+            (x_min, y_max),
+        ];
+
+        let centroids: Vec<(F, F)> = centroids.rows().into_iter().map(|c| (c[0], c[1])).collect();
+        centroids
+            .iter()
+            .enumerate()
+            .map(|(i, &(xi, yi))| {
+                centroids.iter().enumerate().filter(|&(j, _)| j != i).fold(
+                    bounding_box.clone(),
+                    |cell, (j, &(xj, yj))| {
+                        if (xi, yi) == (xj, yj) {
+                            // Ties go to the centroid with the lowest index, as in `predict`
+                            return if j < i { Vec::new() } else { cell };
+                        }
+                        // Points closer to centroid i than to centroid j lie on the side of the
+                        // perpendicular bisector where `a * x + b * y <= c`
+                        let (a, b) = (xj - xi, yj - yi);
+                        let c = (xj * xj + yj * yj - xi * xi - yi * yi) / F::cast(2.);
+                        clip(&cell, a, b, c)
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Clips the convex polygon `polygon` to the half-plane `a * x + b * y <= c`
+/// (Sutherland-Hodgman algorithm with a single clipping edge)
+fn clip<F: Float>(polygon: &[(F, F)], a: F, b: F, c: F) -> Vec<(F, F)> {
+    let side = |&(x, y): &(F, F)| a * x + b * y - c;
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (k, current) in polygon.iter().enumerate() {
+        let next = &polygon[(k + 1) % polygon.len()];
+        let (side_current, side_next) = (side(current), side(next));
+        if side_current <= F::zero() {
+            clipped.push(*current);
+        }
+        if (side_current < F::zero()) != (side_next < F::zero())
+            && side_current != F::zero()
+            && side_next != F::zero()
+        {
+            // The edge crosses the bisector
+            let t = side_current / (side_current - side_next);
+            clipped.push((
+                current.0 + t * (next.0 - current.0),
+                current.1 + t * (next.1 - current.1),
+            ));
+        }
+    }
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{KMeans, KMeansInit};
+    use approx::assert_abs_diff_eq;
+    use linfa::prelude::*;
+    use linfa_datasets::generate;
+    use ndarray::{array, Array1, Array2};
+    use ndarray_rand::rand::SeedableRng;
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+    use rand_xoshiro::Xoshiro256Plus;
+
+    fn area(polygon: &[(f64, f64)]) -> f64 {
+        (0..polygon.len())
+            .map(|k| {
+                let (x0, y0) = polygon[k];
+                let (x1, y1) = polygon[(k + 1) % polygon.len()];
+                x0 * y1 - x1 * y0
+            })
+            .sum::<f64>()
+            / 2.
+    }
+
+    fn contains(polygon: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+        (0..polygon.len()).all(|k| {
+            let (x0, y0) = polygon[k];
+            let (x1, y1) = polygon[(k + 1) % polygon.len()];
+            (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0) >= -1e-9
+        })
+    }
+
+    #[test]
+    fn cells_tile_the_bounding_box() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = Array2::random_using((8, 2), Uniform::new(-10., 10.), &mut rng);
+        let observations = generate::blobs(20, &expected_centroids, &mut rng);
+        let model = KMeans::params_with_rng(8, rng.clone())
+            .fit(&DatasetBase::from(observations))
+            .unwrap();
+
+        let bounds = ((-15., -12.), (15., 12.));
+        let cells = model.voronoi_cells(bounds);
+        assert_eq!(cells.len(), 8);
+        let total: f64 = cells.iter().map(|cell| area(cell)).sum();
+        assert_abs_diff_eq!(total, 30. * 24., epsilon = 1e-8);
+
+        // Each cell contains the points assigned to its centroid
+        let points = Array2::random_using((200, 2), Uniform::new(-12., 12.), &mut rng);
+        let memberships: Array1<usize> = model.predict(&points);
+        for (point, &c) in points.rows().into_iter().zip(&memberships) {
+            assert!(contains(&cells[c], (point[0], point[1])));
+        }
+    }
+
+    #[test]
+    fn duplicate_centroids() {
+        // The update step leaves these centroids in place
+        let centroids = array![[0., 0.], [0., 0.], [4., 0.]];
+        let model = KMeans::params(3)
+            .init_method(KMeansInit::Precomputed(centroids.clone()))
+            .max_n_iterations(1)
+            .n_runs(1)
+            .fit(&DatasetBase::from(centroids.clone()))
+            .unwrap();
+        assert_abs_diff_eq!(model.centroids(), &centroids);
+
+        let cells = model.voronoi_cells(((-2., -2.), (6., 2.)));
+        assert_abs_diff_eq!(area(&cells[0]), 16., epsilon = 1e-10);
+        assert!(cells[1].is_empty());
+        assert_abs_diff_eq!(area(&cells[2]), 16., epsilon = 1e-10);
+    }
+}
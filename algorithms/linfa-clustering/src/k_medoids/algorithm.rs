@@ -0,0 +1,437 @@
+use crate::k_medoids::errors::KMedoidsError;
+use crate::k_medoids::hyperparams::{KMedoidsParams, KMedoidsValidParams};
+use linfa::prelude::*;
+use linfa::{DatasetBase, Float};
+use linfa_nn::distance::{Distance, L2Dist};
+use ndarray::{Array1, Array2, ArrayBase, ArrayView2, Axis, Data, Ix2, Zip};
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// K-medoids clusters observations around `n_clusters` of them, the medoids, chosen to minimize
+/// the sum of the distances of the observations to their closest medoid.
+///
+/// Unlike the centroids of [K-means](crate::KMeans), medoids are actual observations, so the
+/// algorithm only needs the distances between observations: it works with any dissimilarity,
+/// including ones which are not metrics or a mean makes no sense for, and is less sensitive to
+/// outliers than K-means with the squared euclidean distance.
+///
+/// ## The algorithm
+///
+/// This is the PAM (Partitioning Around Medoids) algorithm of Kaufman and Rousseeuw, with the
+/// swap evaluation of FastPAM1 by Schubert and Rousseeuw:
+///
+/// 1. BUILD: medoids are chosen greedily, the first being the observation with the lowest total
+///    distance to the others, and each next one the observation which decreases it the most.
+/// 2. SWAP: among all the swaps of a medoid with another observation, the one which decreases the
+///    total distance the most is applied, until no swap decreases it or `max_n_iterations` swaps
+///    were made.
+///
+/// Both phases are deterministic. Each swap takes `O(n_observations^2)` time, spread across
+/// threads, and the BUILD phase `O(n_clusters * n_observations^2)`. The distances between all
+/// pairs of observations are kept in memory, either computed from the observations by
+/// [`Fit::fit`], or given by [`fit_precomputed`](KMedoidsValidParams::fit_precomputed).
+///
+/// ## Tutorial
+///
+/// ```rust
+/// use linfa::DatasetBase;
+/// use linfa::traits::Fit;
+/// use linfa_clustering::KMedoids;
+/// use linfa_datasets::generate;
+/// use ndarray::array;
+/// use ndarray_rand::rand::SeedableRng;
+/// use rand_xoshiro::Xoshiro256Plus;
+///
+/// let mut rng = Xoshiro256Plus::seed_from_u64(42);
+/// let expected_centroids = array![[0., 1.], [-10., 20.], [-1., 10.]];
+/// let observations = generate::blobs(100, &expected_centroids, &mut rng);
+///
+/// let model = KMedoids::params(3)
+///     .fit(&DatasetBase::from(observations.clone()))
+///     .expect("KMedoids fitted");
+/// // The medoids are observations, given by their index
+/// let medoids = model.medoids(&observations);
+/// assert_eq!(medoids.nrows(), 3);
+/// assert_eq!(model.labels().len(), 300);
+/// ```
+pub struct KMedoids<F> {
+    /// Index of the observation of each medoid
+    medoid_indices: Vec<usize>,
+    /// Cluster of each training observation, the index of its closest medoid
+    labels: Array1<usize>,
+    /// Sum of the distances of the training observations to their closest medoid
+    cost: F,
+    /// Number of swaps made by the SWAP phase
+    n_iterations: u64,
+}
+
+impl<F: Float> KMedoids<F> {
+    /// Configures the hyperparameters with the number of clusters and the euclidean distance
+    pub fn params(n_clusters: usize) -> KMedoidsParams<F, L2Dist> {
+        KMedoidsParams::new(n_clusters, L2Dist)
+    }
+
+    /// Configures the hyperparameters with the number of clusters and a custom distance metric
+    pub fn params_with<D: Distance<F>>(n_clusters: usize, dist_fn: D) -> KMedoidsParams<F, D> {
+        KMedoidsParams::new(n_clusters, dist_fn)
+    }
+
+    /// Return the index of the training observation of each medoid, in the order of the clusters
+    pub fn medoid_indices(&self) -> &[usize] {
+        &self.medoid_indices
+    }
+
+    /// Return the medoids as rows of the training `observations`, with shape
+    /// `(n_clusters, n_features)`
+    pub fn medoids(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> Array2<F> {
+        observations.select(Axis(0), &self.medoid_indices)
+    }
+
+    /// Return the cluster of each training observation, that of its closest medoid
+    pub fn labels(&self) -> &Array1<usize> {
+        &self.labels
+    }
+
+    /// Return the sum of the distances of the training observations to their closest medoid
+    pub fn cost(&self) -> F {
+        self.cost
+    }
+
+    /// Return the number of swaps made by the SWAP phase
+    pub fn n_iterations(&self) -> u64 {
+        self.n_iterations
+    }
+}
+
+impl<F: Float, D: Distance<F>> KMedoidsValidParams<F, D> {
+    /// Fit K-medoids on the matrix of the `distances` between observations, where
+    /// `distances[[i, j]]` is the dissimilarity of observation `i` to observation `j` as a
+    /// medoid. The matrix doesn't need to be symmetric, and `dist_fn` is ignored.
+    ///
+    /// Returns an error if the matrix isn't square, if it has a negative or non-finite entry, or
+    /// if there are fewer observations than clusters.
+    pub fn fit_precomputed(
+        &self,
+        distances: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Result<KMedoids<F>, KMedoidsError> {
+        let (n_rows, n_cols) = distances.dim();
+        if n_rows != n_cols {
+            return Err(KMedoidsError::NotSquare { n_rows, n_cols });
+        }
+        if let Some(((row, col), _)) = distances
+            .indexed_iter()
+            .find(|(_, &d)| !d.is_finite() || d < F::zero())
+        {
+            return Err(KMedoidsError::InvalidDistance { row, col });
+        }
+        if n_rows == 0 {
+            return Err(KMedoidsError::EmptyObservations);
+        }
+        if n_rows < self.n_clusters() {
+            return Err(KMedoidsError::NotEnoughObservations {
+                n_observations: n_rows,
+                n_clusters: self.n_clusters(),
+            });
+        }
+
+        let distances = distances.view();
+        let mut medoids = build(distances, self.n_clusters());
+        let mut nearest = Nearest::new(distances, &medoids);
+        let mut n_iterations = 0;
+        while n_iterations < self.max_n_iterations() {
+            match best_swap(distances, &medoids, &nearest) {
+                // Rounding errors could otherwise swap back and forth between equivalent medoids
+                Some((delta, cluster, candidate)) if delta < -F::epsilon() * nearest.cost() => {
+                    medoids[cluster] = candidate;
+                    nearest = Nearest::new(distances, &medoids);
+                    n_iterations += 1;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(KMedoids {
+            cost: nearest.cost(),
+            labels: nearest.first,
+            medoid_indices: medoids,
+            n_iterations,
+        })
+    }
+}
+
+impl<F: Float, R: Data<Elem = F>, T, D: Distance<F>> Fit<ArrayBase<R, Ix2>, T, KMedoidsError>
+    for KMedoidsValidParams<F, D>
+{
+    type Object = KMedoids<F>;
+
+    /// Fit K-medoids on the observations of `dataset`, with the distances between all pairs of
+    /// observations measured by `dist_fn`, see
+    /// [`fit_precomputed`](KMedoidsValidParams::fit_precomputed).
+    fn fit(
+        &self,
+        dataset: &DatasetBase<ArrayBase<R, Ix2>, T>,
+    ) -> Result<Self::Object, KMedoidsError> {
+        let observations = dataset.records().view();
+        let n_samples = observations.nrows();
+        let mut distances = Array2::zeros((n_samples, n_samples));
+        Zip::from(distances.rows_mut())
+            .and(observations.rows())
+            .par_for_each(|mut row, observation| {
+                Zip::from(&mut row)
+                    .and(observations.rows())
+                    .for_each(|d, other| *d = self.dist_fn().distance(observation, other));
+            });
+        self.fit_precomputed(&distances)
+    }
+}
+
+// Greedy initialization of the medoids: each medoid is the observation which decreases the total
+// distance to the closest medoid the most, starting from no medoid at an infinite distance
+fn build<F: Float>(distances: ArrayView2<F>, n_clusters: usize) -> Vec<usize> {
+    let n_samples = distances.nrows();
+    let mut closest = Array1::from_elem(n_samples, F::infinity());
+    let mut medoids = Vec::with_capacity(n_clusters);
+    for _ in 0..n_clusters {
+        // The total distance after adding each candidate, the lowest index winning ties
+        let (_, medoid) = (0..n_samples)
+            .into_par_iter()
+            .filter(|candidate| !medoids.contains(candidate))
+            .map(|candidate| {
+                let total = Zip::from(&closest)
+                    .and(distances.column(candidate))
+                    .fold(F::zero(), |acc, &c, &d| acc + c.min(d));
+                (total, candidate)
+            })
+            .reduce_with(lowest)
+            .expect("there are more observations than medoids");
+        Zip::from(&mut closest)
+            .and(distances.column(medoid))
+            .for_each(|c, &d| *c = c.min(d));
+        medoids.push(medoid);
+    }
+    medoids
+}
+
+// Best swap of a medoid with another observation, as the change of the total distance along with
+// the cluster of the medoid and the observation replacing it. Lower changes win, then lower
+// observations.
+fn best_swap<F: Float>(
+    distances: ArrayView2<F>,
+    medoids: &[usize],
+    nearest: &Nearest<F>,
+) -> Option<(F, usize, usize)> {
+    (0..distances.nrows())
+        .into_par_iter()
+        .filter(|candidate| !medoids.contains(candidate))
+        .map(|candidate| {
+            // Change of the total distance common to the removal of any medoid, for the
+            // observations closer to the candidate than to their medoid, and the extra change of
+            // removing each medoid, for its members which can't move to the candidate
+            let mut common = F::zero();
+            let mut removal = vec![F::zero(); medoids.len()];
+            Zip::from(distances.column(candidate))
+                .and(&nearest.first)
+                .and(&nearest.first_dist)
+                .and(&nearest.second_dist)
+                .for_each(|&d, &first, &first_dist, &second_dist| {
+                    let gain = (d - first_dist).min(F::zero());
+                    common += gain;
+                    removal[first] += d.min(second_dist) - first_dist - gain;
+                });
+            let (change, cluster) = removal
+                .into_iter()
+                .enumerate()
+                .map(|(cluster, change)| (common + change, cluster))
+                .reduce(lowest)
+                .expect("there is at least one medoid");
+            ((change, candidate), cluster)
+        })
+        .reduce_with(|a, b| if lowest(a.0, b.0) == b.0 { b } else { a })
+        .map(|((change, candidate), cluster)| (change, cluster, candidate))
+}
+
+// The lowest of two pairs of a value and an index, by value then by index
+fn lowest<F: Float>(a: (F, usize), b: (F, usize)) -> (F, usize) {
+    if b.0 < a.0 || (b.0 == a.0 && b.1 < a.1) {
+        b
+    } else {
+        a
+    }
+}
+
+// Closest and second closest medoid of each observation, by cluster, along with their distances
+struct Nearest<F> {
+    first: Array1<usize>,
+    first_dist: Array1<F>,
+    second_dist: Array1<F>,
+}
+
+impl<F: Float> Nearest<F> {
+    fn new(distances: ArrayView2<F>, medoids: &[usize]) -> Self {
+        let n_samples = distances.nrows();
+        let mut nearest = Self {
+            first: Array1::zeros(n_samples),
+            first_dist: Array1::zeros(n_samples),
+            second_dist: Array1::zeros(n_samples),
+        };
+        Zip::from(distances.rows())
+            .and(&mut nearest.first)
+            .and(&mut nearest.first_dist)
+            .and(&mut nearest.second_dist)
+            .par_for_each(|row, first, first_dist, second_dist| {
+                *first_dist = F::infinity();
+                *second_dist = F::infinity();
+                for (cluster, &medoid) in medoids.iter().enumerate() {
+                    let d = row[medoid];
+                    if d < *first_dist {
+                        *second_dist = *first_dist;
+                        *first_dist = d;
+                        *first = cluster;
+                    } else if d < *second_dist {
+                        *second_dist = d;
+                    }
+                }
+            });
+        nearest
+    }
+
+    // Sum of the distances of the observations to their closest medoid
+    fn cost(&self) -> F {
+        self.first_dist.sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KMedoidsParamsError;
+    use approx::assert_abs_diff_eq;
+    use linfa_datasets::generate;
+    use linfa_nn::distance::L1Dist;
+    use ndarray::array;
+    use ndarray_rand::rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256Plus;
+
+    #[test]
+    fn autotraits() {
+        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
+        has_autotraits::<KMedoids<f64>>();
+        has_autotraits::<KMedoidsError>();
+        has_autotraits::<KMedoidsParams<f64, L2Dist>>();
+        has_autotraits::<KMedoidsValidParams<f64, L2Dist>>();
+    }
+
+    #[test]
+    fn recovers_blobs() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let expected_centroids = array![[0., 0.], [20., 0.], [0., 20.]];
+        let observations = generate::blobs(50, &expected_centroids, &mut rng);
+        let model = KMedoids::params_with(3, L1Dist)
+            .fit(&DatasetBase::from(observations.clone()))
+            .unwrap();
+
+        // `blobs` generates the observations of each blob contiguously
+        let mut blobs: Vec<_> = model.medoid_indices().iter().map(|&i| i / 50).collect();
+        blobs.sort_unstable();
+        assert_eq!(blobs, vec![0, 1, 2]);
+        for (blob, labels) in model.labels().exact_chunks(50).into_iter().enumerate() {
+            let medoid = model.medoid_indices()[labels[0]];
+            assert_eq!(medoid / 50, blob);
+            assert!(labels.iter().all(|&l| l == labels[0]));
+        }
+        let medoids = model.medoids(&observations);
+        let cost = observations
+            .rows()
+            .into_iter()
+            .fold(0., |acc, observation| {
+                acc + medoids
+                    .rows()
+                    .into_iter()
+                    .map(|medoid| L1Dist.distance(observation, medoid))
+                    .fold(f64::INFINITY, f64::min)
+            });
+        assert_abs_diff_eq!(model.cost(), cost, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn stops_at_a_local_optimum() {
+        // Asymmetric dissimilarities without any geometric meaning
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let distances =
+            Array2::from_shape_fn(
+                (8, 8),
+                |(i, j)| {
+                    if i == j {
+                        0.
+                    } else {
+                        rng.gen_range(1.0..10.0)
+                    }
+                },
+            );
+        let model = KMedoids::<f64>::params(2)
+            .check()
+            .unwrap()
+            .fit_precomputed(&distances)
+            .unwrap();
+
+        let cost = |a: usize, b: usize| {
+            distances
+                .rows()
+                .into_iter()
+                .map(|row| row[a].min(row[b]))
+                .sum::<f64>()
+        };
+        // PAM stops at a local optimum, which no swap of a medoid with another observation
+        // improves
+        let (a, b) = (model.medoid_indices()[0], model.medoid_indices()[1]);
+        assert_abs_diff_eq!(model.cost(), cost(a, b), epsilon = 1e-10);
+        for c in (0..8).filter(|&c| c != a && c != b) {
+            assert!(cost(c, b) >= model.cost() && cost(a, c) >= model.cost());
+        }
+        for (i, &label) in model.labels().iter().enumerate() {
+            let medoid = model.medoid_indices()[label];
+            assert_eq!(
+                distances[[i, medoid]],
+                distances[[i, a]].min(distances[[i, b]])
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let params = KMedoids::<f64>::params(2).check().unwrap();
+        assert!(matches!(
+            params.fit_precomputed(&Array2::zeros((2, 3))),
+            Err(KMedoidsError::NotSquare {
+                n_rows: 2,
+                n_cols: 3
+            })
+        ));
+        assert!(matches!(
+            params.fit_precomputed(&array![[0., -1.], [1., 0.]]),
+            Err(KMedoidsError::InvalidDistance { row: 0, col: 1 })
+        ));
+        assert!(matches!(
+            params.fit_precomputed(&array![[0.]]),
+            Err(KMedoidsError::NotEnoughObservations {
+                n_observations: 1,
+                n_clusters: 2
+            })
+        ));
+        assert!(matches!(
+            params.fit(&DatasetBase::from(Array2::<f64>::zeros((0, 2)))),
+            Err(KMedoidsError::EmptyObservations)
+        ));
+        assert!(matches!(
+            KMedoids::<f64>::params(0).fit(&DatasetBase::from(array![[0.]])),
+            Err(KMedoidsError::InvalidParams(KMedoidsParamsError::NClusters))
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+use thiserror::Error;
+
+/// An error when fitting with an invalid hyperparameter
+#[derive(Error, Debug)]
+pub enum KMedoidsParamsError {
+    #[error("n_clusters cannot be 0")]
+    NClusters,
+    #[error("max_n_iterations cannot be 0")]
+    MaxIterations,
+}
+
+/// An error when fitting a K-medoids model
+#[derive(Error, Debug)]
+pub enum KMedoidsError {
+    /// When any of the hyperparameters are set the wrong value
+    #[error("Invalid hyperparameter: {0}")]
+    InvalidParams(#[from] KMedoidsParamsError),
+    /// When fitting on a dataset without any observation
+    #[error("Fitting failed: No observations")]
+    EmptyObservations,
+    /// When fitting on fewer observations than clusters
+    #[error("Fitting failed: {n_observations} observations for {n_clusters} clusters")]
+    NotEnoughObservations {
+        n_observations: usize,
+        n_clusters: usize,
+    },
+    /// When the precomputed distance matrix isn't square
+    #[error("Invalid distances: expected a square matrix, got {n_rows} rows and {n_cols} columns")]
+    NotSquare { n_rows: usize, n_cols: usize },
+    /// When a distance is negative or not finite
+    #[error("Invalid distances: the distance between observations {row} and {col} is negative or not finite")]
+    InvalidDistance { row: usize, col: usize },
+    #[error(transparent)]
+    LinfaError(#[from] linfa::error::Error),
+}
@@ -0,0 +1,114 @@
+use crate::k_medoids::errors::KMedoidsParamsError;
+use linfa::{Float, ParamGuard};
+use linfa_nn::distance::Distance;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// The set of hyperparameters that can be specified for the execution of
+/// the [K-medoids algorithm](crate::KMedoids).
+pub struct KMedoidsValidParams<F: Float, D: Distance<F>> {
+    /// The number of clusters we will be looking for in the training dataset.
+    n_clusters: usize,
+    /// Maximum number of swaps of the SWAP phase
+    max_n_iterations: u64,
+    /// Distance metric between observations, when not fitting on precomputed distances
+    dist_fn: D,
+    phantom: PhantomData<F>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// An helper struct used to construct a set of [valid hyperparameters](KMedoidsParams) for
+/// the [K-medoids algorithm](crate::KMedoids) (using the builder pattern).
+pub struct KMedoidsParams<F: Float, D: Distance<F>>(KMedoidsValidParams<F, D>);
+
+impl<F: Float, D: Distance<F>> KMedoidsParams<F, D> {
+    /// `new` lets us configure our training algorithm parameters:
+    /// * we will be looking for `n_clusters` in the training dataset;
+    /// * the distance between observations is measured with `dist_fn`.
+    ///
+    /// Defaults are provided if optional parameters are not specified:
+    /// * `max_n_iterations = 100`
+    pub fn new(n_clusters: usize, dist_fn: D) -> Self {
+        Self(KMedoidsValidParams {
+            n_clusters,
+            max_n_iterations: 100,
+            dist_fn,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Change the value of `max_n_iterations`, the maximum number of swaps between a medoid and
+    /// another observation
+    pub fn max_n_iterations(mut self, max_n_iterations: u64) -> Self {
+        self.0.max_n_iterations = max_n_iterations;
+        self
+    }
+
+    /// Change the value of `dist_fn`
+    pub fn dist_fn(mut self, dist_fn: D) -> Self {
+        self.0.dist_fn = dist_fn;
+        self
+    }
+}
+
+impl<F: Float, D: Distance<F>> ParamGuard for KMedoidsParams<F, D> {
+    type Checked = KMedoidsValidParams<F, D>;
+    type Error = KMedoidsParamsError;
+
+    fn check_ref(&self) -> Result<&Self::Checked, Self::Error> {
+        if self.0.n_clusters == 0 {
+            Err(KMedoidsParamsError::NClusters)
+        } else if self.0.max_n_iterations == 0 {
+            Err(KMedoidsParamsError::MaxIterations)
+        } else {
+            Ok(&self.0)
+        }
+    }
+
+    fn check(self) -> Result<Self::Checked, Self::Error> {
+        self.check_ref()?;
+        Ok(self.0)
+    }
+}
+
+impl<F: Float, D: Distance<F>> KMedoidsValidParams<F, D> {
+    /// The number of clusters we will be looking for in the training dataset.
+    pub fn n_clusters(&self) -> usize {
+        self.n_clusters
+    }
+
+    /// Maximum number of swaps of the SWAP phase
+    pub fn max_n_iterations(&self) -> u64 {
+        self.max_n_iterations
+    }
+
+    /// Distance metric between observations
+    pub fn dist_fn(&self) -> &D {
+        &self.dist_fn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{KMedoids, KMedoidsParamsError};
+    use linfa::ParamGuard;
+
+    #[test]
+    fn n_clusters_cannot_be_zero() {
+        let res = KMedoids::<f64>::params(0).check();
+        assert!(matches!(res, Err(KMedoidsParamsError::NClusters)));
+    }
+
+    #[test]
+    fn max_n_iterations_cannot_be_zero() {
+        let res = KMedoids::<f64>::params(2).max_n_iterations(0).check();
+        assert!(matches!(res, Err(KMedoidsParamsError::MaxIterations)));
+    }
+}
@@ -14,23 +14,38 @@
 //!
 //! Right now `linfa-clustering` provides the following clustering algorithms:
 //! * [K-Means](KMeans)
+//! * [K-Medoids](KMedoids)
+//! * [Fuzzy C-Means](FuzzyCMeans)
 //! * [DBSCAN](Dbscan)
 //! * [Approximated DBSCAN](AppxDbscan) (Currently an alias for DBSCAN, due to its superior
 //! performance)
 //! * [Gaussian-Mixture-Model](GaussianMixtureModel)
 //! * [OPTICS](OpticsAnalysis)
+//! * [Spectral clustering](SpectralClustering)
+//!
+//! It also provides measures of the agreement of a clustering with ground-truth classes, such as
+//! the [adjusted Rand index](adjusted_rand_index) and the
+//! [normalized mutual information](normalized_mutual_info), to benchmark them on labeled datasets.
 //!
 //! Implementation choices, algorithmic details and tutorials can be found in the page dedicated to the specific algorithms.
 mod dbscan;
+mod fuzzy_c_means;
 mod gaussian_mixture;
 #[allow(clippy::new_ret_no_self)]
 mod k_means;
+mod k_medoids;
 mod optics;
+mod spectral;
+mod supervised_metrics;
 
 pub use dbscan::*;
+pub use fuzzy_c_means::*;
 pub use gaussian_mixture::*;
 pub use k_means::*;
+pub use k_medoids::*;
 pub use optics::*;
+pub use spectral::*;
+pub use supervised_metrics::*;
 
 // Approx DBSCAN is currently an alias for DBSCAN, due to the old Approx DBSCAN implementation's
 // lower performance and outdated dependencies
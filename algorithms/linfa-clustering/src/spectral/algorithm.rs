@@ -0,0 +1,350 @@
+use crate::spectral::errors::SpectralClusteringError;
+use crate::spectral::hyperparams::{
+    Affinity, SpectralClusteringParams, SpectralClusteringValidParams,
+};
+use crate::KMeans;
+use linfa::prelude::*;
+use linfa::{DatasetBase, Float};
+use linfa_linalg::eigh::{EigSort, EighInto};
+use linfa_nn::distance::{Distance, L2Dist};
+use linfa_nn::{CommonNearestNeighbour, NearestNeighbour};
+use ndarray::{s, Array1, Array2, ArrayBase, ArrayView2, Axis, Data, Ix2};
+use ndarray_rand::rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256Plus;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// Spectral clustering partitions observations by the connected regions of their affinity
+/// graph, rather than around centroids. It separates clusters of any shape, such as nested rings
+/// or interleaving half circles, which [K-means](crate::KMeans) can't, as long as the clusters are
+/// more connected within themselves than with each other.
+///
+/// ## The algorithm
+///
+/// This is the normalized spectral clustering of Ng, Jordan and Weiss:
+///
+/// 1. The affinity matrix `A` between all pairs of observations is built with the chosen
+///    [`Affinity`], and normalized into `D^(-1/2) A D^(-1/2)`, where `D` is the diagonal matrix of
+///    the degrees (row sums) of `A`.
+/// 2. The observations are embedded in `n_clusters` dimensions by the eigenvectors of the
+///    `n_clusters` largest eigenvalues of the normalized affinity, which are the eigenvectors of
+///    the smallest eigenvalues of the normalized graph Laplacian. Each row of the embedding is
+///    scaled to unit length.
+/// 3. K-means clusters the rows of the embedding, and the observations get the labels of their
+///    rows.
+///
+/// The affinity matrix is dense, and its full eigendecomposition takes `O(n_observations^3)`
+/// time, so this suits datasets of a few thousand observations. The clustering is transductive:
+/// it labels the training observations only, and new observations can't be assigned to the
+/// clusters without fitting them again.
+///
+/// ## Tutorial
+///
+/// ```rust
+/// use linfa::DatasetBase;
+/// use linfa::traits::Fit;
+/// use linfa_clustering::{Affinity, SpectralClustering};
+/// use linfa_datasets::generate;
+/// use ndarray_rand::rand::SeedableRng;
+/// use rand_xoshiro::Xoshiro256Plus;
+///
+/// let mut rng = Xoshiro256Plus::seed_from_u64(42);
+/// let (observations, expected) = generate::moons(200, 0.05, &mut rng);
+///
+/// let model = SpectralClustering::params(2)
+///     .affinity(Affinity::NearestNeighbours { n_neighbours: 10 })
+///     .fit(&DatasetBase::from(observations))
+///     .expect("SpectralClustering fitted");
+/// // The two half circles are told apart, up to a permutation of the labels
+/// let labels = model.labels();
+/// let agreement = labels.iter().zip(&expected).filter(|(l, e)| l == e).count();
+/// assert!(agreement == 0 || agreement == 200);
+/// ```
+pub struct SpectralClustering<F> {
+    /// Cluster of each training observation
+    labels: Array1<usize>,
+    /// Spectral embedding of the training observations, with rows of unit length
+    embedding: Array2<F>,
+    /// Largest eigenvalues of the normalized affinity matrix, in decreasing order
+    eigenvalues: Array1<F>,
+}
+
+impl<F: Float> SpectralClustering<F> {
+    /// Configures the hyperparameters with the number of clusters, and the random number
+    /// generator of K-means seeded with 42
+    pub fn params(n_clusters: usize) -> SpectralClusteringParams<F, Xoshiro256Plus> {
+        SpectralClusteringParams::new(n_clusters, Xoshiro256Plus::seed_from_u64(42))
+    }
+
+    /// Configures the hyperparameters with the number of clusters and the random number
+    /// generator of K-means
+    pub fn params_with_rng<R: Rng>(n_clusters: usize, rng: R) -> SpectralClusteringParams<F, R> {
+        SpectralClusteringParams::new(n_clusters, rng)
+    }
+
+    /// Return the cluster of each training observation
+    pub fn labels(&self) -> &Array1<usize> {
+        &self.labels
+    }
+
+    /// Return the spectral embedding of the training observations clustered by K-means, with
+    /// shape `(n_observations, n_clusters)`
+    pub fn embedding(&self) -> &Array2<F> {
+        &self.embedding
+    }
+
+    /// Return the `n_clusters` largest eigenvalues of the normalized affinity matrix, in
+    /// decreasing order. They are 1 for each connected component of the affinity graph, and a
+    /// large gap after the `k`-th eigenvalue hints at `k` well separated clusters.
+    pub fn eigenvalues(&self) -> &Array1<F> {
+        &self.eigenvalues
+    }
+}
+
+impl<F: Float, R: Rng + Clone, D: Data<Elem = F>, T>
+    Fit<ArrayBase<D, Ix2>, T, SpectralClusteringError> for SpectralClusteringValidParams<F, R>
+{
+    type Object = SpectralClustering<F>;
+
+    /// Cluster the observations of `dataset`, see [`SpectralClustering`].
+    ///
+    /// Returns an error if there are fewer observations than clusters, if the observations have
+    /// no features or a NaN or infinite value, or if the nearest neighbours affinity asks for as
+    /// many neighbours as observations.
+    fn fit(
+        &self,
+        dataset: &DatasetBase<ArrayBase<D, Ix2>, T>,
+    ) -> Result<Self::Object, SpectralClusteringError> {
+        let observations = dataset.records().view();
+        let (n_observations, n_features) = observations.dim();
+        let n_clusters = self.n_clusters();
+        if n_observations == 0 {
+            return Err(SpectralClusteringError::EmptyObservations);
+        } else if n_observations < n_clusters {
+            return Err(SpectralClusteringError::NotEnoughObservations {
+                n_observations,
+                n_clusters,
+            });
+        } else if n_features == 0 {
+            return Err(SpectralClusteringError::NoFeatures);
+        }
+        if let Some(index) = observations
+            .rows()
+            .into_iter()
+            .position(|observation| observation.iter().any(|x| !x.is_finite()))
+        {
+            return Err(SpectralClusteringError::NonFiniteObservation { index });
+        }
+
+        let affinity = self.affinity_matrix(observations)?;
+        // Every observation has an affinity of 1 with itself, so the degrees are at least 1
+        let inv_sqrt_degrees = affinity
+            .sum_axis(Axis(1))
+            .mapv(|degree| F::one() / degree.sqrt());
+        let normalized = affinity
+            * inv_sqrt_degrees.view().insert_axis(Axis(1))
+            * inv_sqrt_degrees.view().insert_axis(Axis(0));
+        let (eigenvalues, eigenvectors) = normalized.eigh_into()?.sort_eig_desc();
+
+        let mut embedding = eigenvectors.slice_move(s![.., ..n_clusters]);
+        for mut row in embedding.rows_mut() {
+            let norm = row.dot(&row).sqrt();
+            if norm > F::zero() {
+                row.mapv_inplace(|x| x / norm);
+            }
+        }
+
+        let kmeans = KMeans::params_with_rng(n_clusters, self.rng().clone())
+            .n_runs(self.n_runs())
+            .fit(&DatasetBase::from(embedding.view()))?;
+        let labels = kmeans.predict(&embedding);
+
+        Ok(SpectralClustering {
+            labels,
+            embedding,
+            eigenvalues: eigenvalues.slice_move(s![..n_clusters]),
+        })
+    }
+}
+
+impl<F: Float, R: Rng> SpectralClusteringValidParams<F, R> {
+    /// Symmetric affinity matrix between all pairs of observations, with a diagonal of 1
+    fn affinity_matrix(
+        &self,
+        observations: ArrayView2<F>,
+    ) -> Result<Array2<F>, SpectralClusteringError> {
+        let n_observations = observations.nrows();
+        match *self.affinity() {
+            Affinity::Rbf { gamma } => Ok(Array2::from_shape_fn(
+                (n_observations, n_observations),
+                |(i, j)| {
+                    let sq_dist = L2Dist.rdistance(observations.row(i), observations.row(j));
+                    (-gamma * sq_dist).exp()
+                },
+            )),
+            Affinity::NearestNeighbours { n_neighbours } => {
+                if n_neighbours >= n_observations {
+                    return Err(SpectralClusteringError::TooManyNeighbours {
+                        n_neighbours,
+                        n_observations,
+                    });
+                }
+                // The index needs contiguous rows
+                let observations = observations.as_standard_layout();
+                let nn = CommonNearestNeighbour::KdTree
+                    .from_batch(&observations, L2Dist)
+                    .expect("the observations have features");
+
+                let mut affinity = Array2::eye(n_observations);
+                for (i, observation) in observations.rows().into_iter().enumerate() {
+                    // The observation itself is among its nearest neighbours, unless it has
+                    // duplicates tied with it
+                    let neighbours = nn
+                        .k_nearest(observation, n_neighbours + 1)
+                        .expect("the observation has the dimension of the index");
+                    for (_, j) in neighbours
+                        .into_iter()
+                        .filter(|&(_, j)| j != i)
+                        .take(n_neighbours)
+                    {
+                        affinity[(i, j)] = F::one();
+                        affinity[(j, i)] = F::one();
+                    }
+                }
+                Ok(affinity)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SpectralClusteringParamsError;
+    use linfa_datasets::generate;
+    use ndarray::array;
+
+    #[test]
+    fn autotraits() {
+        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
+        has_autotraits::<SpectralClustering<f64>>();
+        has_autotraits::<SpectralClusteringParams<f64, Xoshiro256Plus>>();
+        has_autotraits::<SpectralClusteringValidParams<f64, Xoshiro256Plus>>();
+        has_autotraits::<Affinity<f64>>();
+        has_autotraits::<SpectralClusteringParamsError>();
+        has_autotraits::<SpectralClusteringError>();
+    }
+
+    // Whether the labels match the expected ones up to a permutation, for two clusters
+    fn separates(labels: &Array1<usize>, expected: &Array1<usize>) -> bool {
+        let agreement = labels.iter().zip(expected).filter(|(l, e)| l == e).count();
+        agreement == 0 || agreement == labels.len()
+    }
+
+    #[test]
+    fn separates_moons() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let (observations, expected) = generate::moons(300, 0.05, &mut rng);
+        let dataset = DatasetBase::from(observations);
+
+        let model = SpectralClustering::params(2)
+            .affinity(Affinity::NearestNeighbours { n_neighbours: 10 })
+            .fit(&dataset)
+            .unwrap();
+        assert!(separates(model.labels(), &expected));
+        assert_eq!(model.embedding().dim(), (300, 2));
+        // The two half circles are almost disconnected components
+        assert!(model.eigenvalues()[1] > 0.99);
+
+        // The half circles aren't convex, so K-means can't separate them
+        let kmeans = KMeans::params(2).fit(&dataset).unwrap();
+        assert!(!separates(&kmeans.predict(&dataset), &expected));
+    }
+
+    #[test]
+    fn separates_circles() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let (observations, expected) = generate::circles(200, 0.3, 0.03, &mut rng);
+
+        let model = SpectralClustering::params(2)
+            .affinity(Affinity::Rbf { gamma: 50. })
+            .fit(&DatasetBase::from(observations))
+            .unwrap();
+        assert!(separates(model.labels(), &expected));
+    }
+
+    #[test]
+    fn invalid_inputs() {
+        let params = SpectralClustering::<f64>::params(2);
+        let res = params.fit(&DatasetBase::from(Array2::<f64>::zeros((0, 2))));
+        assert!(matches!(
+            res,
+            Err(SpectralClusteringError::EmptyObservations)
+        ));
+        let res = params.fit(&DatasetBase::from(array![[1., 1.]]));
+        assert!(matches!(
+            res,
+            Err(SpectralClusteringError::NotEnoughObservations {
+                n_observations: 1,
+                n_clusters: 2
+            })
+        ));
+        let res = params.fit(&DatasetBase::from(Array2::<f64>::zeros((3, 0))));
+        assert!(matches!(res, Err(SpectralClusteringError::NoFeatures)));
+
+        let res = params
+            .affinity(Affinity::NearestNeighbours { n_neighbours: 3 })
+            .fit(&DatasetBase::from(array![[1., 1.], [2., 1.], [3., 1.]]));
+        assert!(matches!(
+            res,
+            Err(SpectralClusteringError::TooManyNeighbours {
+                n_neighbours: 3,
+                n_observations: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn non_finite_observations() {
+        let observations = array![[0., 0.], [1., 0.], [f64::NAN, 1.], [5., 5.]];
+        for affinity in [
+            Affinity::Rbf { gamma: 1. },
+            Affinity::NearestNeighbours { n_neighbours: 2 },
+        ] {
+            let res = SpectralClustering::<f64>::params(2)
+                .affinity(affinity)
+                .fit(&DatasetBase::from(observations.clone()));
+            assert!(matches!(
+                res,
+                Err(SpectralClusteringError::NonFiniteObservation { index: 2 })
+            ));
+        }
+    }
+
+    #[test]
+    fn column_major_observations() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let (observations, _) = generate::moons(100, 0.05, &mut rng);
+        let fortran = observations
+            .t()
+            .as_standard_layout()
+            .into_owned()
+            .reversed_axes();
+        assert!(!fortran.is_standard_layout());
+        for affinity in [
+            Affinity::Rbf { gamma: 10. },
+            Affinity::NearestNeighbours { n_neighbours: 10 },
+        ] {
+            let params = SpectralClustering::params_with_rng(2, rng.clone()).affinity(affinity);
+            let expected = params.fit(&DatasetBase::from(observations.view())).unwrap();
+            let model = params.fit(&DatasetBase::from(fortran.view())).unwrap();
+            assert_eq!(model.labels(), expected.labels());
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use thiserror::Error;
+
+/// An error when fitting with an invalid hyperparameter
+#[derive(Error, Debug)]
+pub enum SpectralClusteringParamsError {
+    #[error("n_clusters cannot be 0")]
+    NClusters,
+    #[error("n_runs cannot be 0")]
+    NRuns,
+    #[error("gamma of the RBF affinity must be positive and finite")]
+    Gamma,
+    #[error("the number of neighbours of the nearest neighbours affinity cannot be 0")]
+    NNeighbours,
+}
+
+/// An error when fitting a spectral clustering
+#[derive(Error, Debug)]
+pub enum SpectralClusteringError {
+    /// When any of the hyperparameters are set the wrong value
+    #[error("Invalid hyperparameter: {0}")]
+    InvalidParams(#[from] SpectralClusteringParamsError),
+    /// When fitting on a dataset without any observation
+    #[error("Fitting failed: No observations")]
+    EmptyObservations,
+    /// When fitting on fewer observations than clusters
+    #[error("Fitting failed: {n_observations} observations for {n_clusters} clusters")]
+    NotEnoughObservations {
+        n_observations: usize,
+        n_clusters: usize,
+    },
+    /// When the nearest neighbours affinity asks for as many neighbours as there are observations
+    #[error("Fitting failed: {n_neighbours} neighbours among {n_observations} observations")]
+    TooManyNeighbours {
+        n_neighbours: usize,
+        n_observations: usize,
+    },
+    /// When fitting on observations without any feature
+    #[error("Fitting failed: the observations have no features")]
+    NoFeatures,
+    /// When an observation has a NaN or infinite feature
+    #[error("Invalid observations: observation {index} has a non-finite value")]
+    NonFiniteObservation { index: usize },
+    /// When the eigendecomposition of the affinity matrix fails
+    #[error(transparent)]
+    LinalgError(#[from] linfa_linalg::LinalgError),
+    /// When clustering the spectral embedding fails
+    #[error(transparent)]
+    KMeansError(#[from] crate::KMeansError),
+    #[error(transparent)]
+    LinfaError(#[from] linfa::error::Error),
+}
@@ -0,0 +1,162 @@
+use crate::spectral::errors::SpectralClusteringParamsError;
+use linfa::{Float, ParamGuard};
+use ndarray_rand::rand::Rng;
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// Affinity between observations of [spectral clustering](crate::SpectralClustering)
+pub enum Affinity<F: Float> {
+    /// Gaussian (RBF) kernel `exp(-gamma * ||x - y||^2)`, connecting all pairs of observations.
+    /// `gamma` must be positive, and is best chosen around the inverse of the squared distance
+    /// between neighbouring observations.
+    Rbf { gamma: F },
+    /// Connectivity of the `n_neighbours` nearest neighbours graph: two observations have an
+    /// affinity of 1 if one of them is among the nearest neighbours of the other, and 0 otherwise.
+    /// This doesn't depend on the scale of the observations, and keeps the affinity matrix sparse.
+    NearestNeighbours { n_neighbours: usize },
+}
+
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Clone, Debug, PartialEq)]
+/// The set of hyperparameters that can be specified for the execution of
+/// [spectral clustering](crate::SpectralClustering).
+pub struct SpectralClusteringValidParams<F: Float, R: Rng> {
+    /// The number of clusters we will be looking for in the training dataset.
+    n_clusters: usize,
+    /// Affinity between observations
+    affinity: Affinity<F>,
+    /// Number of runs of K-means on the spectral embedding
+    n_runs: usize,
+    /// Random number generator of K-means
+    rng: R,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// An helper struct used to construct a set of [valid hyperparameters](SpectralClusteringParams)
+/// for [spectral clustering](crate::SpectralClustering) (using the builder pattern).
+pub struct SpectralClusteringParams<F: Float, R: Rng>(SpectralClusteringValidParams<F, R>);
+
+impl<F: Float, R: Rng> SpectralClusteringParams<F, R> {
+    /// `new` lets us configure our training algorithm parameters:
+    /// * we will be looking for `n_clusters` in the training dataset;
+    /// * the spectral embedding is clustered by K-means, seeded with `rng`.
+    ///
+    /// Defaults are provided if optional parameters are not specified:
+    /// * `affinity = Affinity::Rbf { gamma: 1.0 }`
+    /// * `n_runs = 10`
+    pub fn new(n_clusters: usize, rng: R) -> Self {
+        Self(SpectralClusteringValidParams {
+            n_clusters,
+            affinity: Affinity::Rbf { gamma: F::one() },
+            n_runs: 10,
+            rng,
+        })
+    }
+
+    /// Change the value of `affinity`
+    pub fn affinity(mut self, affinity: Affinity<F>) -> Self {
+        self.0.affinity = affinity;
+        self
+    }
+
+    /// Change the value of `n_runs`, the number of runs of K-means on the spectral embedding
+    pub fn n_runs(mut self, n_runs: usize) -> Self {
+        self.0.n_runs = n_runs;
+        self
+    }
+}
+
+impl<F: Float, R: Rng> ParamGuard for SpectralClusteringParams<F, R> {
+    type Checked = SpectralClusteringValidParams<F, R>;
+    type Error = SpectralClusteringParamsError;
+
+    fn check_ref(&self) -> Result<&Self::Checked, Self::Error> {
+        if self.0.n_clusters == 0 {
+            Err(SpectralClusteringParamsError::NClusters)
+        } else if self.0.n_runs == 0 {
+            Err(SpectralClusteringParamsError::NRuns)
+        } else {
+            match self.0.affinity {
+                Affinity::Rbf { gamma } if !(gamma.is_finite() && gamma > F::zero()) => {
+                    Err(SpectralClusteringParamsError::Gamma)
+                }
+                Affinity::NearestNeighbours { n_neighbours: 0 } => {
+                    Err(SpectralClusteringParamsError::NNeighbours)
+                }
+                _ => Ok(&self.0),
+            }
+        }
+    }
+
+    fn check(self) -> Result<Self::Checked, Self::Error> {
+        self.check_ref()?;
+        Ok(self.0)
+    }
+}
+
+impl<F: Float, R: Rng> SpectralClusteringValidParams<F, R> {
+    /// The number of clusters we will be looking for in the training dataset.
+    pub fn n_clusters(&self) -> usize {
+        self.n_clusters
+    }
+
+    /// Affinity between observations
+    pub fn affinity(&self) -> &Affinity<F> {
+        &self.affinity
+    }
+
+    /// Number of runs of K-means on the spectral embedding
+    pub fn n_runs(&self) -> usize {
+        self.n_runs
+    }
+
+    /// Random number generator of K-means
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Affinity, SpectralClustering, SpectralClusteringParamsError};
+    use linfa::ParamGuard;
+
+    #[test]
+    fn n_clusters_cannot_be_zero() {
+        let res = SpectralClustering::<f64>::params(0).check();
+        assert!(matches!(res, Err(SpectralClusteringParamsError::NClusters)));
+    }
+
+    #[test]
+    fn n_runs_cannot_be_zero() {
+        let res = SpectralClustering::<f64>::params(2).n_runs(0).check();
+        assert!(matches!(res, Err(SpectralClusteringParamsError::NRuns)));
+    }
+
+    #[test]
+    fn invalid_affinity() {
+        for gamma in [0., -1., f64::INFINITY, f64::NAN] {
+            let res = SpectralClustering::params(2)
+                .affinity(Affinity::Rbf { gamma })
+                .check();
+            assert!(matches!(res, Err(SpectralClusteringParamsError::Gamma)));
+        }
+        let res = SpectralClustering::<f64>::params(2)
+            .affinity(Affinity::NearestNeighbours { n_neighbours: 0 })
+            .check();
+        assert!(matches!(
+            res,
+            Err(SpectralClusteringParamsError::NNeighbours)
+        ));
+    }
+}
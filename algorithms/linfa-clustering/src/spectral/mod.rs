@@ -0,0 +1,7 @@
+mod algorithm;
+mod errors;
+mod hyperparams;
+
+pub use algorithm::*;
+pub use errors::*;
+pub use hyperparams::*;
@@ -0,0 +1,277 @@
+//! Measures of the agreement of a clustering with ground-truth classes, to benchmark clustering
+//! algorithms on labeled datasets.
+//!
+//! Each function takes the classes `labels_true` and the clusters `labels_pred` of the same
+//! observations, as `usize` labels that don't need to be contiguous, like
+//! [`contingency_matrix`], and panics if they have different lengths. None of them depends on how
+//! either labeling is numbered.
+use crate::contingency_matrix;
+use crate::k_means::min_cost_matching;
+use ndarray::{Array2, ArrayBase, Axis, Data, Ix1};
+
+/// Returns the [adjusted Rand index](https://en.wikipedia.org/wiki/Rand_index#Adjusted_Rand_index)
+/// of the clusters `labels_pred` against the classes `labels_true`: the fraction of the pairs of
+/// observations on which both labelings agree, whether they put the pair together or apart,
+/// corrected for the agreement expected of random labelings with the same cluster sizes.
+///
+/// It is 1 for identical labelings up to a permutation, around 0 for random ones, and can be
+/// negative. Like scikit-learn, it is 1 when both labelings put all the observations in a single
+/// cluster, or each observation in its own cluster, and without observations.
+pub fn adjusted_rand_index(
+    labels_true: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    labels_pred: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> f64 {
+    let contingency = contingency_matrix(labels_true, labels_pred);
+    let pairs = |count: &usize| (count * count.saturating_sub(1) / 2) as f64;
+    let index: f64 = contingency.iter().map(pairs).sum();
+    let true_pairs: f64 = contingency.sum_axis(Axis(1)).iter().map(pairs).sum();
+    let pred_pairs: f64 = contingency.sum_axis(Axis(0)).iter().map(pairs).sum();
+    let max_index = (true_pairs + pred_pairs) / 2.;
+    // Without any pair in the same cluster, including with fewer than two observations
+    if max_index == 0. {
+        return 1.;
+    }
+    let expected = true_pairs * pred_pairs / pairs(&labels_true.len());
+    if max_index == expected {
+        return 1.;
+    }
+    (index - expected) / (max_index - expected)
+}
+
+/// Returns the [normalized mutual information](https://en.wikipedia.org/wiki/Mutual_information#Normalized_variants)
+/// of the clusters `labels_pred` and the classes `labels_true`, their mutual information divided
+/// by the arithmetic mean of their entropies, which is also the V-measure, the harmonic mean of
+/// [`homogeneity`] and [`completeness`].
+///
+/// It lies between 0 for independent labelings and 1 for identical ones up to a permutation.
+/// Like scikit-learn, it is 1 when both labelings put all the observations in a single cluster,
+/// and without observations.
+pub fn normalized_mutual_info(
+    labels_true: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    labels_pred: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> f64 {
+    let entropies = Entropies::new(&contingency_matrix(labels_true, labels_pred));
+    let mean = (entropies.classes + entropies.clusters) / 2.;
+    if mean == 0. {
+        return 1.;
+    }
+    entropies.mutual_info / mean
+}
+
+/// Returns the homogeneity of the clusters `labels_pred` with respect to the classes
+/// `labels_true`, `1 - H(classes | clusters) / H(classes)` in terms of entropies: 1 when each
+/// cluster only holds members of a single class, whatever the number of clusters, and 0 when the
+/// clusters tell nothing about the classes. It is 1 with a single class.
+///
+/// See [Rosenberg and Hirschberg (2007)](https://aclanthology.org/D07-1043/).
+pub fn homogeneity(
+    labels_true: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    labels_pred: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> f64 {
+    let entropies = Entropies::new(&contingency_matrix(labels_true, labels_pred));
+    if entropies.classes == 0. {
+        return 1.;
+    }
+    entropies.mutual_info / entropies.classes
+}
+
+/// Returns the completeness of the clusters `labels_pred` with respect to the classes
+/// `labels_true`, `1 - H(clusters | classes) / H(clusters)` in terms of entropies: 1 when all
+/// the members of each class are in the same cluster, and 0 when the classes tell nothing about
+/// the clusters. It is the [`homogeneity`] of the classes with respect to the clusters, and is 1
+/// with a single cluster.
+pub fn completeness(
+    labels_true: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    labels_pred: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> f64 {
+    homogeneity(labels_pred, labels_true)
+}
+
+/// Returns the class of `labels_true` best matching each cluster of `labels_pred`, so that
+/// `labels_pred.mapv(|c| matching[c])` relabels the clusters with the classes, for example to
+/// compute a confusion matrix or an accuracy.
+///
+/// Clusters and classes are matched one-to-one to maximize the number of observations whose
+/// cluster is matched with their class, exactly by the
+/// [Hungarian algorithm](https://en.wikipedia.org/wiki/Hungarian_algorithm) over the
+/// [`contingency_matrix`], in `O(n^3)` for `n` clusters or classes, whichever is more numerous.
+/// With more clusters than classes, the clusters left without a class go to the class of most
+/// of their members, the lowest one in case of a tie or without members. The matching has one
+/// entry per cluster, the largest label of `labels_pred` plus one.
+pub fn match_labels(
+    labels_true: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+    labels_pred: &ArrayBase<impl Data<Elem = usize>, Ix1>,
+) -> Vec<usize> {
+    let contingency = contingency_matrix(labels_pred, labels_true);
+    let (n_clusters, n_classes) = contingency.dim();
+    let n = n_clusters.max(n_classes);
+    // Padded with zeros to a square matrix, an overlap to maximize being a cost to minimize
+    let costs = Array2::from_shape_fn((n, n), |(c, k)| {
+        contingency.get((c, k)).map_or(0., |&count| -(count as f64))
+    });
+    let matching = min_cost_matching(&costs);
+    (0..n_clusters)
+        .map(|c| {
+            if matching[c] < n_classes {
+                return matching[c];
+            }
+            let row = contingency.row(c);
+            (0..n_classes).rev().max_by_key(|&k| row[k]).unwrap_or(0)
+        })
+        .collect()
+}
+
+// Entropies of the classes and of the clusters of a contingency matrix with the classes along
+// the rows, and their mutual information, in nats
+struct Entropies {
+    classes: f64,
+    clusters: f64,
+    mutual_info: f64,
+}
+
+impl Entropies {
+    fn new(contingency: &Array2<usize>) -> Self {
+        let n = contingency.sum() as f64;
+        let entropy = |counts: &[usize]| {
+            counts
+                .iter()
+                .filter(|&&count| count > 0)
+                .map(|&count| {
+                    let p = count as f64 / n;
+                    -p * p.ln()
+                })
+                .sum::<f64>()
+        };
+        let class_counts = contingency.sum_axis(Axis(1));
+        let cluster_counts = contingency.sum_axis(Axis(0));
+        let mutual_info = contingency
+            .indexed_iter()
+            .filter(|(_, &count)| count > 0)
+            .map(|((k, c), &count)| {
+                let count = count as f64;
+                count / n * (count * n / (class_counts[k] * cluster_counts[c]) as f64).ln()
+            })
+            .sum::<f64>();
+        Entropies {
+            classes: entropy(class_counts.as_slice().unwrap()),
+            clusters: entropy(cluster_counts.as_slice().unwrap()),
+            // Rounding errors can take it slightly below 0
+            mutual_info: mutual_info.max(0.),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use ndarray::{array, Array1};
+
+    #[test]
+    fn perfect_and_permuted_labelings() {
+        let labels_true = array![0, 0, 1, 1, 2, 2];
+        // Same partition with other, gapped, labels
+        let labels_pred = array![5, 5, 0, 0, 3, 3];
+        for metric in [
+            adjusted_rand_index,
+            normalized_mutual_info,
+            homogeneity,
+            completeness,
+        ] {
+            assert_abs_diff_eq!(metric(&labels_true, &labels_true), 1., epsilon = 1e-12);
+            assert_abs_diff_eq!(metric(&labels_true, &labels_pred), 1., epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn hand_computed_metrics() {
+        // Values of scikit-learn's metrics on the same labelings
+        let labels_true = array![0, 0, 0, 1, 1, 1];
+        let labels_pred = array![0, 0, 1, 1, 2, 2];
+        assert_abs_diff_eq!(
+            adjusted_rand_index(&labels_true, &labels_pred),
+            0.24242424242424243,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            homogeneity(&labels_true, &labels_pred),
+            0.6666666666666669,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            completeness(&labels_true, &labels_pred),
+            0.420619835714305,
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            normalized_mutual_info(&labels_true, &labels_pred),
+            0.5158037429793889,
+            epsilon = 1e-12
+        );
+        // Splitting the classes keeps the clusters homogeneous but not complete
+        let split = array![0, 1, 2, 3, 4, 5];
+        assert_abs_diff_eq!(homogeneity(&labels_true, &split), 1., epsilon = 1e-12);
+        assert!(completeness(&labels_true, &split) < 1.);
+        assert_eq!(adjusted_rand_index(&labels_true, &split), 0.);
+        // Clusters independent of the classes
+        let independent = array![0, 1, 2, 0, 1, 2];
+        assert_abs_diff_eq!(
+            normalized_mutual_info(&labels_true, &independent),
+            0.,
+            epsilon = 1e-12
+        );
+        assert!(adjusted_rand_index(&labels_true, &independent) < 0.);
+    }
+
+    #[test]
+    fn degenerate_labelings() {
+        let single = array![0, 0, 0, 0];
+        let singletons = array![0, 1, 2, 3];
+        assert_eq!(adjusted_rand_index(&single, &single), 1.);
+        assert_eq!(adjusted_rand_index(&singletons, &singletons), 1.);
+        assert_eq!(adjusted_rand_index(&single, &singletons), 0.);
+        assert_eq!(normalized_mutual_info(&single, &single), 1.);
+        assert_eq!(normalized_mutual_info(&single, &singletons), 0.);
+        assert_eq!(homogeneity(&single, &singletons), 1.);
+        assert_eq!(completeness(&single, &singletons), 0.);
+
+        let empty = Array1::<usize>::zeros(0);
+        assert_eq!(adjusted_rand_index(&empty, &empty), 1.);
+        assert_eq!(normalized_mutual_info(&empty, &empty), 1.);
+        assert!(match_labels(&empty, &empty).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn mismatched_lengths() {
+        adjusted_rand_index(&array![0, 1], &array![0, 1, 1]);
+    }
+
+    #[test]
+    fn matches_clusters_with_classes() {
+        let labels_true = array![0, 0, 0, 1, 1, 2, 2, 2];
+        let labels_pred = array![2, 2, 1, 0, 0, 1, 1, 1];
+        // Cluster 1 holds most of class 2, even though it also holds a member of class 0
+        let matching = match_labels(&labels_true, &labels_pred);
+        assert_eq!(matching, vec![1, 2, 0]);
+        assert_eq!(
+            labels_pred.mapv(|c| matching[c]),
+            array![0, 0, 2, 1, 1, 2, 2, 2]
+        );
+
+        // Matching cluster 0 with its largest class 0 would leave cluster 1 without any overlap,
+        // instead of overlaps of 2 for both clusters
+        let labels_true = array![0, 0, 0, 1, 1, 0, 0];
+        let labels_pred = array![0, 0, 0, 0, 0, 1, 1];
+        assert_eq!(match_labels(&labels_true, &labels_pred), vec![1, 0]);
+
+        // The extra cluster 3, and the unused label 2, go to their majority class
+        let labels_true = array![0, 0, 1, 1, 1, 1];
+        let labels_pred = array![1, 1, 0, 0, 3, 3];
+        assert_eq!(match_labels(&labels_true, &labels_pred), vec![1, 0, 0, 1]);
+        // With more classes than clusters, some classes are left unmatched
+        let labels_true = array![0, 0, 1, 1, 1, 2];
+        let labels_pred = array![0, 0, 1, 1, 1, 1];
+        assert_eq!(match_labels(&labels_true, &labels_pred), vec![0, 1]);
+    }
+}
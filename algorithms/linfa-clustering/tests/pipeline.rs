@@ -0,0 +1,53 @@
+use linfa::composing::Pipeline;
+use linfa::prelude::*;
+use linfa_clustering::KMeans;
+use linfa_datasets::generate;
+use linfa_preprocessing::linear_scaling::LinearScaler;
+use linfa_reduction::Pca;
+use ndarray::{array, Axis};
+use ndarray_rand::rand::SeedableRng;
+use rand_xoshiro::Xoshiro256Plus;
+
+#[test]
+fn standardize_reduce_and_cluster() {
+    let mut rng = Xoshiro256Plus::seed_from_u64(42);
+    // Three blobs, in features of very different scales
+    let centroids = array![[0., 0., 0., 0.], [10., 10., 0., 0.], [0., 0., 10., 10.]];
+    let mut observations = generate::blobs(100, &centroids, &mut rng);
+    observations.column_mut(0).mapv_inplace(|x| 1000. * x);
+    observations.column_mut(3).mapv_inplace(|x| 0.001 * x);
+    let dataset = DatasetBase::from(observations.clone());
+
+    // standardize -> PCA -> k-means
+    let pipeline = Pipeline::new(LinearScaler::standard(), Pca::params(2))
+        .then(KMeans::params(3).check().unwrap());
+    let model = pipeline.fit(&dataset).unwrap();
+    let clusters = model.predict(&observations);
+
+    // The clusters are the blobs, up to their order
+    for blob in clusters.axis_chunks_iter(Axis(0), 100) {
+        assert!(blob.iter().all(|&c| c == blob[0]), "{}", blob);
+    }
+    let mut firsts: Vec<usize> = (0..3).map(|b| clusters[100 * b]).collect();
+    firsts.sort_unstable();
+    assert_eq!(firsts, vec![0, 1, 2]);
+
+    // The fitted steps are those of the pipeline
+    let reduced = model.step().transform(observations.clone());
+    assert_eq!(reduced.ncols(), 2);
+    assert_eq!(model.model().predict(&reduced), clusters);
+
+    #[cfg(feature = "serde")]
+    {
+        use linfa::composing::FittedPipeline;
+        use linfa_nn::distance::L2Dist;
+
+        // The fitted pipeline is serialized as a unit
+        let serialized = serde_json::to_string(&model).unwrap();
+        let loaded: FittedPipeline<
+            FittedPipeline<LinearScaler<f64>, Pca<f64>>,
+            KMeans<f64, L2Dist>,
+        > = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(loaded.predict(&observations), clusters);
+    }
+}
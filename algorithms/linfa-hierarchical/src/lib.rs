@@ -18,10 +18,8 @@
 //! `linfa-hierarchical` implements agglomerative hierarchical clustering with support of the
 //! [kodama](https://docs.rs/kodama/0.2.3/kodama/) crate.
 
-use std::collections::HashMap;
-
 use kodama::linkage;
-pub use kodama::Method;
+pub use kodama::{Method, Step};
 
 use linfa::param_guard::TransformGuard;
 use linfa::traits::Transformer;
@@ -108,13 +106,91 @@ impl<F: Float> HierarchicalCluster<F> {
     }
 }
 
-impl<F: Float> Transformer<Kernel<F>, DatasetBase<Kernel<F>, Vec<usize>>>
-    for ValidHierarchicalCluster<F>
-{
-    /// Perform hierarchical clustering of a similarity matrix
+/// Merging steps of an agglomerative hierarchical clustering
+///
+/// The dendrogram records the whole merging process, from one cluster per point to a single
+/// cluster, so that it can be cut at several heights or numbers of clusters without clustering
+/// again. See [`ValidHierarchicalCluster::dendrogram`](ValidHierarchicalCluster::dendrogram).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dendrogram<F: Float> {
+    num_observations: usize,
+    steps: Vec<Step<F>>,
+}
+
+impl<F: Float> Dendrogram<F> {
+    /// Number of clustered points
+    pub fn num_observations(&self) -> usize {
+        self.num_observations
+    }
+
+    /// Merging steps, in order of increasing distance for all methods but `Centroid` and `Median`
     ///
-    /// Returns the class id for each data point
-    fn transform(&self, kernel: Kernel<F>) -> DatasetBase<Kernel<F>, Vec<usize>> {
+    /// Clusters below `num_observations` are single points, and the cluster formed by step `i`
+    /// has the id `num_observations + i`.
+    pub fn steps(&self) -> &[Step<F>] {
+        &self.steps
+    }
+
+    /// Cut the dendrogram into a certain number of clusters
+    ///
+    /// Merges points until at most `num_clusters` clusters are left, and returns the class id of
+    /// each point. Class ids are numbered in the order of the first point of each cluster.
+    pub fn cut_num_clusters(&self, num_clusters: usize) -> Vec<usize> {
+        let num_steps = self.num_observations.saturating_sub(num_clusters);
+        self.cut(num_steps.min(self.steps.len()))
+    }
+
+    /// Cut the dendrogram at a certain distance
+    ///
+    /// Merges points until the distance between the next two clusters to merge is at least
+    /// `max_distance`, and returns the class id of each point like
+    /// [`cut_num_clusters`](Dendrogram::cut_num_clusters).
+    pub fn cut_at_distance(&self, max_distance: F) -> Vec<usize> {
+        let num_steps = self
+            .steps
+            .iter()
+            .position(|step| step.dissimilarity >= max_distance)
+            .unwrap_or(self.steps.len());
+        self.cut(num_steps)
+    }
+
+    // Class id of each point after the first `num_steps` steps
+    fn cut(&self, num_steps: usize) -> Vec<usize> {
+        // clusters formed by a step always have a higher id than the two merged ones, so going
+        // through the ids backwards resolves the final cluster of every id in one pass
+        let mut root: Vec<usize> = (0..self.num_observations + num_steps).collect();
+        let mut merged_into = vec![None; root.len()];
+        for (i, step) in self.steps[..num_steps].iter().enumerate() {
+            merged_into[step.cluster1] = Some(self.num_observations + i);
+            merged_into[step.cluster2] = Some(self.num_observations + i);
+        }
+        for id in (0..root.len()).rev() {
+            if let Some(parent) = merged_into[id] {
+                root[id] = root[parent];
+            }
+        }
+
+        let mut class_ids = vec![None; root.len()];
+        let mut num_classes = 0;
+        root[..self.num_observations]
+            .iter()
+            .map(|&r| {
+                *class_ids[r].get_or_insert_with(|| {
+                    num_classes += 1;
+                    num_classes - 1
+                })
+            })
+            .collect()
+    }
+}
+
+impl<F: Float> ValidHierarchicalCluster<F> {
+    /// Compute the full dendrogram of a similarity matrix with the merging method
+    ///
+    /// The stopping criterion is ignored, the dendrogram can be cut afterwards with
+    /// [`cut_num_clusters`](Dendrogram::cut_num_clusters) or
+    /// [`cut_at_distance`](Dendrogram::cut_at_distance).
+    pub fn dendrogram(&self, kernel: &Kernel<F>) -> Dendrogram<F> {
         // ignore all similarities below this value
         let threshold = F::cast(1e-6);
 
@@ -135,48 +211,30 @@ impl<F: Float> Transformer<Kernel<F>, DatasetBase<Kernel<F>, Vec<usize>>>
         let num_observations = kernel.size();
         let res = linkage(&mut distance, num_observations, self.method);
 
-        // post-process results, iterate through merging step until threshold is reached
-        // at the beginning every node is in its own cluster
-        let mut clusters = (0..num_observations)
-            .map(|x| (x, vec![x]))
-            .collect::<HashMap<_, _>>();
-
-        // counter for new clusters, which are formed as unions of previous ones
-        let mut ct = num_observations;
-
-        for step in res.steps() {
-            let should_stop = match self.stopping {
-                Criterion::NumClusters(max_clusters) => clusters.len() <= max_clusters,
-                Criterion::Distance(dis) => step.dissimilarity >= dis,
-            };
-
-            // break if one of the two stopping condition is reached
-            if should_stop {
-                break;
-            }
-
-            // combine ids from both clusters
-            let mut ids = Vec::with_capacity(2);
-            let mut cl = clusters.remove(&step.cluster1).unwrap();
-            ids.append(&mut cl);
-            let mut cl = clusters.remove(&step.cluster2).unwrap();
-            ids.append(&mut cl);
-
-            // insert into hashmap and increase counter
-            clusters.insert(ct, ids);
-            ct += 1;
+        Dendrogram {
+            num_observations,
+            steps: res.steps().to_vec(),
         }
+    }
+}
 
-        // flatten resulting clusters and reverse index
-        let mut tmp = vec![0; num_observations];
-        for (i, (_, ids)) in clusters.into_iter().enumerate() {
-            for id in ids {
-                tmp[id] = i;
-            }
-        }
+impl<F: Float> Transformer<Kernel<F>, DatasetBase<Kernel<F>, Vec<usize>>>
+    for ValidHierarchicalCluster<F>
+{
+    /// Perform hierarchical clustering of a similarity matrix
+    ///
+    /// Returns the class id for each data point
+    fn transform(&self, kernel: Kernel<F>) -> DatasetBase<Kernel<F>, Vec<usize>> {
+        let dendrogram = self.dendrogram(&kernel);
+
+        // iterate through merging step until the stopping criterion is reached
+        let ids = match self.stopping {
+            Criterion::NumClusters(max_clusters) => dendrogram.cut_num_clusters(max_clusters),
+            Criterion::Distance(dis) => dendrogram.cut_at_distance(dis),
+        };
 
         // return node_index -> cluster_index map
-        DatasetBase::new(kernel, tmp)
+        DatasetBase::new(kernel, ids)
     }
 }
 
@@ -206,11 +264,12 @@ impl<T: Float> Default for ValidHierarchicalCluster<T> {
 mod tests {
     use crate::HierarchicalError;
     use linfa::traits::Transformer;
+    use linfa::ParamGuard;
     use linfa_kernel::{Kernel, KernelMethod};
     use ndarray::{Array, Axis};
     use ndarray_rand::{rand_distr::Normal, RandomExt};
 
-    use super::{Criterion, HierarchicalCluster, ValidHierarchicalCluster};
+    use super::{Criterion, Dendrogram, HierarchicalCluster, Method, ValidHierarchicalCluster};
 
     #[test]
     fn autotraits() {
@@ -219,6 +278,42 @@ mod tests {
         has_autotraits::<HierarchicalCluster<f64>>();
         has_autotraits::<ValidHierarchicalCluster<f64>>();
         has_autotraits::<HierarchicalError<f64>>();
+        has_autotraits::<Dendrogram<f64>>();
+    }
+
+    #[test]
+    fn test_dendrogram_cuts() {
+        // two nested pairs and an outlier, the gaussian kernel gives squared distances / 1000
+        let entries = ndarray::array![[0.], [1.], [10.], [11.], [100.]];
+        let kernel = Kernel::params()
+            .method(KernelMethod::Gaussian(1000.0))
+            .transform(entries.view());
+
+        let dendrogram = HierarchicalCluster::default()
+            .with_method(Method::Single)
+            .check()
+            .unwrap()
+            .dendrogram(&kernel);
+        assert_eq!(dendrogram.num_observations(), 5);
+        assert_eq!(dendrogram.steps().len(), 4);
+        assert_eq!(dendrogram.steps()[3].size, 5);
+
+        assert_eq!(dendrogram.cut_num_clusters(5), vec![0, 1, 2, 3, 4]);
+        assert_eq!(dendrogram.cut_num_clusters(3), vec![0, 0, 1, 1, 2]);
+        assert_eq!(dendrogram.cut_num_clusters(2), vec![0, 0, 0, 0, 1]);
+        assert_eq!(dendrogram.cut_num_clusters(1), vec![0; 5]);
+        assert_eq!(dendrogram.cut_at_distance(0.0005), vec![0, 1, 2, 3, 4]);
+        assert_eq!(dendrogram.cut_at_distance(0.05), vec![0, 0, 1, 1, 2]);
+        assert_eq!(dendrogram.cut_at_distance(1.0), vec![0, 0, 0, 0, 1]);
+        assert_eq!(dendrogram.cut_at_distance(10.0), vec![0; 5]);
+
+        // the transformation cuts the same dendrogram
+        let ids = HierarchicalCluster::default()
+            .with_method(Method::Single)
+            .num_clusters(3)
+            .transform(kernel)
+            .unwrap();
+        assert_eq!(ids.targets(), &vec![0, 0, 1, 1, 2]);
     }
 
     #[test]
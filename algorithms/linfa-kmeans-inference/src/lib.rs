@@ -0,0 +1,189 @@
+#![doc = include_str!("../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::fmt;
+use ndarray::{Array1, Array2, ArrayBase, Data, Ix1, Ix2, Zip};
+use num_traits::Float;
+
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+/// Error returned when building a [`KMeansPredictor`] from invalid centroids
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PredictorError {
+    /// When there is no centroid, or the centroids have no feature
+    EmptyCentroids,
+    /// When the feature scaling doesn't have one factor per feature of the centroids
+    ScaleLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for PredictorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyCentroids => write!(
+                f,
+                "the predictor needs at least one centroid and one feature"
+            ),
+            Self::ScaleLength { expected, actual } => write!(
+                f,
+                "the feature scaling must have {} factors, got {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PredictorError {}
+
+/// Centroids of a fitted K-means model, which assign observations to the closest of them by the
+/// euclidean distance, like `predict` of the model.
+///
+/// Features can be scaled by a factor each, such as the learned feature weights of
+/// `KMeansAlgorithm::WeightedFeatures`, in which case the distance between `x` and a centroid
+/// `c` is that between `scale * x` and `scale * c`, computed feature by feature.
+///
+/// Predictors are built from the centroids with [`new`](KMeansPredictor::new), or exported from
+/// a fitted model with `KMeans::to_predictor` of `linfa-clustering`.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KMeansPredictor<F> {
+    centroids: Array2<F>,
+    scale: Option<Array1<F>>,
+}
+
+impl<F: Float> KMeansPredictor<F> {
+    /// Create a predictor from `centroids`, with shape `(n_clusters, n_features)`, and the
+    /// optional scaling factor of each feature.
+    ///
+    /// Returns an error if there is no centroid or no feature, or if `scale` doesn't have
+    /// `n_features` factors.
+    pub fn new(centroids: Array2<F>, scale: Option<Array1<F>>) -> Result<Self, PredictorError> {
+        if centroids.is_empty() {
+            return Err(PredictorError::EmptyCentroids);
+        }
+        match &scale {
+            Some(scale) if scale.len() != centroids.ncols() => Err(PredictorError::ScaleLength {
+                expected: centroids.ncols(),
+                actual: scale.len(),
+            }),
+            _ => Ok(Self { centroids, scale }),
+        }
+    }
+
+    /// The centroids, with shape `(n_clusters, n_features)`, before feature scaling
+    pub fn centroids(&self) -> &Array2<F> {
+        &self.centroids
+    }
+
+    /// The scaling factor of each feature, if any
+    pub fn scale(&self) -> Option<&Array1<F>> {
+        self.scale.as_ref()
+    }
+
+    /// Return the index of the centroid closest to `observation`, with shape `(n_features)`,
+    /// along with the **squared** euclidean distance between them. Ties go to the centroid with
+    /// the lowest index.
+    ///
+    /// Panics if `observation` doesn't have `n_features` features.
+    pub fn closest_centroid(
+        &self,
+        observation: &ArrayBase<impl Data<Elem = F>, Ix1>,
+    ) -> (usize, F) {
+        assert_eq!(
+            observation.len(),
+            self.centroids.ncols(),
+            "The observation must have as many features as the centroids."
+        );
+        let mut closest = (0, F::infinity());
+        for (index, centroid) in self.centroids.rows().into_iter().enumerate() {
+            let dist = match &self.scale {
+                Some(scale) => Zip::from(&centroid).and(observation).and(scale).fold(
+                    F::zero(),
+                    |acc, &c, &x, &s| {
+                        let diff = s * (x - c);
+                        acc + diff * diff
+                    },
+                ),
+                None => Zip::from(&centroid)
+                    .and(observation)
+                    .fold(F::zero(), |acc, &c, &x| acc + (x - c) * (x - c)),
+            };
+            // A NaN distance never wins, so that an observation with a NaN feature goes to 0
+            if dist < closest.1 {
+                closest = (index, dist);
+            }
+        }
+        closest
+    }
+
+    /// Return the index of the closest centroid of each observation of `observations`, with
+    /// shape `(n_observations, n_features)`, see
+    /// [`closest_centroid`](KMeansPredictor::closest_centroid).
+    ///
+    /// Panics if the observations don't have `n_features` features.
+    pub fn predict(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> Array1<usize> {
+        observations
+            .rows()
+            .into_iter()
+            .map(|observation| self.closest_centroid(&observation).0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn closest_centroids() {
+        let predictor = KMeansPredictor::new(array![[0., 0.], [4., 0.], [0., 4.]], None).unwrap();
+        assert_eq!(predictor.closest_centroid(&array![1., 1.]), (0, 2.));
+        assert_eq!(predictor.closest_centroid(&array![3., 1.]), (1, 2.));
+        // Equidistant to the first two centroids
+        assert_eq!(predictor.closest_centroid(&array![2., 0.]), (0, 4.));
+        assert_eq!(
+            predictor.predict(&array![[1., 1.], [3., 1.], [1., 5.]]),
+            array![0, 1, 2]
+        );
+        assert_eq!(predictor.closest_centroid(&array![f64::NAN, 0.]).0, 0);
+    }
+
+    #[test]
+    fn scaled_features() {
+        let centroids = array![[0., 0.], [4., 1.]];
+        // Without scaling, (2.5, 0) is closer to the second centroid
+        let plain = KMeansPredictor::new(centroids.clone(), None).unwrap();
+        assert_eq!(plain.closest_centroid(&array![2.5, 0.]), (1, 3.25));
+        // Squeezing the first feature makes the second one decide
+        let scaled = KMeansPredictor::new(centroids, Some(array![0.5, 2.])).unwrap();
+        assert_eq!(scaled.closest_centroid(&array![2.5, 0.]), (0, 1.5625));
+    }
+
+    #[test]
+    fn invalid_centroids() {
+        assert_eq!(
+            KMeansPredictor::<f64>::new(Array2::zeros((0, 2)), None),
+            Err(PredictorError::EmptyCentroids)
+        );
+        assert_eq!(
+            KMeansPredictor::new(array![[0., 0.]], Some(array![1.])),
+            Err(PredictorError::ScaleLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn wrong_number_of_features() {
+        let predictor = KMeansPredictor::new(array![[0., 0.]], None).unwrap();
+        predictor.closest_centroid(&array![0.]);
+    }
+}
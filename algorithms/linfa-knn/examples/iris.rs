@@ -0,0 +1,27 @@
+use linfa::metrics::ToConfusionMatrix;
+use linfa::traits::{Fit, Predict};
+use linfa_knn::{KnnParams, KnnWeights, Result};
+use rand_xoshiro::{rand_core::SeedableRng, Xoshiro256Plus};
+
+fn main() -> Result<()> {
+    // Read in the dataset, sorted by class, and shuffle it before splitting it
+    let mut rng = Xoshiro256Plus::seed_from_u64(42);
+    let (train, valid) = linfa_datasets::iris()
+        .shuffle(&mut rng)
+        .split_with_ratio(0.8);
+
+    // Train the model
+    let model = KnnParams::classifier(5)
+        .weights(KnnWeights::Distance)
+        .fit(&train)?;
+
+    // Predict the validation dataset
+    let pred = model.predict(&valid);
+
+    // Construct confusion matrix
+    let cm = pred.confusion_matrix(&valid)?;
+    println!("{:?}", cm);
+    println!("accuracy {}, MCC {}", cm.accuracy(), cm.mcc());
+
+    Ok(())
+}
@@ -0,0 +1,295 @@
+use crate::error::{KnnError, Result};
+use crate::hyperparams::{Classification, KnnValidParams};
+use crate::neighbours::Neighbours;
+use linfa::dataset::{AsSingleTargets, DatasetBase, Labels};
+use linfa::traits::{Fit, PredictInplace};
+use linfa::{Float, Label};
+use linfa_nn::distance::Distance;
+use linfa_nn::NearestNeighbour;
+use ndarray::{Array1, Array2, ArrayBase, Data, Ix2};
+
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+impl<F, L, D, N, DA, T> Fit<ArrayBase<DA, Ix2>, T, KnnError>
+    for KnnValidParams<F, D, N, Classification>
+where
+    F: Float,
+    L: Label,
+    D: Distance<F>,
+    N: NearestNeighbour + Clone,
+    DA: Data<Elem = F>,
+    T: AsSingleTargets<Elem = L> + Labels<Elem = L>,
+{
+    type Object = KnnClassifier<F, L, D, N>;
+
+    fn fit(&self, dataset: &DatasetBase<ArrayBase<DA, Ix2>, T>) -> Result<Self::Object> {
+        let neighbours = Neighbours::new(self, dataset.records())?;
+        let mut classes = dataset.labels();
+        classes.sort();
+        let targets = dataset
+            .as_single_targets()
+            .map(|label| classes.binary_search(label).unwrap());
+        Ok(KnnClassifier {
+            neighbours,
+            classes,
+            targets,
+        })
+    }
+}
+
+/// Fitted K-nearest neighbours classifier.
+///
+/// See [`KnnParams`](crate::KnnParams) for more information on the hyper-parameters.
+///
+/// # Model
+///
+/// The classifier keeps its training records, and predicts the class of an observation by a
+/// vote of its `n_neighbours` closest training records, according to the distance metric. Each
+/// neighbour votes for its class with its weight, see [`KnnWeights`](crate::KnnWeights), and ties
+/// go to the lowest class.
+///
+/// Fitting checks and copies the training records, and builds the spatial index of the nearest
+/// neighbour algorithm over them once, which the model keeps along with its own copy of the
+/// records, so that it doesn't borrow them. Cloned models share the records and the index.
+///
+/// # Model usage example
+///
+/// ```rust
+/// use linfa::prelude::*;
+/// use linfa_knn::{KnnParams, Result};
+/// use ndarray::array;
+///
+/// let records = array![[0., 0.], [0., 1.], [1., 0.], [9., 9.], [9., 10.], [10., 9.]];
+/// let targets = array!["near", "near", "near", "far", "far", "far"];
+/// let dataset = Dataset::new(records, targets);
+///
+/// let model = KnnParams::classifier(3).fit(&dataset)?;
+/// let observations = array![[1., 1.], [8., 8.]];
+/// assert_eq!(model.predict(&observations), array!["near", "far"]);
+/// // The classes are sorted, "far" first
+/// assert_eq!(model.predict_proba(&observations), array![[0., 1.], [1., 0.]]);
+/// # Result::Ok(())
+/// ```
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnnClassifier<F: Float, L, D: Distance<F>, N: NearestNeighbour> {
+    neighbours: Neighbours<F, D, N>,
+    // Sorted classes of the training records
+    classes: Vec<L>,
+    // Index of the class of each training record in `classes`
+    targets: Array1<usize>,
+}
+
+impl<F: Float, L: Label, D: Distance<F>, N: NearestNeighbour + Clone> KnnClassifier<F, L, D, N> {
+    /// Classes of the training records, in increasing order, which is that of the columns of
+    /// [`predict_proba`](Self::predict_proba)
+    pub fn classes(&self) -> &[L] {
+        &self.classes
+    }
+
+    /// Returns the probability of each class for each observation, the sum of the weights of
+    /// the neighbours of the observation in that class divided by the total weight of its
+    /// neighbours, with one row per observation and one column per class of
+    /// [`classes`](Self::classes).
+    ///
+    /// Panics if the observations don't have as many features as the training records, or if
+    /// one of them has a NaN or infinite feature.
+    pub fn predict_proba(&self, observations: &ArrayBase<impl Data<Elem = F>, Ix2>) -> Array2<F> {
+        let mut probas = Array2::zeros((observations.nrows(), self.classes.len()));
+        self.neighbours.for_each(observations, |i, neighbours| {
+            let mut row = probas.row_mut(i);
+            for &(position, weight) in neighbours {
+                row[self.targets[position]] += weight;
+            }
+            let total = row.sum();
+            row /= total;
+        });
+        probas
+    }
+}
+
+impl<F, L, D, N, DA> PredictInplace<ArrayBase<DA, Ix2>, Array1<L>> for KnnClassifier<F, L, D, N>
+where
+    F: Float,
+    L: Label,
+    D: Distance<F>,
+    N: NearestNeighbour + Clone,
+    DA: Data<Elem = F>,
+{
+    /// Predict the class of each observation, the one with the highest
+    /// [probability](KnnClassifier::predict_proba), or the lowest of them in case of a tie
+    fn predict_inplace(&self, observations: &ArrayBase<DA, Ix2>, targets: &mut Array1<L>) {
+        assert_eq!(
+            observations.nrows(),
+            targets.len(),
+            "The number of data points must match the number of output targets."
+        );
+        let probas = self.predict_proba(observations);
+        for (target, row) in targets.iter_mut().zip(probas.rows()) {
+            let best =
+                row.iter()
+                    .enumerate()
+                    .fold((0, F::neg_infinity()), |best, (class, &proba)| {
+                        if proba > best.1 {
+                            (class, proba)
+                        } else {
+                            best
+                        }
+                    });
+            *target = self.classes[best.0].clone();
+        }
+    }
+
+    fn default_target(&self, observations: &ArrayBase<DA, Ix2>) -> Array1<L> {
+        Array1::default(observations.nrows())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KnnParams, KnnWeights};
+    use approx::assert_abs_diff_eq;
+    use linfa::prelude::*;
+    use linfa_nn::distance::{L1Dist, L2Dist};
+    use linfa_nn::CommonNearestNeighbour;
+    use ndarray::array;
+    use rand_xoshiro::{rand_core::SeedableRng, Xoshiro256Plus};
+
+    #[test]
+    fn autotraits() {
+        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
+        has_autotraits::<KnnClassifier<f64, usize, L2Dist, CommonNearestNeighbour>>();
+    }
+
+    #[test]
+    fn votes_of_the_neighbours() {
+        let records = array![[0.], [1.], [2.], [3.], [10.]];
+        let dataset = Dataset::new(records, array![0, 0, 1, 1, 1]);
+        let observations = array![[0.4], [2.4], [9.]];
+
+        let model = KnnParams::classifier(3).fit(&dataset).unwrap();
+        assert_eq!(model.classes(), &[0, 1]);
+        assert_abs_diff_eq!(
+            model.predict_proba(&observations),
+            array![[2. / 3., 1. / 3.], [1. / 3., 2. / 3.], [0., 1.]],
+            epsilon = 1e-12
+        );
+        assert_eq!(model.predict(&observations), array![0, 1, 1]);
+
+        // 1.5 is at 0.5 of records 1 and 2, and 1.5 of records 0 and 3: the classes tie
+        let model = KnnParams::classifier(4).fit(&dataset).unwrap();
+        assert_eq!(model.predict(&array![[1.5]]), array![0]);
+    }
+
+    #[test]
+    fn distance_weights() {
+        let records = array![[0.], [1.], [4.]];
+        let dataset = Dataset::new(records, array![true, true, false]);
+        let model = KnnParams::classifier(3)
+            .weights(KnnWeights::Distance)
+            .fit(&dataset)
+            .unwrap();
+        // Weights 1/3, 1/2 and 1 for the records at distances 3, 2 and 1
+        let total = 1. / 3. + 1. / 2. + 1.;
+        assert_abs_diff_eq!(
+            model.predict_proba(&array![[3.]]),
+            array![[1. / total, (1. / 3. + 1. / 2.) / total]],
+            epsilon = 1e-12
+        );
+        assert_eq!(model.predict(&array![[3.]]), array![false]);
+        // A training record at distance 0 is the only one that counts
+        assert_eq!(model.predict_proba(&array![[1.]]), array![[0., 1.]]);
+    }
+
+    #[test]
+    fn backends_agree() {
+        let mut rng = Xoshiro256Plus::seed_from_u64(42);
+        let (train, valid) = linfa_datasets::iris()
+            .shuffle(&mut rng)
+            .split_with_ratio(0.8);
+        let predictions: Vec<_> = vec![
+            CommonNearestNeighbour::LinearSearch,
+            CommonNearestNeighbour::KdTree,
+            CommonNearestNeighbour::BallTree,
+        ]
+        .into_iter()
+        .map(|nn_algo| {
+            let model = KnnParams::classifier_with(5, L2Dist, nn_algo)
+                .fit(&train)
+                .unwrap();
+            model.predict(&valid)
+        })
+        .collect();
+        assert_eq!(predictions[0], predictions[1]);
+        assert_eq!(predictions[0], predictions[2]);
+        let cm = predictions[0].confusion_matrix(&valid).unwrap();
+        assert!(cm.accuracy() > 0.9, "{}", cm.accuracy());
+
+        // Any metric of `linfa-nn` works with a linear scan
+        let model = KnnParams::classifier_with(5, L1Dist, CommonNearestNeighbour::LinearSearch)
+            .fit(&train)
+            .unwrap();
+        let cm = model.predict(&valid).confusion_matrix(&valid).unwrap();
+        assert!(cm.accuracy() > 0.9, "{}", cm.accuracy());
+    }
+
+    #[test]
+    fn invalid_records() {
+        let dataset = Dataset::new(array![[0.], [1.]], array![0, 1]);
+        let res = KnnParams::classifier(3).fit(&dataset);
+        assert!(matches!(
+            res,
+            Err(KnnError::NotEnoughSamples {
+                n_samples: 2,
+                n_neighbours: 3
+            })
+        ));
+        let dataset = Dataset::new(array![[0.], [f64::NAN]], array![0, 1]);
+        let res = KnnParams::classifier(1).fit(&dataset);
+        assert!(matches!(res, Err(KnnError::NonFiniteRecord { index: 1 })));
+        let dataset = Dataset::new(Array2::<f64>::zeros((2, 0)), array![0, 1]);
+        let res = KnnParams::classifier(1).fit(&dataset);
+        assert!(matches!(res, Err(KnnError::Build(_))));
+    }
+
+    #[test]
+    fn index_outlives_the_training_data() {
+        let fit = || {
+            let dataset = Dataset::new(array![[0.], [1.], [9.], [10.]], array![0, 0, 1, 1]);
+            KnnParams::classifier(2).fit(&dataset).unwrap()
+        };
+        let model = fit();
+        let cloned = model.clone();
+        assert_eq!(cloned, model);
+        drop(model);
+        assert_eq!(cloned.predict(&array![[0.5], [8.]]), array![0, 1]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let dataset = Dataset::new(array![[0.], [1.], [9.], [10.]], array![0, 0, 1, 1]);
+        let model = KnnParams::classifier_with(2, L1Dist, CommonNearestNeighbour::BallTree)
+            .fit(&dataset)
+            .unwrap();
+        // The index isn't serialized, but built again over the records
+        let loaded: KnnClassifier<f64, usize, L1Dist, CommonNearestNeighbour> =
+            serde_json::from_str(&serde_json::to_string(&model).unwrap()).unwrap();
+        assert_eq!(loaded, model);
+        assert_eq!(loaded.predict(&array![[0.5], [8.]]), array![0, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn predict_wrong_features() {
+        let dataset = Dataset::new(array![[0.], [1.]], array![0, 1]);
+        let model = KnnParams::classifier(1).fit(&dataset).unwrap();
+        model.predict(&array![[0., 1.]]);
+    }
+}
@@ -0,0 +1,30 @@
+use linfa_nn::BuildError;
+use thiserror::Error;
+
+/// Simplified `Result` using [`KnnError`](crate::KnnError) as error type
+pub type Result<T> = std::result::Result<T, KnnError>;
+
+/// Error variants from hyper-parameter construction or model estimation
+#[derive(Error, Debug)]
+pub enum KnnError {
+    /// Invalid number of neighbours
+    #[error("n_neighbours must be positive")]
+    NNeighbours,
+    /// Invalid leaf size of the spatial index
+    #[error("leaf_size must be positive")]
+    LeafSize,
+    /// Fewer training records than neighbours
+    #[error("{n_samples} training records for {n_neighbours} neighbours")]
+    NotEnoughSamples {
+        n_samples: usize,
+        n_neighbours: usize,
+    },
+    /// A training record with a NaN or infinite feature
+    #[error("training record {index} has a non-finite value")]
+    NonFiniteRecord { index: usize },
+    /// When the spatial index cannot be built over the training records
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error(transparent)]
+    BaseCrate(#[from] linfa::Error),
+}
@@ -0,0 +1,246 @@
+use crate::KnnError;
+use linfa::{Float, ParamGuard};
+use linfa_nn::distance::{Distance, L2Dist};
+use linfa_nn::{CommonNearestNeighbour, NearestNeighbour};
+use std::marker::PhantomData;
+
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+/// How the neighbours of an observation weigh in its prediction
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnnWeights {
+    /// All the neighbours count the same
+    Uniform,
+    /// Each neighbour counts as the inverse of its distance to the observation, so that closer
+    /// neighbours count more. Neighbours at distance 0, if any, are the only ones that count.
+    Distance,
+}
+
+/// Marker of the hyper-parameters of a [`KnnClassifier`](crate::KnnClassifier)
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classification;
+
+/// Marker of the hyper-parameters of a [`KnnRegressor`](crate::KnnRegressor)
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Regression;
+
+/// A verified hyper-parameter set ready for the estimation of a
+/// [K-nearest neighbours classifier](crate::KnnClassifier) or
+/// [regressor](crate::KnnRegressor), depending on the task `T`, either [`Classification`] or
+/// [`Regression`].
+///
+/// See [`KnnParams`] for information on the hyper-parameters.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnnValidParams<F, D, N, T> {
+    n_neighbours: usize,
+    weights: KnnWeights,
+    leaf_size: usize,
+    dist_fn: D,
+    nn_algo: N,
+    // Phantom data for the float type and the task
+    task: PhantomData<(F, T)>,
+}
+
+impl<F: Float, D: Distance<F>, N: NearestNeighbour, T> KnnValidParams<F, D, N, T> {
+    /// Number of neighbours of an observation used to predict its target
+    pub fn n_neighbours(&self) -> usize {
+        self.n_neighbours
+    }
+
+    /// How the neighbours of an observation weigh in its prediction
+    pub fn weights(&self) -> KnnWeights {
+        self.weights
+    }
+
+    /// Number of points in the leaves of the spatial index
+    pub fn leaf_size(&self) -> usize {
+        self.leaf_size
+    }
+
+    /// Distance metric between the observations and the training records
+    pub fn dist_fn(&self) -> &D {
+        &self.dist_fn
+    }
+
+    /// Nearest neighbour algorithm used to find the neighbours
+    pub fn nn_algo(&self) -> &N {
+        &self.nn_algo
+    }
+}
+
+/// A hyper-parameter set during construction for a
+/// [K-nearest neighbours classifier](crate::KnnClassifier), built with
+/// [`KnnParams::classifier`], or [regressor](crate::KnnRegressor), built with
+/// [`KnnParams::regressor`].
+///
+/// The parameter set can be verified into a [`KnnValidParams`] by calling
+/// [ParamGuard::check](Self::check). It is also possible to directly fit a model with
+/// [Fit::fit](linfa::traits::Fit::fit) which implicitely verifies the parameter set prior to the
+/// model estimation and forwards any error.
+///
+/// # Parameters
+/// | Name | Default | Purpose | Range |
+/// | :--- | :--- | :---| :--- |
+/// | [n_neighbours](Self::n_neighbours) | - | Number of neighbours of an observation used to predict its target | `[1, n_samples]` |
+/// | [weights](Self::weights) | `Uniform` | How the neighbours weigh in the prediction | - |
+/// | [leaf_size](Self::leaf_size) | `16` | Number of points in the leaves of the spatial index | `[1, inf)` |
+/// | [dist_fn](Self::dist_fn) | `L2Dist` | Distance metric, shared with the other algorithms of `linfa-nn` | - |
+/// | [nn_algo](Self::nn_algo) | `KdTree` | Nearest neighbour algorithm, such as a linear scan or a KD tree | - |
+///
+/// # Errors
+///
+/// The following errors can come from invalid hyper-parameters:
+///
+/// Returns [`NNeighbours`](KnnError::NNeighbours) if the number of neighbours is 0, and
+/// [`LeafSize`](KnnError::LeafSize) if the leaf size is 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnnParams<F, D, N, T>(KnnValidParams<F, D, N, T>);
+
+impl<F: Float> KnnParams<F, L2Dist, CommonNearestNeighbour, Classification> {
+    /// Create new hyper-parameters of a classifier predicting the majority class of the
+    /// `n_neighbours` closest training records, with the euclidean distance and a KD tree
+    pub fn classifier(n_neighbours: usize) -> Self {
+        Self::classifier_with(n_neighbours, L2Dist, CommonNearestNeighbour::KdTree)
+    }
+}
+
+impl<F: Float, D: Distance<F>, N: NearestNeighbour> KnnParams<F, D, N, Classification> {
+    /// Create new hyper-parameters of a classifier with a custom distance metric and a custom
+    /// nearest neighbour algorithm
+    pub fn classifier_with(n_neighbours: usize, dist_fn: D, nn_algo: N) -> Self {
+        Self::new(n_neighbours, dist_fn, nn_algo)
+    }
+}
+
+impl<F: Float> KnnParams<F, L2Dist, CommonNearestNeighbour, Regression> {
+    /// Create new hyper-parameters of a regressor predicting the mean target of the
+    /// `n_neighbours` closest training records, with the euclidean distance and a KD tree
+    pub fn regressor(n_neighbours: usize) -> Self {
+        Self::regressor_with(n_neighbours, L2Dist, CommonNearestNeighbour::KdTree)
+    }
+}
+
+impl<F: Float, D: Distance<F>, N: NearestNeighbour> KnnParams<F, D, N, Regression> {
+    /// Create new hyper-parameters of a regressor with a custom distance metric and a custom
+    /// nearest neighbour algorithm
+    pub fn regressor_with(n_neighbours: usize, dist_fn: D, nn_algo: N) -> Self {
+        Self::new(n_neighbours, dist_fn, nn_algo)
+    }
+}
+
+impl<F: Float, D: Distance<F>, N: NearestNeighbour, T> KnnParams<F, D, N, T> {
+    fn new(n_neighbours: usize, dist_fn: D, nn_algo: N) -> Self {
+        Self(KnnValidParams {
+            n_neighbours,
+            weights: KnnWeights::Uniform,
+            leaf_size: 16,
+            dist_fn,
+            nn_algo,
+            task: PhantomData,
+        })
+    }
+
+    /// Set the number of neighbours of an observation used to predict its target
+    pub fn n_neighbours(mut self, n_neighbours: usize) -> Self {
+        self.0.n_neighbours = n_neighbours;
+        self
+    }
+
+    /// Set how the neighbours of an observation weigh in its prediction
+    pub fn weights(mut self, weights: KnnWeights) -> Self {
+        self.0.weights = weights;
+        self
+    }
+
+    /// Set the number of points in the leaves of the spatial index, which only matters for
+    /// tree-like nearest neighbour algorithms
+    pub fn leaf_size(mut self, leaf_size: usize) -> Self {
+        self.0.leaf_size = leaf_size;
+        self
+    }
+
+    /// Set the distance metric. A KD tree only finds the nearest neighbours of the metrics
+    /// computed feature by feature, like [`L2Dist`] or [`LpDist`](linfa_nn::distance::LpDist).
+    pub fn dist_fn(mut self, dist_fn: D) -> Self {
+        self.0.dist_fn = dist_fn;
+        self
+    }
+
+    /// Set the nearest neighbour algorithm used to find the neighbours
+    pub fn nn_algo(mut self, nn_algo: N) -> Self {
+        self.0.nn_algo = nn_algo;
+        self
+    }
+}
+
+impl<F: Float, D: Distance<F>, N: NearestNeighbour, T> ParamGuard for KnnParams<F, D, N, T> {
+    type Checked = KnnValidParams<F, D, N, T>;
+    type Error = KnnError;
+
+    fn check_ref(&self) -> Result<&Self::Checked, Self::Error> {
+        if self.0.n_neighbours == 0 {
+            Err(KnnError::NNeighbours)
+        } else if self.0.leaf_size == 0 {
+            Err(KnnError::LeafSize)
+        } else {
+            Ok(&self.0)
+        }
+    }
+
+    fn check(self) -> Result<Self::Checked, Self::Error> {
+        self.check_ref()?;
+        Ok(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn autotraits() {
+        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
+        has_autotraits::<KnnParams<f64, L2Dist, CommonNearestNeighbour, Classification>>();
+        has_autotraits::<KnnValidParams<f64, L2Dist, CommonNearestNeighbour, Regression>>();
+        has_autotraits::<KnnError>();
+    }
+
+    #[test]
+    fn n_neighbours_cannot_be_zero() {
+        let res = KnnParams::<f64, _, _, _>::classifier(0).check();
+        assert!(matches!(res, Err(KnnError::NNeighbours)));
+        let res = KnnParams::<f64, _, _, _>::regressor(3)
+            .n_neighbours(0)
+            .check();
+        assert!(matches!(res, Err(KnnError::NNeighbours)));
+    }
+
+    #[test]
+    fn leaf_size_cannot_be_zero() {
+        let res = KnnParams::<f64, _, _, _>::classifier(3)
+            .leaf_size(0)
+            .check();
+        assert!(matches!(res, Err(KnnError::LeafSize)));
+    }
+}
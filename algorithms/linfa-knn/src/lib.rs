@@ -0,0 +1,12 @@
+#![doc = include_str!("../README.md")]
+
+mod classifier;
+mod error;
+mod hyperparams;
+mod neighbours;
+mod regressor;
+
+pub use classifier::KnnClassifier;
+pub use error::{KnnError, Result};
+pub use hyperparams::{Classification, KnnParams, KnnValidParams, KnnWeights, Regression};
+pub use regressor::KnnRegressor;
@@ -0,0 +1,215 @@
+use crate::error::{KnnError, Result};
+use crate::hyperparams::{KnnValidParams, KnnWeights};
+use linfa::Float;
+use linfa_nn::distance::Distance;
+use linfa_nn::{NearestNeighbour, NearestNeighbourIndex};
+use ndarray::{Array2, ArrayBase, Data, Ix2};
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde_crate::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+// Spatial index over the training records of a model. It borrows the records and holds the
+// distance metric of the model, but the lifetime of both is erased, see `Neighbours::from_state`.
+type Index<F> = Box<dyn NearestNeighbourIndex<F> + Send + Sync>;
+
+// Training records of a fitted model, along with the spatial index built over them at fit time
+// and how to find and weigh the neighbours of new observations with it.
+//
+// The records are kept behind an `Arc` for the index to borrow them: moving or cloning the model
+// doesn't move them, and they are never mutated. Clones of the model share both.
+#[derive(Clone)]
+pub(crate) struct Neighbours<F: Float, D: Distance<F>, N: NearestNeighbour> {
+    // Declared before the records so that it is dropped first
+    index: Arc<Index<F>>,
+    records: Arc<Array2<F>>,
+    pub(crate) n_neighbours: usize,
+    pub(crate) weights: KnnWeights,
+    leaf_size: usize,
+    dist_fn: D,
+    nn_algo: N,
+}
+
+// Fields of `Neighbours` without the index, which is all that is serialized
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(crate = "serde_crate"))]
+struct NeighboursState<F, D, N> {
+    records: Array2<F>,
+    n_neighbours: usize,
+    weights: KnnWeights,
+    leaf_size: usize,
+    dist_fn: D,
+    nn_algo: N,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+#[serde(crate = "serde_crate")]
+struct NeighboursStateRef<'a, F, D, N> {
+    records: &'a Array2<F>,
+    n_neighbours: usize,
+    weights: KnnWeights,
+    leaf_size: usize,
+    dist_fn: &'a D,
+    nn_algo: &'a N,
+}
+
+impl<F: Float, D: Distance<F>, N: NearestNeighbour> Neighbours<F, D, N> {
+    // Checks the training records, and builds the spatial index over them
+    pub(crate) fn new<T>(
+        params: &KnnValidParams<F, D, N, T>,
+        records: &ArrayBase<impl Data<Elem = F>, Ix2>,
+    ) -> Result<Self>
+    where
+        N: Clone,
+    {
+        if records.nrows() < params.n_neighbours() {
+            return Err(KnnError::NotEnoughSamples {
+                n_samples: records.nrows(),
+                n_neighbours: params.n_neighbours(),
+            });
+        }
+        if let Some(index) = records
+            .rows()
+            .into_iter()
+            .position(|record| record.iter().any(|x| !x.is_finite()))
+        {
+            return Err(KnnError::NonFiniteRecord { index });
+        }
+        Self::from_state(NeighboursState {
+            records: records.as_standard_layout().into_owned(),
+            n_neighbours: params.n_neighbours(),
+            weights: params.weights(),
+            leaf_size: params.leaf_size(),
+            dist_fn: params.dist_fn().clone(),
+            nn_algo: params.nn_algo().clone(),
+        })
+    }
+
+    fn from_state(state: NeighboursState<F, D, N>) -> Result<Self> {
+        let records = Arc::new(state.records);
+        let index = state.nn_algo.from_batch_with_leaf_size(
+            &*records,
+            state.leaf_size,
+            state.dist_fn.clone(),
+        )?;
+        // SAFETY: the index borrows the records, which are on the heap behind an `Arc` that is
+        // never mutated, so they stay in place for as long as one of its clones lives. Every
+        // clone of the index is next to one of the records and is dropped before it. The index
+        // also holds a clone of the distance metric, whose lifetime is that of the model, since
+        // the model holds the metric too, and the index never leaves the model.
+        let index: Index<F> = unsafe { std::mem::transmute(index) };
+        Ok(Neighbours {
+            index: Arc::new(index),
+            records,
+            n_neighbours: state.n_neighbours,
+            weights: state.weights,
+            leaf_size: state.leaf_size,
+            dist_fn: state.dist_fn,
+            nn_algo: state.nn_algo,
+        })
+    }
+
+    // Calls `f` with the positions of the neighbours of each observation among the training
+    // records and their weights, along with the index of the observation
+    pub(crate) fn for_each(
+        &self,
+        observations: &ArrayBase<impl Data<Elem = F>, Ix2>,
+        mut f: impl FnMut(usize, &[(usize, F)]),
+    ) {
+        assert_eq!(
+            observations.ncols(),
+            self.records.ncols(),
+            "The observations must have as many features as the training records."
+        );
+        let observations = observations.as_standard_layout();
+        let mut neighbours = Vec::with_capacity(self.n_neighbours);
+        for (i, observation) in observations.rows().into_iter().enumerate() {
+            neighbours.clear();
+            let nearest = self
+                .index
+                .k_nearest(observation, self.n_neighbours)
+                .expect("the observations have as many features as the training records");
+            neighbours.extend(
+                nearest
+                    .into_iter()
+                    .map(|(point, position)| (position, self.dist_fn.distance(point, observation))),
+            );
+            match self.weights {
+                KnnWeights::Uniform => neighbours.iter_mut().for_each(|(_, w)| *w = F::one()),
+                KnnWeights::Distance if neighbours.iter().any(|&(_, d)| d == F::zero()) => {
+                    neighbours
+                        .iter_mut()
+                        .for_each(|(_, w)| *w = if *w == F::zero() { F::one() } else { F::zero() })
+                }
+                KnnWeights::Distance => neighbours.iter_mut().for_each(|(_, w)| *w = w.recip()),
+            }
+            f(i, &neighbours);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<F, D, N> Serialize for Neighbours<F, D, N>
+where
+    F: Float + Serialize,
+    D: Distance<F> + Serialize,
+    N: NearestNeighbour + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        NeighboursStateRef {
+            records: &self.records,
+            n_neighbours: self.n_neighbours,
+            weights: self.weights,
+            leaf_size: self.leaf_size,
+            dist_fn: &self.dist_fn,
+            nn_algo: &self.nn_algo,
+        }
+        .serialize(serializer)
+    }
+}
+
+// The index is built again over the deserialized records
+#[cfg(feature = "serde")]
+impl<'de, F, D, N> Deserialize<'de> for Neighbours<F, D, N>
+where
+    F: Float + Deserialize<'de>,
+    D: Distance<F> + Deserialize<'de>,
+    N: NearestNeighbour + Deserialize<'de>,
+{
+    fn deserialize<De: Deserializer<'de>>(
+        deserializer: De,
+    ) -> std::result::Result<Self, De::Error> {
+        let state = NeighboursState::deserialize(deserializer)?;
+        Self::from_state(state).map_err(De::Error::custom)
+    }
+}
+
+// The index is left out, as it is entirely determined by the other fields
+impl<F: Float, D: Distance<F> + PartialEq, N: NearestNeighbour + PartialEq> PartialEq
+    for Neighbours<F, D, N>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.records == other.records
+            && self.n_neighbours == other.n_neighbours
+            && self.weights == other.weights
+            && self.leaf_size == other.leaf_size
+            && self.dist_fn == other.dist_fn
+            && self.nn_algo == other.nn_algo
+    }
+}
+
+impl<F: Float, D: Distance<F> + fmt::Debug, N: NearestNeighbour> fmt::Debug
+    for Neighbours<F, D, N>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Neighbours")
+            .field("records", &self.records)
+            .field("n_neighbours", &self.n_neighbours)
+            .field("weights", &self.weights)
+            .field("leaf_size", &self.leaf_size)
+            .field("dist_fn", &self.dist_fn)
+            .field("nn_algo", &self.nn_algo)
+            .finish()
+    }
+}
@@ -0,0 +1,162 @@
+use crate::error::{KnnError, Result};
+use crate::hyperparams::{KnnValidParams, Regression};
+use crate::neighbours::Neighbours;
+use linfa::dataset::{AsSingleTargets, DatasetBase};
+use linfa::traits::{Fit, PredictInplace};
+use linfa::Float;
+use linfa_nn::distance::Distance;
+use linfa_nn::NearestNeighbour;
+use ndarray::{Array1, ArrayBase, Data, Ix2};
+
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+
+impl<F, D, N, DA, T> Fit<ArrayBase<DA, Ix2>, T, KnnError> for KnnValidParams<F, D, N, Regression>
+where
+    F: Float,
+    D: Distance<F>,
+    N: NearestNeighbour + Clone,
+    DA: Data<Elem = F>,
+    T: AsSingleTargets<Elem = F>,
+{
+    type Object = KnnRegressor<F, D, N>;
+
+    fn fit(&self, dataset: &DatasetBase<ArrayBase<DA, Ix2>, T>) -> Result<Self::Object> {
+        Ok(KnnRegressor {
+            neighbours: Neighbours::new(self, dataset.records())?,
+            targets: dataset.as_single_targets().to_owned(),
+        })
+    }
+}
+
+/// Fitted K-nearest neighbours regressor.
+///
+/// See [`KnnParams`](crate::KnnParams) for more information on the hyper-parameters.
+///
+/// # Model
+///
+/// The regressor keeps its training records, and predicts the target of an observation as the
+/// mean of the targets of its `n_neighbours` closest training records, according to the
+/// distance metric, weighted as set by [`KnnWeights`](crate::KnnWeights).
+///
+/// Like the [classifier](crate::KnnClassifier), it builds the spatial index of the nearest
+/// neighbour algorithm over the training records once, when fitting.
+///
+/// # Model usage example
+///
+/// ```rust
+/// use linfa::prelude::*;
+/// use linfa_knn::{KnnParams, Result};
+/// use ndarray::array;
+///
+/// let records = array![[0.], [1.], [2.], [10.]];
+/// let dataset = Dataset::new(records, array![0., 2., 4., 20.]);
+///
+/// let model = KnnParams::regressor(2).fit(&dataset)?;
+/// assert_eq!(model.predict(&array![[0.4], [9.]]), array![1., 12.]);
+/// # Result::Ok(())
+/// ```
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnnRegressor<F: Float, D: Distance<F>, N: NearestNeighbour> {
+    neighbours: Neighbours<F, D, N>,
+    // Target of each training record
+    targets: Array1<F>,
+}
+
+impl<F, D, N, DA> PredictInplace<ArrayBase<DA, Ix2>, Array1<F>> for KnnRegressor<F, D, N>
+where
+    F: Float,
+    D: Distance<F>,
+    N: NearestNeighbour + Clone,
+    DA: Data<Elem = F>,
+{
+    /// Predict the target of each observation, the weighted mean of the targets of its neighbours.
+    ///
+    /// Panics if the observations don't have as many features as the training records, or if
+    /// one of them has a NaN or infinite feature.
+    fn predict_inplace(&self, observations: &ArrayBase<DA, Ix2>, targets: &mut Array1<F>) {
+        assert_eq!(
+            observations.nrows(),
+            targets.len(),
+            "The number of data points must match the number of output targets."
+        );
+        self.neighbours.for_each(observations, |i, neighbours| {
+            let (sum, total) = neighbours.iter().fold(
+                (F::zero(), F::zero()),
+                |(sum, total), &(position, weight)| {
+                    (sum + weight * self.targets[position], total + weight)
+                },
+            );
+            targets[i] = sum / total;
+        });
+    }
+
+    fn default_target(&self, observations: &ArrayBase<DA, Ix2>) -> Array1<F> {
+        Array1::zeros(observations.nrows())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{KnnParams, KnnWeights};
+    use approx::assert_abs_diff_eq;
+    use linfa::prelude::*;
+    use linfa_nn::distance::L2Dist;
+    use linfa_nn::CommonNearestNeighbour;
+    use ndarray::{array, Array2};
+
+    #[test]
+    fn autotraits() {
+        fn has_autotraits<T: Send + Sync + Sized + Unpin>() {}
+        has_autotraits::<KnnRegressor<f64, L2Dist, CommonNearestNeighbour>>();
+    }
+
+    #[test]
+    fn mean_of_the_neighbours() {
+        let records = array![[0., 0.], [1., 0.], [0., 1.], [5., 5.]];
+        let dataset = Dataset::new(records, array![1., 2., 3., 10.]);
+        let observations = array![[0.1, 0.1], [4., 4.]];
+
+        let model = KnnParams::regressor(3).fit(&dataset).unwrap();
+        assert_abs_diff_eq!(
+            model.predict(&observations),
+            array![2., 5.],
+            epsilon = 1e-12
+        );
+
+        // Weights 1, 1/2 for the records at distances 1 and 2
+        let dataset = Dataset::new(array![[0.], [3.], [10.]], array![0., 3., 10.]);
+        let model = KnnParams::regressor(2)
+            .weights(KnnWeights::Distance)
+            .fit(&dataset)
+            .unwrap();
+        assert_abs_diff_eq!(
+            model.predict(&array![[1.]]),
+            array![(0. + 3. / 2.) / (1. + 1. / 2.)],
+            epsilon = 1e-12
+        );
+        assert_eq!(model.predict(&array![[10.]]), array![10.]);
+    }
+
+    #[test]
+    fn recovers_a_smooth_function() {
+        let records = Array2::from_shape_fn((200, 1), |(i, _)| i as f64 / 20.);
+        let targets = records.column(0).mapv(f64::sin);
+        let dataset = Dataset::new(records, targets);
+        let model = KnnParams::regressor(4).fit(&dataset).unwrap();
+        let observations = Array2::from_shape_fn((50, 1), |(i, _)| 0.1 + i as f64 / 6.);
+        // The neighbours of an observation are within 0.1 of it, where sin is 1-Lipschitz
+        let predictions = model.predict(&observations);
+        assert_abs_diff_eq!(
+            predictions,
+            observations.column(0).mapv(f64::sin),
+            epsilon = 0.1
+        );
+    }
+}
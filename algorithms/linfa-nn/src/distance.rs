@@ -1,5 +1,5 @@
 use linfa::Float;
-use ndarray::{Array2, ArrayBase, ArrayView, Axis, Data, Dimension, Ix2, Zip};
+use ndarray::{Array2, ArrayBase, ArrayView, ArrayView2, Axis, Data, Dimension, Ix2, Zip};
 use ndarray_stats::DeviationExt;
 
 #[cfg(feature = "serde")]
@@ -22,6 +22,32 @@ pub trait Distance<F: Float>: Clone + Send + Sync + Unpin {
         self.distance(a, b)
     }
 
+    /// Same as `rdistance`, except that once the result is known to be greater than `bound`, the
+    /// computation can stop early and return any value greater than `bound`. Results lower or
+    /// equal to `bound` are exactly those of `rdistance`.
+    ///
+    /// This lets nearest neighbour searches skip most of the work for points that are farther than
+    /// the best candidate so far. The default implementation just calls `rdistance`.
+    #[inline]
+    fn rdistance_bounded<D: Dimension>(
+        &self,
+        a: ArrayView<F, D>,
+        b: ArrayView<F, D>,
+        bound: F,
+    ) -> F {
+        let _ = bound;
+        self.rdistance(a, b)
+    }
+
+    /// Returns whether the metric is defined at `point`. Algorithms can use this to reject
+    /// invalid inputs before computing any distance. The default implementation accepts every
+    /// point.
+    #[inline]
+    fn is_valid_point<D: Dimension>(&self, point: ArrayView<F, D>) -> bool {
+        let _ = point;
+        true
+    }
+
     /// Converts the result of `rdistance` to `distance`
     #[inline]
     fn rdist_to_dist(&self, rdist: F) -> F {
@@ -33,6 +59,36 @@ pub trait Distance<F: Float>: Clone + Send + Sync + Unpin {
     fn dist_to_rdist(&self, dist: F) -> F {
         dist
     }
+
+    /// Returns whether the distance between two points is never smaller than their difference
+    /// along any single feature, as for the distances of the
+    /// [Minkowski](https://en.wikipedia.org/wiki/Minkowski_distance) family. Spatial trees
+    /// splitting the points feature by feature can only skip the points on the far side of a
+    /// split for such distances. Defaults to `false`, which is always safe.
+    #[inline]
+    fn is_minkowski(&self) -> bool {
+        false
+    }
+
+    /// Computes the `rdistance` between each row of `a` and each row of `b`, as a matrix of shape
+    /// `(a.nrows(), b.nrows())`.
+    ///
+    /// The default implementation calls `rdistance` on every pair of rows. Implementations can
+    /// override it with a faster batched computation, whose results may differ from `rdistance` by
+    /// rounding errors.
+    ///
+    /// Panics if the rows of `a` and `b` have different dimensions.
+    fn rdistance_matrix(&self, a: ArrayView2<F>, b: ArrayView2<F>) -> Array2<F> {
+        let mut dists = Array2::zeros((a.nrows(), b.nrows()));
+        Zip::from(dists.rows_mut())
+            .and(a.rows())
+            .for_each(|mut dists, a| {
+                Zip::from(&mut dists)
+                    .and(b.rows())
+                    .for_each(|dist, b| *dist = self.rdistance(a, b))
+            });
+        dists
+    }
 }
 
 /// L1 or [Manhattan](https://en.wikipedia.org/wiki/Taxicab_geometry) distance
@@ -44,6 +100,11 @@ pub trait Distance<F: Float>: Clone + Send + Sync + Unpin {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct L1Dist;
 impl<F: Float> Distance<F> for L1Dist {
+    #[inline]
+    fn is_minkowski(&self) -> bool {
+        true
+    }
+
     #[inline]
     fn distance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
         a.l1_dist(&b).unwrap()
@@ -59,6 +120,11 @@ impl<F: Float> Distance<F> for L1Dist {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct L2Dist;
 impl<F: Float> Distance<F> for L2Dist {
+    #[inline]
+    fn is_minkowski(&self) -> bool {
+        true
+    }
+
     #[inline]
     fn distance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
         F::from(a.l2_dist(&b).unwrap()).unwrap()
@@ -66,9 +132,54 @@ impl<F: Float> Distance<F> for L2Dist {
 
     #[inline]
     fn rdistance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
+        // Fast path for 2D and 3D points, which are common in geospatial data and for which the
+        // overhead of the generic version dominates
+        if a.len() <= 3 && a.shape() == b.shape() {
+            match (a.as_slice(), b.as_slice()) {
+                (Some(&[a0, a1]), Some(&[b0, b1])) => {
+                    return (a0 - b0) * (a0 - b0) + (a1 - b1) * (a1 - b1)
+                }
+                (Some(&[a0, a1, a2]), Some(&[b0, b1, b2])) => {
+                    return (a0 - b0) * (a0 - b0) + (a1 - b1) * (a1 - b1) + (a2 - b2) * (a2 - b2)
+                }
+                _ => {}
+            }
+        }
         F::from(a.sq_l2_dist(&b).unwrap()).unwrap()
     }
 
+    /// Accumulates the squared differences feature by feature, in the same order as `rdistance`,
+    /// and stops as soon as the partial sum exceeds `bound`. Only high-dimensional points benefit
+    /// from this, others go through `rdistance`.
+    fn rdistance_bounded<D: Dimension>(
+        &self,
+        a: ArrayView<F, D>,
+        b: ArrayView<F, D>,
+        bound: F,
+    ) -> F {
+        // Number of features accumulated between two comparisons with `bound`
+        const BLOCK_SIZE: usize = 8;
+
+        if a.len() <= 2 * BLOCK_SIZE || a.shape() != b.shape() {
+            return self.rdistance(a, b);
+        }
+        match (a.as_slice(), b.as_slice()) {
+            (Some(a), Some(b)) => {
+                let mut sum = F::zero();
+                for (a, b) in a.chunks(BLOCK_SIZE).zip(b.chunks(BLOCK_SIZE)) {
+                    for (&a, &b) in a.iter().zip(b) {
+                        sum += (a - b) * (a - b);
+                    }
+                    if sum > bound {
+                        break;
+                    }
+                }
+                sum
+            }
+            _ => self.rdistance(a, b),
+        }
+    }
+
     #[inline]
     fn rdist_to_dist(&self, rdist: F) -> F {
         rdist.sqrt()
@@ -78,6 +189,27 @@ impl<F: Float> Distance<F> for L2Dist {
     fn dist_to_rdist(&self, dist: F) -> F {
         dist.powi(2)
     }
+
+    /// Uses the `||a - b||² = ||a||² - 2 a·b + ||b||²` identity to compute all the squared
+    /// distances with a single matrix product, which is much faster than computing them one by
+    /// one once points have more than a handful of dimensions.
+    fn rdistance_matrix(&self, a: ArrayView2<F>, b: ArrayView2<F>) -> Array2<F> {
+        assert_eq!(a.ncols(), b.ncols(), "points must have the same dimension");
+        let sq_norms = |points: &ArrayView2<F>| points.map_axis(Axis(1), |p| p.dot(&p));
+        let (a_sq_norms, b_sq_norms) = (sq_norms(&a), sq_norms(&b));
+        let mut dists = a.dot(&b.t());
+        Zip::from(dists.rows_mut())
+            .and(&a_sq_norms)
+            .for_each(|mut dists, &a_sq_norm| {
+                Zip::from(&mut dists)
+                    .and(&b_sq_norms)
+                    .for_each(|dist, &b_sq_norm| {
+                        // Cancellation can make the distance between close points negative
+                        *dist = (a_sq_norm - F::cast(2.) * *dist + b_sq_norm).max(F::zero())
+                    })
+            });
+        dists
+    }
 }
 
 /// L-infinte or [Chebyshev](https://en.wikipedia.org/wiki/Chebyshev_distance) distance
@@ -89,6 +221,11 @@ impl<F: Float> Distance<F> for L2Dist {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LInfDist;
 impl<F: Float> Distance<F> for LInfDist {
+    #[inline]
+    fn is_minkowski(&self) -> bool {
+        true
+    }
+
     #[inline]
     fn distance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
         a.linf_dist(&b).unwrap()
@@ -96,6 +233,9 @@ impl<F: Float> Distance<F> for LInfDist {
 }
 
 /// L-p or [Minkowsky](https://en.wikipedia.org/wiki/Minkowski_distance) distance
+///
+/// Computes `(sum |a - b|^p)^(1/p)`, which is the [`L1Dist`] for `p = 1` and the [`L2Dist`] for
+/// `p = 2`. `p` must be at least 1 for the Triangle Inequality to hold.
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -109,6 +249,11 @@ impl<F: Float> LpDist<F> {
     }
 }
 impl<F: Float> Distance<F> for LpDist<F> {
+    #[inline]
+    fn is_minkowski(&self) -> bool {
+        true
+    }
+
     #[inline]
     fn distance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
         Zip::from(&a)
@@ -118,6 +263,126 @@ impl<F: Float> Distance<F> for LpDist<F> {
     }
 }
 
+/// [Jensen-Shannon](https://en.wikipedia.org/wiki/Jensen%E2%80%93Shannon_divergence) distance
+/// between discrete probability distributions, such as normalized histograms or topic vectors
+///
+/// `rdistance` is the Jensen-Shannon divergence `(KL(a || m) + KL(b || m)) / 2`, where
+/// `m = (a + b) / 2` and `KL` is the Kullback-Leibler divergence with natural logarithms, and lies
+/// between 0 and `ln 2`. `distance` is its square root, which satisfies the Triangle Inequality.
+///
+/// Points are valid when their entries are non-negative and sum to 1 within
+/// [`SUM_TOLERANCE`](JensenShannonDist::SUM_TOLERANCE), see
+/// [`is_valid_point`](Distance::is_valid_point). The mean of distributions is a distribution, so
+/// algorithms averaging points, such as K-means, keep centroids valid.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JensenShannonDist;
+impl JensenShannonDist {
+    /// Largest difference between the sum of the entries of a valid point and 1
+    pub const SUM_TOLERANCE: f64 = 1e-4;
+}
+impl<F: Float> Distance<F> for JensenShannonDist {
+    #[inline]
+    fn distance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
+        self.rdistance(a, b).sqrt()
+    }
+
+    fn rdistance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
+        let half = F::cast(0.5);
+        // Zero probabilities contribute nothing, as `p ln p` goes to 0
+        let kl_term = |p: F, m: F| {
+            if p > F::zero() {
+                p * (p / m).ln()
+            } else {
+                F::zero()
+            }
+        };
+        Zip::from(&a)
+            .and(&b)
+            .fold(F::zero(), |acc, &p, &q| {
+                let m = (p + q) * half;
+                acc + (kl_term(p, m) + kl_term(q, m)) * half
+            })
+            // Rounding errors can make the divergence between close points negative
+            .max(F::zero())
+    }
+
+    fn is_valid_point<D: Dimension>(&self, point: ArrayView<F, D>) -> bool {
+        point.iter().all(|&p| p >= F::zero())
+            && (point.sum() - F::one()).abs() <= F::cast(Self::SUM_TOLERANCE)
+    }
+
+    #[inline]
+    fn rdist_to_dist(&self, rdist: F) -> F {
+        rdist.sqrt()
+    }
+
+    #[inline]
+    fn dist_to_rdist(&self, dist: F) -> F {
+        dist.powi(2)
+    }
+}
+
+/// [Cosine](https://en.wikipedia.org/wiki/Cosine_similarity) distance, which compares the
+/// directions of points regardless of their norms, as is common for text embeddings and
+/// term-frequency vectors
+///
+/// `rdistance` is the cosine distance `1 - cos(a, b)`, between 0 and 2, which doesn't satisfy the
+/// Triangle Inequality. `distance` is the angle `acos(cos(a, b))` between the points, between 0
+/// and `π`, which does and orders points the same way.
+///
+/// The direction of a point without any non-zero coordinate is undefined, so only points with a
+/// non-zero finite norm are valid, see [`is_valid_point`](Distance::is_valid_point). Other points
+/// are at a cosine distance of 1 from every point.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosineDist;
+impl<F: Float> Distance<F> for CosineDist {
+    #[inline]
+    fn distance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
+        self.rdist_to_dist(self.rdistance(a, b))
+    }
+
+    fn rdistance<D: Dimension>(&self, a: ArrayView<F, D>, b: ArrayView<F, D>) -> F {
+        let (dot, a_sq_norm, b_sq_norm) = Zip::from(&a).and(&b).fold(
+            (F::zero(), F::zero(), F::zero()),
+            |(dot, a_sq_norm, b_sq_norm), &a, &b| {
+                (dot + a * b, a_sq_norm + a * a, b_sq_norm + b * b)
+            },
+        );
+        let norms = (a_sq_norm * b_sq_norm).sqrt();
+        if norms > F::zero() && norms.is_finite() {
+            // Rounding errors can push the cosine of (anti)colinear points beyond 1 in magnitude
+            (F::one() - dot / norms).max(F::zero()).min(F::cast(2.))
+        } else {
+            F::one()
+        }
+    }
+
+    fn is_valid_point<D: Dimension>(&self, point: ArrayView<F, D>) -> bool {
+        let sq_norm = point.iter().fold(F::zero(), |acc, &p| acc + p * p);
+        sq_norm > F::zero() && sq_norm.is_finite()
+    }
+
+    #[inline]
+    fn rdist_to_dist(&self, rdist: F) -> F {
+        (F::one() - rdist).acos()
+    }
+
+    #[inline]
+    fn dist_to_rdist(&self, dist: F) -> F {
+        F::one() - dist.cos()
+    }
+}
+
 /// Computes a similarity matrix with gaussian kernel and scaling parameter `eps`
 ///
 /// The generated matrix is a upper triangular matrix with dimension NxN (number of observations) and contains the similarity between all permutations of observations
@@ -146,7 +411,7 @@ pub fn to_gaussian_similarity<F: Float>(
 #[cfg(test)]
 mod test {
     use approx::assert_abs_diff_eq;
-    use ndarray::arr1;
+    use ndarray::{arr1, s, Array1};
 
     use super::*;
 
@@ -157,6 +422,8 @@ mod test {
         has_autotraits::<L2Dist>();
         has_autotraits::<LInfDist>();
         has_autotraits::<LpDist<f64>>();
+        has_autotraits::<JensenShannonDist>();
+        has_autotraits::<CosineDist>();
     }
 
     fn dist_test<D: Distance<f64>>(dist: D, result: f64) {
@@ -195,6 +462,68 @@ mod test {
         assert_abs_diff_eq!(L2Dist.rdistance(a.view(), b.view()), 28.17, epsilon = 1e-3);
     }
 
+    #[test]
+    fn l2_rdist_low_dim() {
+        let points = Array2::from_shape_fn((4, 4), |(i, j)| (i * 4 + j) as f64 * 0.37 - 7.);
+        for n_features in 1..=4 {
+            // Rows are contiguous and take the fast path for 2 and 3 features, columns don't
+            let rows = points.slice(s![.., ..n_features]);
+            let columns = points.slice(s![..n_features, ..]);
+            for (a, b) in [
+                (rows.row(0), rows.row(3)),
+                (columns.column(0), columns.column(3)),
+            ] {
+                let expected = a.to_owned().sq_l2_dist(&b.to_owned()).unwrap();
+                assert_eq!(L2Dist.rdistance(a, b), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn rdistance_matrix() {
+        let a = Array2::from_shape_fn((5, 7), |(i, j)| ((i * 7 + j) as f64 * 1.3).sin() * 10.);
+        let b = Array2::from_shape_fn((3, 7), |(i, j)| ((i * 7 + j) as f64 * 0.7).cos() * 10.);
+        fn pairwise<D: Distance<f64>>(dist: &D, a: &Array2<f64>, b: &Array2<f64>) -> Array2<f64> {
+            Array2::from_shape_fn((a.nrows(), b.nrows()), |(i, j)| {
+                dist.rdistance(a.row(i), b.row(j))
+            })
+        }
+        assert_abs_diff_eq!(
+            L2Dist.rdistance_matrix(a.view(), b.view()),
+            pairwise(&L2Dist, &a, &b),
+            epsilon = 1e-10
+        );
+        assert_eq!(
+            L1Dist.rdistance_matrix(a.view(), b.view()),
+            pairwise(&L1Dist, &a, &b)
+        );
+        // Distances between identical points are never negative
+        assert!(L2Dist
+            .rdistance_matrix(a.view(), a.view())
+            .iter()
+            .all(|&d| d >= 0.));
+    }
+
+    #[test]
+    fn rdistance_bounded() {
+        let a = Array1::from_shape_fn(100, |i| (i as f64 * 1.3).sin() * 10.);
+        let b = Array1::from_shape_fn(100, |i| (i as f64 * 0.7).cos() * 10.);
+        let rdist = L2Dist.rdistance(a.view(), b.view());
+        // Below the bound the result is exact
+        assert_eq!(L2Dist.rdistance_bounded(a.view(), b.view(), rdist), rdist);
+        assert_eq!(
+            L2Dist.rdistance_bounded(a.view(), b.view(), f64::INFINITY),
+            rdist
+        );
+        // Above the bound the result is only guaranteed to exceed it
+        let bounded = L2Dist.rdistance_bounded(a.view(), b.view(), rdist / 10.);
+        assert!(bounded > rdist / 10. && bounded <= rdist);
+        assert_eq!(
+            L1Dist.rdistance_bounded(a.view(), b.view(), 0.),
+            L1Dist.rdistance(a.view(), b.view())
+        );
+    }
+
     #[test]
     fn linf_dist() {
         dist_test(LInfDist, 3.9);
@@ -204,4 +533,74 @@ mod test {
     fn lp_dist() {
         dist_test(LpDist(3.3), 4.635);
     }
+
+    #[test]
+    fn cosine_dist() {
+        let dist = CosineDist;
+        let a = arr1(&[1., 0.]);
+        let b = arr1(&[0., 3.]);
+        let c = arr1(&[2., 2.]);
+        assert_abs_diff_eq!(dist.rdistance(a.view(), b.view()), 1., epsilon = 1e-12);
+        assert_abs_diff_eq!(
+            dist.distance(a.view(), b.view()),
+            std::f64::consts::FRAC_PI_2,
+            epsilon = 1e-12
+        );
+        // Norms don't matter, only directions
+        assert_abs_diff_eq!(
+            dist.rdistance(a.view(), c.view()),
+            1. - 0.5f64.sqrt(),
+            epsilon = 1e-12
+        );
+        assert_eq!(dist.rdistance(c.view(), (&c * 10.).view()), 0.);
+        assert_abs_diff_eq!(dist.rdistance(a.view(), (-&a).view()), 2., epsilon = 1e-12);
+        let ab = dist.distance(a.view(), b.view());
+        assert_abs_diff_eq!(
+            dist.rdist_to_dist(dist.dist_to_rdist(ab)),
+            ab,
+            epsilon = 1e-12
+        );
+        assert!(
+            ab <= dist.distance(a.view(), c.view()) + dist.distance(c.view(), b.view()) + 1e-12
+        );
+
+        let zero = arr1(&[0., 0.]);
+        assert_eq!(dist.rdistance(a.view(), zero.view()), 1.);
+        assert!(dist.is_valid_point(a.view()));
+        assert!(!dist.is_valid_point(zero.view()));
+        assert!(!dist.is_valid_point(arr1(&[f64::INFINITY, 0.]).view()));
+    }
+
+    #[test]
+    fn jensen_shannon_dist() {
+        let a = arr1(&[0.5, 0.5, 0.]);
+        let b = arr1(&[0., 0.5, 0.5]);
+        let c = arr1(&[0.1, 0.2, 0.7]);
+        let dist = JensenShannonDist;
+        // Half of each distribution is disjoint from the other
+        assert_abs_diff_eq!(
+            dist.rdistance(a.view(), b.view()),
+            0.5 * 2f64.ln(),
+            epsilon = 1e-12
+        );
+        assert_eq!(dist.distance(a.view(), a.view()), 0.);
+        assert_abs_diff_eq!(
+            dist.rdistance(arr1(&[1., 0.]).view(), arr1(&[0., 1.]).view()),
+            2f64.ln(),
+            epsilon = 1e-12
+        );
+        assert_eq!(
+            dist.distance(a.view(), c.view()),
+            dist.distance(c.view(), a.view())
+        );
+        let ab = dist.distance(a.view(), b.view());
+        assert_abs_diff_eq!(dist.rdist_to_dist(dist.dist_to_rdist(ab)), ab);
+        assert!(ab <= dist.distance(a.view(), c.view()) + dist.distance(c.view(), b.view()));
+
+        assert!(dist.is_valid_point(a.view()));
+        assert!(dist.is_valid_point(arr1(&[0.3, 0.7 + 1e-6]).view()));
+        assert!(!dist.is_valid_point(arr1(&[0.5, 0.6]).view()));
+        assert!(!dist.is_valid_point(arr1(&[-0.5, 1.5]).view()));
+        assert!(L2Dist.is_valid_point(arr1(&[-0.5, 1.5]).view()));
+    }
 }
@@ -34,7 +34,7 @@ impl SerdeRegex {
 
     fn as_re(&self) -> &Regex {
         use std::ops::Deref;
-        &self.0.deref()
+        self.0.deref()
     }
 }
 
@@ -275,6 +275,30 @@ impl<F: Float> LinearScaler<F> {
     pub fn method(&self) -> &ScalingMethod<F> {
         &self.method
     }
+
+    /// Maps an array of size (nsamples, nfeatures) scaled by this scaler back to the original
+    /// feature space, undoing [`transform`](Transformer::transform).
+    /// Panics if the shape of the input array is not compatible with the shape of the dataset used for fitting.
+    pub fn inverse_transform(&self, x: Array2<F>) -> Array2<F> {
+        if x.is_empty() {
+            return x;
+        }
+        let mut x = match &self.method {
+            ScalingMethod::MinMax(min, max) => (x - *min) / (*max - *min),
+            _ => x,
+        };
+        Zip::from(x.columns_mut())
+            .and(self.offsets())
+            .and(self.scales())
+            .for_each(|mut col, &offset, &scale| {
+                if let ScalingMethod::Standard(false, _) = self.method {
+                    col.mapv_inplace(|el| (el - offset) / scale + offset);
+                } else {
+                    col.mapv_inplace(|el| el / scale + offset);
+                }
+            });
+        x
+    }
 }
 
 impl<F: Float> Transformer<Array2<F>, Array2<F>> for LinearScaler<F> {
@@ -631,4 +655,26 @@ mod tests {
         let dataset = array![[1., -1., 2.], [2., 0., 0.], [0., 1., -1.]].into();
         let _scaler = LinearScaler::min_max_range(10., 5.).fit(&dataset).unwrap();
     }
+
+    #[test]
+    fn test_inverse_transform() {
+        let records = array![[1., -1., 2.], [2., 0., 2.], [0., 1., -4.]];
+        let dataset = DatasetBase::from(records.clone());
+        for params in [
+            LinearScaler::standard(),
+            LinearScaler::standard_no_mean(),
+            LinearScaler::standard_no_std(),
+            LinearScaler::min_max(),
+            LinearScaler::min_max_range(-3., 5.),
+            LinearScaler::max_abs(),
+        ] {
+            let scaler = params.fit(&dataset).unwrap();
+            let restored = scaler.inverse_transform(scaler.transform(records.clone()));
+            assert_abs_diff_eq!(restored, records, epsilon = 1e-10);
+        }
+
+        let scaler = LinearScaler::min_max().fit(&dataset).unwrap();
+        let empty = Array2::<f64>::zeros((0, 3));
+        assert_eq!(scaler.inverse_transform(empty.clone()), empty);
+    }
 }
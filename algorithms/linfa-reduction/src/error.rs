@@ -9,6 +9,8 @@ pub enum ReductionError {
     NotEnoughSamples,
     #[error("embedding dimension smaller {0} than feature dimension")]
     EmbeddingTooSmall(usize),
+    #[error("variance ratio {0} must lie between 0 (excluded) and 1")]
+    InvalidVarianceRatio(f64),
     #[error("Number of steps zero in diffusion map operator")]
     StepsZero,
     #[cfg(feature = "blas")]
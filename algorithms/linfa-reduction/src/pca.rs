@@ -24,7 +24,7 @@
 use crate::error::{ReductionError, Result};
 #[cfg(not(feature = "blas"))]
 use linfa_linalg::{lobpcg::TruncatedSvd, Order};
-use ndarray::{Array1, Array2, ArrayBase, Axis, Data, Ix2};
+use ndarray::{s, Array1, Array2, ArrayBase, Axis, Data, Ix2};
 #[cfg(feature = "blas")]
 use ndarray_linalg::{TruncatedOrder, TruncatedSvd};
 use rand::{prelude::SmallRng, SeedableRng};
@@ -43,10 +43,11 @@ use linfa::{
     derive(Serialize, Deserialize),
     serde(crate = "serde_crate")
 )]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PcaParams {
     embedding_size: usize,
     apply_whitening: bool,
+    variance_ratio: Option<f64>,
 }
 
 impl PcaParams {
@@ -59,6 +60,17 @@ impl PcaParams {
 
         self
     }
+
+    /// Keep only as many components as needed to explain `ratio` of the total variance
+    ///
+    /// The embedding size then acts as an upper bound: the fewest leading components whose
+    /// explained variance sums to at least `ratio` of the variance of the dataset are retained, or
+    /// all of them if none of the prefixes does. Fitting fails if `ratio` doesn't lie in `(0, 1]`.
+    pub fn variance_ratio(mut self, ratio: f64) -> Self {
+        self.variance_ratio = Some(ratio);
+
+        self
+    }
 }
 
 /// Fit a PCA model given a dataset
@@ -81,6 +93,10 @@ impl<T, D: Data<Elem = f64>> Fit<ArrayBase<D, Ix2>, T, ReductionError> for PcaPa
             return Err(ReductionError::NotEnoughSamples);
         } else if dataset.nfeatures() < self.embedding_size || self.embedding_size == 0 {
             return Err(ReductionError::EmbeddingTooSmall(self.embedding_size));
+        } else if let Some(ratio) = self.variance_ratio {
+            if !(ratio > 0. && ratio <= 1.) {
+                return Err(ReductionError::InvalidVarianceRatio(ratio));
+            }
         }
 
         let x = dataset.records();
@@ -88,6 +104,7 @@ impl<T, D: Data<Elem = f64>> Fit<ArrayBase<D, Ix2>, T, ReductionError> for PcaPa
         // safe because of above 0 samples check
         let mean = x.mean_axis(Axis(0)).unwrap();
         let x = x - &mean;
+        let total_variance = x.iter().map(|x| x * x).sum::<f64>();
 
         // estimate Singular Value Decomposition
         #[cfg(feature = "blas")]
@@ -97,7 +114,20 @@ impl<T, D: Data<Elem = f64>> Fit<ArrayBase<D, Ix2>, T, ReductionError> for PcaPa
         let result = TruncatedSvd::new_with_rng(x, Order::Largest, SmallRng::seed_from_u64(42))
             .decompose(self.embedding_size)?;
         // explained variance is the spectral distribution of the eigenvalues
-        let (_, sigma, mut v_t) = result.values_vectors();
+        let (_, mut sigma, mut v_t) = result.values_vectors();
+
+        if let Some(ratio) = self.variance_ratio {
+            let mut explained = 0.;
+            let n_components = sigma
+                .iter()
+                .position(|s| {
+                    explained += s * s;
+                    explained >= ratio * total_variance
+                })
+                .map_or(sigma.len(), |i| i + 1);
+            sigma = sigma.slice_move(s![..n_components]);
+            v_t = v_t.slice_move(s![..n_components, ..]);
+        }
 
         // cut singular values to avoid numerical problems
         let sigma = sigma.mapv(|x| x.max(1e-8));
@@ -160,6 +190,7 @@ impl Pca<f64> {
         PcaParams {
             embedding_size,
             apply_whitening: false,
+            variance_ratio: None,
         }
     }
 
@@ -180,6 +211,23 @@ impl Pca<f64> {
     pub fn singular_values(&self) -> &Array1<f64> {
         &self.sigma
     }
+
+    /// Map embedded records back to the original feature space
+    ///
+    /// The records are reconstructed from their principal components, undoing the whitening if
+    /// it was applied, such that this is the inverse of the projection up to the variance lost by
+    /// the discarded components.
+    pub fn inverse_transform(&self, x: Array2<f64>) -> Array2<f64> {
+        // the rows of the embedding are the principal axes scaled by the whitening factor, which
+        // is divided out twice to both normalize the axes and undo the scaling of the records
+        let mut reconstruction = self.embedding.clone();
+        for mut axis in reconstruction.axis_iter_mut(Axis(0)) {
+            let norm_sq = axis.dot(&axis);
+            axis /= norm_sq;
+        }
+
+        x.dot(&reconstruction) + &self.mean
+    }
 }
 
 impl<F: Float, D: Data<Elem = F>> PredictInplace<ArrayBase<D, Ix2>, Array2<F>> for Pca<F> {
@@ -214,6 +262,13 @@ impl<F: Float, D: Data<Elem = F>, T>
         DatasetBase::new(new_records, targets).with_weights(weights)
     }
 }
+
+impl<F: Float> Transformer<Array2<F>, Array2<F>> for Pca<F> {
+    fn transform(&self, records: Array2<F>) -> Array2<F> {
+        (records - &self.mean).dot(&self.embedding.t())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -379,4 +434,54 @@ mod tests {
             epsilon = 1e-6
         );
     }
+
+    #[test]
+    fn test_inverse_transform() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        let data = Array2::random_using((100, 3), Uniform::new(-1.0f64, 1.), &mut rng) + 2.;
+        let dataset = Dataset::from(data.clone());
+
+        for whiten in [false, true] {
+            let model = Pca::params(3).whiten(whiten).fit(&dataset).unwrap();
+            let restored = model.inverse_transform(model.predict(&data));
+            assert_abs_diff_eq!(restored, data, epsilon = 1e-6);
+        }
+
+        // the reconstruction from fewer components is the projection onto their hyperplane
+        let model = Pca::params(2).fit(&dataset).unwrap();
+        let restored = model.inverse_transform(model.predict(&data));
+        let proj = model.predict(&restored);
+        assert_abs_diff_eq!(proj, model.predict(&data), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_variance_ratio() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        // three features with variances 100, 10 and 1
+        let data = Array2::random_using((500, 3), StandardNormal, &mut rng)
+            * array![10f64, 10f64.sqrt(), 1.];
+        let dataset = Dataset::from(data);
+
+        let n_components = |ratio| {
+            Pca::params(3)
+                .variance_ratio(ratio)
+                .fit(&dataset)
+                .unwrap()
+                .singular_values()
+                .len()
+        };
+        assert_eq!(n_components(0.5), 1);
+        assert_eq!(n_components(0.95), 2);
+        assert_eq!(n_components(1.), 3);
+
+        // the embedding size bounds the number of components
+        let model = Pca::params(2).variance_ratio(1.).fit(&dataset).unwrap();
+        assert_eq!(model.singular_values().len(), 2);
+        assert_eq!(model.predict(&dataset).ncols(), 2);
+
+        for ratio in [0., -0.5, 1.5, f64::NAN] {
+            let res = Pca::params(3).variance_ratio(ratio).fit(&dataset);
+            assert!(matches!(res, Err(ReductionError::InvalidVarianceRatio(_))));
+        }
+    }
 }
@@ -1,9 +1,9 @@
 //! Utility functions for randomly generating datasets
 
 use linfa::Dataset;
-use ndarray::{s, Array, Array2, ArrayBase, Data, Ix1, Ix2};
+use ndarray::{array, s, Array, Array1, Array2, ArrayBase, Axis, Data, Ix1, Ix2};
 use ndarray_rand::{
-    rand::Rng,
+    rand::{seq::SliceRandom, Rng},
     rand_distr::{Distribution, StandardNormal},
     RandomExt,
 };
@@ -43,6 +43,221 @@ pub fn blobs_with_distribution(
     blobs
 }
 
+/// Given an input matrix `blob_centroids`, with shape `(n_blobs, n_features)`, generate
+/// `blob_sizes[i]` data points around the `i`-th blob centroid, sampled from a normal
+/// distribution of standard deviation `blob_stds[i]` in each feature. `blob_stds` can also hold
+/// a single standard deviation for all the blobs.
+///
+/// Unlike `blobs`, the blobs can be imbalanced and overlap: the data points are shuffled and
+/// returned along with the index of the blob of each data point, to score a clustering against.
+///
+/// Panics if `blob_sizes` doesn't have one size per blob, or if `blob_stds` doesn't have one
+/// standard deviation per blob or a single one.
+///
+/// # Example
+/// ```
+/// use ndarray::array;
+/// use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+/// use linfa_datasets::generate::blobs_with;
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let centroids = array![[0., 0.], [3., 0.]];
+/// let (observations, labels) = blobs_with(&[100, 10], &centroids, &[1., 0.5], &mut rng);
+/// assert_eq!(observations.nrows(), 110);
+/// assert_eq!(labels.iter().filter(|&&l| l == 1).count(), 10);
+/// ```
+pub fn blobs_with(
+    blob_sizes: &[usize],
+    blob_centroids: &ArrayBase<impl Data<Elem = f64>, Ix2>,
+    blob_stds: &[f64],
+    rng: &mut impl Rng,
+) -> (Array2<f64>, Array1<usize>) {
+    let (n_centroids, n_features) = blob_centroids.dim();
+    assert_eq!(
+        blob_sizes.len(),
+        n_centroids,
+        "There must be one blob size per blob centroid."
+    );
+    assert!(
+        blob_stds.len() == 1 || blob_stds.len() == n_centroids,
+        "There must be one standard deviation per blob centroid, or a single one."
+    );
+
+    let n_samples = blob_sizes.iter().sum();
+    let mut blobs: Array2<f64> = Array2::zeros((n_samples, n_features));
+    let mut labels = Array1::zeros(n_samples);
+    let mut start = 0;
+    for (blob_index, blob_centroid) in blob_centroids.rows().into_iter().enumerate() {
+        let blob_size = blob_sizes[blob_index];
+        let std = blob_stds[blob_index % blob_stds.len()];
+        let shape = (blob_size, n_features);
+        let blob = Array::random_using(shape, StandardNormal, rng) * std + blob_centroid;
+
+        let indexes = s![start..start + blob_size, ..];
+        blobs.slice_mut(indexes).assign(&blob);
+        labels
+            .slice_mut(s![start..start + blob_size])
+            .fill(blob_index);
+        start += blob_size;
+    }
+
+    shuffle_labeled(blobs, labels, rng)
+}
+
+/// Given an input matrix `blob_centroids`, with shape `(n_blobs, n_features)`, generate
+/// `blob_sizes[i]` data points around the `i`-th blob centroid, sampled from a standard normal
+/// distribution stretched and rotated by `transformation`, with shape `(n_features, n_features)`.
+/// The data points of every blob thus have the covariance `transformation^T transformation`.
+///
+/// Like `blobs_with`, the data points are shuffled and returned along with the index of the blob
+/// of each data point. Elongated blobs break the isotropy assumption of K-means, unlike Gaussian
+/// mixture models with full covariances.
+///
+/// Panics if `blob_sizes` doesn't have one size per blob, or if `transformation` isn't a square
+/// matrix of size `n_features`.
+///
+/// # Example
+/// ```
+/// use ndarray::array;
+/// use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+/// use linfa_datasets::generate::anisotropic_blobs;
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let centroids = array![[0., 0.], [5., 5.]];
+/// let transformation = array![[0.6, -0.6], [-0.4, 0.8]];
+/// let (observations, labels) =
+///     anisotropic_blobs(&[100, 50], &centroids, &transformation, &mut rng);
+/// assert_eq!(observations.dim(), (150, 2));
+/// assert_eq!(labels.iter().filter(|&&l| l == 1).count(), 50);
+/// ```
+pub fn anisotropic_blobs(
+    blob_sizes: &[usize],
+    blob_centroids: &ArrayBase<impl Data<Elem = f64>, Ix2>,
+    transformation: &ArrayBase<impl Data<Elem = f64>, Ix2>,
+    rng: &mut impl Rng,
+) -> (Array2<f64>, Array1<usize>) {
+    let n_features = blob_centroids.ncols();
+    assert_eq!(
+        transformation.dim(),
+        (n_features, n_features),
+        "The transformation must be a square matrix with one row per feature."
+    );
+
+    let origins = Array2::zeros(blob_centroids.raw_dim());
+    let (blobs, labels) = blobs_with(blob_sizes, &origins, &[1.], rng);
+    let blobs =
+        blobs.dot(transformation) + blob_centroids.select(Axis(0), labels.as_slice().unwrap());
+    (blobs, labels)
+}
+
+/// Generate two interleaving half circles of `n_samples` data points in 2 dimensions, with
+/// Gaussian noise of standard deviation `noise` added to every feature.
+///
+/// The first half circle, of label 0, is the upper half of the unit circle, and the second half
+/// circle, of label 1, is the lower half of the unit circle centered at `(1, 0.5)`. The two
+/// clusters aren't convex and can't be separated by centroid based algorithms like K-means, but
+/// they can be by density based algorithms like DBSCAN. The data points are shuffled and returned
+/// along with their labels, the first half circle holding `n_samples / 2` of them.
+///
+/// # Example
+/// ```
+/// use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+/// use linfa_datasets::generate::moons;
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let (observations, labels) = moons(101, 0.05, &mut rng);
+/// assert_eq!(observations.dim(), (101, 2));
+/// assert_eq!(labels.iter().filter(|&&l| l == 1).count(), 51);
+/// ```
+pub fn moons(n_samples: usize, noise: f64, rng: &mut impl Rng) -> (Array2<f64>, Array1<usize>) {
+    let n_outer = n_samples / 2;
+    let n_inner = n_samples - n_outer;
+    let angles = |n| Array1::linspace(0., std::f64::consts::PI, n);
+
+    let mut moons = Array2::zeros((n_samples, 2));
+    for (mut point, t) in moons.rows_mut().into_iter().zip(angles(n_outer)) {
+        point.assign(&array![t.cos(), t.sin()]);
+    }
+    for (mut point, t) in moons
+        .rows_mut()
+        .into_iter()
+        .skip(n_outer)
+        .zip(angles(n_inner))
+    {
+        point.assign(&array![1. - t.cos(), 0.5 - t.sin()]);
+    }
+    let labels = (0..n_samples).map(|i| usize::from(i >= n_outer)).collect();
+
+    let moons = moons + Array2::<f64>::random_using((n_samples, 2), StandardNormal, rng) * noise;
+    shuffle_labeled(moons, labels, rng)
+}
+
+/// Generate two concentric circles of `n_samples` data points in 2 dimensions, with Gaussian noise
+/// of standard deviation `noise` added to every feature.
+///
+/// The outer circle, of label 0, is the unit circle, and the inner circle, of label 1, has the
+/// radius `factor`. Like `moons`, the two clusters aren't convex nor linearly separable. The data
+/// points are shuffled and returned along with their labels, the outer circle holding
+/// `n_samples / 2` of them.
+///
+/// Panics if `factor` doesn't lie in `[0, 1)`.
+///
+/// # Example
+/// ```
+/// use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+/// use linfa_datasets::generate::circles;
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let (observations, labels) = circles(100, 0.5, 0., &mut rng);
+/// for (point, &label) in observations.rows().into_iter().zip(&labels) {
+///     let radius = point.dot(&point).sqrt();
+///     assert!((radius - if label == 0 { 1. } else { 0.5 }).abs() < 1e-10);
+/// }
+/// ```
+pub fn circles(
+    n_samples: usize,
+    factor: f64,
+    noise: f64,
+    rng: &mut impl Rng,
+) -> (Array2<f64>, Array1<usize>) {
+    assert!(
+        (0. ..1.).contains(&factor),
+        "The radius factor of the inner circle must lie between 0 and 1 (excluded)."
+    );
+    let n_outer = n_samples / 2;
+    let n_inner = n_samples - n_outer;
+    // the angles of a circle, excluding 2 pi which would duplicate the first point
+    let angles = |n| (0..n).map(move |i| 2. * std::f64::consts::PI * i as f64 / n as f64);
+
+    let mut circles = Array2::zeros((n_samples, 2));
+    for (mut point, t) in circles.rows_mut().into_iter().zip(angles(n_outer)) {
+        point.assign(&array![t.cos(), t.sin()]);
+    }
+    for (mut point, t) in circles
+        .rows_mut()
+        .into_iter()
+        .skip(n_outer)
+        .zip(angles(n_inner))
+    {
+        point.assign(&array![factor * t.cos(), factor * t.sin()]);
+    }
+    let labels = (0..n_samples).map(|i| usize::from(i >= n_outer)).collect();
+
+    let circles =
+        circles + Array2::<f64>::random_using((n_samples, 2), StandardNormal, rng) * noise;
+    shuffle_labeled(circles, labels, rng)
+}
+
+/// Shuffle the data points of a generated dataset along with their labels
+fn shuffle_labeled(
+    records: Array2<f64>,
+    labels: Array1<usize>,
+    rng: &mut impl Rng,
+) -> (Array2<f64>, Array1<usize>) {
+    let mut order: Vec<usize> = (0..records.nrows()).collect();
+    order.shuffle(rng);
+    (
+        records.select(Axis(0), &order),
+        labels.select(Axis(0), &order),
+    )
+}
+
 /// Generate `blob_size` data points (a "blob") around `blob_centroid` using the given distribution.
 ///
 /// `blob` can be used to quickly assemble a synthetic stereotypical cluster.
@@ -1,13 +1,16 @@
 //! Composition models
 //!
-//! This module contains three composition models:
+//! This module contains four composition models:
 //!  * `MultiClassModel`: combine multiple binary decision models to a single multi-class model
 //!  * `MultiTargetModel`: combine multiple univariate models to a single multi-target model
 //!  * `Platt`: calibrate a classifier (i.e. SVC) to predicted posterior probabilities
+//!  * `Pipeline`: chain transformations and a final model, fitted and applied as one
 mod multi_class_model;
 mod multi_target_model;
+pub mod pipeline;
 pub mod platt_scaling;
 
 pub use multi_class_model::MultiClassModel;
 pub use multi_target_model::MultiTargetModel;
+pub use pipeline::{FittedPipeline, Pipeline, PipelineError};
 pub use platt_scaling::{Platt, PlattError, PlattParams};
@@ -0,0 +1,312 @@
+//! Chain transformations and a final model into a pipeline
+//!
+//! A pipeline fits a transformation on a dataset, transforms the records of the dataset with it,
+//! and fits the next step on the transformed dataset. Pipelines nest, so that any number of
+//! transformations can precede the final model, and the fitted pipeline applies all of them to
+//! new records before predicting with the model.
+//!
+//! # Example
+//!
+//! The algorithms come from other crates of the toolkit, which this one can't depend on, so the
+//! example is not run here but by the `pipeline` integration test of `linfa-clustering`, which
+//! also serializes the fitted pipeline.
+//!
+//! ```rust, ignore
+//! // standardize -> PCA -> k-means
+//! let pipeline = Pipeline::new(LinearScaler::standard(), Pca::params(2))
+//!     .then(KMeans::params(3).check()?);
+//!
+//! let model = pipeline.fit(&train)?;
+//! let clusters = model.predict(&valid);
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::dataset::DatasetBase;
+use crate::traits::{Fit, PredictInplace, Transformer};
+use crate::Float;
+
+use ndarray::{Array2, ArrayBase, Data, Ix2};
+#[cfg(feature = "serde")]
+use serde_crate::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Unfitted pipeline of a transformation and a model
+///
+/// The transformation `step` is fitted first. Its fitted object must transform records into new
+/// records with [`Transformer<Array2<F>, Array2<F>>`](Transformer), and the `model` is fitted on
+/// the transformed records along with the targets and weights of the dataset. The model can
+/// itself be a transformation, such that the pipeline can be chained further with
+/// [`then`](Pipeline::then).
+///
+/// The type parameters `SE` and `ME` are the errors of fitting the step and the model, which are
+/// inferred.
+pub struct Pipeline<S, M, SE, ME> {
+    step: S,
+    model: M,
+    phantom: PhantomData<fn() -> (SE, ME)>,
+}
+
+impl<S, M, SE, ME> Pipeline<S, M, SE, ME> {
+    /// Create a pipeline fitting `step` then `model`
+    pub fn new(step: S, model: M) -> Self {
+        Pipeline {
+            step,
+            model,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Chain another model after this pipeline, whose model becomes a transformation
+    pub fn then<N, NE>(self, next: N) -> Pipeline<Self, N, PipelineError<SE, ME>, NE>
+    where
+        SE: std::error::Error,
+        ME: std::error::Error,
+    {
+        Pipeline::new(self, next)
+    }
+
+    /// Return the transformation fitted first
+    pub fn step(&self) -> &S {
+        &self.step
+    }
+
+    /// Return the model fitted on the transformed dataset
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+}
+
+impl<S: Clone, M: Clone, SE, ME> Clone for Pipeline<S, M, SE, ME> {
+    fn clone(&self) -> Self {
+        Pipeline::new(self.step.clone(), self.model.clone())
+    }
+}
+
+impl<S: std::fmt::Debug, M: std::fmt::Debug, SE, ME> std::fmt::Debug for Pipeline<S, M, SE, ME> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("step", &self.step)
+            .field("model", &self.model)
+            .finish()
+    }
+}
+
+/// Fitted pipeline of a transformation and a model
+///
+/// Predicting or transforming records applies the fitted transformation to them first. This is
+/// the fitted object of a [`Pipeline`], and can be serialized as a unit with the `serde` feature
+/// if both of its steps can.
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FittedPipeline<S, M> {
+    step: S,
+    model: M,
+}
+
+impl<S, M> FittedPipeline<S, M> {
+    /// Return the fitted transformation
+    pub fn step(&self) -> &S {
+        &self.step
+    }
+
+    /// Return the fitted model
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    /// Return the fitted transformation and model
+    pub fn into_inner(self) -> (S, M) {
+        (self.step, self.model)
+    }
+}
+
+/// Error of fitting a [`Pipeline`]
+#[derive(Error, Debug)]
+pub enum PipelineError<SE: std::error::Error, ME: std::error::Error> {
+    /// When fitting the transformation failed
+    #[error("fitting the transformation of the pipeline failed: {0}")]
+    Step(SE),
+    /// When fitting the model on the transformed dataset failed
+    #[error("fitting the model of the pipeline failed: {0}")]
+    Model(ME),
+    #[error(transparent)]
+    BaseCrate(#[from] crate::Error),
+}
+
+impl<F, D, T, S, M, SE, ME> Fit<ArrayBase<D, Ix2>, T, PipelineError<SE, ME>>
+    for Pipeline<S, M, SE, ME>
+where
+    F: Float,
+    D: Data<Elem = F>,
+    T: Clone,
+    S: Fit<ArrayBase<D, Ix2>, T, SE>,
+    S::Object: Transformer<Array2<F>, Array2<F>>,
+    M: Fit<Array2<F>, T, ME>,
+    SE: std::error::Error + From<crate::Error>,
+    ME: std::error::Error + From<crate::Error>,
+{
+    type Object = FittedPipeline<S::Object, M::Object>;
+
+    fn fit(
+        &self,
+        dataset: &DatasetBase<ArrayBase<D, Ix2>, T>,
+    ) -> Result<Self::Object, PipelineError<SE, ME>> {
+        let step = self.step.fit(dataset).map_err(PipelineError::Step)?;
+        let records = step.transform(dataset.records().to_owned());
+        let transformed = DatasetBase::new(records, dataset.targets().clone())
+            .with_weights(dataset.weights.clone());
+        let model = self.model.fit(&transformed).map_err(PipelineError::Model)?;
+
+        Ok(FittedPipeline { step, model })
+    }
+}
+
+impl<F, S, M> Transformer<Array2<F>, Array2<F>> for FittedPipeline<S, M>
+where
+    S: Transformer<Array2<F>, Array2<F>>,
+    M: Transformer<Array2<F>, Array2<F>>,
+{
+    fn transform(&self, records: Array2<F>) -> Array2<F> {
+        self.model.transform(self.step.transform(records))
+    }
+}
+
+impl<F: Float, D: Data<Elem = F>, S, M, T> PredictInplace<ArrayBase<D, Ix2>, T>
+    for FittedPipeline<S, M>
+where
+    S: Transformer<Array2<F>, Array2<F>>,
+    M: PredictInplace<Array2<F>, T>,
+{
+    fn predict_inplace(&self, records: &ArrayBase<D, Ix2>, targets: &mut T) {
+        let records = self.step.transform(records.to_owned());
+        self.model.predict_inplace(&records, targets);
+    }
+
+    fn default_target(&self, records: &ArrayBase<D, Ix2>) -> T {
+        // the targets of the model may depend on the shape of the transformed records
+        let records = self.step.transform(records.to_owned());
+        self.model.default_target(&records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Predict;
+    use ndarray::{array, Array1, Axis};
+
+    // Subtracts the mean of the training records
+    struct CenterParams;
+    struct Center(Array1<f64>);
+
+    impl<D: Data<Elem = f64>, T> Fit<ArrayBase<D, Ix2>, T, crate::Error> for CenterParams {
+        type Object = Center;
+
+        fn fit(&self, dataset: &DatasetBase<ArrayBase<D, Ix2>, T>) -> crate::error::Result<Center> {
+            let mean = dataset
+                .records()
+                .mean_axis(Axis(0))
+                .ok_or(crate::Error::NotEnoughSamples)?;
+            Ok(Center(mean))
+        }
+    }
+
+    impl Transformer<Array2<f64>, Array2<f64>> for Center {
+        fn transform(&self, records: Array2<f64>) -> Array2<f64> {
+            records - &self.0
+        }
+    }
+
+    // Divides by the largest absolute value of the training records
+    struct ScaleParams;
+    struct Scale(f64);
+
+    impl<T> Fit<Array2<f64>, T, crate::Error> for ScaleParams {
+        type Object = Scale;
+
+        fn fit(&self, dataset: &DatasetBase<Array2<f64>, T>) -> crate::error::Result<Scale> {
+            Ok(Scale(dataset.records().fold(0., |max, x| x.abs().max(max))))
+        }
+    }
+
+    impl Transformer<Array2<f64>, Array2<f64>> for Scale {
+        fn transform(&self, records: Array2<f64>) -> Array2<f64> {
+            records / self.0
+        }
+    }
+
+    // Predicts whether the first feature is positive, after checking the number of targets
+    struct SignParams;
+    struct Sign;
+
+    impl Fit<Array2<f64>, Array1<bool>, crate::Error> for SignParams {
+        type Object = Sign;
+
+        fn fit(
+            &self,
+            dataset: &DatasetBase<Array2<f64>, Array1<bool>>,
+        ) -> crate::error::Result<Sign> {
+            if dataset.targets().len() != dataset.records().nrows() {
+                return Err(crate::Error::MismatchedShapes(
+                    dataset.targets().len(),
+                    dataset.records().nrows(),
+                ));
+            }
+            Ok(Sign)
+        }
+    }
+
+    impl PredictInplace<Array2<f64>, Array1<bool>> for Sign {
+        fn predict_inplace(&self, records: &Array2<f64>, targets: &mut Array1<bool>) {
+            *targets = records.column(0).mapv(|x| x > 0.);
+        }
+
+        fn default_target(&self, records: &Array2<f64>) -> Array1<bool> {
+            Array1::from_elem(records.nrows(), false)
+        }
+    }
+
+    #[test]
+    fn chains_steps() {
+        let dataset = DatasetBase::new(
+            array![[1.], [2.], [3.], [10.]],
+            array![false, false, false, true],
+        );
+        let model = Pipeline::new(CenterParams, ScaleParams)
+            .then(SignParams)
+            .fit(&dataset)
+            .unwrap();
+
+        // centered on 4 and scaled by 6
+        assert_eq!(model.step().step().0, array![4.]);
+        assert_eq!(model.step().model().0, 6.);
+        assert_eq!(
+            model.step().transform(array![[7.], [1.]]),
+            array![[0.5], [-0.5]]
+        );
+        let predicted: Array1<bool> = model.predict(&array![[5.], [3.]]);
+        assert_eq!(predicted, array![true, false]);
+    }
+
+    #[test]
+    fn forwards_errors() {
+        let empty = DatasetBase::new(Array2::<f64>::zeros((0, 1)), Array1::<bool>::from(vec![]));
+        let res = Pipeline::new(CenterParams, SignParams).fit(&empty);
+        assert!(matches!(
+            res,
+            Err(PipelineError::Step(crate::Error::NotEnoughSamples))
+        ));
+
+        let mismatched = DatasetBase::new(array![[1.], [2.]], array![true]);
+        let res = Pipeline::new(CenterParams, SignParams).fit(&mismatched);
+        assert!(matches!(
+            res,
+            Err(PipelineError::Model(crate::Error::MismatchedShapes(1, 2)))
+        ));
+    }
+}